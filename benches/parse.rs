@@ -0,0 +1,30 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::hint::black_box;
+use tinyvg_rs::TinyVg;
+
+const SAMPLES: [(&str, &[u8]); 3] = [
+    ("tiger", include_bytes!("../examples/tiger.tvg")),
+    ("chart", include_bytes!("../examples/chart.tvg")),
+    ("app-icon", include_bytes!("../examples/app-icon.tvg")),
+];
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("from_bytes");
+    for (name, bytes) in SAMPLES {
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_function(name, |b| b.iter(|| TinyVg::from_bytes(black_box(bytes)).unwrap()));
+    }
+    group.finish();
+}
+
+fn spans(c: &mut Criterion) {
+    let mut group = c.benchmark_group("command_spans");
+    for (name, bytes) in SAMPLES {
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_function(name, |b| b.iter(|| TinyVg::command_spans(black_box(bytes)).unwrap()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse, spans);
+criterion_main!(benches);
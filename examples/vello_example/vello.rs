@@ -3,7 +3,7 @@ use peniko::kurbo::SvgArc;
 use peniko::{Brush, Fill, Gradient};
 use std::sync::Arc;
 use tinyvg_rs::color_table::ColorTable;
-use tinyvg_rs::commands::{DrawCommand, Path, PathCommand, Point, Segment, Style};
+use tinyvg_rs::commands::{DrawCommand, Path, PathCommand, PathCommands, Point, Segment, Style};
 use tinyvg_rs::common::Unit;
 use tinyvg_rs::TinyVg;
 use vello::kurbo::{Affine, BezPath, Line, Stroke};
@@ -290,7 +290,7 @@ fn draw_path(scene: &mut Scene, path: &Path, fill_style: &Style, line_width: Opt
                     }
                     current = current.move_to(&arc_ellipse.target);
                 }
-                PathCommand::ClosePath => {
+                PathCommand::ClosePath(_) => {
                     bezier_path.close_path();
                 }
                 PathCommand::QuadraticBezier(quadratic_bezier, _line_width) => {
@@ -373,12 +373,12 @@ fn draw_tiny_vg(scene: &mut Scene, tiny_vg: &TinyVg, affine: Affine) {
                 let start = data.points[0];
                 let mut segment = Segment {
                     start,
-                    path_commands: vec![],
+                    path_commands: PathCommands::new(),
                 };
                 for point in &data.points {
                     segment.path_commands.push(PathCommand::Line(*point, None));
                 }
-                segment.path_commands.push(PathCommand::ClosePath);
+                segment.path_commands.push(PathCommand::ClosePath(None));
                 let path = Path {
                     segments: vec![segment],
                 };
@@ -429,12 +429,12 @@ fn draw_tiny_vg(scene: &mut Scene, tiny_vg: &TinyVg, affine: Affine) {
                 let start = data.points[0];
                 let mut segment = Segment {
                     start,
-                    path_commands: vec![],
+                    path_commands: PathCommands::new(),
                 };
                 for point in &data.points {
                     segment.path_commands.push(PathCommand::Line(*point, None));
                 }
-                segment.path_commands.push(PathCommand::ClosePath);
+                segment.path_commands.push(PathCommand::ClosePath(None));
                 let path = Path {
                     segments: vec![segment],
                 };
@@ -0,0 +1,87 @@
+#ifndef TINYVG_H
+#define TINYVG_H
+
+/* This file is generated by cbindgen. Do not edit it by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef struct TinyVg TinyVg;
+
+/**
+ * A byte buffer owned by Rust. Release it with `tvg_buffer_free`.
+ */
+typedef struct TvgBuffer {
+  uint8_t *data;
+  size_t length;
+} TvgBuffer;
+
+/**
+ * Parses a binary TinyVG document.
+ *
+ * Returns null if `data` is null or does not hold a valid document. The returned
+ * document must be released with `tvg_free`.
+ *
+ * # Safety
+ *
+ * `data` must point to `length` readable bytes.
+ */
+TinyVg *tvg_parse(const uint8_t *data, size_t length);
+
+/**
+ * Releases a document returned by `tvg_parse`. Passing null does nothing.
+ *
+ * # Safety
+ *
+ * `document` must be null or a pointer returned by `tvg_parse` that was not freed yet.
+ */
+void tvg_free(TinyVg *document);
+
+/**
+ * Writes the width and height of the document in display units. Returns false if any
+ * pointer is null.
+ *
+ * # Safety
+ *
+ * `document` must be a live pointer returned by `tvg_parse`, and `width` and `height`
+ * must be null or valid for writes.
+ */
+bool tvg_get_size(const TinyVg *document, uint32_t *width, uint32_t *height);
+
+/**
+ * Renders the document into an image of `width` by `height` pixels, scaled uniformly to
+ * fit and centered. The buffer holds four bytes per pixel, red, green, blue and alpha,
+ * in sRGB with straight alpha, row by row. Returns an empty buffer if `document` is null,
+ * the image is empty or larger than 4096 * 4096 pixels, or rendering failed.
+ *
+ * # Safety
+ *
+ * `document` must be null or a live pointer returned by `tvg_parse`.
+ */
+TvgBuffer tvg_render_rgba8(const TinyVg *document, uint32_t width, uint32_t height);
+
+#if defined(TINYVG_SVG_TO_TVG)
+/**
+ * Converts an SVG document into a binary TinyVG document. Returns an empty buffer if
+ * the SVG could not be converted. Requires the `svg-to-tvg` feature.
+ *
+ * # Safety
+ *
+ * `svg` must point to `length` readable bytes.
+ */
+TvgBuffer tvg_svg_to_tvg(const uint8_t *svg, size_t length);
+#endif
+
+/**
+ * Releases a buffer returned by this library. Passing an empty buffer does nothing.
+ *
+ * # Safety
+ *
+ * `buffer` must have been returned by this library and not freed yet.
+ */
+void tvg_buffer_free(TvgBuffer buffer);
+
+#endif  /* TINYVG_H */
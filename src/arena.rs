@@ -0,0 +1,48 @@
+use crate::borrowed::DrawCommandRef;
+use crate::color_table::{parse_color_table, ColorTable};
+use crate::commands::{check_trailing_data, parse_draw_commands};
+use crate::common::Reader;
+use crate::header::TinyVgHeader;
+use crate::{ParseOptions, TinyVg, TinyVgParseError};
+use bumpalo::Bump;
+
+/// A document parsed into a bump arena, see `ArenaTinyVg::from_bytes_in`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArenaTinyVg<'a> {
+    pub header: TinyVgHeader,
+    pub color_table: ColorTable,
+    pub draw_commands: &'a [DrawCommandRef<'a>],
+}
+
+impl<'a> ArenaTinyVg<'a> {
+    /// Parses a document like `TinyVgRef::from_bytes`, but allocates the command list in
+    /// `arena`. As the commands borrow their runs, paths and text from `data`, parsing
+    /// allocates nothing else per command, and resetting the arena frees every document
+    /// parsed into it at once, as for loading and dropping many documents per frame.
+    pub fn from_bytes_in(data: &'a [u8], arena: &'a Bump) -> Result<ArenaTinyVg<'a>, TinyVgParseError> {
+        let mut reader = Reader::new(data, TinyVgParseError::InvalidHeader);
+
+        let header = TinyVgHeader::parse(&mut reader)?;
+        let color_table = parse_color_table(&mut reader, &header, None)?;
+        let mut draw_commands = bumpalo::collections::Vec::new_in(arena);
+        let options = ParseOptions::default();
+        parse_draw_commands(&mut reader, &header, &options, None, |command| draw_commands.push(command))?;
+        check_trailing_data(&reader, &options)?;
+
+        Ok(ArenaTinyVg {
+            header,
+            color_table,
+            draw_commands: draw_commands.into_bump_slice(),
+        })
+    }
+
+    /// Decodes every command into an owned document that no longer borrows the input or
+    /// the arena.
+    pub fn to_owned_document(&self) -> TinyVg {
+        TinyVg {
+            header: self.header.clone(),
+            color_table: self.color_table.clone(),
+            draw_commands: self.draw_commands.iter().cloned().map(DrawCommandRef::into_owned).collect(),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use crate::color_table::ColorTable;
+use crate::commands::DrawCommand;
+use crate::common::Affine;
+#[cfg(feature = "rayon")]
+use crate::fit::FitMode;
+use crate::header::{ColorEncoding, CoordinateRange, TinyVgHeader, TINYVG_VERSION};
+use crate::palette::{for_each_color_index_mut, intern_color};
+#[cfg(feature = "rayon")]
+use crate::render::RgbaImage;
+use crate::transform::transform_command;
+use crate::TinyVg;
+
+/// Where a sprite was placed in the atlas, in whole display units.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AtlasSprite {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug)]
+pub struct Atlas {
+    /// A single document drawing every sprite at its place.
+    pub document: TinyVg,
+
+    /// One entry per input document, in input order.
+    pub sprites: Vec<AtlasSprite>,
+}
+
+/// Packs `documents` into a single document, so that a whole icon set can be drawn with
+/// one document bind and addressed by the returned sprite rectangles.
+///
+/// Sprites are placed on shelves, tallest first, in an atlas roughly as wide as it is
+/// high, with `padding` display units between them. The sprites' geometry is moved to
+/// their place, their color tables are merged into one without duplicates, and the atlas
+/// gets the scale and coordinate range that encode the result most accurately.
+pub fn build_atlas(documents: &[TinyVg], padding: u32) -> Atlas {
+    let sprites = pack(documents, padding);
+
+    let mut color_table: ColorTable = Vec::new();
+    let mut draw_commands: Vec<DrawCommand> = Vec::new();
+    for (document, sprite) in documents.iter().zip(&sprites) {
+        let indices: Vec<u64> = document.color_table.iter().map(|color| intern_color(&mut color_table, *color)).collect();
+        let translation = Affine::translate(sprite.x as f64, sprite.y as f64);
+
+        for command in &document.draw_commands {
+            let mut command = command.clone();
+            transform_command(&mut command, &translation);
+            for_each_color_index_mut(&mut command, &mut |index| {
+                if let Some(new_index) = indices.get(*index as usize) {
+                    *index = *new_index;
+                }
+            });
+            draw_commands.push(command);
+        }
+    }
+
+    let header = TinyVgHeader {
+        magic: [0x72, 0x56],
+        version: TINYVG_VERSION,
+        scale: 0,
+        color_encoding: ColorEncoding::RgbaF32,
+        coordinate_range: CoordinateRange::Default,
+        width: sprites.iter().map(|sprite| sprite.x + sprite.width).max().unwrap_or(0),
+        height: sprites.iter().map(|sprite| sprite.y + sprite.height).max().unwrap_or(0),
+        color_count: color_table.len() as u64,
+    };
+    let mut document = TinyVg { header, color_table, draw_commands };
+    document.requantize();
+
+    Atlas { document, sprites }
+}
+
+/// Shelf packing: sprites are sorted by height and placed left to right, starting a new
+/// shelf below the tallest sprite of the current one when the row is full.
+fn pack(documents: &[TinyVg], padding: u32) -> Vec<AtlasSprite> {
+    let sizes: Vec<(u32, u32)> = documents.iter().map(|document| (document.header.width, document.header.height)).collect();
+    let area: f64 = sizes.iter().map(|&(width, height)| (width + padding) as f64 * (height + padding) as f64).sum();
+    let widest = sizes.iter().map(|&(width, _)| width).max().unwrap_or(0);
+    let row_width = (area.sqrt().ceil() as u32).max(widest);
+
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&index| std::cmp::Reverse(sizes[index].1));
+
+    let mut sprites = vec![AtlasSprite { x: 0, y: 0, width: 0, height: 0 }; sizes.len()];
+    let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+    for index in order {
+        let (width, height) = sizes[index];
+        if x > 0 && x + width > row_width {
+            x = 0;
+            y += shelf_height + padding;
+            shelf_height = 0;
+        }
+        sprites[index] = AtlasSprite { x, y, width, height };
+        x += width + padding;
+        shelf_height = shelf_height.max(height);
+    }
+    sprites
+}
+
+/// Where a document was drawn in a rendered atlas, in texture coordinates from 0 to 1 with
+/// the origin at the top left corner of the image.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct UvRect {
+    pub u_min: f32,
+    pub v_min: f32,
+    pub u_max: f32,
+    pub v_max: f32,
+}
+
+/// Renders `documents` concurrently into one image, each fitted into a cell of `cell_size`
+/// (width, height) pixels, and returns the image with the rectangle of every document in
+/// input order.
+///
+/// Cells are laid out in a grid roughly as wide as it is high, with `padding` pixels
+/// between them so that texture filtering does not bleed across cells.
+#[cfg(feature = "rayon")]
+pub fn render_atlas(documents: &[TinyVg], cell_size: (u32, u32), padding: u32) -> (RgbaImage, Vec<UvRect>) {
+    use rayon::prelude::*;
+
+    let columns = (documents.len() as f64).sqrt().ceil() as u32;
+    let rows = (documents.len() as u32).div_ceil(columns.max(1));
+    let extent = |cells: u32, size: u32| (cells * size + cells.saturating_sub(1) * padding).max(cells.min(1));
+    let mut atlas = RgbaImage::new(extent(columns, cell_size.0), extent(rows, cell_size.1));
+
+    let cells: Vec<RgbaImage> = documents.par_iter().map(|document| document.render_rgba(cell_size, FitMode::Contain)).collect();
+
+    let mut rects = Vec::with_capacity(cells.len());
+    for (index, cell) in cells.iter().enumerate() {
+        let x = (index as u32 % columns) * (cell_size.0 + padding);
+        let y = (index as u32 / columns) * (cell_size.1 + padding);
+        atlas.copy_from(cell, x, y);
+        rects.push(UvRect {
+            u_min: x as f32 / atlas.width as f32,
+            v_min: y as f32 / atlas.height as f32,
+            u_max: (x + cell_size.0) as f32 / atlas.width as f32,
+            v_max: (y + cell_size.1) as f32 / atlas.height as f32,
+        });
+    }
+    (atlas, rects)
+}
@@ -0,0 +1,201 @@
+use crate::color_table::ColorTable;
+use crate::commands::{
+    check_trailing_data, parse_draw_commands, skip_path, DrawCommand, DrawLineLoopData, DrawLinePathData, DrawLineStripData, DrawLinesData,
+    FillPathData, FillPolygonData, FillRectanglesData, Line, OutlineFillPathData, OutlineFillPolygonData,
+    OutlineFillRectanglesData, Path, Point, Rectangle, Style, TextHintData,
+};
+use crate::common::{Reader, Unit, UnitDecoder, UnitRun};
+use crate::header::TinyVgHeader;
+use crate::{parse_header_and_color_table, ParseOptions, TinyVg, TinyVgParseError};
+use std::borrow::Cow;
+
+/// A document that borrows from the bytes it was parsed from. Text is not copied, and runs
+/// of points, rectangles, lines and glyph offsets as well as paths are decoded when they
+/// are accessed, so parsing allocates nothing but the command list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TinyVgRef<'a> {
+    pub header: TinyVgHeader,
+    pub color_table: ColorTable,
+    pub draw_commands: Vec<DrawCommandRef<'a>>,
+}
+
+impl<'a> TinyVgRef<'a> {
+    pub fn from_bytes(data: &'a [u8]) -> Result<TinyVgRef<'a>, TinyVgParseError> {
+        let mut reader = Reader::new(data, TinyVgParseError::InvalidHeader);
+
+        let options = ParseOptions::default();
+        let (header, color_table) = parse_header_and_color_table(&mut reader, &options)?;
+        let mut draw_commands = Vec::new();
+        parse_draw_commands(&mut reader, &header, &options, None, |command| draw_commands.push(command))?;
+        check_trailing_data(&reader, &options)?;
+
+        Ok(TinyVgRef {
+            header,
+            color_table,
+            draw_commands,
+        })
+    }
+
+    /// Decodes every command into an owned document that no longer borrows the input.
+    pub fn into_owned(self) -> TinyVg {
+        TinyVg {
+            header: self.header,
+            color_table: self.color_table,
+            draw_commands: self.draw_commands.into_iter().map(DrawCommandRef::into_owned).collect(),
+        }
+    }
+}
+
+/// A draw command of a `TinyVgRef`, with the same fields as the data of the matching
+/// `DrawCommand`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawCommandRef<'a> {
+    FillPolygon {
+        style: Style,
+        points: UnitRun<'a, Point>,
+    },
+    FillRectangles {
+        style: Style,
+        rectangles: UnitRun<'a, Rectangle>,
+    },
+    FillPath {
+        style: Style,
+        path: PathRef<'a>,
+    },
+    DrawLines {
+        line_style: Style,
+        line_width: Unit,
+        lines: UnitRun<'a, Line>,
+    },
+    DrawLineLoop {
+        line_style: Style,
+        line_width: Unit,
+        points: UnitRun<'a, Point>,
+    },
+    DrawLineStrip {
+        style: Style,
+        line_width: Unit,
+        points: UnitRun<'a, Point>,
+    },
+    DrawLinePath {
+        style: Style,
+        line_width: Unit,
+        path: PathRef<'a>,
+    },
+    OutlineFillPolygon {
+        fill_style: Style,
+        line_style: Style,
+        line_width: Unit,
+        points: UnitRun<'a, Point>,
+    },
+    OutlineFillRectangles {
+        fill_style: Style,
+        line_style: Style,
+        line_width: Unit,
+        rectangles: UnitRun<'a, Rectangle>,
+    },
+    OutlineFillPath {
+        fill_style: Style,
+        line_style: Style,
+        line_width: Unit,
+        path: PathRef<'a>,
+    },
+    TextHint {
+        center: Point,
+        rotation: Unit,
+        height: Unit,
+        text: Cow<'a, str>,
+        glyph_offset: UnitRun<'a, (Unit, Unit)>,
+    },
+}
+
+impl DrawCommandRef<'_> {
+    pub fn into_owned(self) -> DrawCommand {
+        match self {
+            DrawCommandRef::FillPolygon { style, points } => {
+                DrawCommand::FillPolygon(FillPolygonData { style, points: points.iter().collect() })
+            }
+            DrawCommandRef::FillRectangles { style, rectangles } => {
+                DrawCommand::FillRectangles(FillRectanglesData { style, rectangles: rectangles.to_vec() })
+            }
+            DrawCommandRef::FillPath { style, path } => DrawCommand::FillPath(FillPathData { style, path: path.to_path() }),
+            DrawCommandRef::DrawLines { line_style, line_width, lines } => {
+                DrawCommand::DrawLines(DrawLinesData { lines: lines.to_vec(), line_width, line_style })
+            }
+            DrawCommandRef::DrawLineLoop { line_style, line_width, points } => {
+                DrawCommand::DrawLineLoop(DrawLineLoopData { line_style, line_width, points: points.iter().collect() })
+            }
+            DrawCommandRef::DrawLineStrip { style, line_width, points } => {
+                DrawCommand::DrawLineStrip(DrawLineStripData { style, line_width, points: points.iter().collect() })
+            }
+            DrawCommandRef::DrawLinePath { style, line_width, path } => {
+                DrawCommand::DrawLinePath(DrawLinePathData { style, line_width, path: path.to_path() })
+            }
+            DrawCommandRef::OutlineFillPolygon { fill_style, line_style, line_width, points } => {
+                DrawCommand::OutlineFillPolygon(OutlineFillPolygonData { fill_style, line_style, line_width, points: points.iter().collect() })
+            }
+            DrawCommandRef::OutlineFillRectangles { fill_style, line_style, line_width, rectangles } => {
+                DrawCommand::OutlineFillRectangles(OutlineFillRectanglesData {
+                    fill_style,
+                    line_style,
+                    line_width,
+                    rectangles: rectangles.to_vec(),
+                })
+            }
+            DrawCommandRef::OutlineFillPath { fill_style, line_style, line_width, path } => {
+                DrawCommand::OutlineFillPath(OutlineFillPathData { path: path.to_path(), fill_style, line_style, line_width })
+            }
+            DrawCommandRef::TextHint { center, rotation, height, text, glyph_offset } => DrawCommand::TextHint(TextHintData {
+                center,
+                text: text.into_owned(),
+                rotation,
+                height,
+                glyph_length: glyph_offset.len() as u64,
+                glyph_offset: glyph_offset.to_vec(),
+            }),
+        }
+    }
+}
+
+/// A path of a `DrawCommandRef`. The whole path is checked when it is parsed, and its
+/// segments are decoded by `to_path`.
+#[derive(Copy, Clone)]
+pub struct PathRef<'a> {
+    bytes: &'a [u8],
+    segment_count: usize,
+    units: UnitDecoder,
+}
+
+impl<'a> PathRef<'a> {
+    pub(crate) fn read(reader: &mut Reader<'a>, header: &TinyVgHeader, segment_count: usize) -> Result<Self, TinyVgParseError> {
+        let units = UnitDecoder::new(header);
+        let bytes = reader.take_skipped(|reader| skip_path(reader, units.size, segment_count as u64))?;
+        Ok(PathRef { bytes, segment_count, units })
+    }
+
+    pub fn segment_count(&self) -> usize {
+        self.segment_count
+    }
+
+    pub fn to_path(&self) -> Path {
+        let mut reader = Reader::new(self.bytes, TinyVgParseError::InvalidCommand);
+        Path::read(&mut reader, &self.units, self.segment_count).expect("the path was checked when it was parsed")
+    }
+
+    /// The encoded path as it is stored in the file.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+}
+
+impl std::fmt::Debug for PathRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.to_path().fmt(f)
+    }
+}
+
+impl PartialEq for PathRef<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_path() == other.to_path()
+    }
+}
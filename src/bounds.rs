@@ -0,0 +1,103 @@
+use crate::commands::{DrawCommand, Rectangle};
+use crate::common::{Affine, Unit};
+use crate::geometry::command_outline;
+use crate::transform::transform_command;
+use crate::TinyVg;
+
+/// The flattening tolerance for curves in display units.
+const BOUNDS_TOLERANCE: f64 = 0.01;
+
+/// An axis-aligned box as `(min_x, min_y, max_x, max_y)`.
+pub(crate) type Bounds = (f64, f64, f64, f64);
+
+/// The extents of everything the command paints, including half the line width around
+/// strokes. Returns `None` for commands without geometry.
+pub(crate) fn command_bounds(command: &DrawCommand) -> Option<Bounds> {
+    let outline = command_outline(command, BOUNDS_TOLERANCE)?;
+    let half_width = outline.line_width / 2.0;
+    let fill_points = outline.fill.iter().flat_map(|polyline| &polyline.points).map(|point| (point, 0.0));
+    let stroke_points = outline.stroke.iter().flat_map(|polyline| &polyline.points).map(|point| (point, half_width));
+
+    fill_points.chain(stroke_points).fold(None, |bounds, (point, margin)| {
+        let (x, y) = (point.x.0, point.y.0);
+        let (min_x, min_y, max_x, max_y) = bounds.unwrap_or((f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY));
+        Some((min_x.min(x - margin), min_y.min(y - margin), max_x.max(x + margin), max_y.max(y + margin)))
+    })
+}
+
+pub(crate) fn union(a: Bounds, b: Bounds) -> Bounds {
+    (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3))
+}
+
+pub(crate) fn bounds_rectangle((min_x, min_y, max_x, max_y): Bounds) -> Rectangle {
+    Rectangle {
+        x: Unit(min_x),
+        y: Unit(min_y),
+        width: Unit(max_x - min_x),
+        height: Unit(max_y - min_y),
+    }
+}
+
+impl TinyVg {
+    /// The smallest rectangle containing everything the document paints, including half
+    /// the line width around strokes. Curves are measured on their flattened outline.
+    /// Returns `None` if the document draws nothing.
+    pub fn content_bounds(&self) -> Option<Rectangle> {
+        self.draw_commands.iter().filter_map(command_bounds).reduce(union).map(bounds_rectangle)
+    }
+
+    /// Shrinks the document to its content bounds, rounded out to whole display units,
+    /// and moves all commands so the content starts at the origin. Returns the cropped
+    /// area in the original coordinates, or `None` if the document draws nothing and
+    /// was left unchanged.
+    pub fn autocrop(&mut self) -> Option<Rectangle> {
+        let bounds = self.content_bounds()?;
+        let left = bounds.x.0.floor();
+        let top = bounds.y.0.floor();
+        let width = ((bounds.x.0 + bounds.width.0).ceil() - left).max(1.0);
+        let height = ((bounds.y.0 + bounds.height.0).ceil() - top).max(1.0);
+
+        // Whole-unit offsets keep every coordinate exactly representable.
+        let translation = Affine::translate(-left, -top);
+        for command in &mut self.draw_commands {
+            transform_command(command, &translation);
+        }
+        self.header.width = width as u32;
+        self.header.height = height as u32;
+
+        Some(Rectangle { x: Unit(left), y: Unit(top), width: Unit(width), height: Unit(height) })
+    }
+
+    /// The area to repaint after the commands at `changed_command_indices` were edited, from
+    /// `old` to this document: the union of the bounds of those commands in both documents.
+    /// Each index is looked up in both documents, so for inserted and removed commands the
+    /// command that moved into the index is included as well, which only makes the area
+    /// larger. Returns `None` if none of the commands paints anything.
+    ///
+    /// Edits to the color table change every command using the color and are not covered.
+    pub fn dirty_region(&self, old: &TinyVg, changed_command_indices: &[usize]) -> Option<Rectangle> {
+        changed_command_indices
+            .iter()
+            .flat_map(|&index| [old.draw_commands.get(index), self.draw_commands.get(index)])
+            .flatten()
+            .filter_map(command_bounds)
+            .reduce(union)
+            .map(bounds_rectangle)
+    }
+
+    /// Iterates over the draw commands whose painted area intersects `rect`, in drawing
+    /// order, so renderers can skip geometry outside the visible area. Commands that only
+    /// touch the edge of the rectangle are included. Text hints paint nothing and are
+    /// never yielded.
+    ///
+    /// The bounds of each command are computed on the fly, which costs a flattening pass
+    /// over every command per call.
+    pub fn commands_in(&self, rect: Rectangle) -> impl Iterator<Item = &DrawCommand> {
+        let view = (rect.x.0, rect.y.0, rect.x.0 + rect.width.0, rect.y.0 + rect.height.0);
+        self.draw_commands.iter().filter(move |command| {
+            command_bounds(command).is_some_and(|bounds| {
+                bounds.0 <= view.2 && bounds.2 >= view.0 && bounds.1 <= view.3 && bounds.3 >= view.1
+            })
+        })
+    }
+}
@@ -0,0 +1,421 @@
+use crate::color_table::{ColorTable, RgbaF32};
+use crate::commands::{
+    CubicBezier, DrawCommand, DrawLineLoopData, DrawLinePathData, DrawLineStripData, DrawLinesData, FillPathData, FillPolygonData,
+    FillRectanglesData, FlatColored, Line, LinearGradient, OutlineFillPathData, OutlineFillPolygonData,
+    OutlineFillRectanglesData, Path, PathCommand, PathCommands, Point, QuadraticBezier, RadialGradient, Rectangle, Segment, Style,
+};
+use crate::common::Unit;
+use crate::header::{ColorEncoding, CoordinateRange, TinyVgHeader, TINYVG_VERSION};
+use crate::palette::intern_color;
+use crate::shapes::ResolvedStyle;
+use crate::validation::OUTLINE_ITEM_LIMIT;
+use crate::TinyVg;
+
+impl From<RgbaF32> for ResolvedStyle {
+    fn from(color: RgbaF32) -> Self {
+        ResolvedStyle::FlatColor(color)
+    }
+}
+
+/// Builds a document from draw commands that take their colors directly, for generating
+/// charts and icons in code. The color table is managed by the builder, and the scale and
+/// coordinate range are chosen to fit the geometry when the document is built. Every
+/// method returns the builder, so commands can be chained before calling `build`.
+#[derive(Debug)]
+pub struct TinyVgBuilder {
+    width: u32,
+    height: u32,
+    color_encoding: Option<ColorEncoding>,
+    color_table: ColorTable,
+    draw_commands: Vec<DrawCommand>,
+}
+
+impl TinyVgBuilder {
+    pub fn new(width: u32, height: u32) -> Self {
+        TinyVgBuilder { width, height, color_encoding: None, color_table: Vec::new(), draw_commands: Vec::new() }
+    }
+
+    /// Sets how the color table is encoded when the document is written. By default the
+    /// smallest encoding that keeps the colors exact is chosen. `Rgb565` suits embedded
+    /// targets but drops alpha and rounds the channels.
+    pub fn color_encoding(&mut self, color_encoding: ColorEncoding) -> &mut Self {
+        self.color_encoding = Some(color_encoding);
+        self
+    }
+
+    pub fn fill_polygon(&mut self, points: Vec<Point>, fill: impl Into<ResolvedStyle>) -> &mut Self {
+        let style = self.style(fill.into());
+        self.push(DrawCommand::FillPolygon(FillPolygonData { style, points: points.into_iter().collect() }))
+    }
+
+    pub fn fill_rectangles(&mut self, rectangles: Vec<Rectangle>, fill: impl Into<ResolvedStyle>) -> &mut Self {
+        let style = self.style(fill.into());
+        self.push(DrawCommand::FillRectangles(FillRectanglesData { style, rectangles }))
+    }
+
+    pub fn fill_path(&mut self, path: Path, fill: impl Into<ResolvedStyle>) -> &mut Self {
+        let style = self.style(fill.into());
+        self.push(DrawCommand::FillPath(FillPathData { style, path }))
+    }
+
+    pub fn draw_lines(&mut self, lines: Vec<Line>, line_width: Unit, stroke: impl Into<ResolvedStyle>) -> &mut Self {
+        let line_style = self.style(stroke.into());
+        self.push(DrawCommand::DrawLines(DrawLinesData { lines, line_width, line_style }))
+    }
+
+    pub fn draw_line_loop(&mut self, points: Vec<Point>, line_width: Unit, stroke: impl Into<ResolvedStyle>) -> &mut Self {
+        let line_style = self.style(stroke.into());
+        self.push(DrawCommand::DrawLineLoop(DrawLineLoopData { line_style, line_width, points: points.into_iter().collect() }))
+    }
+
+    pub fn draw_line_strip(&mut self, points: Vec<Point>, line_width: Unit, stroke: impl Into<ResolvedStyle>) -> &mut Self {
+        let style = self.style(stroke.into());
+        self.push(DrawCommand::DrawLineStrip(DrawLineStripData { style, line_width, points: points.into_iter().collect() }))
+    }
+
+    pub fn draw_line_path(&mut self, path: Path, line_width: Unit, stroke: impl Into<ResolvedStyle>) -> &mut Self {
+        let style = self.style(stroke.into());
+        self.push(DrawCommand::DrawLinePath(DrawLinePathData { style, line_width, path }))
+    }
+
+    /// Fills and outlines a polygon. Outline fill commands hold at most 64 points, so larger
+    /// polygons are filled and outlined by two commands.
+    pub fn outline_fill_polygon(
+        &mut self,
+        points: Vec<Point>,
+        fill: impl Into<ResolvedStyle>,
+        line_width: Unit,
+        stroke: impl Into<ResolvedStyle>,
+    ) -> &mut Self {
+        if points.len() > OUTLINE_ITEM_LIMIT {
+            return self.fill_polygon(points.clone(), fill).draw_line_loop(points, line_width, stroke);
+        }
+        let fill_style = self.style(fill.into());
+        let line_style = self.style(stroke.into());
+        self.push(DrawCommand::OutlineFillPolygon(OutlineFillPolygonData { fill_style, line_style, line_width, points: points.into_iter().collect() }))
+    }
+
+    /// Fills and outlines rectangles. Outline fill commands hold at most 64 rectangles, so
+    /// more rectangles are split over several commands.
+    pub fn outline_fill_rectangles(
+        &mut self,
+        rectangles: Vec<Rectangle>,
+        fill: impl Into<ResolvedStyle>,
+        line_width: Unit,
+        stroke: impl Into<ResolvedStyle>,
+    ) -> &mut Self {
+        let fill_style = self.style(fill.into());
+        let line_style = self.style(stroke.into());
+        for rectangles in rectangles.chunks(OUTLINE_ITEM_LIMIT) {
+            self.push(DrawCommand::OutlineFillRectangles(OutlineFillRectanglesData {
+                fill_style: fill_style.clone(),
+                line_style: line_style.clone(),
+                line_width,
+                rectangles: rectangles.to_vec(),
+            }));
+        }
+        self
+    }
+
+    /// Fills and outlines a path. Outline fill commands hold at most 64 segments, so paths
+    /// with more segments are filled and outlined by two commands.
+    pub fn outline_fill_path(&mut self, path: Path, fill: impl Into<ResolvedStyle>, line_width: Unit, stroke: impl Into<ResolvedStyle>) -> &mut Self {
+        if path.segments.len() > OUTLINE_ITEM_LIMIT {
+            return self.fill_path(path.clone(), fill).draw_line_path(path, line_width, stroke);
+        }
+        let fill_style = self.style(fill.into());
+        let line_style = self.style(stroke.into());
+        self.push(DrawCommand::OutlineFillPath(OutlineFillPathData { path, fill_style, line_style, line_width }))
+    }
+
+    /// Appends a command as is. Its color indices must refer to colors added with
+    /// `color_index`.
+    pub fn push(&mut self, command: DrawCommand) -> &mut Self {
+        self.draw_commands.push(command);
+        self
+    }
+
+    /// The color table index of `color`, adding it to the table if needed.
+    pub fn color_index(&mut self, color: RgbaF32) -> u64 {
+        intern_color(&mut self.color_table, color)
+    }
+
+    /// Finishes the document with the scale and coordinate range that encode its geometry
+    /// most accurately.
+    pub fn build(&self) -> TinyVg {
+        let header = TinyVgHeader {
+            magic: [0x72, 0x56],
+            version: TINYVG_VERSION,
+            scale: 0,
+            color_encoding: self.color_encoding.unwrap_or_else(|| ColorEncoding::smallest_for(&self.color_table, 0.0)),
+            coordinate_range: CoordinateRange::Default,
+            width: self.width,
+            height: self.height,
+            color_count: self.color_table.len() as u64,
+        };
+        let mut document = TinyVg { header, color_table: self.color_table.clone(), draw_commands: self.draw_commands.clone() };
+        document.requantize();
+        document
+    }
+
+    fn style(&mut self, paint: ResolvedStyle) -> Style {
+        match paint {
+            ResolvedStyle::FlatColor(color) => Style::FlatColor(FlatColored { color_index: self.color_index(color) }),
+            ResolvedStyle::LinearGradient { point_0, point_1, color_0, color_1 } => Style::LinearGradient(LinearGradient {
+                point_0,
+                point_1,
+                color_index_0: self.color_index(color_0),
+                color_index_1: self.color_index(color_1),
+            }),
+            ResolvedStyle::RadialGradient { point_0, point_1, color_0, color_1 } => Style::RadialGradient(RadialGradient {
+                point_0,
+                point_1,
+                color_index_0: self.color_index(color_0),
+                color_index_1: self.color_index(color_1),
+            }),
+        }
+    }
+}
+
+/// An immediate-mode drawing surface in the style of the HTML canvas, recording into a
+/// document: set the paints, build a path with `move_to`, `line_to` and friends, then
+/// `fill` or `stroke` it. The path is kept after it is drawn until `begin_path` starts a
+/// new one, so it can be filled and stroked in turn.
+#[derive(Debug)]
+pub struct Canvas {
+    builder: TinyVgBuilder,
+    fill: Option<ResolvedStyle>,
+    stroke: Option<(ResolvedStyle, Unit)>,
+    segments: Vec<Segment>,
+    current_segment: Option<Segment>,
+    current: Option<Point>,
+    subpath_start: Point,
+}
+
+impl Canvas {
+    pub fn new(width: u32, height: u32) -> Self {
+        Canvas {
+            builder: TinyVgBuilder::new(width, height),
+            fill: None,
+            stroke: None,
+            segments: Vec::new(),
+            current_segment: None,
+            current: None,
+            subpath_start: point(0.0, 0.0),
+        }
+    }
+
+    /// Sets the paint used by `fill`.
+    pub fn set_fill(&mut self, fill: impl Into<ResolvedStyle>) {
+        self.fill = Some(fill.into());
+    }
+
+    /// Makes `fill` draw nothing until a fill paint is set again.
+    pub fn set_no_fill(&mut self) {
+        self.fill = None;
+    }
+
+    /// Sets the paint and line width used by `stroke`.
+    pub fn set_stroke(&mut self, stroke: impl Into<ResolvedStyle>, line_width: f64) {
+        self.stroke = Some((stroke.into(), Unit(line_width)));
+    }
+
+    /// Makes `stroke` draw nothing until a stroke paint is set again.
+    pub fn set_no_stroke(&mut self) {
+        self.stroke = None;
+    }
+
+    /// Discards the current path.
+    pub fn begin_path(&mut self) {
+        self.segments.clear();
+        self.current_segment = None;
+        self.current = None;
+    }
+
+    /// Starts a new subpath at the point.
+    pub fn move_to(&mut self, x: f64, y: f64) {
+        self.finish_segment();
+        self.current = Some(point(x, y));
+        self.subpath_start = point(x, y);
+        self.current_segment = Some(Segment { start: point(x, y), path_commands: PathCommands::new() });
+    }
+
+    /// Adds a straight line to the point. Without a current point this only moves there.
+    pub fn line_to(&mut self, x: f64, y: f64) {
+        self.push(PathCommand::Line(point(x, y), None), point(x, y));
+    }
+
+    /// Adds a quadratic Bézier curve through the control point to the end point.
+    pub fn quad_to(&mut self, control_x: f64, control_y: f64, x: f64, y: f64) {
+        let quadratic = QuadraticBezier { control_point: point(control_x, control_y), point_1: point(x, y) };
+        self.push(PathCommand::QuadraticBezier(quadratic, None), point(x, y));
+    }
+
+    /// Adds a cubic Bézier curve through both control points to the end point.
+    pub fn curve_to(&mut self, control_0_x: f64, control_0_y: f64, control_1_x: f64, control_1_y: f64, x: f64, y: f64) {
+        let cubic = CubicBezier {
+            control_point_0: point(control_0_x, control_0_y),
+            control_point_1: point(control_1_x, control_1_y),
+            point_1: point(x, y),
+        };
+        self.push(PathCommand::CubicBezier(cubic, None), point(x, y));
+    }
+
+    /// Closes the current subpath with a line back to its start.
+    pub fn close(&mut self) {
+        if let Some(segment) = &mut self.current_segment
+            && !segment.path_commands.is_empty()
+        {
+            segment.path_commands.push(PathCommand::ClosePath(None));
+            self.finish_segment();
+            self.current = Some(self.subpath_start);
+        }
+    }
+
+    /// Adds a closed rectangle as its own subpath.
+    pub fn rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        self.move_to(x, y);
+        self.push(PathCommand::HorizontalLine(Unit(x + width), None), point(x + width, y));
+        self.push(PathCommand::VerticalLine(Unit(y + height), None), point(x + width, y + height));
+        self.push(PathCommand::HorizontalLine(Unit(x), None), point(x, y + height));
+        self.close();
+    }
+
+    /// Fills the current path with the fill paint, if one is set.
+    pub fn fill(&mut self) {
+        if let (Some(fill), Some(path)) = (self.fill.clone(), self.path()) {
+            self.builder.fill_path(path, fill);
+        }
+    }
+
+    /// Strokes the current path with the stroke paint, if one is set.
+    pub fn stroke(&mut self) {
+        if let (Some((stroke, line_width)), Some(path)) = (self.stroke.clone(), self.path()) {
+            self.builder.draw_line_path(path, line_width, stroke);
+        }
+    }
+
+    /// Fills and strokes the current path with a single command, or does whichever of the
+    /// two has a paint set.
+    pub fn fill_and_stroke(&mut self) {
+        match (self.fill.clone(), self.stroke.clone(), self.path()) {
+            (Some(fill), Some((stroke, line_width)), Some(path)) => {
+                self.builder.outline_fill_path(path, fill, line_width, stroke);
+            }
+            (Some(_), None, _) => self.fill(),
+            (None, Some(_), _) => self.stroke(),
+            _ => {}
+        }
+    }
+
+    /// Finishes the recording into a document, see `TinyVgBuilder::build`.
+    pub fn finish(self) -> TinyVg {
+        self.builder.build()
+    }
+
+    fn finish_segment(&mut self) {
+        if let Some(segment) = self.current_segment.take()
+            && !segment.path_commands.is_empty()
+        {
+            self.segments.push(segment);
+        }
+    }
+
+    /// Appends a command ending at `end`. Drawing after a close path starts a new subpath
+    /// at the start of the closed one.
+    fn push(&mut self, command: PathCommand, end: Point) {
+        let Some(start) = self.current else {
+            self.move_to(end.x.0, end.y.0);
+            return;
+        };
+        self.current_segment
+            .get_or_insert_with(|| Segment { start, path_commands: PathCommands::new() })
+            .path_commands
+            .push(command);
+        self.current = Some(end);
+    }
+
+    /// The current path including the open subpath, or `None` if it is empty.
+    fn path(&self) -> Option<Path> {
+        let mut segments = self.segments.clone();
+        if let Some(segment) = &self.current_segment
+            && !segment.path_commands.is_empty()
+        {
+            segments.push(segment.clone());
+        }
+        (!segments.is_empty()).then_some(Path { segments })
+    }
+}
+
+fn point(x: f64, y: f64) -> Point {
+    Point::new(Unit(x), Unit(y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{point, Canvas, TinyVgBuilder};
+    use crate::color_table::RgbaF32;
+    use crate::commands::{DrawCommand, Rectangle};
+    use crate::common::Unit;
+
+    const RED: RgbaF32 = RgbaF32(1.0, 0.0, 0.0, 1.0);
+    const BLACK: RgbaF32 = RgbaF32(0.0, 0.0, 0.0, 1.0);
+
+    fn kinds(commands: &[DrawCommand]) -> Vec<&'static str> {
+        commands
+            .iter()
+            .map(|command| match command {
+                DrawCommand::FillPolygon(_) => "fill polygon",
+                DrawCommand::FillPath(_) => "fill path",
+                DrawCommand::DrawLineLoop(_) => "line loop",
+                DrawCommand::DrawLinePath(_) => "line path",
+                DrawCommand::OutlineFillPolygon(_) => "outline polygon",
+                DrawCommand::OutlineFillRectangles(_) => "outline rectangles",
+                DrawCommand::OutlineFillPath(_) => "outline path",
+                _ => "other",
+            })
+            .collect()
+    }
+
+    /// The corners of a regular polygon with `count` corners around (50, 50).
+    fn polygon(count: usize) -> Vec<crate::commands::Point> {
+        (0..count)
+            .map(|index| {
+                let angle = index as f64 / count as f64 * std::f64::consts::TAU;
+                point(50.0 + 40.0 * angle.cos(), 50.0 + 40.0 * angle.sin())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn outline_fills_with_more_than_64_items_are_split() {
+        let rectangles: Vec<Rectangle> =
+            (0..100).map(|index| Rectangle { x: Unit(index as f64), y: Unit(0.0), width: Unit(1.0), height: Unit(1.0) }).collect();
+        let document = TinyVgBuilder::new(100, 100)
+            .outline_fill_polygon(polygon(64), RED, Unit(1.0), BLACK)
+            .outline_fill_polygon(polygon(100), RED, Unit(1.0), BLACK)
+            .outline_fill_rectangles(rectangles, RED, Unit(1.0), BLACK)
+            .build();
+
+        assert_eq!(document.validate(), Ok(()));
+        assert_eq!(
+            kinds(&document.draw_commands),
+            ["outline polygon", "fill polygon", "line loop", "outline rectangles", "outline rectangles"]
+        );
+    }
+
+    #[test]
+    fn canvas_splits_filled_and_stroked_paths_with_more_than_64_segments() {
+        let mut canvas = Canvas::new(100, 100);
+        canvas.set_fill(RED);
+        canvas.set_stroke(BLACK, 1.0);
+        for index in 0..65 {
+            canvas.rect(index as f64, 0.0, 1.0, 1.0);
+        }
+        canvas.fill_and_stroke();
+        let document = canvas.finish();
+
+        assert_eq!(document.validate(), Ok(()));
+        assert_eq!(kinds(&document.draw_commands), ["fill path", "line path"]);
+    }
+}
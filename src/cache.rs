@@ -0,0 +1,92 @@
+use crate::{TinyVg, TinyVgParseError};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
+
+/// Keeps parsed documents by the content of the bytes they were parsed from, so that
+/// loading the same asset again returns the document parsed before instead of parsing it
+/// anew. Holds at most `capacity` documents and drops the least recently used one to make
+/// room for another. Files that fail to parse are not kept.
+#[derive(Debug, Clone)]
+pub struct TinyVgCache {
+    capacity: usize,
+    entries: HashMap<u64, CacheEntry>,
+    /// Counts lookups, to find the least recently used entry.
+    clock: u64,
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    /// The bytes the document was parsed from, compared on lookups so that two files with
+    /// the same hash never share a document.
+    data: Box<[u8]>,
+    document: Arc<TinyVg>,
+    last_used: u64,
+}
+
+impl TinyVgCache {
+    pub fn new(capacity: usize) -> Self {
+        TinyVgCache { capacity, entries: HashMap::new(), clock: 0 }
+    }
+
+    /// The document parsed from `data`, parsing it only if no document was kept for the same
+    /// bytes.
+    pub fn get_or_parse(&mut self, data: &[u8]) -> Result<Arc<TinyVg>, TinyVgParseError> {
+        self.clock += 1;
+        let key = content_hash(data);
+        if let Some(entry) = self.entries.get_mut(&key)
+            && *entry.data == *data
+        {
+            entry.last_used = self.clock;
+            return Ok(entry.document.clone());
+        }
+
+        let document = Arc::new(TinyVg::from_bytes(data)?);
+        if self.capacity > 0 {
+            if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+                self.evict_least_recently_used();
+            }
+            let entry = CacheEntry { data: data.into(), document: document.clone(), last_used: self.clock };
+            self.entries.insert(key, entry);
+        }
+        Ok(document)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Changes the number of documents kept, dropping the least recently used ones that no
+    /// longer fit.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.entries.len() > capacity {
+            self.evict_least_recently_used();
+        }
+    }
+
+    /// The number of documents kept.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    fn evict_least_recently_used(&mut self) {
+        if let Some(key) = self.entries.iter().min_by_key(|(_, entry)| entry.last_used).map(|(key, _)| *key) {
+            self.entries.remove(&key);
+        }
+    }
+}
+
+fn content_hash(data: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    hasher.finish()
+}
@@ -0,0 +1,257 @@
+use crate::color_table::{ColorTable, RgbaF32};
+use crate::commands::{CenterArc, DrawCommand, Path, PathCommand, Point, Rectangle, Style};
+use crate::common::Unit;
+use crate::TinyVg;
+
+impl TinyVg {
+    /// Exports the document as a JSON command list that replays it on an HTML canvas
+    /// 2D context, for web embeds that cannot run a TinyVG renderer.
+    ///
+    /// The result has the form `{"width":w,"height":h,"commands":[...]}`, where every
+    /// command is an array of a context method or property name followed by its
+    /// arguments, such as `["moveTo",1,2]` or `["lineWidth",2]`. Colors are CSS hex
+    /// strings and gradients are objects of the form
+    /// `{"type":"linear","points":[x0,y0,x1,y1],"colors":[c0,c1]}`, or
+    /// `{"type":"radial","points":[x,y,r],"colors":[c0,c1]}`. A minimal player:
+    ///
+    /// ```js
+    /// function paint(ctx, value) {
+    ///   if (typeof value !== "object") return value;
+    ///   const gradient = value.type === "linear"
+    ///     ? ctx.createLinearGradient(...value.points)
+    ///     : ctx.createRadialGradient(value.points[0], value.points[1], 0, ...value.points);
+    ///   gradient.addColorStop(0, value.colors[0]);
+    ///   gradient.addColorStop(1, value.colors[1]);
+    ///   return gradient;
+    /// }
+    /// for (const [name, ...args] of doc.commands) {
+    ///   if (typeof ctx[name] === "function") ctx[name](...args);
+    ///   else ctx[name] = paint(ctx, args[0]);
+    /// }
+    /// ```
+    ///
+    /// Canvas paths have a single line width, so per-command line widths inside
+    /// paths are ignored. Text hints produce no commands.
+    pub fn to_canvas_json(&self) -> String {
+        let mut writer = CanvasWriter { color_table: &self.color_table, commands: Vec::new() };
+        writer.property("lineCap", "\"round\"".to_string());
+        writer.property("lineJoin", "\"round\"".to_string());
+
+        for command in &self.draw_commands {
+            match command {
+                DrawCommand::FillPolygon(data) => {
+                    writer.polygon(&data.points, true);
+                    writer.fill(&data.style, "evenodd");
+                }
+                DrawCommand::FillRectangles(data) => {
+                    writer.rectangles(&data.rectangles);
+                    writer.fill(&data.style, "nonzero");
+                }
+                DrawCommand::FillPath(data) => {
+                    writer.path(&data.path);
+                    writer.fill(&data.style, "evenodd");
+                }
+                DrawCommand::DrawLines(data) => {
+                    writer.call("beginPath", &[]);
+                    for line in &data.lines {
+                        writer.call("moveTo", &[line.start.x.0, line.start.y.0]);
+                        writer.call("lineTo", &[line.end.x.0, line.end.y.0]);
+                    }
+                    writer.stroke(&data.line_style, data.line_width);
+                }
+                DrawCommand::DrawLineLoop(data) => {
+                    writer.polygon(&data.points, true);
+                    writer.stroke(&data.line_style, data.line_width);
+                }
+                DrawCommand::DrawLineStrip(data) => {
+                    writer.polygon(&data.points, false);
+                    writer.stroke(&data.style, data.line_width);
+                }
+                DrawCommand::DrawLinePath(data) => {
+                    writer.path(&data.path);
+                    writer.stroke(&data.style, data.line_width);
+                }
+                DrawCommand::OutlineFillPolygon(data) => {
+                    writer.polygon(&data.points, true);
+                    writer.fill(&data.fill_style, "evenodd");
+                    writer.stroke(&data.line_style, data.line_width);
+                }
+                DrawCommand::OutlineFillRectangles(data) => {
+                    writer.rectangles(&data.rectangles);
+                    writer.fill(&data.fill_style, "nonzero");
+                    writer.stroke(&data.line_style, data.line_width);
+                }
+                DrawCommand::OutlineFillPath(data) => {
+                    writer.path(&data.path);
+                    writer.fill(&data.fill_style, "evenodd");
+                    writer.stroke(&data.line_style, data.line_width);
+                }
+                DrawCommand::TextHint(_) => {}
+            }
+        }
+
+        format!(
+            "{{\"width\":{},\"height\":{},\"commands\":[{}]}}",
+            self.header.width,
+            self.header.height,
+            writer.commands.join(",")
+        )
+    }
+}
+
+struct CanvasWriter<'a> {
+    color_table: &'a ColorTable,
+    commands: Vec<String>,
+}
+
+impl CanvasWriter<'_> {
+    fn call(&mut self, name: &str, arguments: &[f64]) {
+        let mut command = format!("[\"{name}\"");
+        for argument in arguments {
+            command.push(',');
+            command.push_str(&number(*argument));
+        }
+        command.push(']');
+        self.commands.push(command);
+    }
+
+    /// `value` must already be valid JSON.
+    fn property(&mut self, name: &str, value: String) {
+        self.commands.push(format!("[\"{name}\",{value}]"));
+    }
+
+    fn fill(&mut self, style: &Style, fill_rule: &str) {
+        let paint = self.paint(style);
+        self.property("fillStyle", paint);
+        self.commands.push(format!("[\"fill\",\"{fill_rule}\"]"));
+    }
+
+    fn stroke(&mut self, style: &Style, line_width: Unit) {
+        let paint = self.paint(style);
+        self.property("strokeStyle", paint);
+        self.property("lineWidth", number(line_width.0));
+        self.call("stroke", &[]);
+    }
+
+    fn polygon(&mut self, points: &[Point], closed: bool) {
+        self.call("beginPath", &[]);
+        for (i, point) in points.iter().enumerate() {
+            self.call(if i == 0 { "moveTo" } else { "lineTo" }, &[point.x.0, point.y.0]);
+        }
+        if closed {
+            self.call("closePath", &[]);
+        }
+    }
+
+    fn rectangles(&mut self, rectangles: &[Rectangle]) {
+        self.call("beginPath", &[]);
+        for rectangle in rectangles {
+            self.call("rect", &[rectangle.x.0, rectangle.y.0, rectangle.width.0, rectangle.height.0]);
+        }
+    }
+
+    fn path(&mut self, path: &Path) {
+        self.call("beginPath", &[]);
+        for segment in &path.segments {
+            let mut current = segment.start;
+            self.call("moveTo", &[current.x.0, current.y.0]);
+
+            for path_command in &segment.path_commands {
+                match path_command {
+                    PathCommand::Line(point, _) => {
+                        current = *point;
+                        self.call("lineTo", &[current.x.0, current.y.0]);
+                    }
+                    PathCommand::HorizontalLine(x, _) => {
+                        current = Point::new(*x, current.y);
+                        self.call("lineTo", &[current.x.0, current.y.0]);
+                    }
+                    PathCommand::VerticalLine(y, _) => {
+                        current = Point::new(current.x, *y);
+                        self.call("lineTo", &[current.x.0, current.y.0]);
+                    }
+                    PathCommand::CubicBezier(cubic, _) => {
+                        let (c0, c1, end) = (cubic.control_point_0, cubic.control_point_1, cubic.point_1);
+                        self.call("bezierCurveTo", &[c0.x.0, c0.y.0, c1.x.0, c1.y.0, end.x.0, end.y.0]);
+                        current = end;
+                    }
+                    PathCommand::QuadraticBezier(quadratic, _) => {
+                        let (control, end) = (quadratic.control_point, quadratic.point_1);
+                        self.call("quadraticCurveTo", &[control.x.0, control.y.0, end.x.0, end.y.0]);
+                        current = end;
+                    }
+                    PathCommand::ArcCircle(arc, _) => {
+                        let center_arc = CenterArc::from_endpoints(current, arc.target, arc.radius.0, arc.radius.0, 0.0, arc.large_arc, arc.sweep);
+                        self.arc(center_arc, arc.target);
+                        current = arc.target;
+                    }
+                    PathCommand::ArcEllipse(arc, _) => {
+                        let center_arc = CenterArc::from_endpoints(current, arc.target, arc.radius_x.0, arc.radius_y.0, arc.rotation.0, arc.large_arc, arc.sweep);
+                        self.arc(center_arc, arc.target);
+                        current = arc.target;
+                    }
+                    PathCommand::ClosePath(_) => {
+                        self.call("closePath", &[]);
+                        current = segment.start;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Canvas arcs use the center parameterization, so degenerate arcs become lines.
+    fn arc(&mut self, arc: Option<CenterArc>, target: Point) {
+        match arc {
+            Some(arc) => self.call("ellipse", &[
+                arc.center_x,
+                arc.center_y,
+                arc.radius_x,
+                arc.radius_y,
+                arc.rotation,
+                arc.start_angle,
+                arc.start_angle + arc.sweep_angle,
+                // The counterclockwise flag, as 1 or 0, which the canvas treats as a boolean.
+                if arc.sweep_angle < 0.0 { 1.0 } else { 0.0 },
+            ]),
+            None => self.call("lineTo", &[target.x.0, target.y.0]),
+        }
+    }
+
+    fn paint(&self, style: &Style) -> String {
+        match style {
+            Style::FlatColor(flat) => color(self.color_table, flat.color_index),
+            Style::LinearGradient(gradient) => format!(
+                "{{\"type\":\"linear\",\"points\":[{},{},{},{}],\"colors\":[{},{}]}}",
+                number(gradient.point_0.x.0),
+                number(gradient.point_0.y.0),
+                number(gradient.point_1.x.0),
+                number(gradient.point_1.y.0),
+                color(self.color_table, gradient.color_index_0),
+                color(self.color_table, gradient.color_index_1),
+            ),
+            Style::RadialGradient(gradient) => {
+                let radius = (gradient.point_1.x.0 - gradient.point_0.x.0).hypot(gradient.point_1.y.0 - gradient.point_0.y.0);
+                format!(
+                    "{{\"type\":\"radial\",\"points\":[{},{},{}],\"colors\":[{},{}]}}",
+                    number(gradient.point_0.x.0),
+                    number(gradient.point_0.y.0),
+                    number(radius),
+                    color(self.color_table, gradient.color_index_0),
+                    color(self.color_table, gradient.color_index_1),
+                )
+            }
+        }
+    }
+}
+
+/// Formats a number as JSON, which has no representation for NaN or infinity.
+fn number(value: f64) -> String {
+    if value.is_finite() { value.to_string() } else { "0".to_string() }
+}
+
+/// A color as a quoted `#rrggbbaa` string. Missing colors are transparent.
+fn color(color_table: &ColorTable, index: u64) -> String {
+    let RgbaF32(r, g, b, a) = color_table.get(index as usize).copied().unwrap_or(RgbaF32(0.0, 0.0, 0.0, 0.0));
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!("\"#{:02x}{:02x}{:02x}{:02x}\"", channel(r), channel(g), channel(b), channel(a))
+}
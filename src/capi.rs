@@ -0,0 +1,142 @@
+//! A C API for parsing documents and converting SVGs, enabled with the `capi` feature.
+//!
+//! The C header is generated with cbindgen: `cbindgen --config cbindgen.toml --output include/tinyvg.h`.
+//! The shared library is built with `cargo rustc --lib --release --features capi --crate-type cdylib`,
+//! so that Rust dependents of the crate do not build it as well.
+
+use crate::fit::FitMode;
+use crate::TinyVg;
+use std::ptr;
+
+/// A byte buffer owned by Rust. Release it with `tvg_buffer_free`.
+#[repr(C)]
+pub struct TvgBuffer {
+    pub data: *mut u8,
+    pub length: usize,
+}
+
+impl TvgBuffer {
+    fn empty() -> Self {
+        TvgBuffer { data: ptr::null_mut(), length: 0 }
+    }
+
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        let length = bytes.len();
+        let data = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+        TvgBuffer { data, length }
+    }
+}
+
+/// Parses a binary TinyVG document.
+///
+/// Returns null if `data` is null or does not hold a valid document. The returned
+/// document must be released with `tvg_free`.
+///
+/// # Safety
+///
+/// `data` must point to `length` readable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tvg_parse(data: *const u8, length: usize) -> *mut TinyVg {
+    if data.is_null() {
+        return ptr::null_mut();
+    }
+
+    let bytes = unsafe { std::slice::from_raw_parts(data, length) };
+    match TinyVg::from_bytes(bytes) {
+        Ok(document) => Box::into_raw(Box::new(document)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Releases a document returned by `tvg_parse`. Passing null does nothing.
+///
+/// # Safety
+///
+/// `document` must be null or a pointer returned by `tvg_parse` that was not freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tvg_free(document: *mut TinyVg) {
+    if !document.is_null() {
+        drop(unsafe { Box::from_raw(document) });
+    }
+}
+
+/// Writes the width and height of the document in display units. Returns false if any
+/// pointer is null.
+///
+/// # Safety
+///
+/// `document` must be a live pointer returned by `tvg_parse`, and `width` and `height`
+/// must be null or valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tvg_get_size(document: *const TinyVg, width: *mut u32, height: *mut u32) -> bool {
+    if document.is_null() || width.is_null() || height.is_null() {
+        return false;
+    }
+
+    let document = unsafe { &*document };
+    unsafe {
+        *width = document.header.width;
+        *height = document.header.height;
+    }
+    true
+}
+
+/// The largest image, in pixels, that `tvg_render_rgba8` renders. The renderer keeps
+/// several buffers per pixel, so this bounds its memory to well under a gigabyte.
+const MAX_RENDER_PIXELS: u64 = 4096 * 4096;
+
+/// Renders the document into an image of `width` by `height` pixels, scaled uniformly to
+/// fit and centered. The buffer holds four bytes per pixel, red, green, blue and alpha,
+/// in sRGB with straight alpha, row by row. Returns an empty buffer if `document` is null,
+/// the image is empty or larger than 4096 * 4096 pixels, or rendering failed.
+///
+/// # Safety
+///
+/// `document` must be null or a live pointer returned by `tvg_parse`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tvg_render_rgba8(document: *const TinyVg, width: u32, height: u32) -> TvgBuffer {
+    if document.is_null() || width == 0 || height == 0 || u64::from(width) * u64::from(height) > MAX_RENDER_PIXELS {
+        return TvgBuffer::empty();
+    }
+
+    let document = unsafe { &*document };
+    // Panics must not unwind into C.
+    match std::panic::catch_unwind(|| document.render_rgba((width, height), FitMode::Contain)) {
+        Ok(image) => TvgBuffer::from_vec(image.data),
+        Err(_) => TvgBuffer::empty(),
+    }
+}
+
+/// Converts an SVG document into a binary TinyVG document. Returns an empty buffer if
+/// the SVG could not be converted. Requires the `svg-to-tvg` feature.
+///
+/// # Safety
+///
+/// `svg` must point to `length` readable bytes.
+#[cfg(feature = "svg-to-tvg")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tvg_svg_to_tvg(svg: *const u8, length: usize) -> TvgBuffer {
+    if svg.is_null() {
+        return TvgBuffer::empty();
+    }
+
+    let bytes = unsafe { std::slice::from_raw_parts(svg, length) };
+    // The converter panics on malformed input, which must not unwind into C.
+    match std::panic::catch_unwind(|| crate::svg_to_tvg::svg_to_tvg::svg_to_tvg(bytes)) {
+        Ok(Ok(tvg)) => TvgBuffer::from_vec(tvg),
+        _ => TvgBuffer::empty(),
+    }
+}
+
+/// Releases a buffer returned by this library. Passing an empty buffer does nothing.
+///
+/// # Safety
+///
+/// `buffer` must have been returned by this library and not freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tvg_buffer_free(buffer: TvgBuffer) {
+    if !buffer.data.is_null() {
+        let slice = ptr::slice_from_raw_parts_mut(buffer.data, buffer.length);
+        drop(unsafe { Box::from_raw(slice) });
+    }
+}
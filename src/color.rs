@@ -0,0 +1,197 @@
+use crate::color_table::RgbaF32;
+
+/// Converts an sRGB encoded channel to linear light.
+pub(crate) fn srgb_to_linear(value: f64) -> f64 {
+    if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) }
+}
+
+/// Converts a linear light channel to sRGB encoding.
+pub(crate) fn linear_to_srgb(value: f64) -> f64 {
+    if value <= 0.0031308 { value * 12.92 } else { 1.055 * value.powf(1.0 / 2.4) - 0.055 }
+}
+
+/// A color in the Oklab perceptual color space as `(lightness, a, b)`.
+pub(crate) type Oklab = (f64, f64, f64);
+
+/// Converts the color channels of an sRGB color to Oklab, ignoring alpha.
+pub(crate) fn srgb_to_oklab(color: RgbaF32) -> Oklab {
+    let (r, g, b) = (srgb_to_linear(color.0 as f64), srgb_to_linear(color.1 as f64), srgb_to_linear(color.2 as f64));
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+    (
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    )
+}
+
+/// Converts an Oklab color to sRGB with the given alpha. The channels are not clamped,
+/// so colors outside the sRGB gamut have channels below 0 or above 1.
+pub(crate) fn oklab_to_srgb((lightness, a, b): Oklab, alpha: f32) -> RgbaF32 {
+    let l = (lightness + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+    let m = (lightness - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+    let s = (lightness - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+    RgbaF32(
+        linear_to_srgb(4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s) as f32,
+        linear_to_srgb(-1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s) as f32,
+        linear_to_srgb(-0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s) as f32,
+        alpha,
+    )
+}
+
+fn in_gamut(color: &RgbaF32) -> bool {
+    const MARGIN: f32 = 1e-4;
+    [color.0, color.1, color.2].iter().all(|channel| (-MARGIN..=1.0 + MARGIN).contains(channel))
+}
+
+/// Converts an Oklab color to sRGB, reducing its chroma while keeping lightness and hue
+/// until it fits the sRGB gamut.
+pub(crate) fn oklab_to_srgb_in_gamut((lightness, a, b): Oklab, alpha: f32) -> RgbaF32 {
+    let color = oklab_to_srgb((lightness, a, b), alpha);
+    if in_gamut(&color) {
+        return clamp(color);
+    }
+
+    let (mut low, mut high) = (0.0f64, 1.0f64);
+    for _ in 0..20 {
+        let middle = (low + high) / 2.0;
+        if in_gamut(&oklab_to_srgb((lightness, a * middle, b * middle), alpha)) {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    clamp(oklab_to_srgb((lightness, a * low, b * low), alpha))
+}
+
+fn clamp(color: RgbaF32) -> RgbaF32 {
+    RgbaF32(color.0.clamp(0.0, 1.0), color.1.clamp(0.0, 1.0), color.2.clamp(0.0, 1.0), color.3)
+}
+
+/// A color in the CIELAB color space under the D65 white point as `(lightness, a, b)`,
+/// with lightness from 0 to 100.
+pub(crate) type Lab = (f64, f64, f64);
+
+/// Converts the color channels of an sRGB color to CIELAB, ignoring alpha.
+pub(crate) fn srgb_to_lab(color: RgbaF32) -> Lab {
+    let (r, g, b) = (srgb_to_linear(color.0 as f64), srgb_to_linear(color.1 as f64), srgb_to_linear(color.2 as f64));
+    // XYZ relative to the D65 white point, so that white maps to 1 on every axis.
+    let x = (0.4124564 * r + 0.3575761 * g + 0.1804375 * b) / 0.95047;
+    let y = 0.2126729 * r + 0.7151522 * g + 0.0721750 * b;
+    let z = (0.0193339 * r + 0.1191920 * g + 0.9503041 * b) / 1.08883;
+
+    let f = |t: f64| if t > 216.0 / 24389.0 { t.cbrt() } else { (24389.0 / 27.0 * t + 16.0) / 116.0 };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    (116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz))
+}
+
+/// The CIEDE2000 color difference of two CIELAB colors, where about 1 is the smallest
+/// difference people notice side by side.
+pub(crate) fn ciede2000((l1, a1, b1): Lab, (l2, a2, b2): Lab) -> f64 {
+    let chroma_mean = (a1.hypot(b1) + a2.hypot(b2)) / 2.0;
+    let g = 0.5 * (1.0 - (chroma_mean.powi(7) / (chroma_mean.powi(7) + 25f64.powi(7))).sqrt());
+    let (a1, a2) = (a1 * (1.0 + g), a2 * (1.0 + g));
+    let (c1, c2) = (a1.hypot(b1), a2.hypot(b2));
+    let hue = |a: f64, b: f64| if a == 0.0 && b == 0.0 { 0.0 } else { b.atan2(a).to_degrees().rem_euclid(360.0) };
+    let (h1, h2) = (hue(a1, b1), hue(a2, b2));
+
+    let delta_l = l2 - l1;
+    let delta_c = c2 - c1;
+    let delta_h = if c1 * c2 == 0.0 {
+        0.0
+    } else if (h2 - h1).abs() <= 180.0 {
+        h2 - h1
+    } else if h2 <= h1 {
+        h2 - h1 + 360.0
+    } else {
+        h2 - h1 - 360.0
+    };
+    let delta_hue = 2.0 * (c1 * c2).sqrt() * (delta_h / 2.0).to_radians().sin();
+
+    let l_mean = (l1 + l2) / 2.0;
+    let c_mean = (c1 + c2) / 2.0;
+    let h_mean = if c1 * c2 == 0.0 {
+        h1 + h2
+    } else if (h1 - h2).abs() <= 180.0 {
+        (h1 + h2) / 2.0
+    } else if h1 + h2 < 360.0 {
+        (h1 + h2 + 360.0) / 2.0
+    } else {
+        (h1 + h2 - 360.0) / 2.0
+    };
+
+    let t = 1.0 - 0.17 * (h_mean - 30.0).to_radians().cos() + 0.24 * (2.0 * h_mean).to_radians().cos()
+        + 0.32 * (3.0 * h_mean + 6.0).to_radians().cos()
+        - 0.20 * (4.0 * h_mean - 63.0).to_radians().cos();
+    let delta_theta = 30.0 * (-((h_mean - 275.0) / 25.0).powi(2)).exp();
+    let rotation_c = 2.0 * (c_mean.powi(7) / (c_mean.powi(7) + 25f64.powi(7))).sqrt();
+    let s_l = 1.0 + 0.015 * (l_mean - 50.0).powi(2) / (20.0 + (l_mean - 50.0).powi(2)).sqrt();
+    let s_c = 1.0 + 0.045 * c_mean;
+    let s_h = 1.0 + 0.015 * c_mean * t;
+    let rotation = -(2.0 * delta_theta).to_radians().sin() * rotation_c;
+
+    let (l, c, h) = (delta_l / s_l, delta_c / s_c, delta_hue / s_h);
+    (l * l + c * c + h * h + rotation * c * h).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ciede2000, srgb_to_lab, Lab};
+    use crate::color_table::RgbaF32;
+
+    /// Pairs of CIELAB colors with their CIEDE2000 difference, from the test data of
+    /// Sharma, Wu and Dalal, "The CIEDE2000 Color-Difference Formula" (2005).
+    const SHARMA_PAIRS: [(Lab, Lab, f64); 34] = [
+        ((50.0, 2.6772, -79.7751), (50.0, 0.0, -82.7485), 2.0425),
+        ((50.0, 3.1571, -77.2803), (50.0, 0.0, -82.7485), 2.8615),
+        ((50.0, 2.8361, -74.0200), (50.0, 0.0, -82.7485), 3.4412),
+        ((50.0, -1.3802, -84.2814), (50.0, 0.0, -82.7485), 1.0000),
+        ((50.0, -1.1848, -84.8006), (50.0, 0.0, -82.7485), 1.0000),
+        ((50.0, -0.9009, -85.5211), (50.0, 0.0, -82.7485), 1.0000),
+        ((50.0, 0.0, 0.0), (50.0, -1.0, 2.0), 2.3669),
+        ((50.0, -1.0, 2.0), (50.0, 0.0, 0.0), 2.3669),
+        ((50.0, 2.49, -0.001), (50.0, -2.49, 0.0009), 7.1792),
+        ((50.0, 2.49, -0.001), (50.0, -2.49, 0.0010), 7.1792),
+        ((50.0, 2.49, -0.001), (50.0, -2.49, 0.0011), 7.2195),
+        ((50.0, 2.49, -0.001), (50.0, -2.49, 0.0012), 7.2195),
+        ((50.0, -0.001, 2.49), (50.0, 0.0009, -2.49), 4.8045),
+        ((50.0, -0.001, 2.49), (50.0, 0.0010, -2.49), 4.8045),
+        ((50.0, -0.001, 2.49), (50.0, 0.0011, -2.49), 4.7461),
+        ((50.0, 2.5, 0.0), (50.0, 0.0, -2.5), 4.3065),
+        ((50.0, 2.5, 0.0), (73.0, 25.0, -18.0), 27.1492),
+        ((50.0, 2.5, 0.0), (61.0, -5.0, 29.0), 22.8977),
+        ((50.0, 2.5, 0.0), (56.0, -27.0, -3.0), 31.9030),
+        ((50.0, 2.5, 0.0), (58.0, 24.0, 15.0), 19.4535),
+        ((50.0, 2.5, 0.0), (50.0, 3.1736, 0.5854), 1.0000),
+        ((50.0, 2.5, 0.0), (50.0, 3.2972, 0.0), 1.0000),
+        ((50.0, 2.5, 0.0), (50.0, 1.8634, 0.5757), 1.0000),
+        ((50.0, 2.5, 0.0), (50.0, 3.2592, 0.3350), 1.0000),
+        ((60.2574, -34.0099, 36.2677), (60.4626, -34.1751, 39.4387), 1.2644),
+        ((63.0109, -31.0961, -5.8663), (62.8187, -29.7946, -4.0864), 1.2630),
+        ((61.2901, 3.7196, -5.3901), (61.4292, 2.2480, -4.9620), 1.8731),
+        ((35.0831, -44.1164, 3.7933), (35.0232, -40.0716, 1.5901), 1.8645),
+        ((22.7233, 20.0904, -46.6940), (23.0331, 14.9730, -42.5619), 2.0373),
+        ((36.4612, 47.8580, 18.3852), (36.2715, 50.5065, 21.2231), 1.4146),
+        ((90.8027, -2.0831, 1.4410), (91.1528, -1.6435, 0.0447), 1.4441),
+        ((90.9257, -0.5406, -0.9208), (88.6381, -0.8985, -0.7239), 1.5381),
+        ((6.7747, -0.2908, -2.4247), (5.8714, -0.0985, -2.2286), 0.6377),
+        ((2.0776, 0.0795, -1.1350), (0.9033, -0.0636, -0.5514), 0.9082),
+    ];
+
+    #[test]
+    fn ciede2000_matches_sharma_test_data() {
+        for (first, second, expected) in SHARMA_PAIRS {
+            let difference = ciede2000(first, second);
+            assert!((difference - expected).abs() < 1e-4, "{first:?} and {second:?}: {difference}, expected {expected}");
+            assert!((ciede2000(second, first) - expected).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn lab_of_white_and_black() {
+        let close = |(l1, a1, b1): Lab, (l2, a2, b2): Lab| (l1 - l2).abs() + (a1 - a2).abs() + (b1 - b2).abs() < 1e-3;
+        assert!(close(srgb_to_lab(RgbaF32(1.0, 1.0, 1.0, 1.0)), (100.0, 0.0, 0.0)));
+        assert!(close(srgb_to_lab(RgbaF32(0.0, 0.0, 0.0, 1.0)), (0.0, 0.0, 0.0)));
+    }
+}
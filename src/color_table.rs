@@ -1,26 +1,116 @@
-use std::io::Cursor;
-use byteorder::{LittleEndian, ReadBytesExt};
-use crate::{TinyVgParseError};
+use crate::{TinyVg, TinyVgParseError};
+use crate::color::{linear_to_srgb, srgb_to_linear};
+use crate::common::Reader;
 use crate::header::{ColorEncoding, TinyVgHeader};
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct RgbaF32(pub f32, pub f32, pub f32, pub f32);
 
 pub type ColorTable = Vec<RgbaF32>;
 
-pub(crate) fn parse_color_table(cursor: &mut Cursor<&[u8]>, header: &TinyVgHeader) -> Result<ColorTable, TinyVgParseError> {
-    let mut color_table_rgba_f32 = Vec::with_capacity(header.color_count as usize);
+/// How the color channels of a color are encoded. Alpha is always linear.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ColorSpace {
+    /// Channels are encoded with the sRGB transfer function, as in `Rgba8888` and `Rgb565`.
+    Srgb,
+
+    /// Channels are linear light with the sRGB primaries, as in the scRGB values of `RgbaF32`.
+    Linear,
+}
+
+impl ColorEncoding {
+    /// The color space the encoding stores its channels in. Custom encodings are assumed
+    /// to be sRGB.
+    pub fn color_space(&self) -> ColorSpace {
+        match self {
+            ColorEncoding::RgbaF32 => ColorSpace::Linear,
+            ColorEncoding::Rgba8888 | ColorEncoding::Rgb565 | ColorEncoding::Custom => ColorSpace::Srgb,
+        }
+    }
+}
+
+/// The color every entry of a custom color table is parsed as, so that documents with
+/// colors the parser cannot decode still render as silhouettes.
+pub const CUSTOM_COLOR_PLACEHOLDER: RgbaF32 = RgbaF32(0.0, 0.0, 0.0, 1.0);
+
+/// The raw color table of a document with a custom color encoding, which only the
+/// application that defined the encoding can decode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomColors {
+    /// The size of each color in bytes.
+    pub color_size: usize,
+
+    /// The colors one after another, `color_size` bytes each.
+    pub bytes: Vec<u8>,
+}
+
+impl CustomColors {
+    /// Reads the custom color table of an encoded document whose colors are `color_size`
+    /// bytes each. Fails with `TinyVgParseError::InvalidColorTable` if the document does not
+    /// use a custom color encoding.
+    pub fn from_bytes(data: &[u8], color_size: usize) -> Result<CustomColors, TinyVgParseError> {
+        let mut reader = Reader::new(data, TinyVgParseError::InvalidHeader);
+        let header = TinyVgHeader::parse(&mut reader)?;
+        if header.color_encoding != ColorEncoding::Custom {
+            return Err(TinyVgParseError::InvalidColorTable);
+        }
+        reader.error = TinyVgParseError::InvalidColorTable;
+        let bytes = reader.take(color_table_length(&reader, &header, color_size)?)?;
+        Ok(CustomColors { color_size, bytes: bytes.to_vec() })
+    }
+
+    /// The bytes of the color at `index`.
+    pub fn get(&self, index: usize) -> Option<&[u8]> {
+        let start = index.checked_mul(self.color_size)?;
+        self.bytes.get(start..start.checked_add(self.color_size)?)
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len().checked_div(self.color_size).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
 
-    for _ in 0..header.color_count {
-        match header.color_encoding {
+fn color_table_length(reader: &Reader, header: &TinyVgHeader, color_size: usize) -> Result<usize, TinyVgParseError> {
+    usize::try_from(header.color_count).ok().and_then(|count| count.checked_mul(color_size)).ok_or(reader.error)
+}
+
+/// Parses the color table. Custom colors of `custom_color_size` bytes each are skipped and
+/// parsed as `CUSTOM_COLOR_PLACEHOLDER`. Without a size they cannot be skipped, and a
+/// custom color table that is not empty fails with `TinyVgParseError::InvalidColorTable`.
+pub(crate) fn parse_color_table(
+    reader: &mut Reader,
+    header: &TinyVgHeader,
+    custom_color_size: Option<usize>,
+) -> Result<ColorTable, TinyVgParseError> {
+    reader.error = TinyVgParseError::InvalidColorTable;
+    let color_size = match header.color_encoding {
+        ColorEncoding::Rgba8888 => 4,
+        ColorEncoding::Rgb565 => 2,
+        ColorEncoding::RgbaF32 => 16,
+        ColorEncoding::Custom if header.color_count == 0 => return Ok(Vec::new()),
+        ColorEncoding::Custom => {
+            // A size of 0 would let a tiny file claim any number of colors.
+            let color_size = custom_color_size.filter(|&size| size > 0).ok_or(reader.error)?;
+            reader.take(color_table_length(reader, header, color_size)?)?;
+            return Ok(vec![CUSTOM_COLOR_PLACEHOLDER; header.color_count as usize]);
+        }
+    };
+
+    // The whole table is bounds checked at once.
+    let bytes = reader.take(color_table_length(reader, header, color_size)?)?;
+
+    let color_table_rgba_f32 = bytes
+        .chunks_exact(color_size)
+        .map(|color| match header.color_encoding {
             ColorEncoding::Rgba8888 => {
-                let r = cursor.read_u8().map_err(|_| TinyVgParseError::InvalidColorTable)? as f32;
-                let g = cursor.read_u8().map_err(|_| TinyVgParseError::InvalidColorTable)? as f32;
-                let b = cursor.read_u8().map_err(|_| TinyVgParseError::InvalidColorTable)? as f32;
-                let a = cursor.read_u8().map_err(|_| TinyVgParseError::InvalidColorTable)? as f32;
-                color_table_rgba_f32.push(RgbaF32(r / 255.0, g / 255.0, b / 255.0, a / 255.0));
+                let [r, g, b, a] = [color[0], color[1], color[2], color[3]].map(|channel| channel as f32);
+                RgbaF32(r / 255.0, g / 255.0, b / 255.0, a / 255.0)
             }
             ColorEncoding::Rgb565 => {
-                let color = cursor.read_u16::<LittleEndian>().map_err(|_| TinyVgParseError::InvalidColorTable)?;
+                let color = u16::from_le_bytes([color[0], color[1]]);
                 const FIVE_BIT_MASK: u16 = 31;
                 const SIX_BIT_MASK: u16 = 63;
                 // Red color channel between 0 and 100% intensity, mapped to integer values 0 to 31.
@@ -29,20 +119,203 @@ pub(crate) fn parse_color_table(cursor: &mut Cursor<&[u8]>, header: &TinyVgHeade
                 let green: u8 = ((color >> 5) & SIX_BIT_MASK) as u8;
                 // Blue color channel between 0 and 100% intensity, mapped to integer values 0 to 31.
                 let blue: u8 = ((color >> 11) & FIVE_BIT_MASK) as u8;
-                color_table_rgba_f32.push(RgbaF32(red as f32 / 31.0, green as f32 / 63.0, blue as f32 / 31.0, 1.0));
+                RgbaF32(red as f32 / 31.0, green as f32 / 63.0, blue as f32 / 31.0, 1.0)
             }
             ColorEncoding::RgbaF32 => {
-                let r = cursor.read_f32::<LittleEndian>().map_err(|_| TinyVgParseError::InvalidColorTable)?;
-                let g = cursor.read_f32::<LittleEndian>().map_err(|_| TinyVgParseError::InvalidColorTable)?;
-                let b = cursor.read_f32::<LittleEndian>().map_err(|_| TinyVgParseError::InvalidColorTable)?;
-                let a = cursor.read_f32::<LittleEndian>().map_err(|_| TinyVgParseError::InvalidColorTable)?;
-
-                color_table_rgba_f32.push(RgbaF32(r, g, b, a));
+                let channel = |index: usize| f32::from_le_bytes([color[index], color[index + 1], color[index + 2], color[index + 3]]);
+                RgbaF32(channel(0), channel(4), channel(8), channel(12))
             }
-            ColorEncoding::Custom => unreachable!("Custom color encoding not supported.")
+            ColorEncoding::Custom => unreachable!("custom color tables return early"),
+        })
+        .collect();
+
+    Ok(color_table_rgba_f32)
+}
+  
+impl RgbaF32 {
+    /// Converts sRGB encoded channels to linear light, keeping alpha.
+    pub fn to_linear(&self) -> RgbaF32 {
+        let channel = |value: f32| srgb_to_linear(value as f64) as f32;
+        RgbaF32(channel(self.0), channel(self.1), channel(self.2), self.3)
+    }
 
+    /// Converts linear light channels to the sRGB encoding, keeping alpha.
+    pub fn to_srgb(&self) -> RgbaF32 {
+        let channel = |value: f32| linear_to_srgb(value as f64) as f32;
+        RgbaF32(channel(self.0), channel(self.1), channel(self.2), self.3)
+    }
+
+    /// The color with its channels multiplied by its alpha, as most GPU compositors expect.
+    /// Premultiply linear colors to blend correctly.
+    pub fn premultiplied(&self) -> RgbaF32 {
+        RgbaF32(self.0 * self.3, self.1 * self.3, self.2 * self.3, self.3)
+    }
+
+    /// Reverts `premultiplied`, giving the straight alpha colors that color tables store.
+    /// Fully transparent colors become transparent black.
+    pub fn unpremultiplied(&self) -> RgbaF32 {
+        if self.3 == 0.0 {
+            return RgbaF32(0.0, 0.0, 0.0, 0.0);
         }
+        RgbaF32(self.0 / self.3, self.1 / self.3, self.2 / self.3, self.3)
     }
 
-    Ok(color_table_rgba_f32)
-}   
\ No newline at end of file
+    /// Converts the color from one color space to another.
+    pub fn convert(&self, from: ColorSpace, to: ColorSpace) -> RgbaF32 {
+        match (from, to) {
+            (ColorSpace::Srgb, ColorSpace::Linear) => self.to_linear(),
+            (ColorSpace::Linear, ColorSpace::Srgb) => self.to_srgb(),
+            _ => *self,
+        }
+    }
+}
+
+impl ColorEncoding {
+    /// The smallest encoding that stores every color with channels, including alpha, off
+    /// by at most `tolerance`. `Rgb565` needs opaque colors, and colors with channels
+    /// outside the 0 to 1 range need `RgbaF32`. A tolerance of half an 8-bit step, `0.5 / 255`,
+    /// accepts `Rgba8888` for every color in range.
+    pub fn smallest_for(colors: &[RgbaF32], tolerance: f32) -> ColorEncoding {
+        // Absorbs the float error of the comparison itself, so that a tolerance of half a
+        // step accepts channels that lie exactly between two steps.
+        const ROUNDING_SLACK: f32 = 1e-6;
+        let fits = |colors: &[RgbaF32], steps: [f32; 4]| {
+            colors.iter().all(|color| {
+                [color.0, color.1, color.2, color.3].iter().zip(steps).all(|(&channel, steps)| {
+                    (0.0..=1.0).contains(&channel) && ((channel * steps).round() / steps - channel).abs() <= tolerance + ROUNDING_SLACK
+                })
+            })
+        };
+
+        if fits(colors, [31.0, 63.0, 31.0, 1.0]) && colors.iter().all(|color| color.3 == 1.0) {
+            ColorEncoding::Rgb565
+        } else if fits(colors, [255.0; 4]) {
+            ColorEncoding::Rgba8888
+        } else {
+            ColorEncoding::RgbaF32
+        }
+    }
+}
+
+impl TinyVg {
+    /// The color table converted to `color_space`. `TinyVg::color_table` holds the colors
+    /// as they are encoded, which is sRGB for `Rgba8888` and `Rgb565` and linear scRGB for
+    /// `RgbaF32`, so renderers that blend in one color space should read the colors here.
+    pub fn color_table_in(&self, color_space: ColorSpace) -> ColorTable {
+        let encoded = self.header.color_encoding.color_space();
+        self.color_table.iter().map(|color| color.convert(encoded, color_space)).collect()
+    }
+
+    /// The color table converted to `color_space` with premultiplied alpha, ready to be
+    /// uploaded for compositing.
+    pub fn premultiplied_color_table_in(&self, color_space: ColorSpace) -> ColorTable {
+        let mut color_table = self.color_table_in(color_space);
+        color_table.premultiply();
+        color_table
+    }
+}
+
+impl RgbaF32 {
+    /// Parses a hexadecimal color as `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`. The `#` is
+    /// optional. Returns `None` for anything else.
+    pub fn from_hex(hex: &str) -> Option<RgbaF32> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        if !digits.chars().all(|digit| digit.is_ascii_hexdigit()) {
+            return None;
+        }
+        let channel = |digits: &str| u8::from_str_radix(digits, 16).ok().map(|value| value as f32 / 255.0);
+        let short = |index: usize| channel(&digits[index..index + 1].repeat(2));
+        let long = |index: usize| channel(&digits[index * 2..index * 2 + 2]);
+
+        match digits.len() {
+            3 => Some(RgbaF32(short(0)?, short(1)?, short(2)?, 1.0)),
+            4 => Some(RgbaF32(short(0)?, short(1)?, short(2)?, short(3)?)),
+            6 => Some(RgbaF32(long(0)?, long(1)?, long(2)?, 1.0)),
+            8 => Some(RgbaF32(long(0)?, long(1)?, long(2)?, long(3)?)),
+            _ => None,
+        }
+    }
+
+    /// Formats the color as `#rrggbb`, or `#rrggbbaa` if it is not opaque. Channels are
+    /// clamped to the 0 to 1 range and rounded to 8 bits.
+    pub fn to_hex(&self) -> String {
+        let byte = |channel: f32| (channel.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let alpha = byte(self.3);
+        if alpha == 255 {
+            format!("#{:02x}{:02x}{:02x}", byte(self.0), byte(self.1), byte(self.2))
+        } else {
+            format!("#{:02x}{:02x}{:02x}{:02x}", byte(self.0), byte(self.1), byte(self.2), alpha)
+        }
+    }
+
+    /// Parses a CSS color in hexadecimal notation or as `rgb()` or `rgba()`. Both the comma
+    /// separated and the space separated syntax with an optional `/ alpha` are accepted,
+    /// and channels may be given as numbers or percentages. Named colors are not supported.
+    pub fn from_css(css: &str) -> Option<RgbaF32> {
+        let css = css.trim();
+        if css.starts_with('#') {
+            return RgbaF32::from_hex(css);
+        }
+
+        let lower = css.to_ascii_lowercase();
+        let arguments = lower.strip_prefix("rgba(").or_else(|| lower.strip_prefix("rgb("))?.strip_suffix(')')?;
+        let (channels, alpha) = match arguments.split_once('/') {
+            Some((channels, alpha)) => (channels, Some(alpha)),
+            None => (arguments, None),
+        };
+
+        let mut values: Vec<&str> = channels.split([',', ' ']).map(str::trim).filter(|value| !value.is_empty()).collect();
+        let alpha = match (alpha, values.len()) {
+            (Some(alpha), 3) => alpha.trim(),
+            (None, 4) => values.pop()?,
+            (None, 3) => "1",
+            _ => return None,
+        };
+
+        let color_channel = |value: &str| css_number(value, 255.0);
+        Some(RgbaF32(color_channel(values[0])?, color_channel(values[1])?, color_channel(values[2])?, css_number(alpha, 1.0)?))
+    }
+}
+
+/// Parses a CSS number or percentage and maps it to the 0 to 1 range, where `full` is the
+/// number that stands for 100%.
+fn css_number(value: &str, full: f32) -> Option<f32> {
+    let number = match value.strip_suffix('%') {
+        Some(percentage) => percentage.parse::<f32>().ok()? / 100.0,
+        None => value.parse::<f32>().ok()? / full,
+    };
+    number.is_finite().then(|| number.clamp(0.0, 1.0))
+}
+
+/// Color table operations that are not provided by `Vec`.
+pub trait ColorTableExt {
+    /// Parses a CSS color with `RgbaF32::from_css` and appends it to the table. Returns the
+    /// index of the new color, or `None` if the color could not be parsed.
+    fn push_css(&mut self, css: &str) -> Option<u64>;
+
+    /// Multiplies the channels of every color by its alpha, see `RgbaF32::premultiplied`.
+    fn premultiply(&mut self);
+
+    /// Converts premultiplied colors back to straight alpha before a table is written,
+    /// see `RgbaF32::unpremultiplied`.
+    fn unpremultiply(&mut self);
+}
+
+impl ColorTableExt for ColorTable {
+    fn push_css(&mut self, css: &str) -> Option<u64> {
+        self.push(RgbaF32::from_css(css)?);
+        Some((self.len() - 1) as u64)
+    }
+
+    fn premultiply(&mut self) {
+        for color in self.iter_mut() {
+            *color = color.premultiplied();
+        }
+    }
+
+    fn unpremultiply(&mut self) {
+        for color in self.iter_mut() {
+            *color = color.unpremultiplied();
+        }
+    }
+}
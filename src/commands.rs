@@ -1,11 +1,15 @@
-use crate::common::{read_unit, read_variable_sized_unsigned_number, Unit};
+use crate::borrowed::{DrawCommandRef, PathRef};
+use crate::common::{Reader, Unit, UnitDecoder};
+#[cfg(feature = "rayon")]
+use crate::encoded_size::unit_size;
 use crate::header::TinyVgHeader;
-use crate::TinyVgParseError;
-use byteorder::ReadBytesExt;
-use std::io::{Cursor, Read};
+use crate::{ParseOptions, TinyVgParseError, UnknownCommandPolicy};
+use std::borrow::Cow;
+use std::io::Cursor;
+use std::ops::{Add, Mul, Neg, Range, Sub};
 
 #[repr(u8)]
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum StyleType {
     /// Colored The shape is uniformly colored with a single color.
     Flat = 0,
@@ -16,16 +20,16 @@ pub enum StyleType {
 }
 
 impl StyleType {
-    pub(crate) fn from_u8(value: u8) -> Self {
+    /// The value 3 is reserved and makes the command invalid.
+    pub(crate) fn from_u8(value: u8) -> Result<Self, TinyVgParseError> {
         match value {
-            0 => Self::Flat,
-            1 => Self::Linear,
-            2 => Self::Radial,
-            _ => unreachable!("Style::from_u8 must be 0, 1, or 2.")
+            0 => Ok(Self::Flat),
+            1 => Ok(Self::Linear),
+            2 => Ok(Self::Radial),
+            _ => Err(TinyVgParseError::InvalidCommand)
         }
     }
 
-    #[allow(dead_code)]
     pub(crate) fn from_style(style: &Style) -> Self {
         match style {
             Style::FlatColor(..)   => StyleType::Flat,
@@ -35,13 +39,17 @@ impl StyleType {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct FlatColored {
     pub color_index: u64
 }
 impl FlatColored {
     pub fn read_from_cursor(cursor: &mut Cursor<&[u8]>) -> Result<FlatColored, TinyVgParseError> {
-        let color_index = read_variable_sized_unsigned_number(cursor)?;
+        Reader::with_cursor(cursor, TinyVgParseError::InvalidCommand, FlatColored::read)
+    }
+
+    fn read(reader: &mut Reader) -> Result<FlatColored, TinyVgParseError> {
+        let color_index = reader.read_variable_sized_unsigned_number()?;
 
         Ok(FlatColored {
             color_index,
@@ -50,7 +58,7 @@ impl FlatColored {
 
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct LinearGradient {
     pub point_0: Point,
     pub point_1: Point,
@@ -60,11 +68,15 @@ pub struct LinearGradient {
 
 impl LinearGradient {
     pub fn read_from_cursor(header: &TinyVgHeader, cursor: &mut Cursor<&[u8]>) -> Result<LinearGradient, TinyVgParseError> {
-        let point_0 = Point::read_point(header, cursor)?;
-        let point_1 = Point::read_point(header, cursor)?;
+        Reader::with_cursor(cursor, TinyVgParseError::InvalidCommand, |reader| LinearGradient::read(header, reader))
+    }
+
+    fn read(header: &TinyVgHeader, reader: &mut Reader) -> Result<LinearGradient, TinyVgParseError> {
+        let point_0 = reader.read_point(header)?;
+        let point_1 = reader.read_point(header)?;
 
-        let color_index_0 = read_variable_sized_unsigned_number(cursor)?;
-        let color_index_1 = read_variable_sized_unsigned_number(cursor)?;
+        let color_index_0 = reader.read_variable_sized_unsigned_number()?;
+        let color_index_1 = reader.read_variable_sized_unsigned_number()?;
 
         Ok(LinearGradient {
             point_0,
@@ -75,7 +87,7 @@ impl LinearGradient {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RadialGradient {
     pub point_0: Point,
     pub point_1: Point,
@@ -85,11 +97,15 @@ pub struct RadialGradient {
 
 impl RadialGradient {
     pub fn read_from_cursor(header: &TinyVgHeader, cursor: &mut Cursor<&[u8]>) -> Result<RadialGradient, TinyVgParseError> {
-        let point_0 = Point::read_point(header, cursor)?;
-        let point_1 = Point::read_point(header, cursor)?;
+        Reader::with_cursor(cursor, TinyVgParseError::InvalidCommand, |reader| RadialGradient::read(header, reader))
+    }
 
-        let color_index_0 = read_variable_sized_unsigned_number(cursor)?;
-        let color_index_1 = read_variable_sized_unsigned_number(cursor)?;
+    fn read(header: &TinyVgHeader, reader: &mut Reader) -> Result<RadialGradient, TinyVgParseError> {
+        let point_0 = reader.read_point(header)?;
+        let point_1 = reader.read_point(header)?;
+
+        let color_index_0 = reader.read_variable_sized_unsigned_number()?;
+        let color_index_1 = reader.read_variable_sized_unsigned_number()?;
 
         Ok(RadialGradient {
             point_0,
@@ -100,7 +116,7 @@ impl RadialGradient {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Style {
     FlatColor(FlatColored),
     LinearGradient(LinearGradient),
@@ -108,18 +124,18 @@ pub enum Style {
 }
 
 impl Style {
-    fn read_cursor_using_style_type(header: &TinyVgHeader, cursor: &mut Cursor<&[u8]>, style_type: &StyleType) ->  Result<Style, TinyVgParseError> {
+    fn read_using_style_type(header: &TinyVgHeader, reader: &mut Reader, style_type: &StyleType) ->  Result<Style, TinyVgParseError> {
         match style_type {
-            StyleType::Flat   => Ok(Style::FlatColor(FlatColored::read_from_cursor(cursor)?)),
-            StyleType::Linear => Ok(Style::LinearGradient(LinearGradient::read_from_cursor(header, cursor)?)),
-            StyleType::Radial => Ok(Style::RadialGradient(RadialGradient::read_from_cursor(header, cursor)?))
+            StyleType::Flat   => Ok(Style::FlatColor(FlatColored::read(reader)?)),
+            StyleType::Linear => Ok(Style::LinearGradient(LinearGradient::read(header, reader)?)),
+            StyleType::Radial => Ok(Style::RadialGradient(RadialGradient::read(header, reader)?))
         }
     }
 }
 
 /// The next draw command.
 #[repr(u8)]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum CommandType {
     /// This command determines the end of file.
     EndOfDocument = 0,
@@ -159,8 +175,10 @@ pub enum CommandType {
 }
 
 impl CommandType {
-    fn from_u8(value: u8) -> Self {
-        match value {
+    /// The command with the index `value`, or `None` for indices that this version of the
+    /// format does not define.
+    pub(crate) fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
             0 => Self::EndOfDocument,
             1 => Self::FillPolygon,
             2 => Self::FillRectangles,
@@ -173,25 +191,18 @@ impl CommandType {
             9 => Self::OutlineFillRectangles,
             10 => Self::OutlineFillPath,
             11 => Self::TextHint,
-            _ => unreachable!("Style::from_u8 must be in the range 0 to 11.")
-        }
+            _ => return None,
+        })
     }
 }
 
-#[derive(Debug, Copy, Clone,)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Point {
     pub x: Unit,
     pub y: Unit,
 }
 
 impl Point {
-    fn read_point(header: &TinyVgHeader, cursor: &mut Cursor<&[u8]>) ->  Result<Point, TinyVgParseError> {
-        let x = read_unit(header.scale, cursor, &header.coordinate_range)?;
-        let y = read_unit(header.scale, cursor, &header.coordinate_range)?;
-        let start = Point { x, y };
-        Ok(start)
-    }
-    
     pub fn move_to(&self, point: &Point) -> Self {
         point.clone()
     }
@@ -202,9 +213,62 @@ impl Point {
             y
         }
     }
+
+    /// The fixed-point values of both coordinates, see `Unit::to_raw`.
+    pub fn to_raw(&self, scale: u8) -> (i32, i32) {
+        (self.x.to_raw(scale), self.y.to_raw(scale))
+    }
+
+    /// The straight-line distance to `other` in display units.
+    pub fn distance(&self, other: &Point) -> f64 {
+        (other.x.0 - self.x.0).hypot(other.y.0 - self.y.0)
+    }
+
+    /// The point a fraction `t` of the way from `self` to `other`.
+    pub fn lerp(&self, other: &Point, t: f64) -> Point {
+        Point::new(self.x.lerp(other.x, t), self.y.lerp(other.y, t))
+    }
+}
+
+impl From<(f64, f64)> for Point {
+    fn from((x, y): (f64, f64)) -> Self {
+        Point::new(Unit(x), Unit(y))
+    }
+}
+
+impl Add for Point {
+    type Output = Point;
+
+    fn add(self, other: Point) -> Point {
+        Point::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for Point {
+    type Output = Point;
+
+    fn sub(self, other: Point) -> Point {
+        Point::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Mul<f64> for Point {
+    type Output = Point;
+
+    fn mul(self, factor: f64) -> Point {
+        Point::new(self.x * factor, self.y * factor)
+    }
 }
 
-#[derive(Debug)]
+impl Neg for Point {
+    type Output = Point;
+
+    fn neg(self) -> Point {
+        Point::new(-self.x, -self.y)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Rectangle {
     pub x: Unit,
     pub y: Unit,
@@ -213,16 +277,12 @@ pub struct Rectangle {
 }
 
 impl Rectangle {
-    fn read_rectangle(header: &TinyVgHeader, cursor: &mut Cursor<&[u8]>) ->  Result<Rectangle, TinyVgParseError> {
-        let x = read_unit(header.scale, cursor, &header.coordinate_range)?;
-        let y = read_unit(header.scale, cursor, &header.coordinate_range)?;
-        let width = read_unit(header.scale, cursor, &header.coordinate_range)?;
-        let height = read_unit(header.scale, cursor, &header.coordinate_range)?;
-        Ok(Rectangle { x, y, width, height})
+    fn decode(units: &UnitDecoder, bytes: &[u8]) -> Rectangle {
+        Rectangle { x: units.unit(bytes, 0), y: units.unit(bytes, 1), width: units.unit(bytes, 2), height: units.unit(bytes, 3) }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Line {
     /// Start point of the line
     pub start: Point,
@@ -231,68 +291,225 @@ pub struct Line {
 }
 
 impl Line {
-    fn read_line(header: &TinyVgHeader, cursor: &mut Cursor<&[u8]>) ->  Result<Line, TinyVgParseError> {
-        let start = Point::read_point(header, cursor)?;
-        let end = Point::read_point(header, cursor)?;
-        Ok(Line{ start, end })
+    fn decode(units: &UnitDecoder, bytes: &[u8]) -> Line {
+        Line { start: units.point(bytes, 0), end: units.point(bytes, 2) }
+    }
+}
+
+/// The points of a polygon or line, see `InlineVec`.
+pub type Points = InlineVec<Point, 8>;
+
+/// The path commands of a segment, see `InlineVec`.
+pub type PathCommands = InlineVec<PathCommand, 4>;
+
+/// A list that dereferences to a slice, used for the points and path commands of draw
+/// commands. With the `smallvec` feature, lists of up to `N` items are stored inline
+/// instead of in a heap allocation of their own. The storage is not part of the type, so
+/// enabling the feature changes no API.
+#[derive(Clone, PartialEq)]
+pub struct InlineVec<T, const N: usize> {
+    #[cfg(not(feature = "smallvec"))]
+    items: Vec<T>,
+    #[cfg(feature = "smallvec")]
+    items: smallvec::SmallVec<[T; N]>,
+}
+
+impl<T, const N: usize> InlineVec<T, N> {
+    pub fn new() -> Self {
+        InlineVec { items: Default::default() }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        #[cfg(not(feature = "smallvec"))]
+        let items = Vec::with_capacity(capacity);
+        #[cfg(feature = "smallvec")]
+        let items = smallvec::SmallVec::with_capacity(capacity);
+        InlineVec { items }
+    }
+
+    pub fn push(&mut self, item: T) {
+        self.items.push(item);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        self.items.pop()
+    }
+
+    pub fn insert(&mut self, index: usize, item: T) {
+        self.items.insert(index, item);
+    }
+
+    pub fn remove(&mut self, index: usize) -> T {
+        self.items.remove(index)
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.items.truncate(len);
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    /// Moves all items of `other` to the end of the list, leaving `other` empty.
+    pub fn append(&mut self, other: &mut Self) {
+        self.items.append(&mut other.items);
+    }
+
+    pub fn drain(&mut self, range: impl std::ops::RangeBounds<usize>) -> impl Iterator<Item = T> + '_ {
+        self.items.drain(range)
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&T) -> bool) {
+        self.items.retain(|item| keep(item));
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        #[cfg(not(feature = "smallvec"))]
+        return self.items;
+        #[cfg(feature = "smallvec")]
+        return self.items.into_vec();
+    }
+}
+
+impl<T, const N: usize> Default for InlineVec<T, N> {
+    fn default() -> Self {
+        InlineVec::new()
+    }
+}
+
+impl<T: std::fmt::Debug, const N: usize> std::fmt::Debug for InlineVec<T, N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T, const N: usize> std::ops::Deref for InlineVec<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.items
+    }
+}
+
+impl<T, const N: usize> std::ops::DerefMut for InlineVec<T, N> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        &mut self.items
+    }
+}
+
+impl<T, const N: usize> From<Vec<T>> for InlineVec<T, N> {
+    fn from(items: Vec<T>) -> Self {
+        #[cfg(not(feature = "smallvec"))]
+        return InlineVec { items };
+        #[cfg(feature = "smallvec")]
+        return InlineVec { items: smallvec::SmallVec::from_vec(items) };
+    }
+}
+
+impl<T: Clone, const N: usize> From<&[T]> for InlineVec<T, N> {
+    fn from(items: &[T]) -> Self {
+        InlineVec { items: items.into() }
+    }
+}
+
+impl<T, const N: usize> FromIterator<T> for InlineVec<T, N> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        InlineVec { items: iter.into_iter().collect() }
+    }
+}
+
+impl<T, const N: usize> Extend<T> for InlineVec<T, N> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.items.extend(iter);
     }
 }
 
-#[derive(Debug)]
+impl<T, const N: usize> IntoIterator for InlineVec<T, N> {
+    type Item = T;
+    #[cfg(not(feature = "smallvec"))]
+    type IntoIter = std::vec::IntoIter<T>;
+    #[cfg(feature = "smallvec")]
+    type IntoIter = smallvec::IntoIter<[T; N]>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a InlineVec<T, N> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a mut InlineVec<T, N> {
+    type Item = &'a mut T;
+    type IntoIter = std::slice::IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter_mut()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct FillPolygonData {
     pub style: Style,
-    pub points: Vec<Point>,
+    pub points: Points,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct FillRectanglesData {
     pub style: Style,
     pub rectangles: Vec<Rectangle>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct FillPathData {
     pub style: Style,
     pub path: Path,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct DrawLinesData {
     pub lines: Vec<Line>,
     pub line_width: Unit,
     pub line_style: Style,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct DrawLineLoopData {
     pub line_style: Style,
     pub line_width: Unit,
-    pub points: Vec<Point>
+    pub points: Points
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct DrawLineStripData {
     pub style: Style,
     pub line_width: Unit,
-    pub points: Vec<Point>
+    pub points: Points
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct DrawLinePathData {
     pub style: Style,
     pub line_width: Unit,
     pub path: Path,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct OutlineFillPolygonData {
     pub fill_style: Style,
     pub line_style: Style,
     pub line_width: Unit,
-    pub points: Vec<Point>,
+    pub points: Points,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct OutlineFillRectanglesData {
     pub fill_style: Style,
     pub line_style: Style,
@@ -300,7 +517,7 @@ pub struct OutlineFillRectanglesData {
     pub rectangles: Vec<Rectangle>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct OutlineFillPathData {
     pub path: Path,
     pub fill_style: Style,
@@ -308,7 +525,7 @@ pub struct OutlineFillPathData {
     pub line_width: Unit
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct TextHintData {
     /// The center of the descender line for the defined text.
     pub center: Point,
@@ -325,7 +542,28 @@ pub struct TextHintData {
     pub glyph_offset: Vec<(Unit, Unit)>,
 }
 
-#[derive(Debug)]
+impl TextHintData {
+    /// Replaces the text, as for localized variants of a document. Without font metrics
+    /// the glyphs are assumed to be one per character and of equal width, spread over the
+    /// extent of the previous glyphs. With the `text` feature, `set_text_with_font`
+    /// computes the real glyph offsets instead.
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        self.text = text.into();
+        let glyph_count = self.text.chars().count();
+
+        let start = self.glyph_offset.iter().map(|(start, _)| start.0).fold(f64::INFINITY, f64::min);
+        let end = self.glyph_offset.iter().map(|(_, end)| end.0).fold(f64::NEG_INFINITY, f64::max);
+        let (start, end) = if start <= end { (start, end) } else { (0.0, 0.0) };
+        let glyph_width = if glyph_count > 0 { (end - start) / glyph_count as f64 } else { 0.0 };
+
+        self.glyph_offset = (0..glyph_count)
+            .map(|index| (Unit(start + glyph_width * index as f64), Unit(start + glyph_width * (index + 1) as f64)))
+            .collect();
+        self.glyph_length = glyph_count as u64;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum DrawCommand {
     /// This command fills an N-gon.
     FillPolygon(FillPolygonData),
@@ -363,9 +601,28 @@ pub enum DrawCommand {
     TextHint(TextHintData)
 }
 
+impl DrawCommand {
+    /// The type the command is encoded as.
+    pub fn command_type(&self) -> CommandType {
+        match self {
+            DrawCommand::FillPolygon(_) => CommandType::FillPolygon,
+            DrawCommand::FillRectangles(_) => CommandType::FillRectangles,
+            DrawCommand::FillPath(_) => CommandType::FillPath,
+            DrawCommand::DrawLines(_) => CommandType::DrawLines,
+            DrawCommand::DrawLineLoop(_) => CommandType::DrawLineLoop,
+            DrawCommand::DrawLineStrip(_) => CommandType::DrawLineStrip,
+            DrawCommand::DrawLinePath(_) => CommandType::DrawLinePath,
+            DrawCommand::OutlineFillPolygon(_) => CommandType::OutlineFillPolygon,
+            DrawCommand::OutlineFillRectangles(_) => CommandType::OutlineFillRectangles,
+            DrawCommand::OutlineFillPath(_) => CommandType::OutlineFillPath,
+            DrawCommand::TextHint(_) => CommandType::TextHint,
+        }
+    }
+}
+
 #[repr(u8)]
-#[derive(Debug, PartialEq)]
-pub(crate) enum PathCommandType {
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PathCommandType {
     Line = 0,
     HorizontalLine = 1,
     VerticalLine = 2,
@@ -377,7 +634,7 @@ pub(crate) enum PathCommandType {
 }
 
 impl PathCommandType {
-    fn from_u8(value: u8) -> Self {
+    pub(crate) fn from_u8(value: u8) -> Self {
         match value {
             0 => Self::Line,
             1 => Self::HorizontalLine,
@@ -392,14 +649,14 @@ impl PathCommandType {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct CubicBezier {
     pub control_point_0: Point,
     pub control_point_1: Point,
     pub point_1: Point,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ArcCircle {
     pub large_arc: bool,
     pub sweep: bool,
@@ -407,7 +664,7 @@ pub struct ArcCircle {
     pub target: Point,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ArcEllipse {
     pub large_arc: bool,
     pub sweep: bool,
@@ -417,13 +674,59 @@ pub struct ArcEllipse {
     pub target: Point,
 }
 
-#[derive(Debug, Clone)]
+impl ArcCircle {
+    /// Approximates the arc drawn from `start` with cubic Bézier curves, one per quarter
+    /// turn or less, for backends without arc primitives.
+    ///
+    /// Radii that are too small to span the endpoints are scaled up as in SVG. An arc with
+    /// a zero radius becomes a single straight cubic, and an arc that ends where it
+    /// starts draws nothing.
+    pub fn to_cubics(&self, start: Point) -> Vec<CubicBezier> {
+        arc_to_cubics(start, self.target, self.radius.0, self.radius.0, 0.0, self.large_arc, self.sweep)
+    }
+}
+
+impl ArcEllipse {
+    /// Approximates the arc drawn from `start` with cubic Bézier curves, one per quarter
+    /// turn or less, for backends without arc primitives. See `ArcCircle::to_cubics`.
+    pub fn to_cubics(&self, start: Point) -> Vec<CubicBezier> {
+        arc_to_cubics(start, self.target, self.radius_x.0, self.radius_y.0, self.rotation.0, self.large_arc, self.sweep)
+    }
+}
+
+fn arc_to_cubics(start: Point, target: Point, radius_x: f64, radius_y: f64, rotation: f64, large_arc: bool, sweep: bool) -> Vec<CubicBezier> {
+    if start.x.0 == target.x.0 && start.y.0 == target.y.0 {
+        return Vec::new();
+    }
+
+    match CenterArc::from_endpoints(start, target, radius_x, radius_y, rotation, large_arc, sweep) {
+        Some(arc) => {
+            let mut cubics = arc.to_cubics();
+            // Land exactly on the target rather than on the evaluated end angle.
+            if let Some(last) = cubics.last_mut() {
+                last.point_1 = target;
+            }
+            cubics
+        }
+        None => {
+            let third = |t: f64| {
+                Point::new(
+                    Unit(start.x.0 + (target.x.0 - start.x.0) * t),
+                    Unit(start.y.0 + (target.y.0 - start.y.0) * t),
+                )
+            };
+            vec![CubicBezier { control_point_0: third(1.0 / 3.0), control_point_1: third(2.0 / 3.0), point_1: target }]
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct QuadraticBezier {
     pub control_point: Point,
     pub point_1: Point,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum PathCommand {
     Line(Point, Option<Unit>),
     HorizontalLine(Unit, Option<Unit>),
@@ -431,70 +734,93 @@ pub enum PathCommand {
     CubicBezier(CubicBezier, Option<Unit>),
     ArcCircle(ArcCircle, Option<Unit>),
     ArcEllipse(ArcEllipse, Option<Unit>),
-    ClosePath,
+    ClosePath(Option<Unit>),
     QuadraticBezier(QuadraticBezier, Option<Unit>),
 }
 
-#[derive(Debug, Clone)]
+impl PathCommand {
+    /// The type the path command is encoded as.
+    pub fn command_type(&self) -> PathCommandType {
+        match self {
+            PathCommand::Line(..) => PathCommandType::Line,
+            PathCommand::HorizontalLine(..) => PathCommandType::HorizontalLine,
+            PathCommand::VerticalLine(..) => PathCommandType::VerticalLine,
+            PathCommand::CubicBezier(..) => PathCommandType::CubicBezier,
+            PathCommand::ArcCircle(..) => PathCommandType::ArcCircle,
+            PathCommand::ArcEllipse(..) => PathCommandType::ArcEllipse,
+            PathCommand::ClosePath(..) => PathCommandType::ClosePath,
+            PathCommand::QuadraticBezier(..) => PathCommandType::QuadraticBezier,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Segment {
     pub start: Point,
-    pub path_commands: Vec<PathCommand>,
+    pub path_commands: PathCommands,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Path {
     pub segments: Vec<Segment>,
 }
 
 impl Path {
     pub fn parse(cursor: &mut Cursor<&[u8]>, header: &TinyVgHeader, segment_count: usize) -> Result<Self, TinyVgParseError> {
-        let mut segment_command_counts: Vec<usize> = Vec::new();
+        Reader::with_cursor(cursor, TinyVgParseError::InvalidCommand, |reader| Path::read(reader, &UnitDecoder::new(header), segment_count))
+    }
+
+    pub(crate) fn read(reader: &mut Reader, units: &UnitDecoder, segment_count: usize) -> Result<Self, TinyVgParseError> {
+        // The command counts of all segments come first. They are skipped here and read
+        // again with a second reader while parsing the segments, which saves collecting them.
+        let mut segment_command_counts = reader.clone();
         for _ in 0..segment_count {
-            let segment_length = read_variable_sized_unsigned_number(cursor)? + 1;
-            segment_command_counts.push(segment_length as usize);
+            reader.read_variable_sized_unsigned_number()?;
         }
 
-        let mut segments: Vec<Segment> = Vec::new();
+        // Every segment holds at least its start point and one path command tag.
+        let mut segments: Vec<Segment> = Vec::with_capacity(reader.bounded_capacity(segment_count as u64, 2 * units.size + 1));
 
-        for i in 0..segment_count {
-            let start = Point::read_point(header, cursor)?;
+        for _ in 0..segment_count {
+            let commands_count = segment_command_counts.read_count()?;
+            let start = units.point(reader.take(2 * units.size)?, 0);
 
             let mut segment = Segment {
                 start,
-                path_commands: vec![],
+                path_commands: PathCommands::with_capacity(reader.bounded_capacity(commands_count, 1)),
             };
 
-            let commands_count = segment_command_counts[i];
-
             for _ in 0..commands_count {
-                let command_tag = cursor.read_u8().map_err(|_| TinyVgParseError::InvalidCommand)?;
+                let command_tag = reader.read_u8()?;
                 let path_command_raw = command_tag & 0b00_00_01_11;
                 let path_command = PathCommandType::from_u8(path_command_raw);
                 let has_line_width = (command_tag & 0b00_01_00_00) != 0;
-                let mut line_width: Option<Unit> = None;
 
-                if has_line_width {
-                    // FIXME: Figure out how this should be used in the Vello example.
-                    line_width = Some(read_unit(header.scale, cursor, &header.coordinate_range)?);
-                }
+                // The line width and the payload are bounds checked together.
+                let line_width_size = if has_line_width { units.size } else { 0 };
+                let bytes = reader.take(line_width_size + path_command_payload_size(path_command, units.size))?;
+                let (line_width_bytes, payload) = bytes.split_at(line_width_size);
+
+                // FIXME: Figure out how this should be used in the Vello example.
+                let line_width = has_line_width.then(|| units.unit(line_width_bytes, 0));
 
                 match path_command {
                     PathCommandType::Line => {
-                        let point = Point::read_point(header, cursor)?;
+                        let point = units.point(payload, 0);
                         segment.path_commands.push(PathCommand::Line(point, line_width));
                     }
                     PathCommandType::HorizontalLine => {
-                        let pos_x = read_unit(header.scale, cursor, &header.coordinate_range)?;
+                        let pos_x = units.unit(payload, 0);
                         segment.path_commands.push(PathCommand::HorizontalLine(pos_x, line_width));
                     }
                     PathCommandType::VerticalLine => {
-                        let pos_y = read_unit(header.scale, cursor, &header.coordinate_range)?;
+                        let pos_y = units.unit(payload, 0);
                         segment.path_commands.push(PathCommand::VerticalLine(pos_y, line_width));
                     }
                     PathCommandType::CubicBezier => {
-                        let control_0 = Point::read_point(header, cursor)?;
-                        let control_1 = Point::read_point(header, cursor)?;
-                        let point_1 = Point::read_point(header, cursor)?;
+                        let control_0 = units.point(payload, 0);
+                        let control_1 = units.point(payload, 2);
+                        let point_1 = units.point(payload, 4);
 
                         segment.path_commands.push(PathCommand::CubicBezier(
                             CubicBezier {
@@ -506,11 +832,11 @@ impl Path {
                         ));
                     }
                     PathCommandType::ArcCircle => {
-                        let large_arc_sweep_padding = cursor.read_u8().map_err(|_| TinyVgParseError::InvalidCommand)?;
+                        let (large_arc_sweep_padding, payload) = (payload[0], &payload[1..]);
                         let large_arc = (large_arc_sweep_padding & 0b00_00_00_01) != 0;
                         let sweep = (large_arc_sweep_padding & 0b00_00_00_10) != 0;
-                        let radius = read_unit(header.scale, cursor, &header.coordinate_range)?;
-                        let target = Point::read_point(header, cursor)?;
+                        let radius = units.unit(payload, 0);
+                        let target = units.point(payload, 1);
 
                         segment.path_commands.push(PathCommand::ArcCircle(
                             ArcCircle {
@@ -523,14 +849,14 @@ impl Path {
                         ))
                     }
                     PathCommandType::ArcEllipse => {
-                        let large_arc_sweep_padding = cursor.read_u8().map_err(|_| TinyVgParseError::InvalidCommand)?;
+                        let (large_arc_sweep_padding, payload) = (payload[0], &payload[1..]);
                         let large_arc = (large_arc_sweep_padding & 0b00_00_00_01) != 0;
                         let sweep = (large_arc_sweep_padding & 0b00_00_00_10) != 0;
 
-                        let radius_x = read_unit(header.scale, cursor, &header.coordinate_range)?;
-                        let radius_y = read_unit(header.scale, cursor, &header.coordinate_range)?;
-                        let rotation = read_unit(header.scale, cursor, &header.coordinate_range)?;
-                        let target = Point::read_point(header, cursor)?;
+                        let radius_x = units.unit(payload, 0);
+                        let radius_y = units.unit(payload, 1);
+                        let rotation = units.unit(payload, 2);
+                        let target = units.point(payload, 3);
 
                         let arc_ellipse = ArcEllipse {
                             large_arc,
@@ -543,11 +869,11 @@ impl Path {
                         segment.path_commands.push(PathCommand::ArcEllipse(arc_ellipse, line_width));
                     }
                     PathCommandType::ClosePath => {
-                        segment.path_commands.push(PathCommand::ClosePath);
+                        segment.path_commands.push(PathCommand::ClosePath(line_width));
                     }
                     PathCommandType::QuadraticBezier => {
-                        let control = Point::read_point(header, cursor)?;
-                        let point_1 = Point::read_point(header, cursor)?;
+                        let control = units.point(payload, 0);
+                        let point_1 = units.point(payload, 2);
 
                         let quadratic_bezier = QuadraticBezier {
                             control_point: control,
@@ -567,327 +893,1066 @@ impl Path {
     }
 }
 
-pub(crate) fn parse_draw_commands(cursor: &mut Cursor<&[u8]>, header: &TinyVgHeader) -> Result<Vec<DrawCommand>, TinyVgParseError> {
-    let mut draw_commands: Vec<DrawCommand> = Vec::new();
+/// The number of bytes after the tag and line width of a path command.
+fn path_command_payload_size(path_command: PathCommandType, unit: usize) -> usize {
+    match path_command {
+        PathCommandType::Line => 2 * unit,
+        PathCommandType::HorizontalLine | PathCommandType::VerticalLine => unit,
+        PathCommandType::CubicBezier => 6 * unit,
+        // Arcs start with a byte holding their flags.
+        PathCommandType::ArcCircle => 1 + 3 * unit,
+        PathCommandType::ArcEllipse => 1 + 5 * unit,
+        PathCommandType::ClosePath => 0,
+        PathCommandType::QuadraticBezier => 4 * unit,
+    }
+}
 
-    loop {
-        let encoded_command = cursor.read_u8().map_err(|_| TinyVgParseError::InvalidCommand)?;
-        // bits 0–6 = command_index
-        let command_index = encoded_command & 0b00_11_11_11;
-        // bits 7-8 = prim_style_kind
-        let prim_style_kind = (encoded_command & 0b11_00_00_00) >> 6;
+impl Path {
+    /// Approximates every segment of the path with straight lines, so that no point of
+    /// the flattened outline is further than `tolerance` from the real curve, and yields
+    /// the points of all segments one after another. Use `flatten_segments` to keep the
+    /// segments apart.
+    pub fn flatten(&self, tolerance: f64) -> impl Iterator<Item = Point> + '_ {
+        self.segments.iter().flat_map(move |segment| segment.flatten(tolerance))
+    }
 
-        let command = CommandType::from_u8(command_index);
+    /// Flattens each segment of the path into its own polyline, see `Segment::flatten`.
+    pub fn flatten_segments(&self, tolerance: f64) -> impl Iterator<Item = Vec<Point>> + '_ {
+        self.segments.iter().map(move |segment| segment.flatten(tolerance).collect())
+    }
+}
 
-        // If this command is read, the TinyVG file has ended. This command must have prim_style_kind to be
-        // set to 0, so the last byte of every TinyVG file is 0x00.
-        if matches!(command, CommandType::EndOfDocument) {
-            break;
+impl Path {
+    /// The path as SVG path data, for the `d` attribute of an SVG `<path>`. Every segment
+    /// starts with an absolute move-to and all commands use absolute coordinates. Arcs are
+    /// kept as `A` commands, and the line widths of path commands, which SVG has no place
+    /// for, are dropped.
+    pub fn to_svg_d(&self) -> String {
+        let mut data = String::new();
+        for segment in &self.segments {
+            push_svg_command(&mut data, 'M', &[segment.start.x.0, segment.start.y.0]);
+            for path_command in &segment.path_commands {
+                match path_command {
+                    PathCommand::Line(point, _) => push_svg_command(&mut data, 'L', &[point.x.0, point.y.0]),
+                    PathCommand::HorizontalLine(x, _) => push_svg_command(&mut data, 'H', &[x.0]),
+                    PathCommand::VerticalLine(y, _) => push_svg_command(&mut data, 'V', &[y.0]),
+                    PathCommand::CubicBezier(cubic, _) => push_svg_command(&mut data, 'C', &[
+                        cubic.control_point_0.x.0,
+                        cubic.control_point_0.y.0,
+                        cubic.control_point_1.x.0,
+                        cubic.control_point_1.y.0,
+                        cubic.point_1.x.0,
+                        cubic.point_1.y.0,
+                    ]),
+                    PathCommand::QuadraticBezier(quadratic, _) => push_svg_command(&mut data, 'Q', &[
+                        quadratic.control_point.x.0,
+                        quadratic.control_point.y.0,
+                        quadratic.point_1.x.0,
+                        quadratic.point_1.y.0,
+                    ]),
+                    PathCommand::ArcCircle(arc, _) => push_svg_command(&mut data, 'A', &[
+                        arc.radius.0,
+                        arc.radius.0,
+                        0.0,
+                        arc.large_arc as u8 as f64,
+                        arc.sweep as u8 as f64,
+                        arc.target.x.0,
+                        arc.target.y.0,
+                    ]),
+                    PathCommand::ArcEllipse(arc, _) => push_svg_command(&mut data, 'A', &[
+                        arc.radius_x.0,
+                        arc.radius_y.0,
+                        arc.rotation.0,
+                        arc.large_arc as u8 as f64,
+                        arc.sweep as u8 as f64,
+                        arc.target.x.0,
+                        arc.target.y.0,
+                    ]),
+                    PathCommand::ClosePath(_) => push_svg_command(&mut data, 'Z', &[]),
+                }
+            }
         }
+        data
+    }
+}
 
-        let style_type = StyleType::from_u8(prim_style_kind);
+/// Appends a command letter and its numbers to SVG path data, separated by spaces. Numbers
+/// are written in the shortest decimal form that reads back as the same value, which never
+/// uses exponents.
+fn push_svg_command(data: &mut String, letter: char, numbers: &[f64]) {
+    if !data.is_empty() {
+        data.push(' ');
+    }
+    data.push(letter);
+    for number in numbers {
+        // Adding zero turns negative zero into zero, which would otherwise be written as -0.
+        data.push_str(&format!(" {}", number + 0.0));
+    }
+}
 
-        match command {
-            CommandType::EndOfDocument => {
-                unreachable!("We should have broken out of the loop above.")
+impl Path {
+    /// A circle as two half-circle arcs, starting at its rightmost point.
+    pub fn circle(center: Point, radius: Unit) -> Self {
+        Path::ellipse(center, radius, radius)
+    }
+
+    /// An axis-aligned ellipse as two half-ellipse arcs, starting at its rightmost point.
+    /// Circular ellipses use the more compact circle arcs.
+    pub fn ellipse(center: Point, radius_x: Unit, radius_y: Unit) -> Self {
+        let right = Point::new(Unit(center.x.0 + radius_x.0), center.y);
+        let left = Point::new(Unit(center.x.0 - radius_x.0), center.y);
+        let arc = |target: Point| {
+            if radius_x.0 == radius_y.0 {
+                PathCommand::ArcCircle(ArcCircle { large_arc: false, sweep: true, radius: radius_x, target }, None)
+            } else {
+                PathCommand::ArcEllipse(ArcEllipse { large_arc: false, sweep: true, radius_x, radius_y, rotation: Unit(0.0), target }, None)
             }
-            CommandType::FillPolygon => {
-                // The number of points in the polygon. This value is offset by 1.
-                let point_count = read_variable_sized_unsigned_number(cursor)? + 1;
-                let mut points: Vec<Point> = Vec::with_capacity(point_count as usize);
-
-                // The style that is used to fill the polygon.
-                let style = Style::read_cursor_using_style_type(header, cursor, &style_type)?;
-
-                // The points of the polygon.
-                for _ in 0..point_count {
-                    let point = Point::read_point(header, cursor)?;
-                    points.push(point);
-                }
+        };
+        Path { segments: vec![Segment { start: right, path_commands: [arc(left), arc(right), PathCommand::ClosePath(None)].into_iter().collect() }] }
+    }
 
-                let data = FillPolygonData {
-                    style,
-                    points,
-                };
-                draw_commands.push(DrawCommand::FillPolygon(data))
+    /// A rectangle with corners rounded by quarter-circle arcs, drawn clockwise. The radius
+    /// is limited to half the shorter side, and a radius of 0 gives a plain rectangle.
+    pub fn rounded_rectangle(rectangle: &Rectangle, radius: Unit) -> Self {
+        let (left, top) = (rectangle.x.0, rectangle.y.0);
+        let (right, bottom) = (left + rectangle.width.0, top + rectangle.height.0);
+        let radius = radius.0.clamp(0.0, rectangle.width.0.abs().min(rectangle.height.0.abs()) / 2.0);
+        let point = |x: f64, y: f64| Point::new(Unit(x), Unit(y));
+
+        if radius == 0.0 {
+            let path_commands = [
+                PathCommand::HorizontalLine(Unit(right), None),
+                PathCommand::VerticalLine(Unit(bottom), None),
+                PathCommand::HorizontalLine(Unit(left), None),
+                PathCommand::ClosePath(None),
+            ];
+            return Path { segments: vec![Segment { start: point(left, top), path_commands: path_commands.into_iter().collect() }] };
+        }
+
+        let corner = |x: f64, y: f64| PathCommand::ArcCircle(ArcCircle { large_arc: false, sweep: true, radius: Unit(radius), target: point(x, y) }, None);
+        let path_commands = [
+            PathCommand::HorizontalLine(Unit(right - radius), None),
+            corner(right, top + radius),
+            PathCommand::VerticalLine(Unit(bottom - radius), None),
+            corner(right - radius, bottom),
+            PathCommand::HorizontalLine(Unit(left + radius), None),
+            corner(left, bottom - radius),
+            PathCommand::VerticalLine(Unit(top + radius), None),
+            corner(left + radius, top),
+            PathCommand::ClosePath(None),
+        ];
+        Path { segments: vec![Segment { start: point(left + radius, top), path_commands: path_commands.into_iter().collect() }] }
+    }
+
+    /// A star with `tips` points, alternating between `outer_radius` and `inner_radius`
+    /// around the center, with the first tip pointing up. Fewer than two tips give an
+    /// empty path.
+    pub fn star(center: Point, tips: usize, outer_radius: Unit, inner_radius: Unit) -> Self {
+        if tips < 2 {
+            return Path { segments: Vec::new() };
+        }
+
+        let vertex = |index: usize| {
+            let radius = if index.is_multiple_of(2) { outer_radius.0 } else { inner_radius.0 };
+            let angle = -std::f64::consts::FRAC_PI_2 + std::f64::consts::PI * index as f64 / tips as f64;
+            Point::new(Unit(center.x.0 + radius * angle.cos()), Unit(center.y.0 + radius * angle.sin()))
+        };
+        let mut path_commands: PathCommands = (1..tips * 2).map(|index| PathCommand::Line(vertex(index), None)).collect();
+        path_commands.push(PathCommand::ClosePath(None));
+        Path { segments: vec![Segment { start: vertex(0), path_commands }] }
+    }
+}
+
+impl Segment {
+    /// Approximates the segment with straight lines, so that no point of the flattened
+    /// outline is further than `tolerance` from the real curve. Bézier curves are split
+    /// into steps based on their curvature and arcs based on their radius.
+    ///
+    /// The points start with the segment's start point. A segment that ends with a
+    /// `ClosePath` also ends with its start point, so the points always form an open
+    /// polyline. A `tolerance` that is NaN, not positive or infinite is clamped to a
+    /// positive finite value.
+    pub fn flatten(&self, tolerance: f64) -> impl Iterator<Item = Point> + use<> {
+        let (mut points, _, closed) = self.flatten_points(tolerance);
+        if closed && points.len() > 1 {
+            points.push(self.start);
+        }
+        points.into_iter()
+    }
+
+    /// Approximates the segment with straight lines, so that no point of the
+    /// flattened outline is further than `tolerance` from the real curve.
+    ///
+    /// Returns the polyline, the indices of the points where one path command ends and
+    /// the next one begins, and whether the segment ends with a `ClosePath`. The start
+    /// point is not repeated at the end of a closed polyline.
+    pub(crate) fn flatten_points(&self, tolerance: f64) -> (Vec<Point>, Vec<usize>, bool) {
+        let tolerance = flatten_tolerance(tolerance);
+        let mut points = vec![self.start];
+        let mut joints = Vec::with_capacity(self.path_commands.len() + 1);
+        let mut current = self.start;
+        let mut closed = false;
+
+        for path_command in &self.path_commands {
+            joints.push(points.len() - 1);
+            closed = false;
+            match path_command {
+                PathCommand::Line(point, _) => {
+                    current = *point;
+                    points.push(current);
+                }
+                PathCommand::HorizontalLine(x, _) => {
+                    current = Point::new(*x, current.y);
+                    points.push(current);
+                }
+                PathCommand::VerticalLine(y, _) => {
+                    current = Point::new(current.x, *y);
+                    points.push(current);
+                }
+                PathCommand::CubicBezier(cubic, _) => {
+                    flatten_cubic(current, cubic, tolerance, &mut points);
+                    current = cubic.point_1;
+                }
+                PathCommand::QuadraticBezier(quadratic, _) => {
+                    flatten_quadratic(current, quadratic, tolerance, &mut points);
+                    current = quadratic.point_1;
+                }
+                PathCommand::ArcCircle(arc, _) => {
+                    flatten_arc(current, arc.target, arc.radius.0, arc.radius.0, 0.0, arc.large_arc, arc.sweep, tolerance, &mut points);
+                    current = arc.target;
+                }
+                PathCommand::ArcEllipse(arc, _) => {
+                    flatten_arc(current, arc.target, arc.radius_x.0, arc.radius_y.0, arc.rotation.0, arc.large_arc, arc.sweep, tolerance, &mut points);
+                    current = arc.target;
+                }
+                PathCommand::ClosePath(_) => {
+                    // A straight line is drawn back to the start of the segment.
+                    current = self.start;
+                    points.push(current);
+                    closed = true;
+                }
             }
-            CommandType::FillRectangles => {
-                // The number of points in the polygon. This value is offset by 1.
-                let rectangle_count = read_variable_sized_unsigned_number(cursor)? + 1;
-
-                // The style that is used to fill all rectangles.
-                let style = Style::read_cursor_using_style_type(header, cursor, &style_type)?;
-                
-                // The list of rectangles to be filled.
-                let mut rectangles: Vec<Rectangle> = Vec::with_capacity(rectangle_count as usize);
-                for _ in 0..rectangle_count {
-                    // Horizontal distance of the left side to the origin.
-                    let x = read_unit(header.scale, cursor, &header.coordinate_range)?;
-                    
-                    // Vertical distance of the upper side to the origin.
-                    let y = read_unit(header.scale, cursor, &header.coordinate_range)?;
-                    
-                    // Horizontal extent of the rectangle.
-                    let width = read_unit(header.scale, cursor, &header.coordinate_range)?;
-                    
-                    // Vertical extent of the rectangle origin.
-                    let height = read_unit(header.scale, cursor, &header.coordinate_range)?;
-                    rectangles.push(Rectangle { x, y, width, height });
+        }
+
+        if closed && points.len() > 1 {
+            points.pop();
+        } else {
+            joints.push(points.len() - 1);
+        }
+        joints.dedup();
+
+        (points, joints, closed)
+    }
+}
+
+/// The largest number of lines a single curve is split into when flattening.
+const MAX_FLATTEN_STEPS: f64 = 1024.0;
+
+/// The smallest flattening tolerance. Smaller ones would hit `MAX_FLATTEN_STEPS` anyway.
+const MIN_FLATTEN_TOLERANCE: f64 = 1e-9;
+
+/// Clamps `tolerance` to a positive finite value. A NaN tolerance would otherwise split
+/// curves into zero steps and drop them.
+fn flatten_tolerance(tolerance: f64) -> f64 {
+    if tolerance.is_nan() { MIN_FLATTEN_TOLERANCE } else { tolerance.clamp(MIN_FLATTEN_TOLERANCE, f64::MAX) }
+}
+
+fn flatten_cubic(start: Point, cubic: &CubicBezier, tolerance: f64, points: &mut Vec<Point>) {
+    let (x0, y0) = (start.x.0, start.y.0);
+    let (x1, y1) = (cubic.control_point_0.x.0, cubic.control_point_0.y.0);
+    let (x2, y2) = (cubic.control_point_1.x.0, cubic.control_point_1.y.0);
+    let (x3, y3) = (cubic.point_1.x.0, cubic.point_1.y.0);
+
+    // The second differences bound how far the curve strays from its chords.
+    let dd_0 = (x0 - 2.0 * x1 + x2).hypot(y0 - 2.0 * y1 + y2);
+    let dd_1 = (x1 - 2.0 * x2 + x3).hypot(y1 - 2.0 * y2 + y3);
+    let steps = (0.75 * dd_0.max(dd_1) / tolerance).sqrt().ceil().clamp(1.0, MAX_FLATTEN_STEPS) as usize;
+
+    for i in 1..=steps {
+        let t = i as f64 / steps as f64;
+        let mt = 1.0 - t;
+        let a = mt * mt * mt;
+        let b = 3.0 * mt * mt * t;
+        let c = 3.0 * mt * t * t;
+        let d = t * t * t;
+        points.push(Point::new(
+            Unit(a * x0 + b * x1 + c * x2 + d * x3),
+            Unit(a * y0 + b * y1 + c * y2 + d * y3),
+        ));
+    }
+}
+
+fn flatten_quadratic(start: Point, quadratic: &QuadraticBezier, tolerance: f64, points: &mut Vec<Point>) {
+    let (x0, y0) = (start.x.0, start.y.0);
+    let (x1, y1) = (quadratic.control_point.x.0, quadratic.control_point.y.0);
+    let (x2, y2) = (quadratic.point_1.x.0, quadratic.point_1.y.0);
+
+    let dd = (x0 - 2.0 * x1 + x2).hypot(y0 - 2.0 * y1 + y2);
+    let steps = (0.25 * dd / tolerance).sqrt().ceil().clamp(1.0, MAX_FLATTEN_STEPS) as usize;
+
+    for i in 1..=steps {
+        let t = i as f64 / steps as f64;
+        let mt = 1.0 - t;
+        let a = mt * mt;
+        let b = 2.0 * mt * t;
+        let c = t * t;
+        points.push(Point::new(Unit(a * x0 + b * x1 + c * x2), Unit(a * y0 + b * y1 + c * y2)));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn flatten_arc(
+    start: Point,
+    target: Point,
+    radius_x: f64,
+    radius_y: f64,
+    rotation: f64,
+    large_arc: bool,
+    sweep: bool,
+    tolerance: f64,
+    points: &mut Vec<Point>,
+) {
+    let Some(arc) = CenterArc::from_endpoints(start, target, radius_x, radius_y, rotation, large_arc, sweep) else {
+        // Degenerate arcs are drawn as straight lines.
+        points.push(target);
+        return;
+    };
+
+    let radius = arc.radius_x.max(arc.radius_y);
+    let max_step = if tolerance < radius {
+        2.0 * (1.0 - tolerance / radius).acos()
+    } else {
+        std::f64::consts::FRAC_PI_2
+    };
+    let steps = (arc.sweep_angle.abs() / max_step).ceil().clamp(1.0, MAX_FLATTEN_STEPS) as usize;
+
+    for i in 1..steps {
+        let angle = arc.start_angle + arc.sweep_angle * (i as f64 / steps as f64);
+        points.push(arc.point_at(angle));
+    }
+    points.push(target);
+}
+
+/// An elliptical arc in center parameterization, converted from the endpoint
+/// parameterization used by TinyVG and SVG (SVG 1.1, appendix F.6.5).
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct CenterArc {
+    pub(crate) center_x: f64,
+    pub(crate) center_y: f64,
+    pub(crate) radius_x: f64,
+    pub(crate) radius_y: f64,
+    /// The rotation of the ellipse's x-axis in radians.
+    pub(crate) rotation: f64,
+    /// The angle of the start point in radians, before rotation.
+    pub(crate) start_angle: f64,
+    /// The signed angle swept from the start point to the end point, in radians.
+    pub(crate) sweep_angle: f64,
+}
+
+impl CenterArc {
+    /// `rotation` is in degrees. Returns `None` for arcs that degenerate to a straight line.
+    pub(crate) fn from_endpoints(
+        start: Point,
+        target: Point,
+        radius_x: f64,
+        radius_y: f64,
+        rotation: f64,
+        large_arc: bool,
+        sweep: bool,
+    ) -> Option<CenterArc> {
+        let (x1, y1) = (start.x.0, start.y.0);
+        let (x2, y2) = (target.x.0, target.y.0);
+        let mut radius_x = radius_x.abs();
+        let mut radius_y = radius_y.abs();
+
+        if (x1 == x2 && y1 == y2) || radius_x == 0.0 || radius_y == 0.0 {
+            return None;
+        }
+
+        let rotation = rotation.to_radians();
+        let (sin, cos) = rotation.sin_cos();
+
+        let half_dx = (x1 - x2) / 2.0;
+        let half_dy = (y1 - y2) / 2.0;
+        let x1_prime = cos * half_dx + sin * half_dy;
+        let y1_prime = -sin * half_dx + cos * half_dy;
+
+        // Scale the radii up if they are too small to span both endpoints.
+        let lambda = (x1_prime * x1_prime) / (radius_x * radius_x) + (y1_prime * y1_prime) / (radius_y * radius_y);
+        if lambda > 1.0 {
+            radius_x *= lambda.sqrt();
+            radius_y *= lambda.sqrt();
+        }
+
+        let rx2 = radius_x * radius_x;
+        let ry2 = radius_y * radius_y;
+        let numerator = rx2 * ry2 - rx2 * y1_prime * y1_prime - ry2 * x1_prime * x1_prime;
+        let denominator = rx2 * y1_prime * y1_prime + ry2 * x1_prime * x1_prime;
+        let mut coefficient = (numerator / denominator).max(0.0).sqrt();
+        if large_arc == sweep {
+            coefficient = -coefficient;
+        }
+
+        let center_x_prime = coefficient * radius_x * y1_prime / radius_y;
+        let center_y_prime = -coefficient * radius_y * x1_prime / radius_x;
+
+        let center_x = cos * center_x_prime - sin * center_y_prime + (x1 + x2) / 2.0;
+        let center_y = sin * center_x_prime + cos * center_y_prime + (y1 + y2) / 2.0;
+
+        let start_vector = ((x1_prime - center_x_prime) / radius_x, (y1_prime - center_y_prime) / radius_y);
+        let end_vector = ((-x1_prime - center_x_prime) / radius_x, (-y1_prime - center_y_prime) / radius_y);
+
+        let start_angle = start_vector.1.atan2(start_vector.0);
+        let mut sweep_angle = end_vector.1.atan2(end_vector.0) - start_angle;
+
+        let tau = std::f64::consts::TAU;
+        if sweep && sweep_angle < 0.0 {
+            sweep_angle += tau;
+        } else if !sweep && sweep_angle > 0.0 {
+            sweep_angle -= tau;
+        }
+
+        Some(CenterArc {
+            center_x,
+            center_y,
+            radius_x,
+            radius_y,
+            rotation,
+            start_angle,
+            sweep_angle,
+        })
+    }
+
+    pub(crate) fn point_at(&self, angle: f64) -> Point {
+        let (sin, cos) = self.rotation.sin_cos();
+        let (angle_sin, angle_cos) = angle.sin_cos();
+        let x = self.center_x + self.radius_x * cos * angle_cos - self.radius_y * sin * angle_sin;
+        let y = self.center_y + self.radius_x * sin * angle_cos + self.radius_y * cos * angle_sin;
+        Point::new(Unit(x), Unit(y))
+    }
+
+    /// The derivative of `point_at` with respect to the angle.
+    fn tangent_at(&self, angle: f64) -> (f64, f64) {
+        let (sin, cos) = self.rotation.sin_cos();
+        let (angle_sin, angle_cos) = angle.sin_cos();
+        let dx = -self.radius_x * cos * angle_sin - self.radius_y * sin * angle_cos;
+        let dy = -self.radius_x * sin * angle_sin + self.radius_y * cos * angle_cos;
+        (dx, dy)
+    }
+
+    /// Approximates the arc with one cubic Bézier curve per quarter turn or less.
+    pub(crate) fn to_cubics(self) -> Vec<CubicBezier> {
+        let count = (self.sweep_angle.abs() / std::f64::consts::FRAC_PI_2).ceil().max(1.0) as usize;
+        let step = self.sweep_angle / count as f64;
+        // The control point distance that makes the curve meet the arc at its midpoint.
+        let k = 4.0 / 3.0 * (step / 4.0).tan();
+
+        (0..count)
+            .map(|i| {
+                let angle_0 = self.start_angle + step * i as f64;
+                let angle_1 = angle_0 + step;
+                let start = self.point_at(angle_0);
+                let end = self.point_at(angle_1);
+                let (dx_0, dy_0) = self.tangent_at(angle_0);
+                let (dx_1, dy_1) = self.tangent_at(angle_1);
+                CubicBezier {
+                    control_point_0: Point::new(Unit(start.x.0 + k * dx_0), Unit(start.y.0 + k * dy_0)),
+                    control_point_1: Point::new(Unit(end.x.0 - k * dx_1), Unit(end.y.0 - k * dy_1)),
+                    point_1: end,
                 }
+            })
+            .collect()
+    }
+}
+
+/// Parses draw commands up to the end of the document and passes each to `add`. If `spans`
+/// is given, the byte range of every command in the reader's data is appended to it.
+/// Commands with an unknown index are handled as `options` say. The reader is left after
+/// the end of the document; the bytes after it are checked by `check_trailing_data`.
+pub(crate) fn parse_draw_commands<'a>(
+    reader: &mut Reader<'a>,
+    header: &TinyVgHeader,
+    options: &ParseOptions,
+    mut spans: Option<&mut Vec<Range<usize>>>,
+    mut add: impl FnMut(DrawCommandRef<'a>),
+) -> Result<(), TinyVgParseError> {
+    reader.error = TinyVgParseError::InvalidCommand;
+
+    loop {
+        let command_start = reader.position();
+        if at_unknown_command(reader) {
+            return skip_unknown_commands(reader, options.unknown_commands);
+        }
+        if options.strict {
+            check_end_of_document(reader)?;
+        }
+        let Some(draw_command) = parse_draw_command(reader, header)? else {
+            break;
+        };
+        add(draw_command);
 
-                let data = FillRectanglesData {
-                    rectangles,
-                    style,
-                };
-                draw_commands.push(DrawCommand::FillRectangles(data))
+        if let Some(spans) = spans.as_deref_mut() {
+            spans.push(command_start..reader.position());
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the bytes after the end of the document. They are skipped with a warning, and
+/// in strict mode any of them that is not zero is an error.
+pub(crate) fn check_trailing_data(reader: &Reader, options: &ParseOptions) -> Result<(), TinyVgParseError> {
+    let trailing = reader.clone().take_rest();
+    if options.strict && trailing.iter().any(|&byte| byte != 0) {
+        return Err(TinyVgParseError::TrailingData);
+    }
+    if !trailing.is_empty() {
+        log_warning!("skipped {} bytes after the end of the document", trailing.len());
+    }
+    Ok(())
+}
+
+/// Fails if the next byte ends the document but has style bits set, which the format
+/// requires to be zero so that the last byte of every file is 0x00.
+fn check_end_of_document(reader: &Reader) -> Result<(), TinyVgParseError> {
+    match reader.peek_u8() {
+        Some(encoded_command) if encoded_command & 0b00_11_11_11 == 0 && encoded_command != 0 => Err(TinyVgParseError::InvalidCommand),
+        _ => Ok(()),
+    }
+}
+
+/// Whether the next byte encodes a command index this version of the format does not define.
+fn at_unknown_command(reader: &Reader) -> bool {
+    reader.peek_u8().is_some_and(|encoded_command| CommandType::from_u8(encoded_command & 0b00_11_11_11).is_none())
+}
+
+/// Ends the command list at an unknown command as `policy` says. Commands do not store
+/// their size, so the only one that can be skipped is the last: the rest of the data is
+/// skipped if it ends with the end of document byte. Stopping moves past the rest of the
+/// data without looking at it.
+fn skip_unknown_commands(reader: &mut Reader, policy: UnknownCommandPolicy) -> Result<(), TinyVgParseError> {
+    match policy {
+        UnknownCommandPolicy::Error => Err(TinyVgParseError::UnknownCommand),
+        UnknownCommandPolicy::StopBeforeUnknown => {
+            reader.take_rest();
+            Ok(())
+        }
+        UnknownCommandPolicy::SkipWithWarning => {
+            let mut rest = reader.clone();
+            let skipped = rest.take_rest();
+            if skipped.last() != Some(&0) {
+                return Err(TinyVgParseError::UnknownCommand);
             }
-            CommandType::FillPath => {
-                // The number of segments in the path. This value is offset by 1.
-                let segment_count = read_variable_sized_unsigned_number(cursor)? + 1;
-                
-                // The style that is used to fill the path.
-                let style = Style::read_cursor_using_style_type(header, cursor, &style_type)?;
-
-                // A path with segment_count segments.
-                let path = Path::parse(cursor, header, segment_count as usize)?;
-
-                let data = FillPathData {
-                    path,
-                    style,
-                };
-                draw_commands.push(DrawCommand::FillPath(data));
+            log_warning!("skipped an unknown command with index {} and the {} bytes up to the end of the document", skipped[0] & 0b00_11_11_11, skipped.len());
+            *reader = rest;
+            Ok(())
+        }
+    }
+}
+
+/// Parses the next draw command, or returns `None` at the end of the document.
+pub(crate) fn parse_draw_command<'a>(
+    reader: &mut Reader<'a>,
+    header: &TinyVgHeader,
+) -> Result<Option<DrawCommandRef<'a>>, TinyVgParseError> {
+    let encoded_command = reader.read_u8()?;
+    // bits 0–6 = command_index
+    let command_index = encoded_command & 0b00_11_11_11;
+    // bits 7-8 = prim_style_kind
+    let prim_style_kind = (encoded_command & 0b11_00_00_00) >> 6;
+
+    let command = CommandType::from_u8(command_index).ok_or(TinyVgParseError::UnknownCommand)?;
+
+    // If this command is read, the TinyVG file has ended. This command must have prim_style_kind to be
+    // set to 0, so the last byte of every TinyVG file is 0x00.
+    if matches!(command, CommandType::EndOfDocument) {
+        return Ok(None);
+    }
+
+    let style_type = StyleType::from_u8(prim_style_kind)?;
+
+    let draw_command = match command {
+        CommandType::EndOfDocument => {
+            unreachable!("We should have returned above.")
+        }
+        CommandType::FillPolygon => {
+            // The number of points in the polygon. This value is offset by 1.
+            let point_count = reader.read_count()?;
+
+            // The style that is used to fill the polygon.
+            let style = Style::read_using_style_type(header, reader, &style_type)?;
+
+            // The points of the polygon.
+            let points = reader.read_point_run(header, point_count)?;
+
+            DrawCommandRef::FillPolygon {
+                style,
+                points,
             }
-            CommandType::DrawLines => {
-                // The number of rectangles. This value is offset by 1.
-                let line_count = read_variable_sized_unsigned_number(cursor)? + 1;
-                
-                // The style that is used to draw the all rectangles.
-                let line_style = Style::read_cursor_using_style_type(header, cursor, &style_type)?;
-
-                // The width of the line.
-                let line_width = read_unit(header.scale, cursor, &header.coordinate_range)?;
-
-                // The list of lines.
-                let mut lines: Vec<Line> = Vec::with_capacity(line_count as usize);
-                for _ in 0..line_count {
-                    let line = Line::read_line(header, cursor)?;
-                    lines.push(line);
-                }
-                
-                let data = DrawLinesData {
-                    lines,
-                    line_width,
-                    line_style,
-                };
-                draw_commands.push(DrawCommand::DrawLines(data));
+        }
+        CommandType::FillRectangles => {
+            // The number of points in the polygon. This value is offset by 1.
+            let rectangle_count = reader.read_count()?;
+
+            // The style that is used to fill all rectangles.
+            let style = Style::read_using_style_type(header, reader, &style_type)?;
+            
+            // The list of rectangles to be filled, each stored as the horizontal and vertical
+            // distance of its upper left corner to the origin followed by its extents.
+            let rectangles = reader.read_run(header, rectangle_count, 4, Rectangle::decode)?;
+
+            DrawCommandRef::FillRectangles {
+                rectangles,
+                style,
             }
-            CommandType::DrawLineLoop => {
-                // The number of points. This value is offset by 1.
-                let point_count = read_variable_sized_unsigned_number(cursor)? + 1;
+        }
+        CommandType::FillPath => {
+            // The number of segments in the path. This value is offset by 1.
+            let segment_count = reader.read_count()?;
+            
+            // The style that is used to fill the path.
+            let style = Style::read_using_style_type(header, reader, &style_type)?;
+
+            // A path with segment_count segments.
+            let path = PathRef::read(reader, header, segment_count as usize)?;
+
+            DrawCommandRef::FillPath {
+                path,
+                style,
+            }
+        }
+        CommandType::DrawLines => {
+            // The number of rectangles. This value is offset by 1.
+            let line_count = reader.read_count()?;
+            
+            // The style that is used to draw the all rectangles.
+            let line_style = Style::read_using_style_type(header, reader, &style_type)?;
+
+            // The width of the line.
+            let line_width = reader.read_unit(header)?;
+
+            // The list of lines.
+            let lines = reader.read_run(header, line_count, 4, Line::decode)?;
+            
+            DrawCommandRef::DrawLines {
+                lines,
+                line_width,
+                line_style,
+            }
+        }
+        CommandType::DrawLineLoop => {
+            // The number of points. This value is offset by 1.
+            let point_count = reader.read_count()?;
 
-                // The style that is used to draw the all rectangles.
-                let line_style = Style::read_cursor_using_style_type(header, cursor, &style_type)?;
+            // The style that is used to draw the all rectangles.
+            let line_style = Style::read_using_style_type(header, reader, &style_type)?;
 
-                // The width of the line.
-                let line_width = read_unit(header.scale, cursor, &header.coordinate_range)?;
+            // The width of the line.
+            let line_width = reader.read_unit(header)?;
 
-                // The points of the polygon.
-                let mut points: Vec<Point> = Vec::with_capacity(point_count as usize);
-                for _ in 0..point_count {
-                    let point = Point::read_point(header, cursor)?;
-                    points.push(point);
-                }
+            // The points of the polygon.
+            let points = reader.read_point_run(header, point_count)?;
 
-                let data = DrawLineLoopData {
-                    line_style,
-                    line_width,
-                    points,
-                };
-                draw_commands.push(DrawCommand::DrawLineLoop(data));
+            DrawCommandRef::DrawLineLoop {
+                line_style,
+                line_width,
+                points,
             }
-            CommandType::DrawLineStrip => {
-                // The number of points. This value is offset by 1.
-                let point_count = read_variable_sized_unsigned_number(cursor)? + 1;
+        }
+        CommandType::DrawLineStrip => {
+            // The number of points. This value is offset by 1.
+            let point_count = reader.read_count()?;
 
-                // The style that is used to draw the all rectangles.
-                let style = Style::read_cursor_using_style_type(header, cursor, &style_type)?;
+            // The style that is used to draw the all rectangles.
+            let style = Style::read_using_style_type(header, reader, &style_type)?;
 
-                // The width of the line.
-                let line_width = read_unit(header.scale, cursor, &header.coordinate_range)?;
+            // The width of the line.
+            let line_width = reader.read_unit(header)?;
 
-                // The points of the line strip.
-                let mut points: Vec<Point> = Vec::with_capacity(point_count as usize);
-                for _ in 0..point_count {
-                    let point = Point::read_point(header, cursor)?;
-                    points.push(point);
-                }
+            // The points of the line strip.
+            let points = reader.read_point_run(header, point_count)?;
 
-                let data = DrawLineStripData {
-                    style,
-                    line_width,
-                    points
-                };
-                draw_commands.push(DrawCommand::DrawLineStrip(data));
+            DrawCommandRef::DrawLineStrip {
+                style,
+                line_width,
+                points
             }
-            CommandType::DrawLinePath => {
-                // The number of segments in the path. This value is offset by 1.
-                let segment_count = read_variable_sized_unsigned_number(cursor)? + 1;
+        }
+        CommandType::DrawLinePath => {
+            // The number of segments in the path. This value is offset by 1.
+            let segment_count = reader.read_count()?;
 
-                // The style that is used to draw the all rectangles.
-                let style = Style::read_cursor_using_style_type(header, cursor, &style_type)?;
+            // The style that is used to draw the all rectangles.
+            let style = Style::read_using_style_type(header, reader, &style_type)?;
 
-                // The width of the line.
-                let line_width = read_unit(header.scale, cursor, &header.coordinate_range)?;
+            // The width of the line.
+            let line_width = reader.read_unit(header)?;
 
-                // A path with segment_count segments.
-                let path = Path::parse(cursor, header, segment_count as usize)?;
+            // A path with segment_count segments.
+            let path = PathRef::read(reader, header, segment_count as usize)?;
 
-                let data = DrawLinePathData {
-                    style,
-                    line_width,
-                    path,
-                };
-                draw_commands.push(DrawCommand::DrawLinePath(data));
+            DrawCommandRef::DrawLinePath {
+                style,
+                line_width,
+                path,
             }
-            CommandType::OutlineFillPolygon => {
-                let point_count_sec_style_kind = cursor.read_u8().map_err(|_| TinyVgParseError::InvalidCommand)?;
-                // The number of points in the polygon. This value is offset by 1.
-                let point_count = (point_count_sec_style_kind & 0b00_11_11_11) + 1;
+        }
+        CommandType::OutlineFillPolygon => {
+            let point_count_sec_style_kind = reader.read_u8()?;
+            // The number of points in the polygon. This value is offset by 1.
+            let point_count = (point_count_sec_style_kind & 0b00_11_11_11) + 1;
 
-                // The secondary style used in this command.
-                let sec_style_kind = point_count_sec_style_kind & 0b11_00_00_00;
+            // The secondary style used in this command.
+            let sec_style_kind = (point_count_sec_style_kind & 0b11_00_00_00) >> 6;
 
-                // The style that is used to fill the polygon.
-                let fill_style = Style::read_cursor_using_style_type(header, cursor, &style_type)?;
+            // The style that is used to fill the polygon.
+            let fill_style = Style::read_using_style_type(header, reader, &style_type)?;
 
-                // The style that is used to draw the outline of the polygon.
-                let line_style = Style::read_cursor_using_style_type(header, cursor, &StyleType::from_u8(sec_style_kind))?;
+            // The style that is used to draw the outline of the polygon.
+            let line_style = Style::read_using_style_type(header, reader, &StyleType::from_u8(sec_style_kind)?)?;
 
-                // The width of the line.
-                let line_width = read_unit(header.scale, cursor, &header.coordinate_range)?;
+            // The width of the line.
+            let line_width = reader.read_unit(header)?;
 
-                // The set of points of this polygon.
-                let mut points: Vec<Point> = Vec::with_capacity(point_count as usize);
-                for _ in 0..point_count {
-                    let point = Point::read_point(header, cursor)?;
-                    points.push(point);
-                }
+            // The set of points of this polygon.
+            let points = reader.read_point_run(header, point_count as u64)?;
 
-                let data = OutlineFillPolygonData {
-                    points,
-                    line_width,
-                    line_style,
-                    fill_style,
-                };
-                draw_commands.push(DrawCommand::OutlineFillPolygon(data));
+            DrawCommandRef::OutlineFillPolygon {
+                points,
+                line_width,
+                line_style,
+                fill_style,
             }
-            CommandType::OutlineFillRectangles => {
-                let rect_count_sec_style_kind = cursor.read_u8().map_err(|_| TinyVgParseError::InvalidCommand)?;
-                // The number of rectangles. This value is offset by 1.
-                let rect_count = (rect_count_sec_style_kind & 0b00_11_11_11) + 1;
+        }
+        CommandType::OutlineFillRectangles => {
+            let rect_count_sec_style_kind = reader.read_u8()?;
+            // The number of rectangles. This value is offset by 1.
+            let rect_count = (rect_count_sec_style_kind & 0b00_11_11_11) + 1;
 
-                // The secondary style used in this command.
-                let sec_style_kind = rect_count_sec_style_kind & 0b11_00_00_00;
+            // The secondary style used in this command.
+            let sec_style_kind = (rect_count_sec_style_kind & 0b11_00_00_00) >> 6;
 
-                // The style that is used to fill the polygon.
-                let fill_style = Style::read_cursor_using_style_type(header, cursor, &style_type)?;
+            // The style that is used to fill the polygon.
+            let fill_style = Style::read_using_style_type(header, reader, &style_type)?;
 
-                // The style that is used to draw the outline of the polygon.
-                let line_style = Style::read_cursor_using_style_type(header, cursor, &StyleType::from_u8(sec_style_kind))?;
+            // The style that is used to draw the outline of the polygon.
+            let line_style = Style::read_using_style_type(header, reader, &StyleType::from_u8(sec_style_kind)?)?;
 
-                // The width of the line.
-                let line_width = read_unit(header.scale, cursor, &header.coordinate_range)?;
+            // The width of the line.
+            let line_width = reader.read_unit(header)?;
 
-                // The list of rectangles to be drawn.
-                let mut rectangles: Vec<Rectangle> = Vec::with_capacity(rect_count as usize);
-                for _ in 0..rect_count {
-                    let rectangle = Rectangle::read_rectangle(header, cursor)?;
-                    rectangles.push(rectangle);
-                }
+            // The list of rectangles to be drawn.
+            let rectangles = reader.read_run(header, rect_count as u64, 4, Rectangle::decode)?;
 
-                let data = OutlineFillRectanglesData {
-                    fill_style,
-                    line_style,
-                    line_width,
-                    rectangles,
-                };
-                draw_commands.push(DrawCommand::OutlineFillRectangles(data));
+            DrawCommandRef::OutlineFillRectangles {
+                fill_style,
+                line_style,
+                line_width,
+                rectangles,
             }
-            CommandType::OutlineFillPath => {
-                let segment_count_and_sec_style_kind = cursor.read_u8().map_err(|_| TinyVgParseError::InvalidCommand)?;
+        }
+        CommandType::OutlineFillPath => {
+            let segment_count_and_sec_style_kind = reader.read_u8()?;
 
-                // The number of points in the polygon. This value is offset by 1
-                let segment_count = (segment_count_and_sec_style_kind & 0b00_11_11_11) + 1;
+            // The number of points in the polygon. This value is offset by 1
+            let segment_count = (segment_count_and_sec_style_kind & 0b00_11_11_11) + 1;
 
-                // The secondary style used in this command.
-                let sec_style_kind = segment_count_and_sec_style_kind & 0b11_00_00_00;
-                let sec_style_type = StyleType::from_u8(sec_style_kind);
+            // The secondary style used in this command.
+            let sec_style_kind = (segment_count_and_sec_style_kind & 0b11_00_00_00) >> 6;
+            let sec_style_type = StyleType::from_u8(sec_style_kind)?;
 
-                // The style that is used to fill the polygon.
-                let fill_style = Style::read_cursor_using_style_type(header, cursor, &style_type)?;
+            // The style that is used to fill the polygon.
+            let fill_style = Style::read_using_style_type(header, reader, &style_type)?;
 
-                // The style that is used to draw the outline of the polygon.
-                let line_style = Style::read_cursor_using_style_type(header, cursor, &sec_style_type)?;
+            // The style that is used to draw the outline of the polygon.
+            let line_style = Style::read_using_style_type(header, reader, &sec_style_type)?;
 
-                // The width of the line.
-                let line_width = read_unit(header.scale, cursor, &header.coordinate_range)?;
+            // The width of the line.
+            let line_width = reader.read_unit(header)?;
 
-                // The path that should be drawn
-                let path = Path::parse(cursor, header, segment_count as usize)?;
+            // The path that should be drawn
+            let path = PathRef::read(reader, header, segment_count as usize)?;
 
-                let data = OutlineFillPathData {
-                    path,
-                    fill_style,
-                    line_style,
-                    line_width,
-                };
-                draw_commands.push(DrawCommand::OutlineFillPath(data));
+            DrawCommandRef::OutlineFillPath {
+                path,
+                fill_style,
+                line_style,
+                line_width,
             }
+        }
 
-            CommandType::TextHint => {
-                // The center of the descender line for the defined text.
-                let center = Point::read_point(header, cursor)?;
-
-                // The amount of degrees the text is rotated.
-                let rotation = read_unit(header.scale, cursor, &header.coordinate_range)?;
-
-                // The font size or distance from the ascender line to the
-                // descender line for the text.
-                let height = read_unit(header.scale, cursor, &header.coordinate_range)?;
-
-                // The number of bytes used to encode the text.
-                let text_length = read_variable_sized_unsigned_number(cursor)?;
-
-                // The UTF-8 encoded bytes corresponding to the text.
-                let mut text_buffer: Vec<u8> = vec![0; text_length as usize];
-                cursor.read_exact(text_buffer.as_mut_slice()).map_err(|_| TinyVgParseError::InvalidCommand)?;
-                let text = String::from_utf8(text_buffer).map_err(|_| TinyVgParseError::InvalidCommand)?;
-
-                // The number of glyphs within the text.
-                let glyph_length = read_variable_sized_unsigned_number(cursor)?;
-
-                // The start and end offset on the descender line from the
-                // center for each glyph.
-                let mut glyph_offset: Vec<(Unit, Unit)> = Vec::with_capacity(glyph_length as usize);
-                for _ in 0..glyph_length {
-                    let start_offset = read_unit(header.scale, cursor, &header.coordinate_range)?;
-                    let end_offset = read_unit(header.scale, cursor, &header.coordinate_range)?;
-                    glyph_offset.push((start_offset, end_offset));
+        CommandType::TextHint => {
+            // The center of the descender line for the defined text.
+            let center = reader.read_point(header)?;
+
+            // The amount of degrees the text is rotated.
+            let rotation = reader.read_unit(header)?;
+
+            // The font size or distance from the ascender line to the
+            // descender line for the text.
+            let height = reader.read_unit(header)?;
+
+            // The number of bytes used to encode the text.
+            let text_length = reader.read_variable_sized_unsigned_number()?;
+
+            // The UTF-8 encoded bytes corresponding to the text.
+            let text_length = usize::try_from(text_length).map_err(|_| TinyVgParseError::InvalidCommand)?;
+            let text_buffer = reader.take(text_length)?;
+            let text = Cow::Borrowed(std::str::from_utf8(text_buffer).map_err(|_| TinyVgParseError::InvalidCommand)?);
+
+            // The number of glyphs within the text.
+            let glyph_length = reader.read_variable_sized_unsigned_number()?;
+
+            // The start and end offset on the descender line from the
+            // center for each glyph.
+            let glyph_offset = reader.read_run(header, glyph_length, 2, |units, bytes| (units.unit(bytes, 0), units.unit(bytes, 1)))?;
+
+            DrawCommandRef::TextHint {
+                center,
+                text,
+                rotation,
+                height,
+                glyph_offset
+            }
+        }
+    };
+
+    Ok(Some(draw_command))
+}
+
+/// Finds the byte range of every draw command up to the end of the document without
+/// decoding the commands. Payloads are skipped by their size, which only needs the counts,
+/// styles and path command tags to be read, so that the commands can be decoded
+/// independently of each other afterwards.
+#[cfg(feature = "rayon")]
+pub(crate) fn scan_draw_commands(
+    reader: &mut Reader,
+    header: &TinyVgHeader,
+    options: &ParseOptions,
+) -> Result<Vec<Range<usize>>, TinyVgParseError> {
+    reader.error = TinyVgParseError::InvalidCommand;
+    let unit = unit_size(header.coordinate_range);
+    let mut spans = Vec::new();
+
+    loop {
+        let command_start = reader.position();
+        if at_unknown_command(reader) {
+            skip_unknown_commands(reader, options.unknown_commands)?;
+            return Ok(spans);
+        }
+        if options.strict {
+            check_end_of_document(reader)?;
+        }
+        let encoded_command = reader.read_u8()?;
+        let command = CommandType::from_u8(encoded_command & 0b00_11_11_11).ok_or(TinyVgParseError::UnknownCommand)?;
+        let style_type = StyleType::from_u8((encoded_command & 0b11_00_00_00) >> 6)?;
+
+        match command {
+            CommandType::EndOfDocument => break,
+            CommandType::FillPolygon | CommandType::FillRectangles | CommandType::FillPath => {
+                let count = reader.read_count()?;
+                skip_style(reader, unit, &style_type)?;
+                match command {
+                    CommandType::FillPolygon => skip_units(reader, unit, count, 2)?,
+                    CommandType::FillRectangles => skip_units(reader, unit, count, 4)?,
+                    _ => skip_path(reader, unit, count)?,
+                }
+            }
+            CommandType::DrawLines | CommandType::DrawLineLoop | CommandType::DrawLineStrip | CommandType::DrawLinePath => {
+                let count = reader.read_count()?;
+                skip_style(reader, unit, &style_type)?;
+                // The line width.
+                skip_units(reader, unit, 1, 1)?;
+                match command {
+                    CommandType::DrawLines => skip_units(reader, unit, count, 4)?,
+                    CommandType::DrawLinePath => skip_path(reader, unit, count)?,
+                    _ => skip_units(reader, unit, count, 2)?,
+                }
+            }
+            CommandType::OutlineFillPolygon | CommandType::OutlineFillRectangles | CommandType::OutlineFillPath => {
+                let count_sec_style_kind = reader.read_u8()?;
+                let count = (count_sec_style_kind & 0b00_11_11_11) as u64 + 1;
+                skip_style(reader, unit, &style_type)?;
+                skip_style(reader, unit, &StyleType::from_u8((count_sec_style_kind & 0b11_00_00_00) >> 6)?)?;
+                // The line width.
+                skip_units(reader, unit, 1, 1)?;
+                match command {
+                    CommandType::OutlineFillPolygon => skip_units(reader, unit, count, 2)?,
+                    CommandType::OutlineFillRectangles => skip_units(reader, unit, count, 4)?,
+                    _ => skip_path(reader, unit, count)?,
                 }
+            }
+            CommandType::TextHint => {
+                // The center, rotation and height.
+                skip_units(reader, unit, 1, 4)?;
+                let text_length = reader.read_variable_sized_unsigned_number()?;
+                reader.take(usize::try_from(text_length).map_err(|_| TinyVgParseError::InvalidCommand)?)?;
+                let glyph_length = reader.read_variable_sized_unsigned_number()?;
+                skip_units(reader, unit, glyph_length, 2)?;
+            }
+        }
+
+        spans.push(command_start..reader.position());
+    }
+
+    Ok(spans)
+}
 
-                let data = TextHintData {
-                    center,
-                    text,
-                    rotation,
-                    height,
-                    glyph_length,
-                    glyph_offset
-                };
-                draw_commands.push(DrawCommand::TextHint(data));
+/// Skips `count` items of `units_per_item` units each.
+fn skip_units(reader: &mut Reader, unit: usize, count: u64, units_per_item: usize) -> Result<(), TinyVgParseError> {
+    let length = usize::try_from(count).ok().and_then(|count| count.checked_mul(units_per_item * unit)).ok_or(reader.error)?;
+    reader.take(length)?;
+    Ok(())
+}
+
+#[cfg(feature = "rayon")]
+fn skip_style(reader: &mut Reader, unit: usize, style_type: &StyleType) -> Result<(), TinyVgParseError> {
+    if !matches!(style_type, StyleType::Flat) {
+        // The two points of the gradient.
+        skip_units(reader, unit, 1, 4)?;
+        reader.read_variable_sized_unsigned_number()?;
+    }
+    reader.read_variable_sized_unsigned_number()?;
+    Ok(())
+}
+
+/// Skips a path with `segment_count` segments, checking that the data holds all of it.
+pub(crate) fn skip_path(reader: &mut Reader, unit: usize, segment_count: u64) -> Result<(), TinyVgParseError> {
+    // As in `Path::read`, the command counts are read again with a second reader.
+    let mut segment_command_counts = reader.clone();
+    for _ in 0..segment_count {
+        reader.read_variable_sized_unsigned_number()?;
+    }
+
+    for _ in 0..segment_count {
+        let commands_count = segment_command_counts.read_count()?;
+        // The start point of the segment.
+        skip_units(reader, unit, 1, 2)?;
+
+        for _ in 0..commands_count {
+            let command_tag = reader.read_u8()?;
+            let path_command = PathCommandType::from_u8(command_tag & 0b00_00_01_11);
+            let line_width_size = if (command_tag & 0b00_01_00_00) != 0 { unit } else { 0 };
+            reader.take(line_width_size + path_command_payload_size(path_command, unit))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CubicBezier, DrawCommand, PathCommand, PathCommands, Point, Segment};
+    use crate::borrowed::TinyVgRef;
+    use crate::common::Unit;
+    use crate::{TinyVg, TinyVgParseError};
+
+    /// A 16 by 16 document with one opaque black color and no commands yet.
+    fn document_start() -> Vec<u8> {
+        vec![0x72, 0x56, 1, 0x00, 16, 0, 16, 0, 1, 0, 0, 0, 255]
+    }
+
+    /// The VarUInt encoding of `value`.
+    fn varuint(mut value: u64) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                bytes.push(byte);
+                return bytes;
             }
+            bytes.push(byte | 0x80);
         }
+    }
+
+    #[test]
+    fn counts_above_32_bits_are_invalid() {
+        for count in [u32::MAX as u64 + 1, u64::MAX] {
+            let mut data = document_start();
+            // A flat colored polygon.
+            data.push(0x01);
+            data.extend(varuint(count));
+            data.extend([0, 0]);
+            assert_eq!(TinyVg::from_bytes(&data), Err(TinyVgParseError::InvalidCommand));
+        }
+    }
+
+    #[test]
+    fn path_command_counts_above_32_bits_are_invalid() {
+        let mut data = document_start();
+        // A flat colored path with one segment, using color 0.
+        data.extend([0x03, 0x00, 0x00]);
+        data.extend(varuint(u64::MAX));
+        data.extend([0; 8]);
+        assert_eq!(TinyVg::from_bytes(&data), Err(TinyVgParseError::InvalidCommand));
+        assert_eq!(TinyVgRef::from_bytes(&data).err(), Some(TinyVgParseError::InvalidCommand));
+    }
+
+    #[test]
+    fn reserved_style_kind_is_invalid() {
+        let mut data = document_start();
+        // A polygon with the reserved style kind 3.
+        data.extend([0xC1, 0x00, 0x00]);
+        data.extend([0; 4]);
+        assert_eq!(TinyVg::from_bytes(&data), Err(TinyVgParseError::InvalidCommand));
+        assert_eq!(TinyVgRef::from_bytes(&data).err(), Some(TinyVgParseError::InvalidCommand));
+    }
+
+    #[test]
+    fn reserved_secondary_style_kind_is_invalid() {
+        let mut data = document_start();
+        // An outlined polygon with one point, a flat fill and the reserved line style kind 3.
+        data.extend([0x08, 0xC0, 0x00, 0x00, 0x00]);
+        data.extend([0; 4]);
+        assert_eq!(TinyVg::from_bytes(&data), Err(TinyVgParseError::InvalidCommand));
+        assert_eq!(TinyVgRef::from_bytes(&data).err(), Some(TinyVgParseError::InvalidCommand));
+    }
 
+    #[test]
+    fn close_path_line_widths_round_trip() {
+        let mut data = document_start();
+        // A flat colored path with one segment of two commands from (0, 0): a line to
+        // (8, 0) and a close path that sets the line width to 2.
+        data.extend([0x03, 0x00, 0x00, 0x01, 0, 0, 0, 0]);
+        data.extend([0x00, 8, 0, 0, 0]);
+        data.extend([0x16, 2, 0]);
+        data.push(0x00);
+
+        let document = TinyVg::from_bytes(&data).unwrap();
+        let DrawCommand::FillPath(fill) = &document.draw_commands[0] else {
+            panic!("{:?}", document.draw_commands);
+        };
+        assert_eq!(fill.path.segments[0].path_commands[1], PathCommand::ClosePath(Some(Unit(2.0))));
+        assert_eq!(document.to_bytes().unwrap(), data);
     }
 
-    Ok(draw_commands)
-}
\ No newline at end of file
+    #[test]
+    fn non_canonical_counts_are_written_canonically() {
+        let mut data = document_start();
+        // A flat colored polygon with two points, its count padded to two bytes.
+        data.extend([0x01, 0x81, 0x00, 0x00]);
+        data.extend([0, 0, 0, 0, 8, 0, 8, 0]);
+        data.push(0x00);
+
+        let document = TinyVg::from_bytes(&data).unwrap();
+        let encoded = document.to_bytes().unwrap();
+        assert_eq!(encoded.len(), data.len() - 1);
+        assert_eq!(TinyVg::from_bytes(&encoded), Ok(document));
+    }
+
+    #[test]
+    fn invalid_flatten_tolerances_are_clamped() {
+        let point = |x, y| Point::new(Unit(x), Unit(y));
+        let cubic = CubicBezier { control_point_0: point(0.0, 10.0), control_point_1: point(10.0, 10.0), point_1: point(10.0, 0.0) };
+        let mut path_commands = PathCommands::new();
+        path_commands.push(PathCommand::CubicBezier(cubic, None));
+        let segment = Segment { start: point(0.0, 0.0), path_commands };
+
+        for tolerance in [f64::NAN, 0.0, -1.0, f64::INFINITY] {
+            let points: Vec<Point> = segment.flatten(tolerance).collect();
+            assert!(points.len() >= 2, "{tolerance}");
+            assert_eq!(points.last(), Some(&point(10.0, 0.0)), "{tolerance}");
+            assert!(points.iter().all(|point| point.x.0.is_finite() && point.y.0.is_finite()), "{tolerance}");
+        }
+    }
+}
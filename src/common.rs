@@ -1,58 +1,356 @@
 use crate::commands::Point;
+use crate::encoded_size::unit_size;
 use crate::header::TinyVgHeader;
 use crate::{CoordinateRange, TinyVgParseError};
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{LittleEndian, WriteBytesExt};
 use std::io::Cursor;
+use std::ops::{Add, Mul, Neg, Sub};
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Unit(pub f64);
 
-/// Unit may be 8, 16, or 32 bits, so we will advance the cursor conditionally.
-pub(crate) fn read_size(coordinate_range: &CoordinateRange, cursor: &mut Cursor<&[u8]>) -> Result<u32, TinyVgParseError> {
-    let res = match coordinate_range {
-        CoordinateRange::Reduced  => cursor.read_u8().map_err(|_| TinyVgParseError::InvalidHeader)? as u32,
-        CoordinateRange::Default  => cursor.read_u16::<LittleEndian>().map_err(|_| TinyVgParseError::InvalidHeader)? as u32,
-        CoordinateRange::Enhanced => cursor.read_u32::<LittleEndian>().map_err(|_| TinyVgParseError::InvalidHeader)?
-    };
-    Ok(res)
-}
-
-/// Page 4, VarUInt.
-/// This type is used to encode 32-bit unsigned integers while keeping the number of bytes low. It is encoded
-/// as a variable-sized integer that uses 7 bit per byte for integer bits and the 7th bit to encode that there
-/// are more bits available.
-pub(crate) fn read_variable_sized_unsigned_number(cursor: &mut Cursor<&[u8]>) -> Result<u64, TinyVgParseError> {
-    let mut count = 0u64;
-    let mut result = 0u64;
-    loop {
-        let byte = cursor.read_u8().map_err(|_| TinyVgParseError::InvalidHeader)?;
-        let val: u64 = (byte as u64 & 0x7F) << (7 * count);
-        result |= val;
-        if (byte & 0x80) == 0 {
-            break;
+impl Unit {
+    /// The unit for a fixed-point value as it is stored in a file, with `scale` fraction
+    /// bits as given by the header.
+    pub fn from_raw(raw: i32, scale: u8) -> Unit {
+        Unit(raw as f64 / (1u32 << scale) as f64)
+    }
+
+    /// The fixed-point value the unit is stored as with `scale` fraction bits, for
+    /// renderers that work in integers. Units read from a file give back exactly the value
+    /// that was stored. Other values are rounded to the nearest step and saturate at the
+    /// limits of `i32`.
+    pub fn to_raw(self, scale: u8) -> i32 {
+        (self.0 * (1u32 << scale) as f64).round() as i32
+    }
+
+    /// The value a fraction `t` of the way from `self` to `other`.
+    pub fn lerp(self, other: Unit, t: f64) -> Unit {
+        Unit(self.0 + (other.0 - self.0) * t)
+    }
+}
+
+impl From<f64> for Unit {
+    fn from(value: f64) -> Self {
+        Unit(value)
+    }
+}
+
+impl Add for Unit {
+    type Output = Unit;
+
+    fn add(self, other: Unit) -> Unit {
+        Unit(self.0 + other.0)
+    }
+}
+
+impl Sub for Unit {
+    type Output = Unit;
+
+    fn sub(self, other: Unit) -> Unit {
+        Unit(self.0 - other.0)
+    }
+}
+
+impl Mul<f64> for Unit {
+    type Output = Unit;
+
+    fn mul(self, factor: f64) -> Unit {
+        Unit(self.0 * factor)
+    }
+}
+
+impl Neg for Unit {
+    type Output = Unit;
+
+    fn neg(self) -> Unit {
+        Unit(-self.0)
+    }
+}
+
+/// Reads the fields of a file directly from its bytes. Runs of units whose length is known
+/// up front, such as the points of a polygon, are bounds checked once and decoded in bulk.
+#[derive(Clone)]
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+
+    /// The error reported when the data ends early, set by the part of the file being read.
+    pub(crate) error: TinyVgParseError,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8], error: TinyVgParseError) -> Self {
+        Reader { data, position: 0, error }
+    }
+
+    /// Runs `read` on the bytes after the cursor's position and advances the cursor past
+    /// the bytes it read if it succeeds, for the parsing functions that take a cursor.
+    pub(crate) fn with_cursor<T>(
+        cursor: &mut Cursor<&'a [u8]>,
+        error: TinyVgParseError,
+        read: impl FnOnce(&mut Reader<'a>) -> Result<T, TinyVgParseError>,
+    ) -> Result<T, TinyVgParseError> {
+        let data = *cursor.get_ref();
+        let position = (cursor.position() as usize).min(data.len());
+        let mut reader = Reader { data, position, error };
+        let value = read(&mut reader)?;
+        cursor.set_position(reader.position as u64);
+        Ok(value)
+    }
+
+    #[inline]
+    pub(crate) fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The number of bytes after the position.
+    #[inline]
+    pub(crate) fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+
+    /// A capacity for `count` items that take at least `min_item_size` bytes each, limited
+    /// to what the remaining data could hold. Counts are read from the file, so a short
+    /// hostile file must not be able to make the parser allocate for billions of items
+    /// before it runs out of data.
+    pub(crate) fn bounded_capacity(&self, count: u64, min_item_size: usize) -> usize {
+        usize::try_from(count).unwrap_or(usize::MAX).min(self.remaining() / min_item_size.max(1))
+    }
+
+    /// The byte at the position, without moving past it.
+    #[inline]
+    pub(crate) fn peek_u8(&self) -> Option<u8> {
+        self.data.get(self.position).copied()
+    }
+
+    /// Moves past all remaining bytes and returns them.
+    pub(crate) fn take_rest(&mut self) -> &'a [u8] {
+        let rest = &self.data[self.position..];
+        self.position = self.data.len();
+        rest
+    }
+
+    /// The next `length` bytes, checked once against the end of the data.
+    #[inline]
+    pub(crate) fn take(&mut self, length: usize) -> Result<&'a [u8], TinyVgParseError> {
+        let end = self.position.checked_add(length).filter(|&end| end <= self.data.len()).ok_or(self.error)?;
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    /// Runs `skip` and returns the bytes it moved past.
+    pub(crate) fn take_skipped(
+        &mut self,
+        skip: impl FnOnce(&mut Reader<'a>) -> Result<(), TinyVgParseError>,
+    ) -> Result<&'a [u8], TinyVgParseError> {
+        let start = self.position;
+        skip(self)?;
+        Ok(&self.data[start..self.position])
+    }
+
+    pub(crate) fn read_array<const N: usize>(&mut self) -> Result<[u8; N], TinyVgParseError> {
+        let bytes = self.take(N)?;
+        Ok(bytes.try_into().expect("took N bytes"))
+    }
+
+    #[inline]
+    pub(crate) fn read_u8(&mut self) -> Result<u8, TinyVgParseError> {
+        let byte = *self.data.get(self.position).ok_or(self.error)?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    /// Unit may be 8, 16, or 32 bits, so we will advance the position conditionally.
+    pub(crate) fn read_size(&mut self, coordinate_range: CoordinateRange) -> Result<u32, TinyVgParseError> {
+        let res = match coordinate_range {
+            CoordinateRange::Reduced => self.read_u8()? as u32,
+            CoordinateRange::Default => u16::from_le_bytes(self.read_array()?) as u32,
+            CoordinateRange::Enhanced => u32::from_le_bytes(self.read_array()?),
+        };
+        Ok(res)
+    }
+
+    /// Page 4, VarUInt.
+    /// This type is used to encode 32-bit unsigned integers while keeping the number of bytes low. It is encoded
+    /// as a variable-sized integer that uses 7 bit per byte for integer bits and the 7th bit to encode that there
+    /// are more bits available. Numbers that do not fit 64 bits are an error.
+    #[inline]
+    pub(crate) fn read_variable_sized_unsigned_number(&mut self) -> Result<u64, TinyVgParseError> {
+        let mut result = 0u64;
+        for count in 0..10 {
+            let byte = self.read_u8()?;
+            result |= (byte as u64 & 0x7F) << (7 * count);
+            if (byte & 0x80) == 0 {
+                return Ok(result);
+            }
         }
-        count += 1;
+        Err(self.error)
     }
 
-    Ok(result)
+    /// Reads an item count, which is stored as a VarUInt one less than the count. The
+    /// format limits VarUInt to 32 bits, so larger values are an invalid command.
+    #[inline]
+    pub(crate) fn read_count(&mut self) -> Result<u64, TinyVgParseError> {
+        let count = self.read_variable_sized_unsigned_number()?;
+        if count > u32::MAX as u64 {
+            return Err(TinyVgParseError::InvalidCommand);
+        }
+        Ok(count + 1)
+    }
+
+    #[inline]
+    pub(crate) fn read_unit(&mut self, header: &TinyVgHeader) -> Result<Unit, TinyVgParseError> {
+        let units = UnitDecoder::new(header);
+        Ok(units.unit(self.take(units.size)?, 0))
+    }
+
+    #[inline]
+    pub(crate) fn read_point(&mut self, header: &TinyVgHeader) -> Result<Point, TinyVgParseError> {
+        let units = UnitDecoder::new(header);
+        Ok(units.point(self.take(2 * units.size)?, 0))
+    }
+
+    /// Takes `count` items of `units_per_item` units each with a single bounds check. The
+    /// items are decoded with `decode` when they are accessed, see `UnitRun`.
+    pub(crate) fn read_run<T>(
+        &mut self,
+        header: &TinyVgHeader,
+        count: u64,
+        units_per_item: usize,
+        decode: fn(&UnitDecoder, &[u8]) -> T,
+    ) -> Result<UnitRun<'a, T>, TinyVgParseError> {
+        let units = UnitDecoder::new(header);
+        let item_size = units_per_item * units.size;
+        let length = usize::try_from(count).ok().and_then(|count| count.checked_mul(item_size)).ok_or(self.error)?;
+        let bytes = self.take(length)?;
+        Ok(UnitRun { bytes, units, item_size, decode })
+    }
+
+    pub(crate) fn read_point_run(&mut self, header: &TinyVgHeader, count: u64) -> Result<UnitRun<'a, Point>, TinyVgParseError> {
+        // Almost every file uses the default range, so its points get a decoder without a
+        // match on the range per unit.
+        let decode: fn(&UnitDecoder, &[u8]) -> Point = match header.coordinate_range {
+            CoordinateRange::Default => UnitDecoder::point_i16,
+            CoordinateRange::Reduced | CoordinateRange::Enhanced => |units, bytes| units.point(bytes, 0),
+        };
+        self.read_run(header, count, 2, decode)
+    }
+}
+
+/// Items made of a fixed number of units that are stored back to back in a file, such as
+/// the points of a polygon. The items stay in the file's bytes and are decoded each time
+/// they are accessed.
+pub struct UnitRun<'a, T> {
+    bytes: &'a [u8],
+    units: UnitDecoder,
+    item_size: usize,
+    decode: fn(&UnitDecoder, &[u8]) -> T,
 }
 
+impl<'a, T> UnitRun<'a, T> {
+    pub fn len(&self) -> usize {
+        self.bytes.len() / self.item_size
+    }
 
-pub(crate) fn read_unit(scale: u8, cursor: &mut Cursor<&[u8]>, coordinate_range: &CoordinateRange) -> Result<Unit, TinyVgParseError> {
-    let raw: i64;
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
 
-    match coordinate_range {
-        CoordinateRange::Default => raw = cursor.read_i16::<LittleEndian>().map_err(|_| TinyVgParseError::InvalidCommand)? as i64,
-        CoordinateRange::Reduced => raw = cursor.read_i8().map_err(|_| TinyVgParseError::InvalidCommand)? as i64,
-        CoordinateRange::Enhanced => raw = cursor.read_i32::<LittleEndian>().map_err(|_| TinyVgParseError::InvalidCommand)? as i64,
+    pub fn get(&self, index: usize) -> Option<T> {
+        let start = index.checked_mul(self.item_size)?;
+        let item = self.bytes.get(start..start.checked_add(self.item_size)?)?;
+        Some((self.decode)(&self.units, item))
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = T> + use<'a, T> {
+        let (units, decode) = (self.units, self.decode);
+        self.bytes.chunks_exact(self.item_size).map(move |item| decode(&units, item))
+    }
+
+    pub fn to_vec(&self) -> Vec<T> {
+        self.iter().collect()
+    }
+
+    /// The encoded items as they are stored in the file.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+}
+
+impl<T> Clone for UnitRun<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for UnitRun<'_, T> {}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for UnitRun<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: PartialEq> PartialEq for UnitRun<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+/// Decodes units stored back to back with the coordinate range and scale of a header.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct UnitDecoder {
+    range: CoordinateRange,
+    factor: f64,
+
+    /// The number of bytes of a unit.
+    pub(crate) size: usize,
+}
+
+impl UnitDecoder {
+    #[inline]
+    pub(crate) fn new(header: &TinyVgHeader) -> Self {
+        UnitDecoder {
+            range: header.coordinate_range,
+            // Multiplying by a power of two is exact, so this matches `Unit::from_raw`.
+            factor: 1.0 / (1u32 << header.scale) as f64,
+            size: unit_size(header.coordinate_range),
+        }
+    }
+
+    /// The unit at `index` in `bytes`, counted in units.
+    #[inline]
+    pub(crate) fn unit(&self, bytes: &[u8], index: usize) -> Unit {
+        let raw = match self.range {
+            CoordinateRange::Reduced => bytes[index] as i8 as i32,
+            CoordinateRange::Default => i16::from_le_bytes([bytes[2 * index], bytes[2 * index + 1]]) as i32,
+            CoordinateRange::Enhanced => {
+                let offset = 4 * index;
+                i32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+            }
+        };
+        Unit(raw as f64 * self.factor)
+    }
+
+    /// The first point in `bytes`, read as a pair of `i16` for the default coordinate range.
+    #[inline]
+    fn point_i16(&self, bytes: &[u8]) -> Point {
+        let &[x_0, x_1, y_0, y_1] = bytes.first_chunk().expect("a point takes 4 bytes");
+        Point::new(
+            Unit(i16::from_le_bytes([x_0, x_1]) as f64 * self.factor),
+            Unit(i16::from_le_bytes([y_0, y_1]) as f64 * self.factor),
+        )
     }
-    
-    let units_in_css_px: f64 = raw as f64 / (1 << scale) as f64;
 
-    Ok(Unit(units_in_css_px))
+    /// The point made of the units at `index` and `index + 1` in `bytes`.
+    #[inline]
+    pub(crate) fn point(&self, bytes: &[u8], index: usize) -> Point {
+        Point::new(self.unit(bytes, index), self.unit(bytes, index + 1))
+    }
 }
 
-#[allow(dead_code)]
 pub(crate) fn write_unit(
     scale: u8,
     cursor: &mut Cursor<Vec<u8>>,
@@ -83,7 +381,6 @@ pub(crate) fn write_unit(
     }
 }
 
-#[allow(dead_code)]
 pub(crate) fn write_variable_sized_unsigned_number(
     cursor: &mut Cursor<Vec<u8>>,
     mut value: u64,
@@ -102,7 +399,6 @@ pub(crate) fn write_variable_sized_unsigned_number(
     Ok(())
 }
 
-#[allow(dead_code)]
 pub(crate) fn write_size(
     range: &CoordinateRange,
     cursor: &mut Cursor<Vec<u8>>,
@@ -115,9 +411,92 @@ pub(crate) fn write_size(
     }
 }
 
-#[allow(dead_code)]
 pub(crate) fn write_point(point: &Point, header: &TinyVgHeader, cursor: &mut Cursor<Vec<u8>>) -> Result<(), TinyVgParseError> {
     write_unit(header.scale, cursor, &header.coordinate_range, point.x)?;
     write_unit(header.scale, cursor, &header.coordinate_range, point.y)?;
     Ok(())
-}
\ No newline at end of file
+}
+
+/// A 2D affine transform stored as the coefficients `[a, b, c, d, e, f]`, mapping
+/// a point to `(a * x + c * y + e, b * x + d * y + f)`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Affine(pub [f64; 6]);
+
+impl Affine {
+    pub const IDENTITY: Affine = Affine([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+
+    pub fn new(coefficients: [f64; 6]) -> Self {
+        Affine(coefficients)
+    }
+
+    pub fn translate(x: f64, y: f64) -> Self {
+        Affine([1.0, 0.0, 0.0, 1.0, x, y])
+    }
+
+    pub fn scale(factor: f64) -> Self {
+        Affine::scale_non_uniform(factor, factor)
+    }
+
+    pub fn scale_non_uniform(x: f64, y: f64) -> Self {
+        Affine([x, 0.0, 0.0, y, 0.0, 0.0])
+    }
+
+    /// A shear that slants vertical lines by `angle_x` radians and horizontal lines by
+    /// `angle_y` radians, as the SVG `skewX` and `skewY` transforms do.
+    pub fn skew(angle_x: f64, angle_y: f64) -> Self {
+        Affine([1.0, angle_y.tan(), angle_x.tan(), 1.0, 0.0, 0.0])
+    }
+
+    /// A rotation by `angle` radians, clockwise on a y-down canvas.
+    pub fn rotate(angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Affine([cos, sin, -sin, cos, 0.0, 0.0])
+    }
+
+    /// The transform that applies `self` first and `other` second.
+    pub fn then(&self, other: &Affine) -> Self {
+        let [a0, b0, c0, d0, e0, f0] = self.0;
+        let [a1, b1, c1, d1, e1, f1] = other.0;
+        Affine([
+            a1 * a0 + c1 * b0,
+            b1 * a0 + d1 * b0,
+            a1 * c0 + c1 * d0,
+            b1 * c0 + d1 * d0,
+            a1 * e0 + c1 * f0 + e1,
+            b1 * e0 + d1 * f0 + f1,
+        ])
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Affine::IDENTITY
+    }
+
+    pub fn determinant(&self) -> f64 {
+        let [a, b, c, d, _, _] = self.0;
+        a * d - b * c
+    }
+
+    /// The transform that undoes this one, or `None` if it collapses the plane onto a
+    /// line or point.
+    pub fn inverse(&self) -> Option<Affine> {
+        let determinant = self.determinant();
+        if determinant == 0.0 || !determinant.is_finite() {
+            return None;
+        }
+        let [a, b, c, d, e, f] = self.0;
+        Some(Affine([
+            d / determinant,
+            -b / determinant,
+            -c / determinant,
+            a / determinant,
+            (c * f - d * e) / determinant,
+            (b * e - a * f) / determinant,
+        ]))
+    }
+
+    pub fn apply(&self, point: Point) -> Point {
+        let [a, b, c, d, e, f] = self.0;
+        let (x, y) = (point.x.0, point.y.0);
+        Point::new(Unit(a * x + c * y + e), Unit(b * x + d * y + f))
+    }
+}
@@ -0,0 +1,99 @@
+use crate::color_table::{ColorSpace, RgbaF32};
+use crate::commands::DrawCommand;
+use crate::palette::{for_each_color_index, relative_luminance};
+use crate::TinyVg;
+
+/// The least contrast WCAG 2 level AA requires for text below the large size.
+pub const AA_NORMAL_TEXT: f64 = 4.5;
+
+/// The least contrast WCAG 2 level AA requires for large text.
+pub const AA_LARGE_TEXT: f64 = 3.0;
+
+/// The least contrast WCAG 2 level AA requires for graphics that are needed to understand
+/// the content, such as icons.
+pub const AA_GRAPHICS: f64 = 3.0;
+
+/// Text of at least 18 points counts as large, which is 24 display units at the nominal
+/// size of 96 units per inch.
+const LARGE_TEXT_HEIGHT: f64 = 24.0;
+
+/// A color used by a command whose contrast against the background is below what WCAG AA
+/// requires for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContrastIssue {
+    /// The index of the command in `TinyVg::draw_commands`.
+    pub command_index: usize,
+
+    /// The index of the color in `TinyVg::color_table`.
+    pub color_index: usize,
+    pub ratio: f64,
+
+    /// The ratio the command needs: `AA_NORMAL_TEXT` or `AA_LARGE_TEXT` for text and
+    /// `AA_GRAPHICS` for everything else.
+    pub required: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContrastAudit {
+    /// The contrast ratio of every color table entry against the background, in table
+    /// order, from 1 for no contrast to 21 for black on white.
+    pub ratios: Vec<f64>,
+
+    /// In drawing order, with one issue per command and color.
+    pub issues: Vec<ContrastIssue>,
+}
+
+impl TinyVg {
+    /// Computes the WCAG contrast ratio of every color against `background` and flags the
+    /// commands that paint with a color below the level AA minimum at the document's
+    /// nominal size. Translucent colors are measured as blended over the background.
+    ///
+    /// Commands directly followed by a text hint, as `text::draw_text` writes them, are
+    /// held to the text minimum for the hint's height. All other commands are graphics.
+    /// `background` is given in the color space of the document's color encoding and is
+    /// taken to be opaque.
+    pub fn contrast_audit(&self, background: RgbaF32) -> ContrastAudit {
+        let color_space = self.header.color_encoding.color_space();
+        let background = background.convert(color_space, ColorSpace::Linear);
+        let background_luminance = relative_luminance(&background) as f64;
+
+        let ratios: Vec<f64> = self
+            .color_table
+            .iter()
+            .map(|color| {
+                let color = color.convert(color_space, ColorSpace::Linear);
+                let blend = |channel: f32, background: f32| channel * color.3 + background * (1.0 - color.3);
+                let blended = RgbaF32(blend(color.0, background.0), blend(color.1, background.1), blend(color.2, background.2), 1.0);
+                contrast_ratio(relative_luminance(&blended) as f64, background_luminance)
+            })
+            .collect();
+
+        let mut issues: Vec<ContrastIssue> = Vec::new();
+        for (command_index, command) in self.draw_commands.iter().enumerate() {
+            let required = match self.draw_commands.get(command_index + 1) {
+                Some(DrawCommand::TextHint(hint)) if !matches!(command, DrawCommand::TextHint(_)) => {
+                    if hint.height.0.abs() >= LARGE_TEXT_HEIGHT { AA_LARGE_TEXT } else { AA_NORMAL_TEXT }
+                }
+                _ => AA_GRAPHICS,
+            };
+
+            for_each_color_index(command, &mut |index| {
+                let color_index = index as usize;
+                let Some(&ratio) = ratios.get(color_index) else {
+                    return;
+                };
+                let reported = issues.iter().any(|issue| issue.command_index == command_index && issue.color_index == color_index);
+                if ratio < required && !reported {
+                    issues.push(ContrastIssue { command_index, color_index, ratio, required });
+                }
+            });
+        }
+
+        ContrastAudit { ratios, issues }
+    }
+}
+
+/// The WCAG contrast ratio of two relative luminances, in either order.
+fn contrast_ratio(a: f64, b: f64) -> f64 {
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
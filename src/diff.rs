@@ -0,0 +1,183 @@
+use crate::bounds::{bounds_rectangle, command_bounds, union};
+use crate::color_table::RgbaF32;
+use crate::commands::{DrawCommand, Rectangle};
+use crate::palette::{for_each_color_index_mut, intern_color};
+use crate::TinyVg;
+
+/// Above this many command pairs the commands between the common start and end are
+/// compared position by position instead of being aligned.
+const MAX_ALIGNMENT_CELLS: usize = 1 << 22;
+
+/// A difference between the color tables of two documents, by position in the table.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PaletteChange {
+    Added { index: usize, color: RgbaF32 },
+    Removed { index: usize, color: RgbaF32 },
+    Changed { index: usize, old: RgbaF32, new: RgbaF32 },
+}
+
+/// A difference between the draw commands of two documents. Indices refer to
+/// `TinyVg::draw_commands` of the old and the new document, and `region` is the area the
+/// command paints, covering both versions of changed commands.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandChange {
+    Added { new_index: usize, region: Option<Rectangle> },
+    Removed { old_index: usize, region: Option<Rectangle> },
+    Changed { old_index: usize, new_index: usize, region: Option<Rectangle> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentDiff {
+    /// Whether the headers differ in size, scale, color encoding or coordinate range.
+    pub header_changed: bool,
+    pub palette: Vec<PaletteChange>,
+
+    /// In order of the old document, with added commands at their new position.
+    pub commands: Vec<CommandChange>,
+}
+
+impl DocumentDiff {
+    pub fn is_empty(&self) -> bool {
+        !self.header_changed && self.palette.is_empty() && self.commands.is_empty()
+    }
+}
+
+/// Compares two documents. Commands are compared by the colors they use rather than their
+/// color table indices, so reordering the color table alone only shows up as palette
+/// changes. Unchanged commands are aligned between the documents, so inserting a command
+/// reports one added command rather than every later command as changed. Removed and added
+/// commands at the same place are paired up as changed commands.
+pub fn diff(old: &TinyVg, new: &TinyVg) -> DocumentDiff {
+    let header_changed = old.header.width != new.header.width
+        || old.header.height != new.header.height
+        || old.header.scale != new.header.scale
+        || old.header.color_encoding != new.header.color_encoding
+        || old.header.coordinate_range as u8 != new.header.coordinate_range as u8;
+
+    let palette_length = old.color_table.len().max(new.color_table.len());
+    let palette = (0..palette_length)
+        .filter_map(|index| match (old.color_table.get(index), new.color_table.get(index)) {
+            (Some(&old), Some(&new)) if old != new => Some(PaletteChange::Changed { index, old, new }),
+            (Some(&color), None) => Some(PaletteChange::Removed { index, color }),
+            (None, Some(&color)) => Some(PaletteChange::Added { index, color }),
+            _ => None,
+        })
+        .collect();
+
+    // Resolve both documents' color indices into one shared table of distinct colors.
+    let mut colors: Vec<RgbaF32> = Vec::new();
+    let old_commands = resolve_colors(old, &mut colors);
+    let new_commands = resolve_colors(new, &mut colors);
+
+    let mut commands = Vec::new();
+    let mut removed: Vec<usize> = Vec::new();
+    let mut added: Vec<usize> = Vec::new();
+    for step in align(&old_commands, &new_commands) {
+        match step {
+            Step::Removed(old_index) => removed.push(old_index),
+            Step::Added(new_index) => added.push(new_index),
+            Step::Same => flush_changes(old, new, &mut removed, &mut added, &mut commands),
+        }
+    }
+    flush_changes(old, new, &mut removed, &mut added, &mut commands);
+
+    DocumentDiff { header_changed, palette, commands }
+}
+
+fn resolve_colors(document: &TinyVg, colors: &mut Vec<RgbaF32>) -> Vec<DrawCommand> {
+    let shared_indices: Vec<u64> = document.color_table.iter().map(|color| intern_color(colors, *color)).collect();
+
+    let mut commands = document.draw_commands.clone();
+    for command in &mut commands {
+        for_each_color_index_mut(command, &mut |index| {
+            // Missing colors get indices past every shared color, and compare by index.
+            *index = shared_indices.get(*index as usize).copied().unwrap_or(u64::MAX - *index);
+        });
+    }
+    commands
+}
+
+fn flush_changes(old: &TinyVg, new: &TinyVg, removed: &mut Vec<usize>, added: &mut Vec<usize>, commands: &mut Vec<CommandChange>) {
+    let region = |document: &TinyVg, index: usize| command_bounds(&document.draw_commands[index]);
+    let paired = removed.len().min(added.len());
+
+    for (&old_index, &new_index) in removed.iter().zip(added.iter()) {
+        let region = match (region(old, old_index), region(new, new_index)) {
+            (Some(a), Some(b)) => Some(union(a, b)),
+            (a, b) => a.or(b),
+        };
+        commands.push(CommandChange::Changed { old_index, new_index, region: region.map(bounds_rectangle) });
+    }
+    for &old_index in &removed[paired..] {
+        commands.push(CommandChange::Removed { old_index, region: region(old, old_index).map(bounds_rectangle) });
+    }
+    for &new_index in &added[paired..] {
+        commands.push(CommandChange::Added { new_index, region: region(new, new_index).map(bounds_rectangle) });
+    }
+
+    removed.clear();
+    added.clear();
+}
+
+enum Step {
+    Same,
+    Removed(usize),
+    Added(usize),
+}
+
+/// An edit script from `old` to `new` that keeps a longest common subsequence of commands.
+fn align(old: &[DrawCommand], new: &[DrawCommand]) -> Vec<Step> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (old_middle, new_middle) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+    let mut steps: Vec<Step> = (0..prefix).map(|_| Step::Same).collect();
+    let (n, m) = (old_middle.len(), new_middle.len());
+
+    if n * m > MAX_ALIGNMENT_CELLS {
+        for i in 0..n.max(m) {
+            match (old_middle.get(i), new_middle.get(i)) {
+                (Some(a), Some(b)) if a == b => steps.push(Step::Same),
+                (a, b) => {
+                    if a.is_some() {
+                        steps.push(Step::Removed(prefix + i));
+                    }
+                    if b.is_some() {
+                        steps.push(Step::Added(prefix + i));
+                    }
+                }
+            }
+        }
+    } else {
+        // lengths[i][j] is the length of the longest common subsequence of old_middle[i..]
+        // and new_middle[j..].
+        let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lengths[i * (m + 1) + j] = if old_middle[i] == new_middle[j] {
+                    lengths[(i + 1) * (m + 1) + j + 1] + 1
+                } else {
+                    lengths[(i + 1) * (m + 1) + j].max(lengths[i * (m + 1) + j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && old_middle[i] == new_middle[j] {
+                steps.push(Step::Same);
+                i += 1;
+                j += 1;
+            } else if j == m || (i < n && lengths[(i + 1) * (m + 1) + j] >= lengths[i * (m + 1) + j + 1]) {
+                steps.push(Step::Removed(prefix + i));
+                i += 1;
+            } else {
+                steps.push(Step::Added(prefix + j));
+                j += 1;
+            }
+        }
+    }
+
+    steps.extend((0..suffix).map(|_| Step::Same));
+    steps
+}
@@ -0,0 +1,167 @@
+use crate::color_table::RgbaF32;
+use crate::commands::DrawCommand;
+use crate::validation::{validate_command, ValidationError};
+use crate::TinyVg;
+
+/// Why an edit was rejected. A rejected edit leaves the document unchanged.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EditError {
+    /// The command or color index is past the end of the document's `len` commands or colors.
+    IndexOutOfRange { index: usize, len: usize },
+
+    /// The edit would remove the document's only command.
+    LastCommand,
+
+    /// The command cannot be encoded with the document's header and color table, or the
+    /// edited document no longer validates.
+    Invalid(ValidationError),
+}
+
+impl TinyVg {
+    /// Inserts a command before the command at `index`, or appends it if `index` is the
+    /// number of commands. The command must only use colors of the color table and fit the
+    /// header's coordinate range.
+    pub fn insert_command(&mut self, index: usize, command: DrawCommand) -> Result<(), EditError> {
+        if index > self.draw_commands.len() {
+            return Err(EditError::IndexOutOfRange { index, len: self.draw_commands.len() });
+        }
+        validate_command(self, &self.header, index, &command).map_err(EditError::Invalid)?;
+        self.draw_commands.insert(index, command);
+        Ok(())
+    }
+
+    /// Removes the command at `index` and returns it. The last remaining command cannot be
+    /// removed, so edited documents always draw something.
+    pub fn remove_command(&mut self, index: usize) -> Result<DrawCommand, EditError> {
+        if index >= self.draw_commands.len() {
+            return Err(EditError::IndexOutOfRange { index, len: self.draw_commands.len() });
+        }
+        if self.draw_commands.len() == 1 {
+            return Err(EditError::LastCommand);
+        }
+        Ok(self.draw_commands.remove(index))
+    }
+
+    /// Replaces the command at `index` and returns the old command. The new command is
+    /// checked as for `insert_command`.
+    pub fn replace_command(&mut self, index: usize, command: DrawCommand) -> Result<DrawCommand, EditError> {
+        if index >= self.draw_commands.len() {
+            return Err(EditError::IndexOutOfRange { index, len: self.draw_commands.len() });
+        }
+        validate_command(self, &self.header, index, &command).map_err(EditError::Invalid)?;
+        Ok(std::mem::replace(&mut self.draw_commands[index], command))
+    }
+
+    /// Sets the color table entry at `index`, or appends a color if `index` is the number
+    /// of colors, and keeps the header's color count in sync.
+    pub fn set_color(&mut self, index: usize, color: RgbaF32) -> Result<(), EditError> {
+        match index.cmp(&self.color_table.len()) {
+            std::cmp::Ordering::Less => self.color_table[index] = color,
+            std::cmp::Ordering::Equal => self.color_table.push(color),
+            std::cmp::Ordering::Greater => return Err(EditError::IndexOutOfRange { index, len: self.color_table.len() }),
+        }
+        self.header.color_count = self.color_table.len() as u64;
+        Ok(())
+    }
+
+    /// Runs a batch of edits and revalidates the whole document afterwards. If `edit` fails
+    /// or the result does not validate, the document is restored to its state before the
+    /// batch. This keeps a copy of the document while the edits run.
+    pub fn edit_validated<T>(&mut self, edit: impl FnOnce(&mut TinyVg) -> Result<T, EditError>) -> Result<T, EditError> {
+        let backup = self.clone();
+        let result = edit(self).and_then(|value| self.validate().map(|()| value).map_err(EditError::Invalid));
+        if result.is_err() {
+            *self = backup;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EditError;
+    use crate::color_table::RgbaF32;
+    use crate::commands::{DrawCommand, FillRectanglesData, FlatColored, Rectangle, Style};
+    use crate::common::Unit;
+    use crate::validation::ValidationError;
+    use crate::TinyVg;
+
+    /// A 16 by 16 document with one color and one rectangle.
+    const DOCUMENT: [u8; 25] = [0x72, 0x56, 1, 0x00, 16, 0, 16, 0, 1, 255, 0, 0, 255, 0x02, 0, 0, 0, 0, 0, 0, 16, 0, 16, 0, 0x00];
+
+    fn rectangle(x: f64, color_index: u64) -> DrawCommand {
+        DrawCommand::FillRectangles(FillRectanglesData {
+            style: Style::FlatColor(FlatColored { color_index }),
+            rectangles: vec![Rectangle { x: Unit(x), y: Unit(0.0), width: Unit(4.0), height: Unit(4.0) }],
+        })
+    }
+
+    #[test]
+    fn commands_are_inserted_replaced_and_removed() {
+        let mut document = TinyVg::from_bytes(&DOCUMENT).unwrap();
+        let original = document.draw_commands[0].clone();
+
+        document.insert_command(1, rectangle(8.0, 0)).unwrap();
+        document.insert_command(0, rectangle(4.0, 0)).unwrap();
+        assert_eq!(document.draw_commands, [rectangle(4.0, 0), original.clone(), rectangle(8.0, 0)]);
+
+        assert_eq!(document.replace_command(2, rectangle(12.0, 0)), Ok(rectangle(8.0, 0)));
+        assert_eq!(document.remove_command(0), Ok(rectangle(4.0, 0)));
+        assert_eq!(document.draw_commands, [original, rectangle(12.0, 0)]);
+        assert_eq!(document.validate(), Ok(()));
+    }
+
+    #[test]
+    fn invalid_edits_leave_the_document_unchanged() {
+        let mut document = TinyVg::from_bytes(&DOCUMENT).unwrap();
+        let unchanged = document.clone();
+
+        assert_eq!(document.insert_command(2, rectangle(0.0, 0)), Err(EditError::IndexOutOfRange { index: 2, len: 1 }));
+        assert_eq!(document.replace_command(1, rectangle(0.0, 0)), Err(EditError::IndexOutOfRange { index: 1, len: 1 }));
+        assert_eq!(document.remove_command(1), Err(EditError::IndexOutOfRange { index: 1, len: 1 }));
+        assert_eq!(document.remove_command(0), Err(EditError::LastCommand));
+
+        let missing = Err(EditError::Invalid(ValidationError::MissingColor { command_index: 0, color_index: 1 }));
+        assert_eq!(document.insert_command(0, rectangle(0.0, 1)), missing);
+        assert_eq!(document.replace_command(0, rectangle(0.0, 1)).map(|_| ()), missing);
+        let out_of_range = Err(EditError::Invalid(ValidationError::UnitOutOfRange { command_index: 0 }));
+        assert_eq!(document.insert_command(0, rectangle(1e6, 0)), out_of_range);
+        assert_eq!(document, unchanged);
+    }
+
+    #[test]
+    fn colors_are_set_or_appended() {
+        let mut document = TinyVg::from_bytes(&DOCUMENT).unwrap();
+        let green = RgbaF32(0.0, 1.0, 0.0, 1.0);
+
+        document.set_color(1, green).unwrap();
+        document.set_color(0, green).unwrap();
+        assert_eq!(document.color_table, [green, green]);
+        assert_eq!(document.header.color_count, 2);
+        assert_eq!(document.set_color(3, green), Err(EditError::IndexOutOfRange { index: 3, len: 2 }));
+    }
+
+    #[test]
+    fn failed_batches_are_rolled_back() {
+        let mut document = TinyVg::from_bytes(&DOCUMENT).unwrap();
+        let unchanged = document.clone();
+
+        // Clearing the color table leaves the rectangle with a missing color.
+        let result = document.edit_validated(|document| {
+            document.color_table.clear();
+            Ok(())
+        });
+        assert_eq!(result, Err(EditError::Invalid(ValidationError::MissingColor { command_index: 0, color_index: 0 })));
+        assert_eq!(document, unchanged);
+
+        let result = document.edit_validated(|document| {
+            document.insert_command(1, rectangle(4.0, 0))?;
+            document.remove_command(5)
+        });
+        assert_eq!(result, Err(EditError::IndexOutOfRange { index: 5, len: 2 }));
+        assert_eq!(document, unchanged);
+
+        document.edit_validated(|document| document.insert_command(1, rectangle(4.0, 0))).unwrap();
+        assert_eq!(document.draw_commands.len(), 2);
+    }
+}
@@ -0,0 +1,87 @@
+use crate::commands::{DrawCommand, Path, PathCommand, Style};
+use crate::header::{CoordinateRange, TinyVgHeader};
+
+impl DrawCommand {
+    /// The exact number of bytes the command occupies when it is encoded with `header`,
+    /// so that encodings can be compared without writing them.
+    pub fn encoded_size(&self, header: &TinyVgHeader) -> usize {
+        let unit = unit_size(header.coordinate_range);
+        let count = |count: usize| varuint_size(count.saturating_sub(1) as u64);
+        let style = |style: &Style| style_size(style, unit);
+        let path = |path: &Path| path_size(path, unit);
+
+        // Every command starts with a byte holding its type and primary style type, and
+        // outline fill commands store their count in a single byte.
+        1 + match self {
+            DrawCommand::FillPolygon(data) => count(data.points.len()) + style(&data.style) + data.points.len() * 2 * unit,
+            DrawCommand::FillRectangles(data) => count(data.rectangles.len()) + style(&data.style) + data.rectangles.len() * 4 * unit,
+            DrawCommand::FillPath(data) => count(data.path.segments.len()) + style(&data.style) + path(&data.path),
+            DrawCommand::DrawLines(data) => count(data.lines.len()) + style(&data.line_style) + unit + data.lines.len() * 4 * unit,
+            DrawCommand::DrawLineLoop(data) => count(data.points.len()) + style(&data.line_style) + unit + data.points.len() * 2 * unit,
+            DrawCommand::DrawLineStrip(data) => count(data.points.len()) + style(&data.style) + unit + data.points.len() * 2 * unit,
+            DrawCommand::DrawLinePath(data) => count(data.path.segments.len()) + style(&data.style) + unit + path(&data.path),
+            DrawCommand::OutlineFillPolygon(data) => {
+                1 + style(&data.fill_style) + style(&data.line_style) + unit + data.points.len() * 2 * unit
+            }
+            DrawCommand::OutlineFillRectangles(data) => {
+                1 + style(&data.fill_style) + style(&data.line_style) + unit + data.rectangles.len() * 4 * unit
+            }
+            DrawCommand::OutlineFillPath(data) => 1 + style(&data.fill_style) + style(&data.line_style) + unit + path(&data.path),
+            DrawCommand::TextHint(data) => {
+                4 * unit
+                    + varuint_size(data.text.len() as u64)
+                    + data.text.len()
+                    + varuint_size(data.glyph_offset.len() as u64)
+                    + data.glyph_offset.len() * 2 * unit
+            }
+        }
+    }
+}
+
+/// The number of bytes of a unit in the coordinate range.
+pub(crate) fn unit_size(range: CoordinateRange) -> usize {
+    match range {
+        CoordinateRange::Reduced => 1,
+        CoordinateRange::Default => 2,
+        CoordinateRange::Enhanced => 4,
+    }
+}
+
+/// The number of bytes of a variable sized unsigned number, 7 bits per byte.
+pub(crate) fn varuint_size(value: u64) -> usize {
+    let bits = (u64::BITS - value.leading_zeros()) as usize;
+    bits.div_ceil(7).max(1)
+}
+
+pub(crate) fn style_size(style: &Style, unit: usize) -> usize {
+    match style {
+        Style::FlatColor(flat) => varuint_size(flat.color_index),
+        Style::LinearGradient(gradient) => 4 * unit + varuint_size(gradient.color_index_0) + varuint_size(gradient.color_index_1),
+        Style::RadialGradient(gradient) => 4 * unit + varuint_size(gradient.color_index_0) + varuint_size(gradient.color_index_1),
+    }
+}
+
+fn path_size(path: &Path, unit: usize) -> usize {
+    path.segments
+        .iter()
+        .map(|segment| {
+            let commands: usize = segment.path_commands.iter().map(|path_command| path_command_size(path_command, unit)).sum();
+            varuint_size(segment.path_commands.len().saturating_sub(1) as u64) + 2 * unit + commands
+        })
+        .sum()
+}
+
+pub(crate) fn path_command_size(path_command: &PathCommand, unit: usize) -> usize {
+    // Each path command starts with a tag byte, followed by the optional line width.
+    let (line_width, payload) = match path_command {
+        PathCommand::Line(_, line_width) => (line_width, 2 * unit),
+        PathCommand::HorizontalLine(_, line_width) => (line_width, unit),
+        PathCommand::VerticalLine(_, line_width) => (line_width, unit),
+        PathCommand::CubicBezier(_, line_width) => (line_width, 6 * unit),
+        PathCommand::ArcCircle(_, line_width) => (line_width, 1 + 3 * unit),
+        PathCommand::ArcEllipse(_, line_width) => (line_width, 1 + 5 * unit),
+        PathCommand::ClosePath(line_width) => (line_width, 0),
+        PathCommand::QuadraticBezier(_, line_width) => (line_width, 4 * unit),
+    };
+    1 + line_width.map_or(0, |_| unit) + payload
+}
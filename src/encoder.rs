@@ -0,0 +1,364 @@
+use crate::color_table::RgbaF32;
+use crate::commands::{CommandType, DrawCommand, Path, PathCommand, PathCommandType, Point, Rectangle, Style, StyleType};
+use crate::common::{write_point, write_size, write_unit, write_variable_sized_unsigned_number};
+use crate::header::{ColorEncoding, CoordinateRange, TinyVgHeader};
+use crate::validation::{validate, ValidationError};
+use crate::{TinyVg, TinyVgParseError};
+use byteorder::{LittleEndian, WriteBytesExt};
+use std::io::{Cursor, Write};
+
+/// Encodes a document with the header's scale and color encoding. The coordinate range is
+/// replaced by the smallest one that holds the geometry, so that the header's range does
+/// not need to be kept in sync with edits. The document is validated first, so that
+/// documents which would produce a corrupt file are rejected with the reason.
+pub fn write_tvg(document: &TinyVg) -> Result<Vec<u8>, ValidationError> {
+    let mut header = document.header.clone();
+    header.coordinate_range = document.smallest_coordinate_range().unwrap_or(CoordinateRange::Enhanced);
+    header.color_count = document.color_table.len() as u64;
+    encode(document, &header)
+}
+
+impl TinyVg {
+    /// Encodes the document with its header as is. Units are stored as the exact multiples
+    /// of the scale that were read, so a parsed document whose geometry was not modified is
+    /// written back byte for byte, as long as it was encoded canonically: counts and color
+    /// indices are always written as the shortest VarUInt, and bytes after the end of the
+    /// document are not kept.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ValidationError> {
+        let mut header = self.header.clone();
+        header.color_count = self.color_table.len() as u64;
+        encode(self, &header)
+    }
+}
+
+fn encode(document: &TinyVg, header: &TinyVgHeader) -> Result<Vec<u8>, ValidationError> {
+    validate(document, header)?;
+
+    let mut writer = Cursor::new(Vec::new());
+    write_header(header, &mut writer)
+        .and_then(|_| write_color_table(&mut writer, header, &document.color_table))
+        .and_then(|_| write_draw_commands(&mut writer, header, &document.draw_commands))
+        .and_then(|_| write_end(&mut writer))
+        .expect("validated documents can be written");
+
+    Ok(writer.into_inner())
+}
+
+pub fn write_header(header: &TinyVgHeader, cursor: &mut Cursor<Vec<u8>>) -> Result<(), TinyVgParseError> {
+    cursor.write_all(&header.magic).map_err(|_| TinyVgParseError::InvalidHeader)?;
+    cursor.write_u8(header.version).map_err(|_| TinyVgParseError::InvalidHeader)?;
+
+    // Pack scale (bits 0–3), color encoding (bits 4–5), coordinate range (bits 6–7)
+    let scc = (header.scale & 0x0F) | ((header.color_encoding as u8) << 4) | ((header.coordinate_range as u8) << 6);
+    cursor.write_u8(scc).map_err(|_| TinyVgParseError::InvalidHeader)?;
+
+    write_size(&header.coordinate_range, cursor, header.width)?;
+    write_size(&header.coordinate_range, cursor, header.height)?;
+    write_variable_sized_unsigned_number(cursor, header.color_count)?;
+
+    Ok(())
+}
+
+pub fn write_color_table(
+    cursor: &mut Cursor<Vec<u8>>,
+    header: &TinyVgHeader,
+    colors: &[RgbaF32],
+) -> Result<(), TinyVgParseError> {
+    for &RgbaF32(r, g, b, a) in colors {
+        match header.color_encoding {
+            ColorEncoding::Rgb565 => {
+                // Channels are rounded to the nearest step, and alpha cannot be stored.
+                let quantize = |channel: f32, max: f32| (channel.clamp(0.0, 1.0) * max).round() as u16;
+                let color = quantize(r, 31.0) | (quantize(g, 63.0) << 5) | (quantize(b, 31.0) << 11);
+                cursor.write_u16::<LittleEndian>(color).map_err(|_| TinyVgParseError::InvalidColorTable)?;
+            }
+            ColorEncoding::Rgba8888 => {
+                for channel in [r, g, b, a] {
+                    let channel = (channel.clamp(0.0, 1.0) * 255.0).round() as u8;
+                    cursor.write_u8(channel).map_err(|_| TinyVgParseError::InvalidColorTable)?;
+                }
+            }
+            ColorEncoding::RgbaF32 => {
+                cursor.write_f32::<LittleEndian>(r).map_err(|_| TinyVgParseError::InvalidColorTable)?;
+                cursor.write_f32::<LittleEndian>(g).map_err(|_| TinyVgParseError::InvalidColorTable)?;
+                cursor.write_f32::<LittleEndian>(b).map_err(|_| TinyVgParseError::InvalidColorTable)?;
+                cursor.write_f32::<LittleEndian>(a).map_err(|_| TinyVgParseError::InvalidColorTable)?;
+            }
+            ColorEncoding::Custom => return Err(TinyVgParseError::InvalidColorTable),
+        }
+    }
+
+    Ok(())
+}
+
+pub fn write_draw_commands(cursor: &mut Cursor<Vec<u8>>, header: &TinyVgHeader, commands: &[DrawCommand]) -> Result<(), TinyVgParseError> {
+    for command in commands {
+        match command {
+            DrawCommand::FillPolygon(data) => {
+                write_command_and_primary_style(cursor, CommandType::FillPolygon, StyleType::from_style(&data.style))?;
+                write_count(cursor, data.points.len())?;
+                write_style(cursor, header, &data.style)?;
+                write_points(&data.points, header, cursor)?;
+            }
+            DrawCommand::FillRectangles(data) => {
+                write_command_and_primary_style(cursor, CommandType::FillRectangles, StyleType::from_style(&data.style))?;
+                write_count(cursor, data.rectangles.len())?;
+                write_style(cursor, header, &data.style)?;
+                write_rectangles(&data.rectangles, header, cursor)?;
+            }
+            DrawCommand::FillPath(data) => {
+                write_command_and_primary_style(cursor, CommandType::FillPath, StyleType::from_style(&data.style))?;
+                write_count(cursor, data.path.segments.len())?;
+                write_style(cursor, header, &data.style)?;
+                write_path(&data.path, cursor, header)?;
+            }
+            DrawCommand::DrawLines(data) => {
+                write_command_and_primary_style(cursor, CommandType::DrawLines, StyleType::from_style(&data.line_style))?;
+                write_count(cursor, data.lines.len())?;
+                write_style(cursor, header, &data.line_style)?;
+                write_unit(header.scale, cursor, &header.coordinate_range, data.line_width)?;
+                for line in &data.lines {
+                    write_point(&line.start, header, cursor)?;
+                    write_point(&line.end, header, cursor)?;
+                }
+            }
+            DrawCommand::DrawLineLoop(data) => {
+                write_command_and_primary_style(cursor, CommandType::DrawLineLoop, StyleType::from_style(&data.line_style))?;
+                write_count(cursor, data.points.len())?;
+                write_style(cursor, header, &data.line_style)?;
+                write_unit(header.scale, cursor, &header.coordinate_range, data.line_width)?;
+                write_points(&data.points, header, cursor)?;
+            }
+            DrawCommand::DrawLineStrip(data) => {
+                write_command_and_primary_style(cursor, CommandType::DrawLineStrip, StyleType::from_style(&data.style))?;
+                write_count(cursor, data.points.len())?;
+                write_style(cursor, header, &data.style)?;
+                write_unit(header.scale, cursor, &header.coordinate_range, data.line_width)?;
+                write_points(&data.points, header, cursor)?;
+            }
+            DrawCommand::DrawLinePath(data) => {
+                write_command_and_primary_style(cursor, CommandType::DrawLinePath, StyleType::from_style(&data.style))?;
+                write_count(cursor, data.path.segments.len())?;
+                write_style(cursor, header, &data.style)?;
+                write_unit(header.scale, cursor, &header.coordinate_range, data.line_width)?;
+                write_path(&data.path, cursor, header)?;
+            }
+            DrawCommand::OutlineFillPolygon(data) => {
+                write_command_and_primary_style(cursor, CommandType::OutlineFillPolygon, StyleType::from_style(&data.fill_style))?;
+                write_count_and_secondary_style(cursor, data.points.len(), &data.line_style)?;
+                write_style(cursor, header, &data.fill_style)?;
+                write_style(cursor, header, &data.line_style)?;
+                write_unit(header.scale, cursor, &header.coordinate_range, data.line_width)?;
+                write_points(&data.points, header, cursor)?;
+            }
+            DrawCommand::OutlineFillRectangles(data) => {
+                write_command_and_primary_style(cursor, CommandType::OutlineFillRectangles, StyleType::from_style(&data.fill_style))?;
+                write_count_and_secondary_style(cursor, data.rectangles.len(), &data.line_style)?;
+                write_style(cursor, header, &data.fill_style)?;
+                write_style(cursor, header, &data.line_style)?;
+                write_unit(header.scale, cursor, &header.coordinate_range, data.line_width)?;
+                write_rectangles(&data.rectangles, header, cursor)?;
+            }
+            DrawCommand::OutlineFillPath(data) => {
+                write_command_and_primary_style(cursor, CommandType::OutlineFillPath, StyleType::from_style(&data.fill_style))?;
+                write_count_and_secondary_style(cursor, data.path.segments.len(), &data.line_style)?;
+                write_style(cursor, header, &data.fill_style)?;
+                write_style(cursor, header, &data.line_style)?;
+                write_unit(header.scale, cursor, &header.coordinate_range, data.line_width)?;
+                write_path(&data.path, cursor, header)?;
+            }
+            DrawCommand::TextHint(data) => {
+                write_command_and_primary_style(cursor, CommandType::TextHint, StyleType::Flat)?;
+                write_point(&data.center, header, cursor)?;
+                write_unit(header.scale, cursor, &header.coordinate_range, data.rotation)?;
+                write_unit(header.scale, cursor, &header.coordinate_range, data.height)?;
+                write_variable_sized_unsigned_number(cursor, data.text.len() as u64)?;
+                cursor.write_all(data.text.as_bytes()).map_err(|_| TinyVgParseError::InvalidCommand)?;
+                write_variable_sized_unsigned_number(cursor, data.glyph_offset.len() as u64)?;
+                for (start, end) in &data.glyph_offset {
+                    write_unit(header.scale, cursor, &header.coordinate_range, *start)?;
+                    write_unit(header.scale, cursor, &header.coordinate_range, *end)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the number of items of a command, which is stored offset by 1.
+fn write_count(cursor: &mut Cursor<Vec<u8>>, count: usize) -> Result<(), TinyVgParseError> {
+    if count == 0 {
+        return Err(TinyVgParseError::InvalidCommand);
+    }
+    write_variable_sized_unsigned_number(cursor, count as u64 - 1)
+}
+
+/// Writes the item count of an outline fill command, offset by 1 in the lower 6 bits,
+/// together with the style type of its line style in the upper 2 bits.
+fn write_count_and_secondary_style(cursor: &mut Cursor<Vec<u8>>, count: usize, line_style: &Style) -> Result<(), TinyVgParseError> {
+    if count == 0 || count > 64 {
+        return Err(TinyVgParseError::InvalidCommand);
+    }
+    let count_and_style = ((StyleType::from_style(line_style) as u8) << 6) | (count - 1) as u8;
+    cursor.write_all(&[count_and_style]).map_err(|_| TinyVgParseError::InvalidCommand)
+}
+
+fn write_points(points: &[Point], header: &TinyVgHeader, cursor: &mut Cursor<Vec<u8>>) -> Result<(), TinyVgParseError> {
+    for point in points {
+        write_point(point, header, cursor)?;
+    }
+    Ok(())
+}
+
+fn write_rectangles(rectangles: &[Rectangle], header: &TinyVgHeader, cursor: &mut Cursor<Vec<u8>>) -> Result<(), TinyVgParseError> {
+    for rectangle in rectangles {
+        write_unit(header.scale, cursor, &header.coordinate_range, rectangle.x)?;
+        write_unit(header.scale, cursor, &header.coordinate_range, rectangle.y)?;
+        write_unit(header.scale, cursor, &header.coordinate_range, rectangle.width)?;
+        write_unit(header.scale, cursor, &header.coordinate_range, rectangle.height)?;
+    }
+    Ok(())
+}
+
+pub fn write_end(
+    cursor: &mut Cursor<Vec<u8>>,
+) -> Result<(), TinyVgParseError> {
+    cursor
+        .write_u8(0)
+        .map_err(|_| TinyVgParseError::InvalidCommand)?;
+
+    Ok(())
+}
+
+pub fn write_style(cursor: &mut Cursor<Vec<u8>>, header: &TinyVgHeader, style: &Style) -> Result<(), TinyVgParseError> {
+    match style {
+        Style::FlatColor(flat_colored) => {
+            write_variable_sized_unsigned_number(cursor, flat_colored.color_index)?;
+        }
+        Style::LinearGradient(linear_gradient) => {
+            write_unit(header.scale, cursor, &header.coordinate_range, linear_gradient.point_0.x)?;
+            write_unit(header.scale, cursor, &header.coordinate_range, linear_gradient.point_0.y)?;
+            write_unit(header.scale, cursor, &header.coordinate_range, linear_gradient.point_1.x)?;
+            write_unit(header.scale, cursor, &header.coordinate_range, linear_gradient.point_1.y)?;
+
+            write_variable_sized_unsigned_number(cursor, linear_gradient.color_index_0)?;
+            write_variable_sized_unsigned_number(cursor, linear_gradient.color_index_1)?;
+        }
+        Style::RadialGradient(radial_gradient) => {
+            write_unit(header.scale, cursor, &header.coordinate_range, radial_gradient.point_0.x)?;
+            write_unit(header.scale, cursor, &header.coordinate_range, radial_gradient.point_0.y)?;
+            write_unit(header.scale, cursor, &header.coordinate_range, radial_gradient.point_1.x)?;
+            write_unit(header.scale, cursor, &header.coordinate_range, radial_gradient.point_1.y)?;
+
+            write_variable_sized_unsigned_number(cursor, radial_gradient.color_index_0)?;
+            write_variable_sized_unsigned_number(cursor, radial_gradient.color_index_1)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn write_path(path: &Path, cursor: &mut Cursor<Vec<u8>>, header: &TinyVgHeader) -> Result<(), TinyVgParseError> {
+    for segment in &path.segments {
+        let cmd_count = segment.path_commands.len();
+        if cmd_count == 0 {
+            return Err(TinyVgParseError::InvalidCommand);
+        }
+        write_variable_sized_unsigned_number(cursor, (cmd_count - 1) as u64)?;
+    }
+
+    for segment in &path.segments {
+        let Point { x, y } = segment.start;
+        write_unit(header.scale, cursor, &header.coordinate_range, x)?;
+        write_unit(header.scale, cursor, &header.coordinate_range, y)?;
+
+        for cmd in &segment.path_commands {
+            let (base_tag, has_lw, lw) = match cmd {
+                PathCommand::Line(_, lw)              => (PathCommandType::Line as u8, lw.is_some(), lw),
+                PathCommand::HorizontalLine(_, lw)    => (PathCommandType::HorizontalLine as u8, lw.is_some(), lw),
+                PathCommand::VerticalLine(_, lw)      => (PathCommandType::VerticalLine as u8, lw.is_some(), lw),
+                PathCommand::CubicBezier(_, lw)       => (PathCommandType::CubicBezier as u8, lw.is_some(), lw),
+                PathCommand::ArcCircle(_, lw)         => (PathCommandType::ArcCircle as u8, lw.is_some(), lw),
+                PathCommand::ArcEllipse(_, lw)        => (PathCommandType::ArcEllipse as u8, lw.is_some(), lw),
+                PathCommand::QuadraticBezier(_, lw)   => (PathCommandType::QuadraticBezier as u8, lw.is_some(), lw),
+                PathCommand::ClosePath(lw)            => (PathCommandType::ClosePath as u8, lw.is_some(), lw),
+            };
+
+            let mut tag = base_tag & 0b0000_0111;
+            if has_lw {
+                tag |= 0b0001_0000;
+            }
+            cursor.write_all(&[tag]).map_err(|_| TinyVgParseError::InvalidCommand)?;
+
+            if let Some(unit) = *lw {
+                write_unit(header.scale, cursor, &header.coordinate_range, unit)?;
+            }
+
+            match cmd {
+                PathCommand::Line(p, _) => {
+                    write_point(p, header, cursor)?
+                }
+                PathCommand::HorizontalLine(u, _) => {
+                    write_unit(header.scale, cursor, &header.coordinate_range, *u)?;
+                }
+                PathCommand::VerticalLine(u, _) => {
+                    write_unit(header.scale, cursor, &header.coordinate_range, *u)?;
+                }
+                PathCommand::CubicBezier(c, _) => {
+                    write_point(&c.control_point_0, header, cursor)?;
+                    write_point(&c.control_point_1, header, cursor)?;
+                    write_point(&c.point_1, header, cursor)?
+                }
+                PathCommand::ArcCircle(a, _) => {
+                    let mut flags = 0;
+                    if a.large_arc { flags |= 0b01; }
+                    if a.sweep     { flags |= 0b10; }
+                    cursor.write_all(&[flags]).map_err(|_| TinyVgParseError::InvalidCommand)?;
+                    write_unit(header.scale, cursor, &header.coordinate_range, a.radius)?;
+                    write_point(&a.target, header, cursor)?
+                }
+                PathCommand::ArcEllipse(a, _) => {
+                    let mut flags = 0;
+                    if a.large_arc { flags |= 0b01; }
+                    if a.sweep     { flags |= 0b10; }
+                    cursor.write_all(&[flags]).map_err(|_| TinyVgParseError::InvalidCommand)?;
+                    write_unit(header.scale, cursor, &header.coordinate_range, a.radius_x)?;
+                    write_unit(header.scale, cursor, &header.coordinate_range, a.radius_y)?;
+                    write_unit(header.scale, cursor, &header.coordinate_range, a.rotation)?;
+                    write_point(&a.target, header, cursor)?
+                }
+                PathCommand::QuadraticBezier(q, _) => {
+                    write_point(&q.control_point, header, cursor)?;
+                    write_point(&q.point_1, header, cursor)?
+                }
+                PathCommand::ClosePath(_) => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+
+pub fn write_command_and_primary_style(
+    cursor: &mut Cursor<Vec<u8>>,
+    command: CommandType,
+    style_type: StyleType,
+) -> Result<(), TinyVgParseError> {
+    let command_u8 = command as u8;
+    let style_u8 = style_type as u8;
+
+    if command_u8 > 0b0011_1111 {
+        return Err(TinyVgParseError::InvalidCommand);
+    }
+
+
+    let combined = (style_u8 << 6) | (command_u8 & 0b0011_1111);
+
+    cursor
+        .write_all(&[combined])
+        .map_err(|_| TinyVgParseError::InvalidCommand)?;
+
+    Ok(())
+}
\ No newline at end of file
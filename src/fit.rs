@@ -0,0 +1,47 @@
+use crate::common::Affine;
+use crate::TinyVg;
+
+/// How a document is placed into a target rectangle, following CSS `object-fit`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FitMode {
+    /// Scale uniformly so the whole document is visible, centered in the target.
+    Contain,
+
+    /// Scale uniformly so the document covers the whole target, centered and cropped.
+    Cover,
+
+    /// Scale each axis independently so the document exactly fills the target.
+    Stretch,
+
+    /// Keep the document at its natural size, centered in the target.
+    None,
+}
+
+impl TinyVg {
+    /// Returns the transform that maps document coordinates into a target of
+    /// `target_size` (width, height) using the given fit mode. Sides of unbounded size, see
+    /// `TinyVgHeader::display_size`, take the size of the target.
+    pub fn fit_transform(&self, target_size: (f64, f64), mode: FitMode) -> Affine {
+        let (target_width, target_height) = target_size;
+        let (width, height) = self.header.display_size().resolve(target_size);
+
+        if width == 0.0 || height == 0.0 {
+            return Affine::IDENTITY;
+        }
+
+        let scale_x = target_width / width;
+        let scale_y = target_height / height;
+
+        let (scale_x, scale_y) = match mode {
+            FitMode::Contain => (scale_x.min(scale_y), scale_x.min(scale_y)),
+            FitMode::Cover => (scale_x.max(scale_y), scale_x.max(scale_y)),
+            FitMode::Stretch => (scale_x, scale_y),
+            FitMode::None => (1.0, 1.0),
+        };
+
+        let offset_x = (target_width - width * scale_x) / 2.0;
+        let offset_y = (target_height - height * scale_y) / 2.0;
+
+        Affine::scale_non_uniform(scale_x, scale_y).then(&Affine::translate(offset_x, offset_y))
+    }
+}
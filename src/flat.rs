@@ -0,0 +1,378 @@
+use std::ops::Range;
+
+use crate::color_table::ColorTable;
+use crate::commands::{
+    ArcCircle, ArcEllipse, CommandType, CubicBezier, DrawCommand, DrawLineLoopData, DrawLinePathData, DrawLineStripData,
+    DrawLinesData, FillPathData, FillPolygonData, FillRectanglesData, Line, OutlineFillPathData, OutlineFillPolygonData,
+    OutlineFillRectanglesData, Path, PathCommand, Point, Points, QuadraticBezier, Rectangle, Segment, Style, TextHintData,
+};
+use crate::common::Unit;
+use crate::header::TinyVgHeader;
+use crate::{TinyVg, TinyVgParseError};
+
+/// A draw command whose geometry lives in the shared arrays of a `FlatDocument`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlatCommand {
+    pub command_type: CommandType,
+
+    /// The fill style, or the line style of commands that only draw lines. Text hints
+    /// have no style.
+    pub primary_style: Option<Style>,
+
+    /// The line style of outline fill commands.
+    pub secondary_style: Option<Style>,
+
+    /// The line width of line and outline fill commands, 0 for fills.
+    pub line_width: Unit,
+
+    /// The command's range in `points` for polygons, line loops and line strips, in
+    /// `points` with two points per line for line lists, in `rectangles` for rectangles, in
+    /// `segments` for paths and in `text_hints` for text hints.
+    pub items: Range<usize>,
+}
+
+/// A path segment whose path commands live in the shared arrays of a `FlatDocument`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlatSegment {
+    pub start: Point,
+
+    /// The segment's range in `verbs` and `verb_line_widths`.
+    pub verbs: Range<usize>,
+
+    /// The index in `points` of the first point of the segment's path commands.
+    pub first_point: usize,
+
+    /// The index in `scalars` of the first scalar of the segment's path commands.
+    pub first_scalar: usize,
+}
+
+/// The kind of a path command along with its arc flags. Its points and scalars follow
+/// those of the previous verb in `points` and `scalars`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PathVerb {
+    /// One point, the end of the line.
+    Line,
+    /// One scalar, the new x coordinate.
+    HorizontalLine,
+    /// One scalar, the new y coordinate.
+    VerticalLine,
+    /// Three points, both control points and the end point.
+    CubicBezier,
+    /// One point, the target, and one scalar, the radius.
+    ArcCircle { large_arc: bool, sweep: bool },
+    /// One point, the target, and three scalars, both radii and the rotation.
+    ArcEllipse { large_arc: bool, sweep: bool },
+    ClosePath,
+    /// Two points, the control point and the end point.
+    QuadraticBezier,
+}
+
+impl PathVerb {
+    /// The number of entries the verb takes from `points` and from `scalars`.
+    pub fn operands(&self) -> (usize, usize) {
+        match self {
+            PathVerb::Line => (1, 0),
+            PathVerb::HorizontalLine | PathVerb::VerticalLine => (0, 1),
+            PathVerb::CubicBezier => (3, 0),
+            PathVerb::ArcCircle { .. } => (1, 1),
+            PathVerb::ArcEllipse { .. } => (1, 3),
+            PathVerb::ClosePath => (0, 0),
+            PathVerb::QuadraticBezier => (2, 0),
+        }
+    }
+}
+
+/// A document decoded into a structure of arrays: the points, rectangles and path
+/// commands of all draw commands are stored one after another in flat arrays, and each
+/// command refers to its range. For very large documents this avoids a heap allocation per
+/// command and segment and keeps geometry contiguous for rendering and transform baking.
+#[derive(Debug, Clone)]
+pub struct FlatDocument {
+    pub header: TinyVgHeader,
+    pub color_table: ColorTable,
+    pub commands: Vec<FlatCommand>,
+    pub points: Vec<Point>,
+    pub rectangles: Vec<Rectangle>,
+    pub segments: Vec<FlatSegment>,
+    pub verbs: Vec<PathVerb>,
+
+    /// The line width changes of the path commands, parallel to `verbs`.
+    pub verb_line_widths: Vec<Option<Unit>>,
+
+    /// The units of path commands that are not points: line coordinates, radii and
+    /// rotations.
+    pub scalars: Vec<Unit>,
+    pub text_hints: Vec<TextHintData>,
+}
+
+impl FlatDocument {
+    pub fn from_bytes(data: &[u8]) -> Result<FlatDocument, TinyVgParseError> {
+        Ok(FlatDocument::from(&TinyVg::from_bytes(data)?))
+    }
+
+    /// The points of a polygon, line loop or line strip, or the end points of the lines of
+    /// a line list.
+    pub fn command_points(&self, command: &FlatCommand) -> &[Point] {
+        match command.command_type {
+            CommandType::FillPolygon
+            | CommandType::DrawLines
+            | CommandType::DrawLineLoop
+            | CommandType::DrawLineStrip
+            | CommandType::OutlineFillPolygon => &self.points[command.items.clone()],
+            _ => &[],
+        }
+    }
+
+    /// The rectangles of a rectangle command.
+    pub fn command_rectangles(&self, command: &FlatCommand) -> &[Rectangle] {
+        match command.command_type {
+            CommandType::FillRectangles | CommandType::OutlineFillRectangles => &self.rectangles[command.items.clone()],
+            _ => &[],
+        }
+    }
+
+    /// The segments of a path command.
+    pub fn command_segments(&self, command: &FlatCommand) -> &[FlatSegment] {
+        match command.command_type {
+            CommandType::FillPath | CommandType::DrawLinePath | CommandType::OutlineFillPath => &self.segments[command.items.clone()],
+            _ => &[],
+        }
+    }
+
+    /// Converts back to the nested representation.
+    pub fn to_tinyvg(&self) -> TinyVg {
+        let draw_commands = self.commands.iter().map(|command| self.draw_command(command)).collect();
+        TinyVg { header: self.header.clone(), color_table: self.color_table.clone(), draw_commands }
+    }
+
+    fn draw_command(&self, command: &FlatCommand) -> DrawCommand {
+        let Some(style) = command.primary_style.clone() else {
+            return DrawCommand::TextHint(self.text_hints[command.items.start].clone());
+        };
+        let secondary_style = || command.secondary_style.clone().unwrap_or_else(|| style.clone());
+        let points = || Points::from(self.command_points(command));
+        let rectangles = || self.command_rectangles(command).to_vec();
+        let path = || self.path(command);
+        let line_width = command.line_width;
+
+        match command.command_type {
+            CommandType::FillPolygon => DrawCommand::FillPolygon(FillPolygonData { style, points: points() }),
+            CommandType::FillRectangles => DrawCommand::FillRectangles(FillRectanglesData { style, rectangles: rectangles() }),
+            CommandType::FillPath => DrawCommand::FillPath(FillPathData { style, path: path() }),
+            CommandType::DrawLines => {
+                let lines = self.command_points(command).chunks_exact(2).map(|pair| Line { start: pair[0], end: pair[1] }).collect();
+                DrawCommand::DrawLines(DrawLinesData { lines, line_width, line_style: style })
+            }
+            CommandType::DrawLineLoop => DrawCommand::DrawLineLoop(DrawLineLoopData { line_style: style, line_width, points: points() }),
+            CommandType::DrawLineStrip => DrawCommand::DrawLineStrip(DrawLineStripData { style, line_width, points: points() }),
+            CommandType::DrawLinePath => DrawCommand::DrawLinePath(DrawLinePathData { style, line_width, path: path() }),
+            CommandType::OutlineFillPolygon => DrawCommand::OutlineFillPolygon(OutlineFillPolygonData {
+                points: points(),
+                line_width,
+                line_style: secondary_style(),
+                fill_style: style.clone(),
+            }),
+            CommandType::OutlineFillRectangles => DrawCommand::OutlineFillRectangles(OutlineFillRectanglesData {
+                fill_style: style.clone(),
+                line_style: secondary_style(),
+                line_width,
+                rectangles: rectangles(),
+            }),
+            CommandType::OutlineFillPath => DrawCommand::OutlineFillPath(OutlineFillPathData {
+                path: path(),
+                fill_style: style.clone(),
+                line_style: secondary_style(),
+                line_width,
+            }),
+            CommandType::TextHint | CommandType::EndOfDocument => DrawCommand::TextHint(self.text_hints[command.items.start].clone()),
+        }
+    }
+
+    fn path(&self, command: &FlatCommand) -> Path {
+        let segments = self.command_segments(command).iter().map(|segment| {
+            let mut points = self.points[segment.first_point..].iter().copied();
+            let mut scalars = self.scalars[segment.first_scalar..].iter().copied();
+            let mut point = || points.next().unwrap_or(segment.start);
+            let mut scalar = || scalars.next().unwrap_or(Unit(0.0));
+
+            let path_commands = segment
+                .verbs
+                .clone()
+                .map(|index| {
+                    let line_width = self.verb_line_widths[index];
+                    match self.verbs[index] {
+                        PathVerb::Line => PathCommand::Line(point(), line_width),
+                        PathVerb::HorizontalLine => PathCommand::HorizontalLine(scalar(), line_width),
+                        PathVerb::VerticalLine => PathCommand::VerticalLine(scalar(), line_width),
+                        PathVerb::CubicBezier => {
+                            let (control_point_0, control_point_1, point_1) = (point(), point(), point());
+                            PathCommand::CubicBezier(CubicBezier { control_point_0, control_point_1, point_1 }, line_width)
+                        }
+                        PathVerb::ArcCircle { large_arc, sweep } => {
+                            let target = point();
+                            PathCommand::ArcCircle(ArcCircle { large_arc, sweep, radius: scalar(), target }, line_width)
+                        }
+                        PathVerb::ArcEllipse { large_arc, sweep } => {
+                            let target = point();
+                            let (radius_x, radius_y, rotation) = (scalar(), scalar(), scalar());
+                            PathCommand::ArcEllipse(ArcEllipse { large_arc, sweep, radius_x, radius_y, rotation, target }, line_width)
+                        }
+                        PathVerb::ClosePath => PathCommand::ClosePath(line_width),
+                        PathVerb::QuadraticBezier => {
+                            let (control_point, point_1) = (point(), point());
+                            PathCommand::QuadraticBezier(QuadraticBezier { control_point, point_1 }, line_width)
+                        }
+                    }
+                })
+                .collect();
+            Segment { start: segment.start, path_commands }
+        });
+        Path { segments: segments.collect() }
+    }
+
+    fn push_path(&mut self, path: &Path) -> Range<usize> {
+        let first_segment = self.segments.len();
+        for segment in &path.segments {
+            let flat_segment = FlatSegment {
+                start: segment.start,
+                verbs: self.verbs.len()..self.verbs.len() + segment.path_commands.len(),
+                first_point: self.points.len(),
+                first_scalar: self.scalars.len(),
+            };
+            for path_command in &segment.path_commands {
+                let (verb, line_width) = match path_command {
+                    PathCommand::Line(point, line_width) => {
+                        self.points.push(*point);
+                        (PathVerb::Line, line_width)
+                    }
+                    PathCommand::HorizontalLine(x, line_width) => {
+                        self.scalars.push(*x);
+                        (PathVerb::HorizontalLine, line_width)
+                    }
+                    PathCommand::VerticalLine(y, line_width) => {
+                        self.scalars.push(*y);
+                        (PathVerb::VerticalLine, line_width)
+                    }
+                    PathCommand::CubicBezier(cubic, line_width) => {
+                        self.points.extend([cubic.control_point_0, cubic.control_point_1, cubic.point_1]);
+                        (PathVerb::CubicBezier, line_width)
+                    }
+                    PathCommand::ArcCircle(arc, line_width) => {
+                        self.points.push(arc.target);
+                        self.scalars.push(arc.radius);
+                        (PathVerb::ArcCircle { large_arc: arc.large_arc, sweep: arc.sweep }, line_width)
+                    }
+                    PathCommand::ArcEllipse(arc, line_width) => {
+                        self.points.push(arc.target);
+                        self.scalars.extend([arc.radius_x, arc.radius_y, arc.rotation]);
+                        (PathVerb::ArcEllipse { large_arc: arc.large_arc, sweep: arc.sweep }, line_width)
+                    }
+                    PathCommand::ClosePath(line_width) => (PathVerb::ClosePath, line_width),
+                    PathCommand::QuadraticBezier(quadratic, line_width) => {
+                        self.points.extend([quadratic.control_point, quadratic.point_1]);
+                        (PathVerb::QuadraticBezier, line_width)
+                    }
+                };
+                self.verbs.push(verb);
+                self.verb_line_widths.push(*line_width);
+            }
+            self.segments.push(flat_segment);
+        }
+        first_segment..self.segments.len()
+    }
+
+    fn push_points(&mut self, points: impl IntoIterator<Item = Point>) -> Range<usize> {
+        let start = self.points.len();
+        self.points.extend(points);
+        start..self.points.len()
+    }
+
+    fn push_rectangles(&mut self, rectangles: &[Rectangle]) -> Range<usize> {
+        let start = self.rectangles.len();
+        self.rectangles.extend_from_slice(rectangles);
+        start..self.rectangles.len()
+    }
+}
+
+impl From<&TinyVg> for FlatDocument {
+    fn from(document: &TinyVg) -> Self {
+        let mut flat = FlatDocument {
+            header: document.header.clone(),
+            color_table: document.color_table.clone(),
+            commands: Vec::with_capacity(document.draw_commands.len()),
+            points: Vec::new(),
+            rectangles: Vec::new(),
+            segments: Vec::new(),
+            verbs: Vec::new(),
+            verb_line_widths: Vec::new(),
+            scalars: Vec::new(),
+            text_hints: Vec::new(),
+        };
+
+        for command in &document.draw_commands {
+            let (command_type, primary_style, secondary_style, line_width, items) = match command {
+                DrawCommand::FillPolygon(data) => {
+                    (CommandType::FillPolygon, &data.style, None, Unit(0.0), flat.push_points(data.points.iter().copied()))
+                }
+                DrawCommand::FillRectangles(data) => {
+                    (CommandType::FillRectangles, &data.style, None, Unit(0.0), flat.push_rectangles(&data.rectangles))
+                }
+                DrawCommand::FillPath(data) => (CommandType::FillPath, &data.style, None, Unit(0.0), flat.push_path(&data.path)),
+                DrawCommand::DrawLines(data) => {
+                    let points = data.lines.iter().flat_map(|line| [line.start, line.end]);
+                    (CommandType::DrawLines, &data.line_style, None, data.line_width, flat.push_points(points))
+                }
+                DrawCommand::DrawLineLoop(data) => {
+                    (CommandType::DrawLineLoop, &data.line_style, None, data.line_width, flat.push_points(data.points.iter().copied()))
+                }
+                DrawCommand::DrawLineStrip(data) => {
+                    (CommandType::DrawLineStrip, &data.style, None, data.line_width, flat.push_points(data.points.iter().copied()))
+                }
+                DrawCommand::DrawLinePath(data) => {
+                    (CommandType::DrawLinePath, &data.style, None, data.line_width, flat.push_path(&data.path))
+                }
+                DrawCommand::OutlineFillPolygon(data) => (
+                    CommandType::OutlineFillPolygon,
+                    &data.fill_style,
+                    Some(&data.line_style),
+                    data.line_width,
+                    flat.push_points(data.points.iter().copied()),
+                ),
+                DrawCommand::OutlineFillRectangles(data) => (
+                    CommandType::OutlineFillRectangles,
+                    &data.fill_style,
+                    Some(&data.line_style),
+                    data.line_width,
+                    flat.push_rectangles(&data.rectangles),
+                ),
+                DrawCommand::OutlineFillPath(data) => (
+                    CommandType::OutlineFillPath,
+                    &data.fill_style,
+                    Some(&data.line_style),
+                    data.line_width,
+                    flat.push_path(&data.path),
+                ),
+                DrawCommand::TextHint(data) => {
+                    flat.text_hints.push(data.clone());
+                    let index = flat.text_hints.len() - 1;
+                    flat.commands.push(FlatCommand {
+                        command_type: CommandType::TextHint,
+                        primary_style: None,
+                        secondary_style: None,
+                        line_width: Unit(0.0),
+                        items: index..index + 1,
+                    });
+                    continue;
+                }
+            };
+            flat.commands.push(FlatCommand {
+                command_type,
+                primary_style: Some(primary_style.clone()),
+                secondary_style: secondary_style.cloned(),
+                line_width,
+                items,
+            });
+        }
+        flat
+    }
+}
@@ -0,0 +1,364 @@
+use crate::commands::{DrawCommand, Path, Point, Rectangle, Segment};
+use crate::common::{Affine, Unit};
+use crate::render::{LineCap, LineJoin, StrokeStyle};
+
+/// How overlapping contours of an outline combine into the filled area.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) enum FillRule {
+    EvenOdd,
+    NonZero,
+}
+
+/// A flattened contour.
+#[derive(Debug, Clone)]
+pub(crate) struct Polyline {
+    pub(crate) points: Vec<Point>,
+    /// The indices of the points that join two source edges, such as polygon corners or
+    /// the ends of path commands. All other points lie on a flattened curve.
+    pub(crate) joints: Vec<usize>,
+    /// Whether the last point connects back to the first one.
+    pub(crate) closed: bool,
+}
+
+impl Polyline {
+    /// A polyline where every point is a joint, as in polygons and line lists.
+    pub(crate) fn with_corners(points: Vec<Point>, closed: bool) -> Self {
+        let joints = (0..points.len()).collect();
+        Polyline { points, joints, closed }
+    }
+}
+
+/// The flattened geometry of a single draw command.
+#[derive(Debug, Clone)]
+pub(crate) struct Outline {
+    /// Contours that are filled. They are always treated as closed.
+    pub(crate) fill: Vec<Polyline>,
+    pub(crate) fill_rule: FillRule,
+    /// Contours that are stroked with `line_width`.
+    pub(crate) stroke: Vec<Polyline>,
+    pub(crate) line_width: f64,
+}
+
+impl Outline {
+    fn filled(fill: Vec<Polyline>, fill_rule: FillRule) -> Self {
+        Outline { fill, fill_rule, stroke: Vec::new(), line_width: 0.0 }
+    }
+
+    fn stroked(stroke: Vec<Polyline>, line_width: f64) -> Self {
+        Outline { fill: Vec::new(), fill_rule: FillRule::EvenOdd, stroke, line_width }
+    }
+
+    /// Applies the transform to every point. Line widths are scaled by the average
+    /// scale factor of the transform.
+    pub(crate) fn transform(&mut self, affine: &Affine) {
+        for polyline in self.fill.iter_mut().chain(self.stroke.iter_mut()) {
+            for point in &mut polyline.points {
+                *point = affine.apply(*point);
+            }
+        }
+        let [a, b, c, d, _, _] = affine.0;
+        self.line_width *= (a * d - b * c).abs().sqrt();
+    }
+}
+
+fn path_polylines(path: &Path, tolerance: f64) -> Vec<Polyline> {
+    path.segments.iter().map(|segment| segment_polyline(segment, tolerance)).collect()
+}
+
+fn segment_polyline(segment: &Segment, tolerance: f64) -> Polyline {
+    let (points, joints, closed) = segment.flatten_points(tolerance);
+    Polyline { points, joints, closed }
+}
+
+fn rectangle_polyline(rectangle: &Rectangle) -> Polyline {
+    let (x, y) = (rectangle.x, rectangle.y);
+    let right = Unit(x.0 + rectangle.width.0);
+    let bottom = Unit(y.0 + rectangle.height.0);
+    Polyline::with_corners(vec![Point::new(x, y), Point::new(right, y), Point::new(right, bottom), Point::new(x, bottom)], true)
+}
+
+/// Flattens a draw command into its filled and stroked contours. Returns `None` for
+/// commands without geometry.
+pub(crate) fn command_outline(command: &DrawCommand, tolerance: f64) -> Option<Outline> {
+    let outline = match command {
+        DrawCommand::FillPolygon(data) => {
+            Outline::filled(vec![Polyline::with_corners(data.points.to_vec(), true)], FillRule::EvenOdd)
+        }
+        DrawCommand::FillRectangles(data) => {
+            // Every rectangle is filled on its own, so overlapping rectangles combine as a union.
+            Outline::filled(data.rectangles.iter().map(rectangle_polyline).collect(), FillRule::NonZero)
+        }
+        DrawCommand::FillPath(data) => {
+            Outline::filled(path_polylines(&data.path, tolerance), FillRule::EvenOdd)
+        }
+        DrawCommand::DrawLines(data) => {
+            let lines = data.lines.iter().map(|line| Polyline::with_corners(vec![line.start, line.end], false)).collect();
+            Outline::stroked(lines, data.line_width.0)
+        }
+        DrawCommand::DrawLineLoop(data) => {
+            Outline::stroked(vec![Polyline::with_corners(data.points.to_vec(), true)], data.line_width.0)
+        }
+        DrawCommand::DrawLineStrip(data) => {
+            Outline::stroked(vec![Polyline::with_corners(data.points.to_vec(), false)], data.line_width.0)
+        }
+        DrawCommand::DrawLinePath(data) => {
+            Outline::stroked(path_polylines(&data.path, tolerance), data.line_width.0)
+        }
+        DrawCommand::OutlineFillPolygon(data) => {
+            let polygon = Polyline::with_corners(data.points.to_vec(), true);
+            Outline {
+                fill: vec![polygon.clone()],
+                fill_rule: FillRule::EvenOdd,
+                stroke: vec![polygon],
+                line_width: data.line_width.0,
+            }
+        }
+        DrawCommand::OutlineFillRectangles(data) => {
+            let rectangles: Vec<Polyline> = data.rectangles.iter().map(rectangle_polyline).collect();
+            Outline {
+                fill: rectangles.clone(),
+                fill_rule: FillRule::NonZero,
+                stroke: rectangles,
+                line_width: data.line_width.0,
+            }
+        }
+        DrawCommand::OutlineFillPath(data) => {
+            let polylines = path_polylines(&data.path, tolerance);
+            Outline {
+                fill: polylines.clone(),
+                fill_rule: FillRule::EvenOdd,
+                stroke: polylines,
+                line_width: data.line_width.0,
+            }
+        }
+        DrawCommand::TextHint(_) => return None,
+    };
+
+    Some(outline)
+}
+
+/// Converts a stroked polyline into polygons whose non-zero union is the stroke area, with
+/// the caps and joins of `style`. Points within flattened curves are joined round, so curves
+/// stay smooth whatever the join.
+pub(crate) fn stroke_polygons(polyline: &Polyline, half_width: f64, style: &StrokeStyle, tolerance: f64) -> Vec<Vec<(f64, f64)>> {
+    let mut polygons = Vec::new();
+    if polyline.points.is_empty() || half_width <= 0.0 {
+        return polygons;
+    }
+
+    // Repeated points have no direction, so they are merged into one vertex.
+    let mut joints = vec![false; polyline.points.len()];
+    for &joint in &polyline.joints {
+        if let Some(joint) = joints.get_mut(joint) {
+            *joint = true;
+        }
+    }
+    let mut vertices: Vec<((f64, f64), bool)> = Vec::with_capacity(polyline.points.len());
+    for (point, &joint) in polyline.points.iter().zip(&joints) {
+        let point = (point.x.0, point.y.0);
+        match vertices.last_mut() {
+            Some((last, last_joint)) if *last == point => *last_joint |= joint,
+            _ => vertices.push((point, joint)),
+        }
+    }
+    let closed = polyline.closed && vertices.len() > 2;
+    if closed && vertices.first().map(|vertex| vertex.0) == vertices.last().map(|vertex| vertex.0) {
+        let (_, joint) = vertices.pop().expect("closed polylines have vertices");
+        vertices[0].1 |= joint;
+    }
+    let points: Vec<(f64, f64)> = vertices.iter().map(|vertex| vertex.0).collect();
+    let circle_steps = circle_steps(half_width, tolerance);
+
+    if points.len() == 1 {
+        let (x, y) = points[0];
+        match style.cap {
+            LineCap::Butt => {}
+            LineCap::Round => polygons.push(circle(points[0], half_width, circle_steps)),
+            LineCap::Square => polygons.push(vec![
+                (x - half_width, y - half_width),
+                (x + half_width, y - half_width),
+                (x + half_width, y + half_width),
+                (x - half_width, y + half_width),
+            ]),
+        }
+        return polygons;
+    }
+
+    // The unit direction of each edge, from a point to the next one.
+    let edge_count = if closed { points.len() } else { points.len() - 1 };
+    let directions: Vec<(f64, f64)> = (0..edge_count)
+        .map(|i| {
+            let ((ax, ay), (bx, by)) = (points[i], points[(i + 1) % points.len()]);
+            let length = (bx - ax).hypot(by - ay);
+            ((bx - ax) / length, (by - ay) / length)
+        })
+        .collect();
+    let normal = |(dx, dy): (f64, f64)| (-dy * half_width, dx * half_width);
+
+    for (i, &direction) in directions.iter().enumerate() {
+        let ((ax, ay), (bx, by)) = (points[i], points[(i + 1) % points.len()]);
+        let (nx, ny) = normal(direction);
+        polygons.push(oriented(vec![(ax + nx, ay + ny), (bx + nx, by + ny), (bx - nx, by - ny), (ax - nx, ay - ny)]));
+    }
+
+    let joined = if closed { 0..points.len() } else { 1..points.len() - 1 };
+    for i in joined {
+        let incoming = directions[(i + edge_count - 1) % edge_count];
+        let outgoing = directions[i % edge_count];
+        let join = if vertices[i].1 { style.join } else { LineJoin::Round };
+        polygons.extend(join_polygon(points[i], incoming, outgoing, half_width, join, style.miter_limit, circle_steps));
+    }
+
+    if !closed {
+        let ends = [(points[0], directions[0], -1.0), (points[points.len() - 1], directions[edge_count - 1], 1.0)];
+        for ((x, y), (dx, dy), sign) in ends {
+            match style.cap {
+                LineCap::Butt => {}
+                LineCap::Round => polygons.push(circle((x, y), half_width, circle_steps)),
+                LineCap::Square => {
+                    let (nx, ny) = normal((dx, dy));
+                    let (ex, ey) = (dx * half_width * sign, dy * half_width * sign);
+                    polygons.push(oriented(vec![(x + nx, y + ny), (x + nx + ex, y + ny + ey), (x - nx + ex, y - ny + ey), (x - nx, y - ny)]));
+                }
+            }
+        }
+    }
+
+    polygons
+}
+
+/// The polygon that fills the outer side of the corner where an edge in the `incoming`
+/// direction meets one in the `outgoing` direction, both unit vectors.
+fn join_polygon(
+    (x, y): (f64, f64),
+    incoming: (f64, f64),
+    outgoing: (f64, f64),
+    half_width: f64,
+    join: LineJoin,
+    miter_limit: f64,
+    circle_steps: usize,
+) -> Option<Vec<(f64, f64)>> {
+    let cross = incoming.0 * outgoing.1 - incoming.1 * outgoing.0;
+    let dot = incoming.0 * outgoing.0 + incoming.1 * outgoing.1;
+    if join == LineJoin::Round {
+        return Some(circle((x, y), half_width, circle_steps));
+    }
+    if cross == 0.0 && dot > 0.0 {
+        return None;
+    }
+
+    // The outer side of the corner is the one the path turns away from.
+    let side = if cross > 0.0 { -half_width } else { half_width };
+    let start = (x - incoming.1 * side, y + incoming.0 * side);
+    let end = (x - outgoing.1 * side, y + outgoing.0 * side);
+
+    // The miter length relative to the line width is 1 / sin(θ / 2) for the angle θ
+    // between the edges, which is 1 / cos(φ / 2) for the turn φ.
+    let half_turn_cos = ((1.0 + dot) / 2.0).max(0.0).sqrt();
+    if join == LineJoin::Miter && half_turn_cos > 0.0 && 1.0 / half_turn_cos <= miter_limit {
+        let (bx, by) = (start.0 + end.0 - 2.0 * x, start.1 + end.1 - 2.0 * y);
+        let length = bx.hypot(by);
+        let reach = half_width / half_turn_cos;
+        let tip = (x + bx / length * reach, y + by / length * reach);
+        return Some(oriented(vec![(x, y), start, tip, end]));
+    }
+    Some(oriented(vec![(x, y), start, end]))
+}
+
+/// The number of corners a circle of `radius` needs to stay within `tolerance`.
+fn circle_steps(radius: f64, tolerance: f64) -> usize {
+    let max_step = if tolerance < radius { 2.0 * (1.0 - tolerance / radius).acos() } else { std::f64::consts::FRAC_PI_2 };
+    (std::f64::consts::TAU / max_step).ceil().clamp(8.0, 256.0) as usize
+}
+
+fn circle((x, y): (f64, f64), radius: f64, steps: usize) -> Vec<(f64, f64)> {
+    (0..steps)
+        .map(|i| {
+            let angle = std::f64::consts::TAU * i as f64 / steps as f64;
+            (x + radius * angle.cos(), y + radius * angle.sin())
+        })
+        .collect()
+}
+
+pub(crate) fn signed_area(polygon: &[(f64, f64)]) -> f64 {
+    let mut area = 0.0;
+    for i in 0..polygon.len() {
+        let (x0, y0) = polygon[i];
+        let (x1, y1) = polygon[(i + 1) % polygon.len()];
+        area += x0 * y1 - x1 * y0;
+    }
+    area / 2.0
+}
+
+/// Reverses the polygon if needed so that its signed area is positive.
+fn oriented(mut polygon: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    if signed_area(&polygon) < 0.0 {
+        polygon.reverse();
+    }
+    polygon
+}
+
+impl Outline {
+    /// Whether the point lies inside the filled area or within half the line width of
+    /// a stroked contour.
+    pub(crate) fn contains(&self, point: (f64, f64)) -> bool {
+        self.fill_contains(point) || self.stroke_contains(point)
+    }
+
+    pub(crate) fn fill_contains(&self, point: (f64, f64)) -> bool {
+        let mut winding = 0;
+        let mut crossings = 0;
+        for polyline in &self.fill {
+            let points = &polyline.points;
+            for i in 0..points.len() {
+                let (x0, y0) = (points[i].x.0, points[i].y.0);
+                let next = &points[(i + 1) % points.len()];
+                let (x1, y1) = (next.x.0, next.y.0);
+                if (y0 > point.1) != (y1 > point.1) && point.0 < x0 + (point.1 - y0) * (x1 - x0) / (y1 - y0) {
+                    crossings += 1;
+                    winding += if y1 > y0 { 1 } else { -1 };
+                }
+            }
+        }
+
+        match self.fill_rule {
+            FillRule::EvenOdd => crossings % 2 == 1,
+            FillRule::NonZero => winding != 0,
+        }
+    }
+
+    pub(crate) fn stroke_contains(&self, point: (f64, f64)) -> bool {
+        let half_width = self.line_width / 2.0;
+        self.stroke.iter().any(|polyline| polyline_distance(polyline, point) <= half_width)
+    }
+}
+
+/// The distance from the point to the nearest point on the polyline.
+pub(crate) fn polyline_distance(polyline: &Polyline, point: (f64, f64)) -> f64 {
+    let points = &polyline.points;
+    match points.len() {
+        0 => f64::INFINITY,
+        1 => (points[0].x.0 - point.0).hypot(points[0].y.0 - point.1),
+        count => {
+            let edge_count = if polyline.closed { count } else { count - 1 };
+            (0..edge_count)
+                .map(|i| {
+                    let next = &points[(i + 1) % count];
+                    segment_distance((points[i].x.0, points[i].y.0), (next.x.0, next.y.0), point)
+                })
+                .fold(f64::INFINITY, f64::min)
+        }
+    }
+}
+
+/// The distance from the point to the line segment between `start` and `end`.
+pub(crate) fn segment_distance(start: (f64, f64), end: (f64, f64), point: (f64, f64)) -> f64 {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared > 0.0 {
+        (((point.0 - start.0) * dx + (point.1 - start.1) * dy) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (start.0 + dx * t - point.0).hypot(start.1 + dy * t - point.1)
+}
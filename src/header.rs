@@ -1,7 +1,13 @@
-use crate::common::{read_size, read_variable_sized_unsigned_number};
-use crate::TinyVgParseError;
-use byteorder::ReadBytesExt;
-use std::io::{Cursor, Read};
+use crate::common::Reader;
+use crate::precision::{raw_limit, size_limit};
+use crate::validation::ValidationError;
+use crate::{TinyVgParseError, VersionPolicy};
+
+/// The version of the format this crate reads and writes.
+pub const TINYVG_VERSION: u8 = 1;
+
+/// The most fraction bits a unit can have, as the scale is stored in 4 bits.
+pub const MAX_SCALE: u8 = 15;
 
 #[repr(u8)]
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -40,7 +46,7 @@ impl ColorEncoding {
 }
 
 #[repr(u8)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum CoordinateRange {
     /// Each Unit takes up 16 bit.
     Default = 0,
@@ -53,17 +59,18 @@ pub enum CoordinateRange {
 }
 
 impl CoordinateRange {
-    fn from_u8(value: u8) -> CoordinateRange {
+    /// The value 3 is reserved and makes the header invalid.
+    fn from_u8(value: u8) -> Result<CoordinateRange, TinyVgParseError> {
         match value {
-            0 => CoordinateRange::Default,
-            1 => CoordinateRange::Reduced,
-            2 => CoordinateRange::Enhanced,
-            _ => unreachable!("CoordinateRange::from_u8 must be a 2-bit value.")
+            0 => Ok(CoordinateRange::Default),
+            1 => Ok(CoordinateRange::Reduced),
+            2 => Ok(CoordinateRange::Enhanced),
+            _ => Err(TinyVgParseError::InvalidHeader)
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct TinyVgHeader {
     /// https://en.wikipedia.org/wiki/File_format#Magic_number
     pub magic: [u8; 2],
@@ -96,19 +103,19 @@ pub struct TinyVgHeader {
 }
 
 impl TinyVgHeader {
-    pub(crate) fn parse(cursor: &mut Cursor<&[u8]>) -> Result<Self, TinyVgParseError> {
-        let mut magic = [0u8; 2];
-        cursor.read_exact(&mut magic).map_err(|_| TinyVgParseError::InvalidHeader)?;
+    pub(crate) fn parse(reader: &mut Reader) -> Result<Self, TinyVgParseError> {
+        reader.error = TinyVgParseError::InvalidHeader;
+        let magic: [u8; 2] = reader.read_array()?;
         
         // Must be { 0x72, 0x56 }
         if magic[0] != 0x72 || magic[1] != 0x56 {
             return Err(TinyVgParseError::InvalidHeader);
         }
         
-        let version = cursor.read_u8().map_err(|_| TinyVgParseError::InvalidHeader)?;
+        let version = reader.read_u8()?;
 
         // The encoded scale, color encoding, and coordinate range data.
-        let scc = cursor.read_u8().map_err(|_| TinyVgParseError::InvalidHeader)?;
+        let scc = reader.read_u8()?;
 
         // bits 0–3 = scale
         let scale: u8 = scc & 0x0F;
@@ -119,12 +126,12 @@ impl TinyVgHeader {
 
         // bits 6–7 = coordinate range
         let coordinate_range_raw = (scc & 0b11_00_00_00) >> 6;
-        let coordinate_range = CoordinateRange::from_u8(coordinate_range_raw);
+        let coordinate_range = CoordinateRange::from_u8(coordinate_range_raw)?;
 
-        let width: u32 = read_size(&coordinate_range, cursor)?;
-        let height: u32 = read_size(&coordinate_range, cursor)?;
+        let width: u32 = reader.read_size(coordinate_range)?;
+        let height: u32 = reader.read_size(coordinate_range)?;
 
-        let color_count = read_variable_sized_unsigned_number(cursor)?;
+        let color_count = reader.read_variable_sized_unsigned_number()?;
 
         let header = TinyVgHeader {
             magic,
@@ -138,5 +145,226 @@ impl TinyVgHeader {
         };
 
         Ok(header)
-    }   
-}
\ No newline at end of file
+    }
+
+    /// The size the document is meant to be displayed at. A width or height of 0 means the
+    /// largest possible size, which is reported as `DisplaySize::Unbounded` so that it is not
+    /// mistaken for an empty document.
+    pub fn display_size(&self) -> DisplaySize {
+        match (self.width, self.height) {
+            (0, _) | (_, 0) => DisplaySize::Unbounded {
+                width: (self.width != 0).then_some(self.width),
+                height: (self.height != 0).then_some(self.height),
+            },
+            (width, height) => DisplaySize::Fixed(width, height),
+        }
+    }
+
+    /// A builder for headers of documents created in code, which fills in the magic number
+    /// and version and checks the other fields.
+    pub fn builder() -> TinyVgHeaderBuilder {
+        TinyVgHeaderBuilder::default()
+    }
+
+    /// The smallest scale whose rounding error is at most `precision` display units, while
+    /// units can still reach every coordinate of a canvas of `size` (width, height) in
+    /// `range`. Without a precision, the largest scale that covers the canvas is returned.
+    /// Returns `None` if the canvas does not fit the range or the precision is not reachable.
+    pub fn recommended_scale(precision: Option<f64>, size: (u32, u32), range: CoordinateRange) -> Option<u8> {
+        let extent = size.0.max(size.1) as f64;
+        let covering = (0..=MAX_SCALE).rev().find(|&scale| extent * (1u32 << scale) as f64 <= raw_limit(range))?;
+        match precision {
+            // Rounding to the nearest multiple of 2^-scale is off by at most half of it.
+            Some(precision) => (0..=covering).find(|&scale| 0.5 / (1u32 << scale) as f64 <= precision),
+            None => Some(covering),
+        }
+    }
+
+    /// Fails if `policy` does not accept the version of this header.
+    pub(crate) fn check_version(&self, policy: VersionPolicy) -> Result<(), TinyVgParseError> {
+        let accepted = match policy {
+            VersionPolicy::RejectUnknown => self.version == TINYVG_VERSION,
+            VersionPolicy::AcceptWithWarning => {
+                if self.version != TINYVG_VERSION {
+                    log_warning!("parsing a document of version {}, this parser supports version {TINYVG_VERSION}", self.version);
+                }
+                true
+            }
+            VersionPolicy::Exact(version) => self.version == version,
+        };
+        if accepted { Ok(()) } else { Err(TinyVgParseError::UnsupportedVersion(self.version)) }
+    }
+
+    /// The factor that maps display units to physical pixels at the given DPI.
+    /// A display unit is a CSS pixel, so 96 DPI maps one unit to one pixel.
+    pub fn dpi_scale(dpi: f64) -> f64 {
+        dpi / DISPLAY_UNITS_PER_INCH
+    }
+
+    /// The width and height of the document in physical pixels at the given DPI,
+    /// rounded up so the whole document fits. Unbounded sides are 0, see `display_size`.
+    pub fn pixel_size_at_dpi(&self, dpi: f64) -> (u32, u32) {
+        let scale = Self::dpi_scale(dpi);
+        let width = (self.width as f64 * scale).ceil() as u32;
+        let height = (self.height as f64 * scale).ceil() as u32;
+        (width, height)
+    }
+}
+
+/// The size of a document in display units, see `TinyVgHeader::display_size`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DisplaySize {
+    /// The width and height.
+    Fixed(u32, u32),
+
+    /// The document may take as much room as it is given along the sides that are `None`,
+    /// as their size is stored as 0. The other side keeps its size.
+    Unbounded { width: Option<u32>, height: Option<u32> },
+}
+
+impl DisplaySize {
+    pub fn is_unbounded(&self) -> bool {
+        matches!(self, DisplaySize::Unbounded { .. })
+    }
+
+    /// The width and height when shown in a viewport of `viewport` (width, height) display
+    /// units. Unbounded sides take the size of the viewport.
+    pub fn resolve(&self, viewport: (f64, f64)) -> (f64, f64) {
+        match *self {
+            DisplaySize::Fixed(width, height) => (width as f64, height as f64),
+            DisplaySize::Unbounded { width, height } => {
+                (width.map_or(viewport.0, |width| width as f64), height.map_or(viewport.1, |height| height as f64))
+            }
+        }
+    }
+
+    /// Like `resolve`, in whole display units.
+    pub fn resolve_whole(&self, viewport: (u32, u32)) -> (u32, u32) {
+        match *self {
+            DisplaySize::Fixed(width, height) => (width, height),
+            DisplaySize::Unbounded { width, height } => (width.unwrap_or(viewport.0), height.unwrap_or(viewport.1)),
+        }
+    }
+}
+
+/// The number of display units (CSS pixels) in one inch.
+pub const DISPLAY_UNITS_PER_INCH: f64 = 96.0;
+/// Builds a `TinyVgHeader`, see `TinyVgHeader::builder`. Every method returns the builder,
+/// so settings can be chained before calling `build`.
+#[derive(Debug, Clone)]
+pub struct TinyVgHeaderBuilder {
+    width: u32,
+    height: u32,
+    scale: Option<u8>,
+    precision: Option<f64>,
+    color_encoding: ColorEncoding,
+    coordinate_range: Option<CoordinateRange>,
+    color_count: u64,
+}
+
+impl Default for TinyVgHeaderBuilder {
+    fn default() -> Self {
+        TinyVgHeaderBuilder {
+            width: 0,
+            height: 0,
+            scale: None,
+            precision: None,
+            color_encoding: ColorEncoding::Rgba8888,
+            coordinate_range: None,
+            color_count: 0,
+        }
+    }
+}
+
+impl TinyVgHeaderBuilder {
+    pub fn size(&mut self, width: u32, height: u32) -> &mut Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    /// Sets the number of fraction bits. By default it is chosen by
+    /// `TinyVgHeader::recommended_scale` from the size and the precision.
+    pub fn scale(&mut self, scale: u8) -> &mut Self {
+        self.scale = Some(scale);
+        self
+    }
+
+    /// Sets the largest rounding error of coordinates in display units that the scale
+    /// must allow, when the scale is not set directly.
+    pub fn precision(&mut self, precision: f64) -> &mut Self {
+        self.precision = Some(precision);
+        self
+    }
+
+    pub fn color_encoding(&mut self, color_encoding: ColorEncoding) -> &mut Self {
+        self.color_encoding = color_encoding;
+        self
+    }
+
+    /// Sets the coordinate range. By default the default range is used, or the enhanced
+    /// range if the size or precision need it.
+    pub fn coordinate_range(&mut self, coordinate_range: CoordinateRange) -> &mut Self {
+        self.coordinate_range = Some(coordinate_range);
+        self
+    }
+
+    pub fn color_count(&mut self, color_count: u64) -> &mut Self {
+        self.color_count = color_count;
+        self
+    }
+
+    /// The header, or the first setting that cannot be encoded: a scale above `MAX_SCALE`,
+    /// a size that does not fit the coordinate range, or a precision that no scale reaches.
+    pub fn build(&self) -> Result<TinyVgHeader, ValidationError> {
+        if self.scale.is_some_and(|scale| scale > MAX_SCALE) {
+            return Err(ValidationError::ScaleOutOfRange);
+        }
+        let ranges = match self.coordinate_range {
+            Some(range) => vec![range],
+            None => vec![CoordinateRange::Default, CoordinateRange::Enhanced],
+        };
+        let size = self.width.max(self.height);
+
+        let mut error = ValidationError::SizeOutOfRange;
+        for coordinate_range in ranges {
+            if size > size_limit(coordinate_range) {
+                continue;
+            }
+            let scale = match self.scale {
+                Some(scale) => Some(scale),
+                None => {
+                    let scale = TinyVgHeader::recommended_scale(self.precision, (self.width, self.height), coordinate_range);
+                    if scale.is_none() && TinyVgHeader::recommended_scale(None, (self.width, self.height), coordinate_range).is_some() {
+                        error = ValidationError::PrecisionOutOfRange;
+                    }
+                    scale
+                }
+            };
+            if let Some(scale) = scale {
+                return Ok(TinyVgHeader {
+                    magic: [0x72, 0x56],
+                    version: TINYVG_VERSION,
+                    scale,
+                    color_encoding: self.color_encoding,
+                    coordinate_range,
+                    width: self.width,
+                    height: self.height,
+                    color_count: self.color_count,
+                });
+            }
+        }
+        Err(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{TinyVg, TinyVgParseError};
+
+    #[test]
+    fn reserved_coordinate_range_is_invalid() {
+        let data = [0x72, 0x56, 1, 0b11_00_00_00, 1, 1, 0];
+        assert_eq!(TinyVg::from_bytes(&data), Err(TinyVgParseError::InvalidHeader));
+    }
+}
@@ -0,0 +1,77 @@
+use crate::commands::Point;
+use crate::geometry::{command_outline, polyline_distance};
+use crate::TinyVg;
+
+/// The flattening tolerance for curves in display units.
+const HIT_TEST_TOLERANCE: f64 = 0.01;
+
+/// Controls which parts of the draw commands are tested.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HitTestOptions {
+    /// Test the filled area of fill and outline-fill commands.
+    pub fills: bool,
+
+    /// Test the stroked lines of line and outline-fill commands.
+    pub strokes: bool,
+
+    /// Extra distance in display units around strokes that still counts as a hit,
+    /// which makes thin lines easier to pick.
+    pub stroke_tolerance: f64,
+}
+
+impl Default for HitTestOptions {
+    fn default() -> Self {
+        HitTestOptions {
+            fills: true,
+            strokes: true,
+            stroke_tolerance: 0.0,
+        }
+    }
+}
+
+/// The part of a draw command that contains the tested point.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum HitKind {
+    Fill,
+    Stroke,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HitResult {
+    /// The index of the command in `TinyVg::draw_commands`.
+    pub command_index: usize,
+    pub kind: HitKind,
+}
+
+impl TinyVg {
+    /// Returns the draw commands that contain `point`, ordered from the topmost
+    /// (last drawn) command down.
+    ///
+    /// Fills are tested by containment using the even-odd rule, except for rectangle
+    /// lists whose rectangles combine as a union. Strokes contain every point within
+    /// half the line width of the line. A command that is hit by both its stroke and
+    /// its fill is reported once, as a stroke hit, since the stroke is drawn on top.
+    pub fn hit_test(&self, point: Point, options: HitTestOptions) -> Vec<HitResult> {
+        let point = (point.x.0, point.y.0);
+        let mut hits = Vec::new();
+
+        for (command_index, command) in self.draw_commands.iter().enumerate().rev() {
+            let Some(outline) = command_outline(command, HIT_TEST_TOLERANCE) else {
+                continue;
+            };
+
+            let half_width = outline.line_width / 2.0 + options.stroke_tolerance;
+            let kind = if options.strokes && outline.stroke.iter().any(|polyline| polyline_distance(polyline, point) <= half_width) {
+                HitKind::Stroke
+            } else if options.fills && outline.fill_contains(point) {
+                HitKind::Fill
+            } else {
+                continue;
+            };
+
+            hits.push(HitResult { command_index, kind });
+        }
+
+        hits
+    }
+}
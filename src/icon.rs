@@ -0,0 +1,171 @@
+use crate::render::RgbaImage;
+use crate::TinyVg;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::io::Write;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// The sizes an ICNS file stores as PNG images, with the type of their element.
+const ICNS_TYPES: [(u32, &[u8; 4]); 7] =
+    [(16, b"icp4"), (32, b"icp5"), (64, b"icp6"), (128, b"ic07"), (256, b"ic08"), (512, b"ic09"), (1024, b"ic10")];
+
+/// Why an icon file could not be written.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IconError {
+    /// No sizes were requested.
+    NoSizes,
+
+    /// The format cannot store an image of this size. ICO stores sizes from 1 to 256
+    /// pixels, and ICNS stores 16, 32, 64, 128, 256, 512 and 1024 pixels.
+    UnsupportedSize(u32),
+}
+
+impl TinyVg {
+    /// Renders the document at each of `sizes`, see `render_sizes`, and packs the images
+    /// into a Windows `.ico` file with one PNG image per size.
+    pub fn to_ico(&self, sizes: &[u32]) -> Result<Vec<u8>, IconError> {
+        if sizes.is_empty() {
+            return Err(IconError::NoSizes);
+        }
+        if let Some(&size) = sizes.iter().find(|&&size| size == 0 || size > 256) {
+            return Err(IconError::UnsupportedSize(size));
+        }
+        let images: Vec<Vec<u8>> = self.render_sizes(sizes).iter().map(encode_png).collect();
+
+        // The directory header, then one 16 byte entry per image, then the images.
+        let mut file = Vec::new();
+        file.extend(0u16.to_le_bytes());
+        file.extend(1u16.to_le_bytes());
+        file.extend((sizes.len() as u16).to_le_bytes());
+        let mut offset = 6 + 16 * sizes.len();
+        for (&size, image) in sizes.iter().zip(&images) {
+            // 256 pixels are stored as 0.
+            let side = (size % 256) as u8;
+            file.extend([side, side, 0, 0]);
+            file.extend(1u16.to_le_bytes());
+            file.extend(32u16.to_le_bytes());
+            file.extend((image.len() as u32).to_le_bytes());
+            file.extend((offset as u32).to_le_bytes());
+            offset += image.len();
+        }
+        for image in images {
+            file.extend(image);
+        }
+        Ok(file)
+    }
+
+    /// Renders the document at each of `sizes`, see `render_sizes`, and packs the images
+    /// into a macOS `.icns` file with one PNG image per size.
+    pub fn to_icns(&self, sizes: &[u32]) -> Result<Vec<u8>, IconError> {
+        if sizes.is_empty() {
+            return Err(IconError::NoSizes);
+        }
+        let types = sizes
+            .iter()
+            .map(|&size| ICNS_TYPES.iter().find(|(icns_size, _)| *icns_size == size).map(|(_, kind)| *kind).ok_or(IconError::UnsupportedSize(size)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut elements = Vec::new();
+        for (kind, image) in types.into_iter().zip(self.render_sizes(sizes)) {
+            let png = encode_png(&image);
+            elements.extend(kind);
+            elements.extend((png.len() as u32 + 8).to_be_bytes());
+            elements.extend(png);
+        }
+
+        let mut file = b"icns".to_vec();
+        file.extend((elements.len() as u32 + 8).to_be_bytes());
+        file.extend(elements);
+        Ok(file)
+    }
+}
+
+/// Encodes an image as a non-interlaced 8-bit RGBA PNG without row filters.
+fn encode_png(image: &RgbaImage) -> Vec<u8> {
+    let mut header = Vec::with_capacity(13);
+    header.extend(image.width.to_be_bytes());
+    header.extend(image.height.to_be_bytes());
+    header.extend([8, 6, 0, 0, 0]);
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    for row in image.data.chunks_exact(image.width as usize * 4) {
+        encoder.write_all(&[0]).and_then(|_| encoder.write_all(row)).expect("writing to memory cannot fail");
+    }
+    let data = encoder.finish().expect("writing to memory cannot fail");
+
+    let mut png = PNG_SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &data);
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    png.extend(kind);
+    png.extend(data);
+    png.extend(crc32(kind.iter().chain(data)).to_be_bytes());
+}
+
+/// The CRC-32 that PNG chunks end with, computed bit by bit.
+fn crc32<'a>(bytes: impl Iterator<Item = &'a u8>) -> u32 {
+    let mut crc = u32::MAX;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { 0xEDB8_8320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IconError;
+    use crate::TinyVg;
+
+    /// A 16 by 16 document filled with red.
+    fn document() -> TinyVg {
+        let data = [0x72, 0x56, 1, 0x00, 16, 0, 16, 0, 1, 255, 0, 0, 255, 0x02, 0, 0, 0, 0, 0, 0, 16, 0, 16, 0, 0x00];
+        TinyVg::from_bytes(&data).unwrap()
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn ico_directory_points_at_png_images() {
+        let ico = document().to_ico(&[16, 256]).unwrap();
+        assert_eq!(&ico[..6], &[0, 0, 1, 0, 2, 0]);
+        for (entry, side) in [(6, 16), (22, 0)] {
+            assert_eq!(ico[entry], side);
+            let (length, offset) = (u32_at(&ico, entry + 8) as usize, u32_at(&ico, entry + 12) as usize);
+            let png = &ico[offset..offset + length];
+            assert_eq!(&png[..8], &super::PNG_SIGNATURE);
+            // The IEND chunk and its fixed CRC.
+            assert_eq!(&png[png.len() - 12..], &[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]);
+        }
+        assert_eq!(document().to_ico(&[512]), Err(IconError::UnsupportedSize(512)));
+    }
+
+    #[test]
+    fn icns_elements_cover_the_file() {
+        let icns = document().to_icns(&[16, 128]).unwrap();
+        assert_eq!(&icns[..4], b"icns");
+        assert_eq!(u32::from_be_bytes(icns[4..8].try_into().unwrap()) as usize, icns.len());
+        assert_eq!(&icns[8..12], b"icp4");
+        let length = u32::from_be_bytes(icns[12..16].try_into().unwrap()) as usize;
+        assert_eq!(&icns[8 + length..12 + length], b"ic07");
+        assert_eq!(document().to_icns(&[48]), Err(IconError::UnsupportedSize(48)));
+        assert_eq!(document().to_icns(&[]), Err(IconError::NoSizes));
+    }
+
+    #[cfg(feature = "trace")]
+    #[test]
+    fn png_images_decode_to_the_render() {
+        let image = document().render_rgba((16, 16), crate::fit::FitMode::Contain);
+        assert_eq!(crate::png::decode_png(&super::encode_png(&image)), Some(image));
+    }
+}
@@ -0,0 +1,144 @@
+use crate::commands::DrawCommand;
+use crate::palette::{colors_by_first_use, reorder_colors};
+use crate::editing::EditError;
+use crate::TinyVg;
+
+impl TinyVg {
+    /// Splits the document into layers, one document per distinct value returned by
+    /// `layer_of` for a command and its index, such as the command's style, the region it
+    /// lies in, or `index / n` for every `n` commands. Layers are returned in the order
+    /// they first appear. Every layer keeps the original header and the relative order of
+    /// its commands, and its color table is reduced to the colors it uses.
+    ///
+    /// Drawing the layers on top of each other in order reproduces the original document
+    /// only if commands of different layers are not interleaved where they overlap.
+    pub fn split_layers<K: PartialEq>(&self, mut layer_of: impl FnMut(usize, &DrawCommand) -> K) -> Vec<TinyVg> {
+        let mut keys: Vec<K> = Vec::new();
+        let mut layers: Vec<TinyVg> = Vec::new();
+
+        for (index, command) in self.draw_commands.iter().enumerate() {
+            let key = layer_of(index, command);
+            let layer = match keys.iter().position(|other| *other == key) {
+                Some(layer) => layer,
+                None => {
+                    keys.push(key);
+                    layers.push(TinyVg { header: self.header.clone(), color_table: self.color_table.clone(), draw_commands: Vec::new() });
+                    layers.len() - 1
+                }
+            };
+            layers[layer].draw_commands.push(command.clone());
+        }
+
+        for layer in &mut layers {
+            let order = colors_by_first_use(layer);
+            reorder_colors(layer, &order);
+        }
+        layers
+    }
+}
+
+impl TinyVg {
+    /// Moves the command at index `from` so that it ends up at index `to`, shifting the
+    /// commands in between. Fails with `EditError::IndexOutOfRange` if either index is out
+    /// of range.
+    pub fn move_command(&mut self, from: usize, to: usize) -> Result<(), EditError> {
+        let len = self.draw_commands.len();
+        if let Some(&index) = [from, to].iter().find(|&&index| index >= len) {
+            return Err(EditError::IndexOutOfRange { index, len });
+        }
+        if from < to {
+            self.draw_commands[from..=to].rotate_left(1);
+        } else {
+            self.draw_commands[to..=from].rotate_right(1);
+        }
+        Ok(())
+    }
+
+    /// Moves the command to the end of the list, so that it is drawn on top of all others.
+    pub fn bring_to_front(&mut self, index: usize) -> Result<(), EditError> {
+        self.move_command(index, self.draw_commands.len().saturating_sub(1))
+    }
+
+    /// Moves the command to the start of the list, so that all others are drawn on top of it.
+    pub fn send_to_back(&mut self, index: usize) -> Result<(), EditError> {
+        self.move_command(index, 0)
+    }
+
+    /// Swaps the command with the one drawn after it. The topmost command stays in place.
+    pub fn bring_forward(&mut self, index: usize) -> Result<(), EditError> {
+        self.move_command(index, (index + 1).min(self.draw_commands.len().saturating_sub(1)))
+    }
+
+    /// Swaps the command with the one drawn before it. The bottommost command stays in place.
+    pub fn send_backward(&mut self, index: usize) -> Result<(), EditError> {
+        self.move_command(index, index.saturating_sub(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::TinyVgBuilder;
+    use crate::color_table::RgbaF32;
+    use crate::commands::{DrawCommand, Rectangle};
+    use crate::common::Unit;
+    use crate::editing::EditError;
+    use crate::TinyVg;
+
+    /// A document with three rectangles at x = 0, 1 and 2, in that order.
+    fn document() -> TinyVg {
+        let mut builder = TinyVgBuilder::new(4, 4);
+        for x in 0..3 {
+            let rectangle = Rectangle { x: Unit(x as f64), y: Unit(0.0), width: Unit(1.0), height: Unit(1.0) };
+            builder.fill_rectangles(vec![rectangle], RgbaF32(0.0, 0.0, 0.0, 1.0));
+        }
+        builder.build()
+    }
+
+    /// The x of the rectangle of every command, in order.
+    fn order(document: &TinyVg) -> Vec<f64> {
+        document
+            .draw_commands
+            .iter()
+            .map(|command| match command {
+                DrawCommand::FillRectangles(data) => data.rectangles[0].x.0,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn commands_move_within_the_list() {
+        let mut document = document();
+        document.move_command(0, 2).unwrap();
+        assert_eq!(order(&document), [1.0, 2.0, 0.0]);
+        document.move_command(2, 0).unwrap();
+        assert_eq!(order(&document), [0.0, 1.0, 2.0]);
+
+        document.bring_to_front(0).unwrap();
+        assert_eq!(order(&document), [1.0, 2.0, 0.0]);
+        document.send_to_back(2).unwrap();
+        assert_eq!(order(&document), [0.0, 1.0, 2.0]);
+
+        // The topmost and bottommost commands stay where they are.
+        document.bring_forward(2).unwrap();
+        document.send_backward(0).unwrap();
+        assert_eq!(order(&document), [0.0, 1.0, 2.0]);
+        document.bring_forward(1).unwrap();
+        assert_eq!(order(&document), [0.0, 2.0, 1.0]);
+    }
+
+    #[test]
+    fn out_of_range_indices_are_rejected() {
+        let mut document = document();
+        assert_eq!(document.move_command(3, 0), Err(EditError::IndexOutOfRange { index: 3, len: 3 }));
+        assert_eq!(document.move_command(0, 3), Err(EditError::IndexOutOfRange { index: 3, len: 3 }));
+        assert_eq!(document.bring_to_front(3), Err(EditError::IndexOutOfRange { index: 3, len: 3 }));
+        assert_eq!(document.send_to_back(3), Err(EditError::IndexOutOfRange { index: 3, len: 3 }));
+        assert_eq!(document.bring_forward(3), Err(EditError::IndexOutOfRange { index: 3, len: 3 }));
+        assert_eq!(document.send_backward(3), Err(EditError::IndexOutOfRange { index: 3, len: 3 }));
+        assert_eq!(order(&document), [0.0, 1.0, 2.0]);
+
+        document.draw_commands.clear();
+        assert_eq!(document.bring_to_front(0), Err(EditError::IndexOutOfRange { index: 0, len: 0 }));
+    }
+}
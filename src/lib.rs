@@ -1,24 +1,161 @@
+/// Reports something the library skipped or approximated through the `log` facade when
+/// the `log` feature is enabled, and compiles to nothing otherwise.
+macro_rules! log_warning {
+    ($($argument:tt)*) => {{
+        #[cfg(feature = "log")]
+        log::warn!($($argument)*);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($argument)*);
+    }};
+}
+
 pub mod header;
+pub mod borrowed;
 pub mod common;
 pub mod color_table;
 pub mod commands;
+pub mod fit;
+pub mod atlas;
+mod bounds;
+pub mod builder;
+pub mod cache;
+mod canvas;
+mod color;
+pub mod contrast;
+pub mod diff;
+pub mod editing;
+mod encoded_size;
+pub mod encoder;
+pub mod flat;
+pub mod hit_test;
+mod geometry;
+mod layers;
+mod mask;
+pub mod measure;
+mod optimize;
+mod path_data;
+pub mod palette;
+pub mod patch;
+mod points;
+mod precision;
+pub mod quantization;
+mod raster;
+pub mod render;
+pub mod sdf;
+pub mod search;
+pub mod shapes;
+pub mod statistics;
+pub mod tokens;
+mod transform;
+pub mod validation;
+pub mod visitor;
+#[cfg(feature = "pdf-import")]
+pub mod pdf_import;
+#[cfg(feature = "trace")]
+mod png;
 #[cfg(feature = "svg-to-tvg")]
 pub mod svg_to_tvg;
+#[cfg(feature = "vectordrawable-to-tvg")]
+pub mod vectordrawable_to_tvg;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "text")]
+pub mod text;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+#[cfg(feature = "bumpalo")]
+pub mod arena;
+#[cfg(feature = "trace")]
+pub mod trace;
+#[cfg(feature = "icon")]
+pub mod icon;
+
+pub use crate::diff::diff;
+pub use crate::header::TINYVG_VERSION;
 
 use crate::color_table::{parse_color_table, ColorTable};
-use crate::commands::{parse_draw_commands, DrawCommand};
+use crate::commands::{check_trailing_data, parse_draw_commands, DrawCommand};
+#[cfg(feature = "rayon")]
+use crate::commands::{parse_draw_command, scan_draw_commands};
+use crate::common::Reader;
 use crate::header::{CoordinateRange, TinyVgHeader};
-use std::io::{Cursor};
+use std::ops::Range;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum TinyVgParseError {
     None,
     InvalidHeader,
     InvalidColorTable,
     InvalidCommand,
+    /// A draw command has an index that this version of the format does not define, as
+    /// files written for a newer version may.
+    UnknownCommand,
+    /// Strict parsing found bytes other than zero after the end of the document.
+    TrailingData,
+    /// The header has a version that the `VersionPolicy` does not accept.
+    UnsupportedVersion(u8),
+}
+
+/// Which format versions the parser accepts. Versions other than `TINYVG_VERSION` may have
+/// been written for a future revision of the format, which this parser may not read
+/// correctly.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum VersionPolicy {
+    /// Fails with `TinyVgParseError::UnsupportedVersion` on versions other than `TINYVG_VERSION`.
+    RejectUnknown,
+
+    /// Parses any version and logs a warning for versions other than `TINYVG_VERSION`.
+    #[default]
+    AcceptWithWarning,
+
+    /// Fails with `TinyVgParseError::UnsupportedVersion` on any version but this one.
+    Exact(u8),
+}
+
+/// What the parser does when it meets a draw command with an unknown index.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum UnknownCommandPolicy {
+    /// Fails with `TinyVgParseError::UnknownCommand`.
+    #[default]
+    Error,
+
+    /// Keeps the commands before the unknown one and logs a warning. Commands do not store
+    /// their size, so this only succeeds when the unknown command's size can be inferred,
+    /// which is when the data after it ends with the end of document byte. The unknown
+    /// command is then taken to be the last one. Otherwise it fails as `Error` does.
+    SkipWithWarning,
+
+    /// Keeps the commands before the unknown one and ends the document there without
+    /// looking at the bytes after it, which count as part of the document.
+    StopBeforeUnknown,
 }
 
-#[derive(Debug)]
+/// Options for `TinyVg::from_bytes_with_options`. The default matches `TinyVg::from_bytes`.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct ParseOptions {
+    pub unknown_commands: UnknownCommandPolicy,
+
+    /// Which header versions are parsed.
+    pub version: VersionPolicy,
+
+    /// Rejects files that parse but were not encoded exactly as the format says: the end of
+    /// document byte must have its style bits cleared and only zero bytes may follow it.
+    /// Without it both are accepted, and trailing bytes are skipped with a warning.
+    pub strict: bool,
+
+    /// The size in bytes of one color of a custom color encoding, which lets the parser
+    /// skip a custom color table it cannot decode. Its colors are parsed as
+    /// `color_table::CUSTOM_COLOR_PLACEHOLDER`, and `color_table::CustomColors` reads the
+    /// raw table. Without it, documents with custom colors fail to parse.
+    pub custom_color_size: Option<usize>,
+}
+
+/// The fewest draw commands `TinyVg::from_bytes_parallel` hands to a thread at once, so
+/// that small commands are not split into tasks that cost more than decoding them.
+#[cfg(feature = "rayon")]
+const PARALLEL_COMMANDS_PER_TASK: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct TinyVg {
     pub header: TinyVgHeader,
     pub color_table: ColorTable,
@@ -28,11 +165,104 @@ pub struct TinyVg {
 impl TinyVg {
 
     pub fn from_bytes(data: &[u8]) -> Result<TinyVg, TinyVgParseError> {
-        let mut cursor = Cursor::new(data);
+        TinyVg::from_bytes_with_options(data, &ParseOptions::default())
+    }
+
+    /// Parses a document like `from_bytes`, with the behavior for unusual input set by `options`.
+    pub fn from_bytes_with_options(data: &[u8], options: &ParseOptions) -> Result<TinyVg, TinyVgParseError> {
+        TinyVg::from_bytes_counted(data, options).map(|(document, _)| document)
+    }
+
+    /// Parses a document like `from_bytes_with_options` and also returns the number of bytes
+    /// of `data` it consumed, up to and including the end of document byte. Anything after
+    /// that is trailing data, which validators can compare against the input length.
+    pub fn from_bytes_counted(data: &[u8], options: &ParseOptions) -> Result<(TinyVg, usize), TinyVgParseError> {
+        let (document, consumed) = TinyVg::from_bytes_prefix(data, options)?;
+        check_trailing_data(&Reader::new(&data[consumed..], TinyVgParseError::InvalidCommand), options)?;
+        Ok((document, consumed))
+    }
 
-        let header = TinyVgHeader::parse(&mut cursor)?;
-        let color_table = parse_color_table(&mut cursor, &header)?;
-        let draw_commands: Vec<DrawCommand> = parse_draw_commands(&mut cursor, &header)?;
+    /// Parses the document at the start of `data` and returns it with the number of bytes it
+    /// consumed. The bytes after the end of the document are not looked at, so they may hold
+    /// another document or anything else that follows in a stream.
+    pub fn from_bytes_prefix(data: &[u8], options: &ParseOptions) -> Result<(TinyVg, usize), TinyVgParseError> {
+        let mut reader = Reader::new(data, TinyVgParseError::InvalidHeader);
+
+        let (header, color_table) = parse_header_and_color_table(&mut reader, options)?;
+        let mut draw_commands: Vec<DrawCommand> = Vec::new();
+        parse_draw_commands(&mut reader, &header, options, None, |command| draw_commands.push(command.into_owned()))?;
+
+        Ok((
+            TinyVg {
+                header,
+                color_table,
+                draw_commands,
+            },
+            reader.position(),
+        ))
+    }
+
+    /// Parses documents that are stored back to back, as in bundles that concatenate several
+    /// files, and returns them in order. Every byte must belong to a document.
+    pub fn parse_all(data: &[u8]) -> Result<Vec<TinyVg>, TinyVgParseError> {
+        let options = ParseOptions::default();
+        let mut documents = Vec::new();
+        let mut rest = data;
+        while !rest.is_empty() {
+            let (document, consumed) = TinyVg::from_bytes_prefix(rest, &options)?;
+            documents.push(document);
+            rest = &rest[consumed..];
+        }
+        Ok(documents)
+    }
+
+    /// Parses a document like `from_bytes` and also returns the byte range of every draw
+    /// command in `data`, in the order of `draw_commands`. The ranges allow random access
+    /// to commands, re-encoding parts of a file and pointing at the bytes of a command in
+    /// error reports.
+    pub fn from_bytes_with_spans(data: &[u8]) -> Result<(TinyVg, Vec<Range<usize>>), TinyVgParseError> {
+        TinyVg::from_bytes_with_spans_counted(data).map(|(document, spans, _)| (document, spans))
+    }
+
+    /// Like `from_bytes_with_spans`, and also returns the number of bytes up to and
+    /// including the end of document byte, as `from_bytes_counted` does.
+    pub(crate) fn from_bytes_with_spans_counted(data: &[u8]) -> Result<(TinyVg, Vec<Range<usize>>, usize), TinyVgParseError> {
+        let mut reader = Reader::new(data, TinyVgParseError::InvalidHeader);
+
+        let options = ParseOptions::default();
+        let (header, color_table) = parse_header_and_color_table(&mut reader, &options)?;
+        let mut spans = Vec::new();
+        let mut draw_commands: Vec<DrawCommand> = Vec::new();
+        parse_draw_commands(&mut reader, &header, &options, Some(&mut spans), |command| draw_commands.push(command.into_owned()))?;
+        let consumed = reader.position();
+        check_trailing_data(&reader, &options)?;
+
+        Ok((TinyVg { header, color_table, draw_commands }, spans, consumed))
+    }
+
+    /// Parses a document like `from_bytes`, decoding the draw commands on the rayon thread
+    /// pool. The commands are found first by skipping their payloads by size, and then
+    /// decoded in parallel, which pays off for large documents such as maps and charts.
+    #[cfg(feature = "rayon")]
+    pub fn from_bytes_parallel(data: &[u8]) -> Result<TinyVg, TinyVgParseError> {
+        use rayon::prelude::*;
+
+        let mut reader = Reader::new(data, TinyVgParseError::InvalidHeader);
+
+        let options = ParseOptions::default();
+        let (header, color_table) = parse_header_and_color_table(&mut reader, &options)?;
+        let spans = scan_draw_commands(&mut reader, &header, &options)?;
+        check_trailing_data(&reader, &options)?;
+
+        // A span holds exactly one command, so decoding it never reaches the end of the document.
+        let draw_commands = spans
+            .into_par_iter()
+            .with_min_len(PARALLEL_COMMANDS_PER_TASK)
+            .map(|span| {
+                let mut command_reader = Reader::new(&data[span], TinyVgParseError::InvalidCommand);
+                parse_draw_command(&mut command_reader, &header)?.map(|command| command.into_owned()).ok_or(TinyVgParseError::InvalidCommand)
+            })
+            .collect::<Result<Vec<DrawCommand>, TinyVgParseError>>()?;
 
         Ok(TinyVg {
             header,
@@ -40,4 +270,23 @@ impl TinyVg {
             draw_commands,
         })
     }
+
+    /// The format version the document was written for, see `VersionPolicy`.
+    pub fn version(&self) -> u8 {
+        self.header.version
+    }
+
+    /// The byte range of every draw command in `data`, see `from_bytes_with_spans`.
+    pub fn command_spans(data: &[u8]) -> Result<Vec<Range<usize>>, TinyVgParseError> {
+        TinyVg::from_bytes_with_spans(data).map(|(_, spans)| spans)
+    }
+}
+
+/// Parses the header and the color table and checks them against `options`, which every
+/// parser does the same way before it reads the draw commands.
+pub(crate) fn parse_header_and_color_table(reader: &mut Reader, options: &ParseOptions) -> Result<(TinyVgHeader, ColorTable), TinyVgParseError> {
+    let header = TinyVgHeader::parse(reader)?;
+    header.check_version(options.version)?;
+    let color_table = parse_color_table(reader, &header, options.custom_color_size)?;
+    Ok((header, color_table))
 }
\ No newline at end of file
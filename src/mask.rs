@@ -0,0 +1,33 @@
+use crate::raster::SAMPLES_PER_AXIS;
+use crate::TinyVg;
+
+impl TinyVg {
+    /// Renders the coverage of the document into an 8-bit alpha mask of `size` (width, height),
+    /// stretching the document to fill it. Pixels are stored row by row, where 255 means the
+    /// pixel is fully covered by some fill or stroke and 0 means it is empty.
+    ///
+    /// Only geometry is taken into account; colors and their alpha are ignored, which makes the
+    /// mask suitable for clipping and hit testing in other renderers.
+    pub fn render_mask(&self, size: (u32, u32)) -> Vec<u8> {
+        let (width, height) = (size.0 as usize, size.1 as usize);
+        let sample_width = width * SAMPLES_PER_AXIS;
+        let samples = self.coverage_samples(size, SAMPLES_PER_AXIS);
+
+        let samples_per_pixel = (SAMPLES_PER_AXIS * SAMPLES_PER_AXIS) as u32;
+        let mut mask = vec![0u8; width * height];
+        for y in 0..height {
+            for x in 0..width {
+                let mut covered = 0u32;
+                for sample_y in 0..SAMPLES_PER_AXIS {
+                    let row = (y * SAMPLES_PER_AXIS + sample_y) * sample_width;
+                    for sample_x in 0..SAMPLES_PER_AXIS {
+                        covered += samples[row + x * SAMPLES_PER_AXIS + sample_x] as u32;
+                    }
+                }
+                mask[y * width + x] = ((covered * 255 + samples_per_pixel / 2) / samples_per_pixel) as u8;
+            }
+        }
+
+        mask
+    }
+}
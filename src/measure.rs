@@ -0,0 +1,85 @@
+use crate::commands::{Path, Point};
+use crate::common::Unit;
+
+/// The flattening tolerance for curves in display units.
+const MEASURE_TOLERANCE: f64 = 0.01;
+
+impl Path {
+    /// The area enclosed by the path, with every segment treated as closed. The signed
+    /// areas of the segments are added up, so holes drawn in the opposite direction of
+    /// their outer contour are subtracted.
+    pub fn area(&self) -> f64 {
+        self.flattened_segments().map(|points| signed_area(&points)).sum::<f64>().abs()
+    }
+
+    /// The length of the path as it is stroked, including the closing edges of segments
+    /// that end with a `ClosePath`.
+    pub fn perimeter(&self) -> f64 {
+        self.flatten_segments(MEASURE_TOLERANCE).map(|points| polyline_length(&points)).sum()
+    }
+
+    /// The center of mass of the area enclosed by the path, as for `area`. Returns `None`
+    /// if the path encloses no area.
+    pub fn centroid(&self) -> Option<Point> {
+        let (area, x, y) = self
+            .flattened_segments()
+            .map(|points| weighted_centroid(&points))
+            .fold((0.0, 0.0, 0.0), |a, b| (a.0 + b.0, a.1 + b.1, a.2 + b.2));
+        centroid_point(area, x, y)
+    }
+
+    fn flattened_segments(&self) -> impl Iterator<Item = Vec<Point>> + '_ {
+        self.segments.iter().map(|segment| segment.flatten_points(MEASURE_TOLERANCE).0)
+    }
+}
+
+/// The area enclosed by a polygon, regardless of its direction.
+pub fn polygon_area(points: &[Point]) -> f64 {
+    signed_area(points).abs()
+}
+
+/// The length of the outline of a polygon, including the edge from the last point back
+/// to the first one.
+pub fn polygon_perimeter(points: &[Point]) -> f64 {
+    match (points.first(), points.last()) {
+        (Some(first), Some(last)) => polyline_length(points) + distance(*last, *first),
+        _ => 0.0,
+    }
+}
+
+/// The center of mass of the area enclosed by a polygon. Returns `None` if the polygon
+/// encloses no area.
+pub fn polygon_centroid(points: &[Point]) -> Option<Point> {
+    let (area, x, y) = weighted_centroid(points);
+    centroid_point(area, x, y)
+}
+
+fn signed_area(points: &[Point]) -> f64 {
+    weighted_centroid(points).0
+}
+
+/// The signed area of the polygon and the sums of its centroid coordinates weighted by
+/// that area, which can be added up over several polygons.
+fn weighted_centroid(points: &[Point]) -> (f64, f64, f64) {
+    let (mut area, mut x, mut y) = (0.0, 0.0, 0.0);
+    for (index, start) in points.iter().enumerate() {
+        let end = points[(index + 1) % points.len()];
+        let cross = start.x.0 * end.y.0 - end.x.0 * start.y.0;
+        area += cross / 2.0;
+        x += (start.x.0 + end.x.0) * cross / 6.0;
+        y += (start.y.0 + end.y.0) * cross / 6.0;
+    }
+    (area, x, y)
+}
+
+fn centroid_point(area: f64, x: f64, y: f64) -> Option<Point> {
+    (area.abs() > f64::EPSILON).then(|| Point::new(Unit(x / area), Unit(y / area)))
+}
+
+fn polyline_length(points: &[Point]) -> f64 {
+    points.windows(2).map(|pair| distance(pair[0], pair[1])).sum()
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    (b.x.0 - a.x.0).hypot(b.y.0 - a.y.0)
+}
@@ -0,0 +1,962 @@
+use crate::bounds::{command_bounds, union, Bounds};
+use crate::commands::{
+    ArcCircle, CubicBezier, DrawCommand, FillPathData, FillPolygonData, FillRectanglesData, OutlineFillPolygonData,
+    OutlineFillRectanglesData, Path, PathCommand, PathCommands, Point, Points, Rectangle, Segment,
+};
+use crate::common::Unit;
+use crate::geometry::segment_distance;
+use crate::encoded_size::varuint_size;
+use crate::palette::{colors_by_first_use, for_each_color_index, reorder_colors};
+use crate::precision::for_each_unit_mut;
+use crate::TinyVg;
+
+impl TinyVg {
+    /// Removes points that deviate less than `tolerance` from the simplified line, using the
+    /// Ramer–Douglas–Peucker algorithm. Polygons, line strips and loops are simplified, as
+    /// are path segments made only of straight lines without per-command line widths.
+    /// The first point of every polyline and the last point of open ones are kept, and
+    /// polygons keep at least three points.
+    ///
+    /// Returns the number of points removed. A `tolerance` that is negative, NaN or
+    /// infinite leaves the document unchanged.
+    pub fn simplify_polylines(&mut self, tolerance: f64) -> usize {
+        if !(0.0..f64::INFINITY).contains(&tolerance) {
+            return 0;
+        }
+
+        let mut removed = 0;
+        let mut simplify = |points: &mut Points, closed: bool| {
+            let simplified = if closed { simplify_closed(points, tolerance) } else { simplify_open(points, tolerance) };
+            if simplified.len() < points.len() && (!closed || simplified.len() >= 3) {
+                removed += points.len() - simplified.len();
+                *points = simplified.into_iter().collect();
+            }
+        };
+
+        for command in &mut self.draw_commands {
+            match command {
+                DrawCommand::FillPolygon(data) => simplify(&mut data.points, true),
+                DrawCommand::DrawLineLoop(data) => simplify(&mut data.points, true),
+                DrawCommand::DrawLineStrip(data) => simplify(&mut data.points, false),
+                DrawCommand::OutlineFillPolygon(data) => simplify(&mut data.points, true),
+                DrawCommand::FillPath(data) => data.path.segments.iter_mut().for_each(|segment| simplify_segment(segment, &mut simplify)),
+                DrawCommand::DrawLinePath(data) => data.path.segments.iter_mut().for_each(|segment| simplify_segment(segment, &mut simplify)),
+                DrawCommand::OutlineFillPath(data) => data.path.segments.iter_mut().for_each(|segment| simplify_segment(segment, &mut simplify)),
+                DrawCommand::FillRectangles(_) | DrawCommand::OutlineFillRectangles(_) | DrawCommand::DrawLines(_) | DrawCommand::TextHint(_) => {}
+            }
+        }
+
+        removed
+    }
+}
+
+/// The points of a segment made only of straight lines without line widths, and whether
+/// it is closed. A closing point equal to the start is left out.
+fn segment_polyline(segment: &Segment) -> Option<(Points, bool)> {
+    let mut points: Points = [segment.start].into_iter().collect();
+    let mut closed = false;
+    for path_command in &segment.path_commands {
+        let current = points[points.len() - 1];
+        match path_command {
+            PathCommand::Line(point, None) => points.push(*point),
+            PathCommand::HorizontalLine(x, None) => points.push(Point::new(*x, current.y)),
+            PathCommand::VerticalLine(y, None) => points.push(Point::new(current.x, *y)),
+            PathCommand::ClosePath(None) => closed = true,
+            _ => return None,
+        }
+    }
+
+    if closed && points.len() > 1 && same_point(&points[points.len() - 1], &segment.start) {
+        points.pop();
+    }
+    Some((points, closed))
+}
+
+/// Like `segment_polyline`, but also rejects segments with a close path in the middle,
+/// which have no polyline equivalent.
+fn simple_segment_polyline(segment: &Segment) -> Option<(Points, bool)> {
+    let close_count = segment.path_commands.iter().filter(|command| matches!(command, PathCommand::ClosePath(_))).count();
+    if close_count > 1 || (close_count == 1 && !matches!(segment.path_commands.last(), Some(PathCommand::ClosePath(_)))) {
+        return None;
+    }
+    segment_polyline(segment)
+}
+
+fn simplify_segment(segment: &mut Segment, simplify: &mut impl FnMut(&mut Points, bool)) {
+    let Some((mut points, closed)) = simple_segment_polyline(segment) else {
+        return;
+    };
+    let original_length = points.len();
+    simplify(&mut points, closed);
+    if points.len() == original_length {
+        return;
+    }
+
+    let mut path_commands = PathCommands::with_capacity(points.len());
+    for pair in points.windows(2) {
+        path_commands.push(line_command(&pair[0], &pair[1], None));
+    }
+    if closed {
+        path_commands.push(PathCommand::ClosePath(None));
+    }
+    segment.path_commands = path_commands;
+}
+
+/// Prefers the shorter horizontal and vertical line encodings where possible.
+fn line_command(from: &Point, to: &Point, line_width: Option<Unit>) -> PathCommand {
+    if from.y.0 == to.y.0 {
+        PathCommand::HorizontalLine(to.x, line_width)
+    } else if from.x.0 == to.x.0 {
+        PathCommand::VerticalLine(to.y, line_width)
+    } else {
+        PathCommand::Line(*to, line_width)
+    }
+}
+
+fn same_point(a: &Point, b: &Point) -> bool {
+    a.x.0 == b.x.0 && a.y.0 == b.y.0
+}
+
+fn distance_to_chord(start: &Point, end: &Point, point: &Point) -> f64 {
+    segment_distance((start.x.0, start.y.0), (end.x.0, end.y.0), (point.x.0, point.y.0))
+}
+
+/// Ramer–Douglas–Peucker on an open polyline, keeping both end points. Uses an explicit
+/// stack so that very long polylines cannot overflow the call stack.
+fn simplify_open(points: &[Point], tolerance: f64) -> Vec<Point> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+    let mut ranges = vec![(0, points.len() - 1)];
+
+    while let Some((first, last)) = ranges.pop() {
+        let farthest = (first + 1..last)
+            .map(|i| (i, distance_to_chord(&points[first], &points[last], &points[i])))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        if let Some((index, distance)) = farthest
+            && distance > tolerance
+        {
+            keep[index] = true;
+            ranges.push((first, index));
+            ranges.push((index, last));
+        }
+    }
+
+    points.iter().zip(keep).filter(|(_, keep)| *keep).map(|(point, _)| *point).collect()
+}
+
+/// Ramer–Douglas–Peucker on a closed ring that keeps its first point. The ring is split at
+/// the point farthest from the first one and both halves are simplified as open polylines.
+pub(crate) fn simplify_closed(points: &[Point], tolerance: f64) -> Vec<Point> {
+    if points.len() < 4 {
+        return points.to_vec();
+    }
+
+    let start = points[0];
+    let distance = |point: &Point| (point.x.0 - start.x.0).hypot(point.y.0 - start.y.0);
+    let (split, _) = points
+        .iter()
+        .enumerate()
+        .max_by(|a, b| distance(a.1).total_cmp(&distance(b.1)))
+        .unwrap_or((0, &start));
+    if split == 0 {
+        return points.to_vec();
+    }
+
+    let mut second_half = points[split..].to_vec();
+    second_half.push(start);
+
+    let mut simplified = simplify_open(&points[..=split], tolerance);
+    let second_half = simplify_open(&second_half, tolerance);
+    simplified.extend_from_slice(&second_half[1..second_half.len() - 1]);
+    simplified
+}
+
+/// The smallest number of consecutive lines that are considered for curve fitting.
+const MIN_FIT_LINES: usize = 3;
+
+/// Turning angles above this, in radians, are kept as corners when fitting curves.
+const FIT_CORNER_ANGLE: f64 = std::f64::consts::FRAC_PI_3;
+
+/// The number of Newton-Raphson passes that refine the curve parameters of a fit.
+const FIT_REPARAMETERIZE_PASSES: usize = 4;
+
+type Vector = (f64, f64);
+
+impl TinyVg {
+    /// Replaces runs of straight lines in paths, such as sampled curves exported from CAD
+    /// or plotting tools, with cubic Bézier curves that pass within `tolerance` of every
+    /// original point. Runs are split at sharp corners, which stay corners, and are only
+    /// replaced if the curves take fewer commands. Lines with per-command line widths are
+    /// left alone.
+    ///
+    /// Returns the number of path commands removed.
+    pub fn fit_curves(&mut self, tolerance: f64) -> usize {
+        let mut removed = 0;
+        for command in &mut self.draw_commands {
+            let path = match command {
+                DrawCommand::FillPath(data) => &mut data.path,
+                DrawCommand::DrawLinePath(data) => &mut data.path,
+                DrawCommand::OutlineFillPath(data) => &mut data.path,
+                _ => continue,
+            };
+            for segment in &mut path.segments {
+                let original_length = segment.path_commands.len();
+                fit_segment(segment, tolerance);
+                removed += original_length - segment.path_commands.len();
+            }
+        }
+
+        removed
+    }
+}
+
+fn fit_segment(segment: &mut Segment, tolerance: f64) {
+    let mut path_commands = PathCommands::with_capacity(segment.path_commands.len());
+    let mut run = PathCommands::new();
+    let mut run_start = segment.start;
+    let mut current = segment.start;
+
+    for path_command in segment.path_commands.drain(..) {
+        let end = match &path_command {
+            PathCommand::Line(point, None) => Some(*point),
+            PathCommand::HorizontalLine(x, None) => Some(Point::new(*x, current.y)),
+            PathCommand::VerticalLine(y, None) => Some(Point::new(current.x, *y)),
+            _ => None,
+        };
+
+        match end {
+            Some(end) => {
+                if run.is_empty() {
+                    run_start = current;
+                }
+                run.push(path_command);
+                current = end;
+            }
+            None => {
+                flush_run(&mut run, run_start, tolerance, &mut path_commands);
+                current = match &path_command {
+                    PathCommand::Line(point, _) => *point,
+                    PathCommand::HorizontalLine(x, _) => Point::new(*x, current.y),
+                    PathCommand::VerticalLine(y, _) => Point::new(current.x, *y),
+                    PathCommand::CubicBezier(cubic, _) => cubic.point_1,
+                    PathCommand::QuadraticBezier(quadratic, _) => quadratic.point_1,
+                    PathCommand::ArcCircle(arc, _) => arc.target,
+                    PathCommand::ArcEllipse(arc, _) => arc.target,
+                    PathCommand::ClosePath(_) => segment.start,
+                };
+                path_commands.push(path_command);
+            }
+        }
+    }
+    flush_run(&mut run, run_start, tolerance, &mut path_commands);
+
+    segment.path_commands = path_commands;
+}
+
+/// Moves the run of lines into `path_commands`, refitted as curves where that is shorter.
+fn flush_run(run: &mut PathCommands, start: Point, tolerance: f64, path_commands: &mut PathCommands) {
+    if run.len() < MIN_FIT_LINES {
+        path_commands.append(run);
+        return;
+    }
+
+    let mut points: Vec<Vector> = vec![(start.x.0, start.y.0)];
+    for path_command in run.iter() {
+        let &(x, y) = points.last().unwrap();
+        points.push(match path_command {
+            PathCommand::Line(point, _) => (point.x.0, point.y.0),
+            PathCommand::HorizontalLine(new_x, _) => (new_x.0, y),
+            PathCommand::VerticalLine(new_y, _) => (x, new_y.0),
+            _ => unreachable!("runs only contain lines"),
+        });
+    }
+    points.dedup();
+    if points.len() < 2 {
+        path_commands.append(run);
+        return;
+    }
+
+    // Split at corners, and fit the pieces between them.
+    let mut corners = vec![0];
+    for i in 1..points.len() - 1 {
+        let incoming = sub(points[i], points[i - 1]);
+        let outgoing = sub(points[i + 1], points[i]);
+        let angle = cross(incoming, outgoing).atan2(dot(incoming, outgoing)).abs();
+        if angle > FIT_CORNER_ANGLE {
+            corners.push(i);
+        }
+    }
+    corners.push(points.len() - 1);
+
+    let mut fitted = PathCommands::new();
+    for pair in corners.windows(2) {
+        let piece = &points[pair[0]..=pair[1]];
+        if piece.len() <= MIN_FIT_LINES {
+            fitted.extend(piece[1..].iter().map(|&(x, y)| PathCommand::Line(vector_point((x, y)), None)));
+            continue;
+        }
+        for [_, control_0, control_1, end] in fit_cubics(piece, tolerance) {
+            fitted.push(PathCommand::CubicBezier(
+                CubicBezier {
+                    control_point_0: vector_point(control_0),
+                    control_point_1: vector_point(control_1),
+                    point_1: vector_point(end),
+                },
+                None,
+            ));
+        }
+    }
+
+    if fitted.len() < run.len() {
+        path_commands.append(&mut fitted);
+        run.clear();
+    } else {
+        path_commands.append(run);
+    }
+}
+
+fn vector_point((x, y): Vector) -> Point {
+    Point::new(Unit(x), Unit(y))
+}
+
+fn sub(a: Vector, b: Vector) -> Vector {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn add(a: Vector, b: Vector) -> Vector {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+fn scale(a: Vector, factor: f64) -> Vector {
+    (a.0 * factor, a.1 * factor)
+}
+
+fn dot(a: Vector, b: Vector) -> f64 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+fn cross(a: Vector, b: Vector) -> f64 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+fn normalize(a: Vector) -> Vector {
+    let length = a.0.hypot(a.1);
+    if length == 0.0 { a } else { scale(a, 1.0 / length) }
+}
+
+fn distance(a: Vector, b: Vector) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+fn bezier_point(curve: &[Vector; 4], t: f64) -> Vector {
+    let mt = 1.0 - t;
+    let weights = [mt * mt * mt, 3.0 * mt * mt * t, 3.0 * mt * t * t, t * t * t];
+    curve.iter().zip(weights).fold((0.0, 0.0), |sum, (point, weight)| add(sum, scale(*point, weight)))
+}
+
+/// Fits cubic curves through the points with Schneider's algorithm ("An Algorithm for
+/// Automatically Fitting Digitized Curves", Graphics Gems, 1990). The pieces are worked
+/// off with an explicit stack, left to right.
+fn fit_cubics(points: &[Vector], tolerance: f64) -> Vec<[Vector; 4]> {
+    let last = points.len() - 1;
+    let mut curves = Vec::new();
+    let mut pieces = vec![(0, last, normalize(sub(points[1], points[0])), normalize(sub(points[last - 1], points[last])))];
+
+    while let Some((first, last, left_tangent, right_tangent)) = pieces.pop() {
+        let piece = &points[first..=last];
+        if piece.len() == 2 {
+            let third = distance(piece[0], piece[1]) / 3.0;
+            curves.push([piece[0], add(piece[0], scale(left_tangent, third)), add(piece[1], scale(right_tangent, third)), piece[1]]);
+            continue;
+        }
+
+        let mut parameters = chord_length_parameters(piece);
+        let mut curve = generate_bezier(piece, &parameters, left_tangent, right_tangent);
+        let (mut error, mut split) = max_error(piece, &curve, &parameters);
+
+        if error > tolerance && error < 4.0 * tolerance {
+            for _ in 0..FIT_REPARAMETERIZE_PASSES {
+                parameters = reparameterize(piece, &curve, &parameters);
+                curve = generate_bezier(piece, &parameters, left_tangent, right_tangent);
+                (error, split) = max_error(piece, &curve, &parameters);
+                if error <= tolerance {
+                    break;
+                }
+            }
+        }
+
+        if error <= tolerance {
+            curves.push(curve);
+            continue;
+        }
+
+        let split = split.clamp(1, piece.len() - 2);
+        let center_tangent = normalize(sub(piece[split - 1], piece[split + 1]));
+        // Pushed in reverse so the left piece is fitted first.
+        pieces.push((first + split, last, scale(center_tangent, -1.0), right_tangent));
+        pieces.push((first, first + split, left_tangent, center_tangent));
+    }
+
+    curves
+}
+
+fn chord_length_parameters(points: &[Vector]) -> Vec<f64> {
+    let mut parameters = vec![0.0];
+    for pair in points.windows(2) {
+        parameters.push(parameters[parameters.len() - 1] + distance(pair[0], pair[1]));
+    }
+    let total = parameters[parameters.len() - 1];
+    if total > 0.0 {
+        parameters.iter_mut().for_each(|parameter| *parameter /= total);
+    }
+    parameters
+}
+
+/// The least-squares curve with the given end tangents.
+fn generate_bezier(points: &[Vector], parameters: &[f64], left_tangent: Vector, right_tangent: Vector) -> [Vector; 4] {
+    let first = points[0];
+    let last = points[points.len() - 1];
+
+    let mut c = [[0.0; 2]; 2];
+    let mut x = [0.0; 2];
+    for (point, &t) in points.iter().zip(parameters) {
+        let mt = 1.0 - t;
+        let (b0, b1, b2, b3) = (mt * mt * mt, 3.0 * mt * mt * t, 3.0 * mt * t * t, t * t * t);
+        let a0 = scale(left_tangent, b1);
+        let a1 = scale(right_tangent, b2);
+        c[0][0] += dot(a0, a0);
+        c[0][1] += dot(a0, a1);
+        c[1][1] += dot(a1, a1);
+        let rest = sub(*point, add(scale(first, b0 + b1), scale(last, b2 + b3)));
+        x[0] += dot(a0, rest);
+        x[1] += dot(a1, rest);
+    }
+    c[1][0] = c[0][1];
+
+    let determinant = c[0][0] * c[1][1] - c[1][0] * c[0][1];
+    let segment_length = distance(first, last);
+    let (mut alpha_left, mut alpha_right) = if determinant.abs() > f64::EPSILON {
+        ((x[0] * c[1][1] - x[1] * c[0][1]) / determinant, (c[0][0] * x[1] - c[1][0] * x[0]) / determinant)
+    } else {
+        (0.0, 0.0)
+    };
+
+    // Fall back to the Wu-Barsky heuristic when the solution is degenerate.
+    let epsilon = 1e-6 * segment_length;
+    if alpha_left < epsilon || alpha_right < epsilon {
+        alpha_left = segment_length / 3.0;
+        alpha_right = segment_length / 3.0;
+    }
+
+    [first, add(first, scale(left_tangent, alpha_left)), add(last, scale(right_tangent, alpha_right)), last]
+}
+
+/// The largest distance between a point and its position on the curve, and its index.
+fn max_error(points: &[Vector], curve: &[Vector; 4], parameters: &[f64]) -> (f64, usize) {
+    let mut worst = (0.0, points.len() / 2);
+    for (i, (point, &t)) in points.iter().zip(parameters).enumerate().skip(1).take(points.len().saturating_sub(2)) {
+        let error = distance(bezier_point(curve, t), *point);
+        if error > worst.0 {
+            worst = (error, i);
+        }
+    }
+    worst
+}
+
+/// Moves each parameter closer to the point on the curve nearest to its point with a
+/// Newton-Raphson step.
+fn reparameterize(points: &[Vector], curve: &[Vector; 4], parameters: &[f64]) -> Vec<f64> {
+    let derivative: [Vector; 3] = [
+        scale(sub(curve[1], curve[0]), 3.0),
+        scale(sub(curve[2], curve[1]), 3.0),
+        scale(sub(curve[3], curve[2]), 3.0),
+    ];
+    let second_derivative: [Vector; 2] = [scale(sub(derivative[1], derivative[0]), 2.0), scale(sub(derivative[2], derivative[1]), 2.0)];
+
+    points
+        .iter()
+        .zip(parameters)
+        .map(|(point, &t)| {
+            let mt = 1.0 - t;
+            let difference = sub(bezier_point(curve, t), *point);
+            let first = add(add(scale(derivative[0], mt * mt), scale(derivative[1], 2.0 * mt * t)), scale(derivative[2], t * t));
+            let second = add(scale(second_derivative[0], mt), scale(second_derivative[1], t));
+            let denominator = dot(first, first) + dot(difference, second);
+            if denominator.abs() < f64::EPSILON {
+                t
+            } else {
+                (t - dot(difference, first) / denominator).clamp(0.0, 1.0)
+            }
+        })
+        .collect()
+}
+
+impl TinyVg {
+    /// Merges adjacent fill commands that share an identical style into single commands:
+    /// rectangle lists are concatenated, and paths absorb neighbouring paths and polygons as
+    /// extra segments. Adjacent polygons are left apart, since a polygon costs one byte
+    /// less per point than the same outline in a path.
+    ///
+    /// Commands are only merged if their bounds do not overlap, because overlapping areas
+    /// would otherwise cancel out under the even-odd rule or lose the double blending of
+    /// translucent colors.
+    ///
+    /// Returns the number of commands removed.
+    pub fn merge_commands(&mut self) -> usize {
+        let original_count = self.draw_commands.len();
+        let mut merged: Vec<(DrawCommand, Option<Bounds>)> = Vec::with_capacity(original_count);
+
+        for command in self.draw_commands.drain(..) {
+            let bounds = command_bounds(&command);
+            if let Some((previous, previous_bounds)) = merged.last_mut()
+                && let (Some(a), Some(b)) = (*previous_bounds, bounds)
+                && !overlaps(a, b)
+                && try_merge(previous, &command)
+            {
+                *previous_bounds = Some(union(a, b));
+                continue;
+            }
+            merged.push((command, bounds));
+        }
+
+        self.draw_commands = merged.into_iter().map(|(command, _)| command).collect();
+        original_count - self.draw_commands.len()
+    }
+}
+
+fn overlaps(a: Bounds, b: Bounds) -> bool {
+    a.0 < b.2 && b.0 < a.2 && a.1 < b.3 && b.1 < a.3
+}
+
+/// Appends `next` to `previous` if both are fills with the same style that can share one
+/// command.
+fn try_merge(previous: &mut DrawCommand, next: &DrawCommand) -> bool {
+    match (previous, next) {
+        (DrawCommand::FillRectangles(previous), DrawCommand::FillRectangles(next)) if previous.style == next.style => {
+            previous.rectangles.extend(next.rectangles.iter().cloned());
+            true
+        }
+        (DrawCommand::FillPath(previous), DrawCommand::FillPath(next)) if previous.style == next.style => {
+            previous.path.segments.extend(next.path.segments.iter().cloned());
+            true
+        }
+        (DrawCommand::FillPath(previous), DrawCommand::FillPolygon(next)) if previous.style == next.style && !next.points.is_empty() => {
+            previous.path.segments.push(polygon_segment(&next.points));
+            true
+        }
+        (previous, DrawCommand::FillPath(next)) => {
+            let DrawCommand::FillPolygon(polygon) = previous else { return false };
+            if polygon.style != next.style || polygon.points.is_empty() {
+                return false;
+            }
+            let mut segments = vec![polygon_segment(&polygon.points)];
+            segments.extend(next.path.segments.iter().cloned());
+            *previous = DrawCommand::FillPath(FillPathData { style: next.style.clone(), path: Path { segments } });
+            true
+        }
+        _ => false,
+    }
+}
+
+fn polygon_segment(points: &[Point]) -> Segment {
+    let mut path_commands: PathCommands = points[1..].iter().map(|point| PathCommand::Line(*point, None)).collect();
+    path_commands.push(PathCommand::ClosePath(None));
+    Segment { start: points[0], path_commands }
+}
+
+impl TinyVg {
+    /// Re-encodes fill and outline-fill paths that consist only of straight lines as the
+    /// more compact rectangle or polygon commands. A path becomes a rectangle list if every
+    /// segment is an axis-aligned rectangle and no two of them overlap, since rectangle
+    /// lists are filled as a union while paths use the even-odd rule. Otherwise a path
+    /// with a single segment becomes a polygon. Outline-fill paths are only converted if
+    /// all of their segments are closed, because polygon and rectangle outlines always are.
+    ///
+    /// Returns the number of commands re-encoded.
+    pub fn detect_shapes(&mut self) -> usize {
+        let mut converted = 0;
+
+        for command in &mut self.draw_commands {
+            let replacement = match command {
+                DrawCommand::FillPath(data) => match path_shape(&data.path, false) {
+                    Some(Shape::Rectangles(rectangles)) => DrawCommand::FillRectangles(FillRectanglesData { style: data.style.clone(), rectangles }),
+                    Some(Shape::Polygon(points)) => DrawCommand::FillPolygon(FillPolygonData { style: data.style.clone(), points }),
+                    None => continue,
+                },
+                DrawCommand::OutlineFillPath(data) => match path_shape(&data.path, true) {
+                    Some(Shape::Rectangles(rectangles)) => DrawCommand::OutlineFillRectangles(OutlineFillRectanglesData {
+                        fill_style: data.fill_style.clone(),
+                        line_style: data.line_style.clone(),
+                        line_width: data.line_width,
+                        rectangles,
+                    }),
+                    Some(Shape::Polygon(points)) => DrawCommand::OutlineFillPolygon(OutlineFillPolygonData {
+                        fill_style: data.fill_style.clone(),
+                        line_style: data.line_style.clone(),
+                        line_width: data.line_width,
+                        points,
+                    }),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            *command = replacement;
+            converted += 1;
+        }
+
+        converted
+    }
+}
+
+enum Shape {
+    Rectangles(Vec<Rectangle>),
+    Polygon(Points),
+}
+
+fn path_shape(path: &Path, require_closed: bool) -> Option<Shape> {
+    let mut polygons = Vec::with_capacity(path.segments.len());
+    for segment in &path.segments {
+        let (points, closed) = simple_segment_polyline(segment)?;
+        if require_closed && !closed {
+            return None;
+        }
+        polygons.push(points);
+    }
+
+    let rectangles: Option<Vec<Rectangle>> = polygons.iter().map(|points| polygon_rectangle(points)).collect();
+    if let Some(rectangles) = rectangles
+        && !rectangles.is_empty()
+        && !rectangles.iter().enumerate().any(|(i, a)| rectangles[i + 1..].iter().any(|b| rectangles_overlap(a, b)))
+    {
+        return Some(Shape::Rectangles(rectangles));
+    }
+
+    match <[Points; 1]>::try_from(polygons) {
+        Ok([points]) if points.len() >= 3 => Some(Shape::Polygon(points)),
+        _ => None,
+    }
+}
+
+/// The rectangle outlined by four corners in order, starting at any corner.
+fn polygon_rectangle(points: &[Point]) -> Option<Rectangle> {
+    let [p0, p1, p2, p3] = points else {
+        return None;
+    };
+    let horizontal_first = p0.y.0 == p1.y.0 && p1.x.0 == p2.x.0 && p2.y.0 == p3.y.0 && p3.x.0 == p0.x.0;
+    let vertical_first = p0.x.0 == p1.x.0 && p1.y.0 == p2.y.0 && p2.x.0 == p3.x.0 && p3.y.0 == p0.y.0;
+    if !horizontal_first && !vertical_first {
+        return None;
+    }
+
+    let (x, y) = (p0.x.0.min(p2.x.0), p0.y.0.min(p2.y.0));
+    let (width, height) = ((p0.x.0 - p2.x.0).abs(), (p0.y.0 - p2.y.0).abs());
+    if width == 0.0 || height == 0.0 {
+        return None;
+    }
+    Some(Rectangle { x: Unit(x), y: Unit(y), width: Unit(width), height: Unit(height) })
+}
+
+fn rectangles_overlap(a: &Rectangle, b: &Rectangle) -> bool {
+    overlaps((a.x.0, a.y.0, a.x.0 + a.width.0, a.y.0 + a.height.0), (b.x.0, b.y.0, b.x.0 + b.width.0, b.y.0 + b.height.0))
+}
+
+impl TinyVg {
+    /// Rewrites the document into a canonical form, so that documents drawing the same
+    /// shapes encode to identical bytes no matter how they were produced:
+    ///
+    /// - every unit is rounded to the grid of the header's scale, with negative zero
+    ///   replaced by zero,
+    /// - path lines use the horizontal or vertical line commands wherever possible, and
+    ///   elliptic arcs with equal radii become circle arcs,
+    /// - duplicate colors are merged, unused colors removed and the remaining colors
+    ///   ordered by their first use.
+    pub fn canonicalize(&mut self) {
+        let factor = (1u32 << self.header.scale.min(15)) as f64;
+        for command in &mut self.draw_commands {
+            for_each_unit_mut(command, &mut |unit| unit.0 = (unit.0 * factor).round() / factor + 0.0);
+            match command {
+                DrawCommand::FillPath(data) => canonicalize_path(&mut data.path),
+                DrawCommand::DrawLinePath(data) => canonicalize_path(&mut data.path),
+                DrawCommand::OutlineFillPath(data) => canonicalize_path(&mut data.path),
+                _ => {}
+            }
+        }
+
+        self.dedup_colors(0.0);
+        let order = colors_by_first_use(self);
+        reorder_colors(self, &order);
+    }
+}
+
+impl TinyVg {
+    /// Reorders the color table so that the most referenced colors get the smallest
+    /// indices, which shortens their variable sized indices in documents with more than
+    /// 128 colors. Colors referenced equally often keep their order, and unused colors
+    /// move to the end.
+    ///
+    /// Returns the number of bytes saved.
+    pub fn sort_colors_by_frequency(&mut self) -> usize {
+        let mut references = vec![0usize; self.color_table.len()];
+        for command in &self.draw_commands {
+            for_each_color_index(command, &mut |index| {
+                if let Some(count) = references.get_mut(index as usize) {
+                    *count += 1;
+                }
+            });
+        }
+
+        let mut order: Vec<usize> = (0..self.color_table.len()).collect();
+        order.sort_by_key(|&index| std::cmp::Reverse(references[index]));
+
+        let index_bytes = |order: &[usize]| -> usize {
+            order.iter().enumerate().map(|(new_index, &old_index)| references[old_index] * varuint_size(new_index as u64)).sum()
+        };
+        let before = index_bytes(&(0..order.len()).collect::<Vec<_>>());
+        let after = index_bytes(&order);
+
+        reorder_colors(self, &order);
+        before - after
+    }
+}
+
+fn canonicalize_path(path: &mut Path) {
+    for segment in &mut path.segments {
+        let mut current = segment.start;
+        for path_command in &mut segment.path_commands {
+            match *path_command {
+                PathCommand::Line(point, line_width) => {
+                    *path_command = line_command(&current, &point, line_width);
+                    current = point;
+                }
+                PathCommand::HorizontalLine(x, line_width) => {
+                    let point = Point::new(x, current.y);
+                    *path_command = line_command(&current, &point, line_width);
+                    current = point;
+                }
+                PathCommand::VerticalLine(y, line_width) => {
+                    let point = Point::new(current.x, y);
+                    *path_command = line_command(&current, &point, line_width);
+                    current = point;
+                }
+                PathCommand::CubicBezier(ref cubic, _) => current = cubic.point_1,
+                PathCommand::QuadraticBezier(ref quadratic, _) => current = quadratic.point_1,
+                PathCommand::ArcCircle(ref arc, _) => current = arc.target,
+                PathCommand::ArcEllipse(ref arc, line_width) => {
+                    current = arc.target;
+                    if arc.radius_x.0 == arc.radius_y.0 {
+                        *path_command = PathCommand::ArcCircle(
+                            ArcCircle { large_arc: arc.large_arc, sweep: arc.sweep, radius: arc.radius_x, target: arc.target },
+                            line_width,
+                        );
+                    }
+                }
+                PathCommand::ClosePath(_) => current = segment.start,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color_table::RgbaF32;
+    use crate::commands::{
+        DrawCommand, DrawLineStripData, FillPathData, FillPolygonData, FillRectanglesData, FlatColored, Path, PathCommand, Point, Points,
+        Rectangle, Style,
+    };
+    use crate::common::Unit;
+    use crate::fit::FitMode;
+    use crate::TinyVg;
+
+    /// A 16 by 16 document with one color and one rectangle.
+    const DOCUMENT: [u8; 25] = [0x72, 0x56, 1, 0x00, 16, 0, 16, 0, 1, 255, 0, 0, 255, 0x02, 0, 0, 0, 0, 0, 0, 16, 0, 16, 0, 0x00];
+
+    /// The test document with its rectangle replaced by `commands`.
+    fn document(commands: Vec<DrawCommand>) -> TinyVg {
+        let mut document = TinyVg::from_bytes(&DOCUMENT).unwrap();
+        document.draw_commands = commands;
+        document
+    }
+
+    fn flat(color_index: u64) -> Style {
+        Style::FlatColor(FlatColored { color_index })
+    }
+
+    fn points(points: &[(f64, f64)]) -> Points {
+        points.iter().map(|&(x, y)| Point::new(Unit(x), Unit(y))).collect()
+    }
+
+    fn line_strip(points: &[(f64, f64)]) -> DrawCommand {
+        DrawCommand::DrawLineStrip(DrawLineStripData { style: flat(0), line_width: Unit(1.0), points: self::points(points) })
+    }
+
+    fn polygon(points: &[(f64, f64)]) -> DrawCommand {
+        DrawCommand::FillPolygon(FillPolygonData { style: flat(0), points: self::points(points) })
+    }
+
+    fn rectangles(color_index: u64, rectangles: &[(f64, f64, f64, f64)]) -> DrawCommand {
+        DrawCommand::FillRectangles(FillRectanglesData {
+            style: flat(color_index),
+            rectangles: rectangles
+                .iter()
+                .map(|&(x, y, width, height)| Rectangle { x: Unit(x), y: Unit(y), width: Unit(width), height: Unit(height) })
+                .collect(),
+        })
+    }
+
+    fn fill_path(data: &str) -> DrawCommand {
+        DrawCommand::FillPath(FillPathData { style: flat(0), path: Path::from_svg_d(data).unwrap() })
+    }
+
+    /// Checks that the optimized document is valid and renders like the original, with no
+    /// channel differing by more than `max_difference`.
+    fn assert_renders_alike(original: &TinyVg, optimized: &TinyVg, max_difference: u8) {
+        assert_eq!(optimized.validate(), Ok(()));
+        let render = |document: &TinyVg| document.render_rgba((32, 32), FitMode::Stretch).data;
+        let difference = render(original).iter().zip(render(optimized)).map(|(a, b)| a.abs_diff(b)).max();
+        assert!(difference <= Some(max_difference), "{difference:?}");
+    }
+
+    #[test]
+    fn simplify_removes_points_within_the_tolerance() {
+        let original = document(vec![
+            line_strip(&[(0.0, 0.0), (4.0, 0.05), (8.0, 0.0), (8.0, 8.0)]),
+            polygon(&[(2.0, 2.0), (6.0, 2.02), (10.0, 2.0), (10.0, 10.0), (2.0, 10.0)]),
+        ]);
+
+        let mut simplified = original.clone();
+        assert_eq!(simplified.simplify_polylines(0.01), 0);
+        assert_eq!(simplified.simplify_polylines(0.1), 2);
+        assert_eq!(simplified.draw_commands[0], line_strip(&[(0.0, 0.0), (8.0, 0.0), (8.0, 8.0)]));
+        assert_eq!(simplified.draw_commands[1], polygon(&[(2.0, 2.0), (10.0, 2.0), (10.0, 10.0), (2.0, 10.0)]));
+        assert_renders_alike(&original, &simplified, 8);
+    }
+
+    #[test]
+    fn simplified_polygons_keep_three_points() {
+        let mut document = document(vec![polygon(&[(0.0, 0.0), (8.0, 0.01), (16.0, 0.0), (8.0, 0.02)])]);
+        let original = document.clone();
+        assert_eq!(document.simplify_polylines(1.0), 0);
+        assert_eq!(document, original);
+    }
+
+    #[test]
+    fn invalid_simplify_tolerances_are_rejected() {
+        let mut document = TinyVg::from_bytes(&DOCUMENT).unwrap();
+        document.draw_commands = vec![line_strip(&[(0.0, 0.0), (4.0, 0.1), (8.0, 0.0), (8.0, 8.0)])];
+        let original = document.clone();
+
+        for tolerance in [f64::NAN, -1.0, f64::INFINITY] {
+            assert_eq!(document.simplify_polylines(tolerance), 0, "{tolerance}");
+            assert_eq!(document, original);
+        }
+    }
+
+    #[test]
+    fn sampled_curves_are_fitted() {
+        // A half disc whose arc is sampled with 32 lines, followed by a sharp corner.
+        let mut data = String::from("M2 8");
+        for i in 1..=32 {
+            let angle = std::f64::consts::PI * (1.0 - i as f64 / 32.0);
+            data += &format!(" L{} {}", 8.0 + 6.0 * angle.cos(), 8.0 - 6.0 * angle.sin());
+        }
+        data += " L8 8 Z";
+        let original = document(vec![fill_path(&data)]);
+
+        let mut fitted = original.clone();
+        let removed = fitted.fit_curves(0.05);
+        let DrawCommand::FillPath(fill) = &fitted.draw_commands[0] else {
+            panic!("{:?}", fitted.draw_commands[0]);
+        };
+        let path_commands = &fill.path.segments[0].path_commands;
+        assert_eq!(removed, 34 - path_commands.len());
+        assert!(path_commands.len() < 10, "{path_commands:?}");
+        assert!(path_commands.iter().any(|command| matches!(command, PathCommand::CubicBezier(..))));
+        assert_eq!(path_commands[path_commands.len() - 2], PathCommand::Line(Point::new(Unit(8.0), Unit(8.0)), None));
+        // Moving an edge by the tolerance can flip a row of four coverage samples in a pixel.
+        assert_renders_alike(&original, &fitted, 64);
+    }
+
+    #[test]
+    fn short_line_runs_are_not_fitted() {
+        let mut document = document(vec![fill_path("M0 0 L8 0 L8 8 L0 8 Z")]);
+        let original = document.clone();
+        assert_eq!(document.fit_curves(0.05), 0);
+        assert_eq!(document, original);
+    }
+
+    #[test]
+    fn adjacent_fills_with_the_same_style_are_merged() {
+        let mut original = document(vec![
+            rectangles(0, &[(0.0, 0.0, 4.0, 4.0)]),
+            rectangles(0, &[(8.0, 0.0, 4.0, 4.0)]),
+            // Overlaps the merged rectangles, so it stays apart.
+            rectangles(0, &[(2.0, 2.0, 4.0, 4.0)]),
+            rectangles(1, &[(0.0, 8.0, 4.0, 4.0)]),
+            polygon(&[(8.0, 8.0), (12.0, 8.0), (10.0, 12.0)]),
+            fill_path("M12 12 L16 12 L16 16 Z"),
+        ]);
+        original.color_table.push(RgbaF32(0.0, 0.0, 1.0, 0.5));
+
+        let mut merged = original.clone();
+        assert_eq!(merged.merge_commands(), 2);
+        assert_eq!(merged.draw_commands[0], rectangles(0, &[(0.0, 0.0, 4.0, 4.0), (8.0, 0.0, 4.0, 4.0)]));
+        assert_eq!(merged.draw_commands[3], fill_path("M8 8 L12 8 L10 12 Z M12 12 L16 12 L16 16 Z"));
+        assert_renders_alike(&original, &merged, 0);
+    }
+
+    #[test]
+    fn line_paths_become_rectangles_and_polygons() {
+        let original = document(vec![
+            fill_path("M0 0 H4 V4 H0 Z M8 8 L8 12 L12 12 L12 8 Z"),
+            fill_path("M0 8 L4 8 L2 12 Z"),
+            // Overlapping rectangles cancel out under the even-odd rule, so they stay a path.
+            fill_path("M8 0 H12 V4 H8 Z M10 2 H14 V6 H10 Z"),
+            fill_path("M12 12 Q14 12 14 14 Z"),
+        ]);
+
+        let mut detected = original.clone();
+        assert_eq!(detected.detect_shapes(), 2);
+        assert_eq!(detected.draw_commands[0], rectangles(0, &[(0.0, 0.0, 4.0, 4.0), (8.0, 8.0, 4.0, 4.0)]));
+        assert_eq!(detected.draw_commands[1], polygon(&[(0.0, 8.0), (4.0, 8.0), (2.0, 12.0)]));
+        assert_eq!(detected.draw_commands[2..], original.draw_commands[2..]);
+        assert_renders_alike(&original, &detected, 0);
+    }
+
+    #[test]
+    fn equivalent_documents_canonicalize_to_the_same_bytes() {
+        let red = RgbaF32(1.0, 0.0, 0.0, 1.0);
+        let mut a = document(vec![
+            DrawCommand::FillPath(FillPathData { style: flat(2), path: Path::from_svg_d("M0 0 L8 0 L8 8 Z").unwrap() }),
+            line_strip(&[(-0.0, 1.0000001), (4.0, 4.0)]),
+        ]);
+        a.color_table = vec![red, RgbaF32(0.0, 0.0, 1.0, 1.0), red];
+        let mut b = document(vec![fill_path("M0 0 H8 V8 Z"), line_strip(&[(0.0, 1.0), (4.0, 4.0)])]);
+
+        a.canonicalize();
+        b.canonicalize();
+        assert_eq!(a.color_table, [red]);
+        assert_eq!(a.to_bytes().unwrap(), b.to_bytes().unwrap());
+        assert_eq!(a.validate(), Ok(()));
+    }
+
+    #[test]
+    fn frequent_colors_get_the_short_indices() {
+        let mut original = document([129, 129, 0, 129, 128, 129, 129].iter().map(|&index| rectangles(index, &[(0.0, 0.0, 4.0, 4.0)])).collect());
+        original.color_table = (0..130).map(|i| RgbaF32(i as f32 / 255.0, 0.5, 0.5, 1.0)).collect();
+        original.header.color_count = 130;
+
+        let mut sorted = original.clone();
+        let saved = sorted.sort_colors_by_frequency();
+        assert_eq!(saved, 6);
+        assert_eq!(original.to_bytes().unwrap().len() - sorted.to_bytes().unwrap().len(), saved);
+        assert_eq!(sorted.color_table[..3], [original.color_table[129], original.color_table[0], original.color_table[128]]);
+        assert_eq!(sorted.color_table.len(), 130);
+        assert_renders_alike(&original, &sorted, 0);
+    }
+}
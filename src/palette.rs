@@ -0,0 +1,502 @@
+use crate::color::{ciede2000, oklab_to_srgb_in_gamut, srgb_to_lab, srgb_to_oklab, Lab};
+use crate::color_table::{ColorSpace, ColorTable, RgbaF32};
+use crate::commands::{DrawCommand, Style};
+use crate::geometry::{command_outline, signed_area, Polyline};
+use crate::TinyVg;
+
+impl TinyVg {
+    /// Merges color table entries whose channels all differ by at most `epsilon` (on the
+    /// 0 to 1 scale) from an earlier entry, remaps the color indices of every style to the
+    /// remaining entries and shrinks the table. An `epsilon` of 0 only merges exact duplicates.
+    ///
+    /// Returns the number of colors removed.
+    pub fn dedup_colors(&mut self, epsilon: f32) -> usize {
+        let mut kept: Vec<RgbaF32> = Vec::with_capacity(self.color_table.len());
+        let remap: Vec<u64> = self
+            .color_table
+            .iter()
+            .map(|color| match kept.iter().position(|other| colors_within(color, other, epsilon)) {
+                Some(index) => index as u64,
+                None => {
+                    kept.push(*color);
+                    (kept.len() - 1) as u64
+                }
+            })
+            .collect();
+
+        let removed = self.color_table.len() - kept.len();
+        if removed == 0 {
+            return 0;
+        }
+
+        for command in &mut self.draw_commands {
+            for_each_color_index_mut(command, &mut |index| {
+                // Indices past the end of the table are invalid either way and are left alone.
+                if let Some(new_index) = remap.get(*index as usize) {
+                    *index = *new_index;
+                }
+            });
+        }
+        self.color_table = kept;
+        self.header.color_count = self.color_table.len() as u64;
+        removed
+    }
+}
+
+impl TinyVg {
+    /// Replaces every color table entry with the result of `f`, which restyles every
+    /// command using it, as for theme variants or tinted icon states.
+    pub fn recolor(&mut self, f: &dyn Fn(RgbaF32) -> RgbaF32) {
+        for color in &mut self.color_table {
+            *color = f(*color);
+        }
+    }
+
+    /// Replaces every color table entry whose channels all differ by at most `epsilon`
+    /// from `old` with `new`. Returns the number of entries replaced.
+    pub fn replace_color(&mut self, old: RgbaF32, new: RgbaF32, epsilon: f32) -> usize {
+        let mut replaced = 0;
+        for color in &mut self.color_table {
+            if colors_within(color, &old, epsilon) {
+                *color = new;
+                replaced += 1;
+            }
+        }
+        replaced
+    }
+
+    /// Inverts the perceptual lightness of every color while keeping its hue and alpha,
+    /// which turns monochrome and duotone icons into dark-mode variants. Lightness is
+    /// mirrored in the Oklch color space, and colors that leave the sRGB gamut lose
+    /// chroma until they fit.
+    pub fn invert_lightness(&mut self) {
+        for color in &mut self.color_table {
+            let (lightness, a, b) = srgb_to_oklab(*color);
+            *color = oklab_to_srgb_in_gamut((1.0 - lightness, a, b), color.3);
+        }
+    }
+
+    /// Turns every color into the gray of the same relative luminance, keeping its alpha,
+    /// as for disabled states of icons.
+    pub fn to_grayscale(&mut self) {
+        self.to_monochrome(RgbaF32(1.0, 1.0, 1.0, 1.0));
+    }
+
+    /// Turns every color into a shade of `color` with the same relative luminance, keeping
+    /// its alpha, as for tinted stencil variants of colored icons. Colors darker than
+    /// `color` become a darker `color`, and lighter ones are mixed with white. `color` is
+    /// given in the color space of the document's color encoding and its alpha is ignored.
+    pub fn to_monochrome(&mut self, color: RgbaF32) {
+        let color_space = self.header.color_encoding.color_space();
+        let tint = color.convert(color_space, ColorSpace::Linear);
+        let tint_luminance = relative_luminance(&tint);
+
+        for entry in &mut self.color_table {
+            let luminance = relative_luminance(&entry.convert(color_space, ColorSpace::Linear));
+            // Luminance is linear in linear light, so scaling towards black or mixing towards
+            // white hits the luminance exactly.
+            let channel = |tint: f32| {
+                // A tint at or above white has nothing to mix towards, so HDR colors brighter
+                // than it are scaled as well.
+                if luminance <= tint_luminance || tint_luminance >= 1.0 {
+                    // A black tint has no luminance to scale and stays black.
+                    tint * luminance / tint_luminance.max(f32::MIN_POSITIVE)
+                } else {
+                    tint + (1.0 - tint) * (luminance - tint_luminance) / (1.0 - tint_luminance)
+                }
+            };
+            let shade = RgbaF32(channel(tint.0), channel(tint.1), channel(tint.2), entry.3);
+            *entry = shade.convert(ColorSpace::Linear, color_space);
+        }
+    }
+}
+
+/// The relative luminance of a linear color, as defined by WCAG with the sRGB primaries.
+pub(crate) fn relative_luminance(color: &RgbaF32) -> f32 {
+    0.2126 * color.0 + 0.7152 * color.1 + 0.0722 * color.2
+}
+
+/// Rebuilds the color table from the old indices in `order`, which must list every index
+/// that is referenced by a style. Colors that are not listed are removed.
+pub(crate) fn reorder_colors(document: &mut TinyVg, order: &[usize]) {
+    let mut remap = vec![None; document.color_table.len()];
+    for (new_index, &old_index) in order.iter().enumerate() {
+        remap[old_index] = Some(new_index as u64);
+    }
+    for command in &mut document.draw_commands {
+        for_each_color_index_mut(command, &mut |index| {
+            if let Some(Some(new_index)) = remap.get(*index as usize) {
+                *index = *new_index;
+            }
+        });
+    }
+    document.color_table = order.iter().map(|&old_index| document.color_table[old_index]).collect();
+    document.header.color_count = document.color_table.len() as u64;
+}
+
+/// The indices of the colors in the order they are first referenced by the commands.
+pub(crate) fn colors_by_first_use(document: &TinyVg) -> Vec<usize> {
+    let mut used = vec![false; document.color_table.len()];
+    let mut order = Vec::new();
+    for command in &document.draw_commands {
+        for_each_color_index(command, &mut |index| {
+            if let Some(seen) = used.get_mut(index as usize)
+                && !*seen
+            {
+                *seen = true;
+                order.push(index as usize);
+            }
+        });
+    }
+    order
+}
+
+/// The index of `color` in the table, appending it if it is not there yet.
+pub(crate) fn intern_color(color_table: &mut ColorTable, color: RgbaF32) -> u64 {
+    match color_table.iter().position(|other| *other == color) {
+        Some(index) => index as u64,
+        None => {
+            color_table.push(color);
+            (color_table.len() - 1) as u64
+        }
+    }
+}
+
+fn colors_within(a: &RgbaF32, b: &RgbaF32, epsilon: f32) -> bool {
+    (a.0 - b.0).abs() <= epsilon && (a.1 - b.1).abs() <= epsilon && (a.2 - b.2).abs() <= epsilon && (a.3 - b.3).abs() <= epsilon
+}
+
+/// Calls `f` with every style of a command, the fill style before the line style.
+pub(crate) fn for_each_style(command: &DrawCommand, f: &mut impl FnMut(&Style)) {
+    match command {
+        DrawCommand::FillPolygon(data) => f(&data.style),
+        DrawCommand::FillRectangles(data) => f(&data.style),
+        DrawCommand::FillPath(data) => f(&data.style),
+        DrawCommand::DrawLines(data) => f(&data.line_style),
+        DrawCommand::DrawLineLoop(data) => f(&data.line_style),
+        DrawCommand::DrawLineStrip(data) => f(&data.style),
+        DrawCommand::DrawLinePath(data) => f(&data.style),
+        DrawCommand::OutlineFillPolygon(data) => {
+            f(&data.fill_style);
+            f(&data.line_style);
+        }
+        DrawCommand::OutlineFillRectangles(data) => {
+            f(&data.fill_style);
+            f(&data.line_style);
+        }
+        DrawCommand::OutlineFillPath(data) => {
+            f(&data.fill_style);
+            f(&data.line_style);
+        }
+        DrawCommand::TextHint(_) => {}
+    }
+}
+
+/// Like `for_each_style`, but allows the styles to be changed in place.
+pub(crate) fn for_each_style_mut(command: &mut DrawCommand, f: &mut impl FnMut(&mut Style)) {
+    match command {
+        DrawCommand::FillPolygon(data) => f(&mut data.style),
+        DrawCommand::FillRectangles(data) => f(&mut data.style),
+        DrawCommand::FillPath(data) => f(&mut data.style),
+        DrawCommand::DrawLines(data) => f(&mut data.line_style),
+        DrawCommand::DrawLineLoop(data) => f(&mut data.line_style),
+        DrawCommand::DrawLineStrip(data) => f(&mut data.style),
+        DrawCommand::DrawLinePath(data) => f(&mut data.style),
+        DrawCommand::OutlineFillPolygon(data) => {
+            f(&mut data.fill_style);
+            f(&mut data.line_style);
+        }
+        DrawCommand::OutlineFillRectangles(data) => {
+            f(&mut data.fill_style);
+            f(&mut data.line_style);
+        }
+        DrawCommand::OutlineFillPath(data) => {
+            f(&mut data.fill_style);
+            f(&mut data.line_style);
+        }
+        DrawCommand::TextHint(_) => {}
+    }
+}
+
+/// Calls `f` with every color table index referenced by a command.
+pub(crate) fn for_each_color_index(command: &DrawCommand, f: &mut impl FnMut(u64)) {
+    for_each_style(command, &mut |style| match style {
+        Style::FlatColor(flat) => f(flat.color_index),
+        Style::LinearGradient(gradient) => {
+            f(gradient.color_index_0);
+            f(gradient.color_index_1);
+        }
+        Style::RadialGradient(gradient) => {
+            f(gradient.color_index_0);
+            f(gradient.color_index_1);
+        }
+    });
+}
+
+/// Like `for_each_color_index`, but allows the indices to be changed in place.
+pub(crate) fn for_each_color_index_mut(command: &mut DrawCommand, f: &mut impl FnMut(&mut u64)) {
+    for_each_style_mut(command, &mut |style| match style {
+        Style::FlatColor(flat) => f(&mut flat.color_index),
+        Style::LinearGradient(gradient) => {
+            f(&mut gradient.color_index_0);
+            f(&mut gradient.color_index_1);
+        }
+        Style::RadialGradient(gradient) => {
+            f(&mut gradient.color_index_0);
+            f(&mut gradient.color_index_1);
+        }
+    });
+}
+
+/// How much a color table entry is used by the draw commands.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ColorUsage {
+    /// The index of the color in `TinyVg::color_table`.
+    pub color_index: usize,
+
+    /// The number of draw commands whose styles reference the color.
+    pub command_count: usize,
+
+    /// An estimate of the area painted with the color in square display units. Overlaps
+    /// are counted repeatedly, strokes count as their length times the line width and
+    /// gradients split their area evenly between both colors.
+    pub area: f64,
+}
+
+/// The flattening tolerance for curves in display units when estimating areas.
+const USAGE_TOLERANCE: f64 = 0.1;
+
+impl TinyVg {
+    /// Reports for every color table entry, in table order, how many commands use it and
+    /// roughly how much area it covers, as for picking a dominant accent color or deciding
+    /// which colors can be merged without a visible difference.
+    pub fn palette_usage(&self) -> Vec<ColorUsage> {
+        let mut usage: Vec<ColorUsage> =
+            (0..self.color_table.len()).map(|color_index| ColorUsage { color_index, command_count: 0, area: 0.0 }).collect();
+
+        for command in &self.draw_commands {
+            let Some(outline) = command_outline(command, USAGE_TOLERANCE) else {
+                continue;
+            };
+            let fill_area: f64 = outline.fill.iter().map(|polyline| signed_area(&polyline_coordinates(polyline)).abs()).sum();
+            let stroke_area: f64 = outline.stroke.iter().map(polyline_length).sum::<f64>() * outline.line_width;
+            // Styles come fill first, and commands with a single style paint either area.
+            let areas = if outline.fill.is_empty() || outline.stroke.is_empty() {
+                vec![fill_area + stroke_area]
+            } else {
+                vec![fill_area, stroke_area]
+            };
+
+            let mut referenced = vec![false; usage.len()];
+            let mut styles = 0;
+            for_each_style(command, &mut |style| {
+                let area = areas.get(styles).copied().unwrap_or(0.0);
+                styles += 1;
+                let painted: &[(u64, f64)] = match style {
+                    Style::FlatColor(flat) => &[(flat.color_index, area)],
+                    Style::LinearGradient(gradient) => &[(gradient.color_index_0, area / 2.0), (gradient.color_index_1, area / 2.0)],
+                    Style::RadialGradient(gradient) => &[(gradient.color_index_0, area / 2.0), (gradient.color_index_1, area / 2.0)],
+                };
+                for &(index, area) in painted {
+                    if let Some(entry) = usage.get_mut(index as usize) {
+                        entry.area += area;
+                        referenced[index as usize] = true;
+                    }
+                }
+            });
+
+            for (entry, referenced) in usage.iter_mut().zip(referenced) {
+                entry.command_count += referenced as usize;
+            }
+        }
+        usage
+    }
+}
+
+fn polyline_coordinates(polyline: &Polyline) -> Vec<(f64, f64)> {
+    polyline.points.iter().map(|point| (point.x.0, point.y.0)).collect()
+}
+
+fn polyline_length(polyline: &Polyline) -> f64 {
+    let points = &polyline.points;
+    let closing = match (polyline.closed, points.first(), points.last()) {
+        (true, Some(first), Some(last)) => Some((last, first)),
+        _ => None,
+    };
+    points.windows(2).map(|pair| (&pair[0], &pair[1])).chain(closing).map(|(a, b)| (b.x.0 - a.x.0).hypot(b.y.0 - a.y.0)).sum()
+}
+
+/// A color table entry that `TinyVg::snap_palette` moved further than the tolerance.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SnappedColor {
+    /// The index of the color in `TinyVg::color_table`.
+    pub color_index: usize,
+
+    /// The color before snapping.
+    pub original: RgbaF32,
+
+    /// The index of the brand color it was snapped to.
+    pub brand_index: usize,
+
+    /// The CIEDE2000 difference between the original and the brand color.
+    pub delta_e: f64,
+}
+
+impl TinyVg {
+    /// Replaces every color table entry with the nearest of the `brand` colors by CIEDE2000
+    /// difference, keeping the entry's alpha, as for enforcing a brand palette across an
+    /// asset library. Returns the entries whose nearest brand color was more than
+    /// `max_delta_e` away, which were snapped as well but likely need a closer look.
+    ///
+    /// The brand colors are given in the color space of the document's color encoding and
+    /// their alpha is ignored. An empty brand palette leaves the table unchanged.
+    pub fn snap_palette(&mut self, brand: &[RgbaF32], max_delta_e: f64) -> Vec<SnappedColor> {
+        let color_space = self.header.color_encoding.color_space();
+        let lab = |color: &RgbaF32| srgb_to_lab(color.convert(color_space, ColorSpace::Srgb));
+        let brand_labs: Vec<Lab> = brand.iter().map(lab).collect();
+
+        let mut exceeded = Vec::new();
+        for (color_index, color) in self.color_table.iter_mut().enumerate() {
+            let color_lab = lab(color);
+            let nearest = brand_labs
+                .iter()
+                .map(|brand_lab| ciede2000(color_lab, *brand_lab))
+                .enumerate()
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            let Some((brand_index, delta_e)) = nearest else {
+                break;
+            };
+
+            if delta_e > max_delta_e {
+                exceeded.push(SnappedColor { color_index, original: *color, brand_index, delta_e });
+            }
+            let snapped = brand[brand_index];
+            *color = RgbaF32(snapped.0, snapped.1, snapped.2, color.3);
+        }
+        exceeded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::srgb_to_oklab;
+    use crate::color_table::RgbaF32;
+    use crate::commands::{DrawCommand, FillRectanglesData, FlatColored, Rectangle, Style};
+    use crate::common::Unit;
+    use crate::fit::FitMode;
+    use crate::TinyVg;
+
+    /// A 16 by 16 document with one color and one rectangle.
+    const DOCUMENT: [u8; 25] = [0x72, 0x56, 1, 0x00, 16, 0, 16, 0, 1, 255, 0, 0, 255, 0x02, 0, 0, 0, 0, 0, 0, 16, 0, 16, 0, 0x00];
+
+    /// The test document with `colors`, and a 4 by 4 square per entry of `color_indices`
+    /// placed left to right.
+    fn document(colors: &[RgbaF32], color_indices: &[u64]) -> TinyVg {
+        let mut document = TinyVg::from_bytes(&DOCUMENT).unwrap();
+        document.color_table = colors.to_vec();
+        document.header.color_count = colors.len() as u64;
+        document.draw_commands = color_indices
+            .iter()
+            .enumerate()
+            .map(|(i, &color_index)| {
+                DrawCommand::FillRectangles(FillRectanglesData {
+                    style: Style::FlatColor(FlatColored { color_index }),
+                    rectangles: vec![Rectangle { x: Unit(4.0 * i as f64), y: Unit(0.0), width: Unit(4.0), height: Unit(4.0) }],
+                })
+            })
+            .collect();
+        document
+    }
+
+    /// The color index of every command.
+    fn color_indices(document: &TinyVg) -> Vec<u64> {
+        document
+            .draw_commands
+            .iter()
+            .map(|command| match command {
+                DrawCommand::FillRectangles(FillRectanglesData { style: Style::FlatColor(flat), .. }) => flat.color_index,
+                other => panic!("{other:?}"),
+            })
+            .collect()
+    }
+
+    fn render(document: &TinyVg) -> Vec<u8> {
+        document.render_rgba((16, 16), FitMode::Stretch).data
+    }
+
+    #[test]
+    fn duplicate_colors_are_merged_within_the_tolerance() {
+        let red = RgbaF32(1.0, 0.0, 0.0, 1.0);
+        let original = document(&[red, RgbaF32(0.99, 0.0, 0.0, 1.0), RgbaF32(0.0, 0.0, 1.0, 1.0), red], &[0, 1, 2, 3]);
+
+        let mut deduplicated = original.clone();
+        assert_eq!(deduplicated.dedup_colors(0.0), 1);
+        assert_eq!(color_indices(&deduplicated), [0, 1, 2, 0]);
+        assert_eq!(render(&deduplicated), render(&original));
+
+        assert_eq!(deduplicated.dedup_colors(0.02), 1);
+        assert_eq!(deduplicated.color_table, [red, RgbaF32(0.0, 0.0, 1.0, 1.0)]);
+        assert_eq!(deduplicated.header.color_count, 2);
+        assert_eq!(color_indices(&deduplicated), [0, 0, 1, 0]);
+        assert_eq!(deduplicated.validate(), Ok(()));
+        let difference = render(&original).iter().zip(render(&deduplicated)).map(|(a, b)| a.abs_diff(b)).max();
+        assert!(difference <= Some(3), "{difference:?}");
+    }
+
+    #[test]
+    fn colors_are_recolored_and_replaced() {
+        let red = RgbaF32(1.0, 0.0, 0.0, 1.0);
+        let blue = RgbaF32(0.0, 0.0, 1.0, 1.0);
+        let mut document = document(&[red, RgbaF32(0.98, 0.0, 0.0, 1.0), RgbaF32(0.0, 1.0, 0.0, 0.5)], &[0, 1, 2]);
+
+        assert_eq!(document.replace_color(red, blue, 0.0), 1);
+        assert_eq!(document.replace_color(red, blue, 0.05), 1);
+        assert_eq!(document.replace_color(red, blue, 0.05), 0);
+        assert_eq!(document.color_table, [blue, blue, RgbaF32(0.0, 1.0, 0.0, 0.5)]);
+
+        document.recolor(&|color| RgbaF32(color.2, color.1, color.0, color.3 / 2.0));
+        assert_eq!(document.color_table, [RgbaF32(1.0, 0.0, 0.0, 0.5), RgbaF32(1.0, 0.0, 0.0, 0.5), RgbaF32(0.0, 1.0, 0.0, 0.25)]);
+        assert_eq!(color_indices(&document), [0, 1, 2]);
+        assert_eq!(document.validate(), Ok(()));
+    }
+
+    #[test]
+    fn lightness_inversion_swaps_light_and_dark() {
+        let colors = [RgbaF32(1.0, 1.0, 1.0, 1.0), RgbaF32(0.0, 0.0, 0.0, 0.5), RgbaF32(0.5, 0.5, 0.5, 1.0), RgbaF32(0.8, 0.2, 0.2, 1.0)];
+        let mut document = document(&colors, &[0, 1, 2, 3]);
+        let close = |a: &RgbaF32, b: &RgbaF32| (a.0 - b.0).abs() < 1e-3 && (a.1 - b.1).abs() < 1e-3 && (a.2 - b.2).abs() < 1e-3 && a.3 == b.3;
+
+        document.invert_lightness();
+        let inverted = document.color_table.clone();
+        assert!(close(&inverted[0], &RgbaF32(0.0, 0.0, 0.0, 1.0)), "{:?}", inverted[0]);
+        assert!(close(&inverted[1], &RgbaF32(1.0, 1.0, 1.0, 0.5)), "{:?}", inverted[1]);
+        // Grays stay gray and the hue of colors is kept.
+        assert!(inverted[2].0 < 0.5 && close(&inverted[2], &RgbaF32(inverted[2].0, inverted[2].0, inverted[2].0, 1.0)), "{:?}", inverted[2]);
+        let hue = |color: RgbaF32| {
+            let (_, a, b) = srgb_to_oklab(color);
+            b.atan2(a)
+        };
+        assert!((hue(inverted[3]) - hue(colors[3])).abs() < 1e-2, "{:?}", inverted[3]);
+        assert_eq!(document.validate(), Ok(()));
+
+        // Colors that stay in gamut come back when inverted twice.
+        document.invert_lightness();
+        for (color, original) in document.color_table.iter().zip(&colors).take(3) {
+            assert!(close(color, original), "{color:?} {original:?}");
+        }
+    }
+
+    #[test]
+    fn monochrome_keeps_hdr_colors_finite() {
+        let mut document = TinyVg::from_bytes(&DOCUMENT).unwrap();
+        document.color_table = vec![RgbaF32(0.5, 0.5, 0.5, 1.0), RgbaF32(2.0, 2.0, 2.0, 0.5)];
+        document.to_grayscale();
+
+        let [gray, bright] = [document.color_table[0], document.color_table[1]];
+        assert!((gray.0 - 0.5).abs() < 1e-4 && (gray.1 - 0.5).abs() < 1e-4 && (gray.2 - 0.5).abs() < 1e-4);
+        for channel in [bright.0, bright.1, bright.2] {
+            assert!(channel.is_finite() && (channel - 2.0).abs() < 1e-3, "{bright:?}");
+        }
+        assert_eq!(bright.3, 0.5);
+    }
+}
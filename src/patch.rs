@@ -0,0 +1,164 @@
+use std::io::Cursor;
+use std::ops::Range;
+
+use crate::commands::DrawCommand;
+use crate::encoder::write_draw_commands;
+use crate::validation::{validate_command, ValidationError};
+use crate::{TinyVg, TinyVgParseError};
+
+/// Why a command could not be patched into an encoded document.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchError {
+    /// The command index is past the end of the document.
+    IndexOutOfRange,
+
+    /// The edit would remove the document's only command, see `EditError::LastCommand`.
+    LastCommand,
+
+    /// The command cannot be encoded with the document's header and color table.
+    Invalid(ValidationError),
+}
+
+/// An encoded document that can be edited one command at a time. Every edit encodes only
+/// the changed command and splices it into the buffer, so large files can be edited without
+/// encoding them again as a whole. The header and color table stay as they are, so new
+/// commands must fit the document's coordinate range and use its colors.
+#[derive(Debug, Clone)]
+pub struct EncodedDocument {
+    bytes: Vec<u8>,
+    document: TinyVg,
+    spans: Vec<Range<usize>>,
+    /// The offset of the end of document byte, which trailing bytes may follow.
+    end_of_document: usize,
+}
+
+impl EncodedDocument {
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<EncodedDocument, TinyVgParseError> {
+        let (document, spans, consumed) = TinyVg::from_bytes_with_spans_counted(&bytes)?;
+        Ok(EncodedDocument { bytes, document, spans, end_of_document: consumed - 1 })
+    }
+
+    /// The document as it is currently encoded.
+    pub fn document(&self) -> &TinyVg {
+        &self.document
+    }
+
+    /// The byte range of every command in `as_bytes`.
+    pub fn command_spans(&self) -> &[Range<usize>] {
+        &self.spans
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// Replaces the command at `index`.
+    pub fn replace_command(&mut self, index: usize, command: DrawCommand) -> Result<(), PatchError> {
+        let span = self.spans.get(index).cloned().ok_or(PatchError::IndexOutOfRange)?;
+        let encoded = self.encode(index, &command)?;
+        self.splice(index, span, encoded);
+        self.document.draw_commands[index] = command;
+        Ok(())
+    }
+
+    /// Inserts a command before the command at `index`, or appends it if `index` is the
+    /// number of commands.
+    pub fn insert_command(&mut self, index: usize, command: DrawCommand) -> Result<(), PatchError> {
+        if index > self.spans.len() {
+            return Err(PatchError::IndexOutOfRange);
+        }
+        let encoded = self.encode(index, &command)?;
+        // Commands are followed by the end of document byte, so appending inserts before it.
+        let position = self.spans.get(index).map_or(self.end_of_document, |span| span.start);
+        self.spans.insert(index, position..position);
+        self.splice(index, position..position, encoded);
+        self.document.draw_commands.insert(index, command);
+        Ok(())
+    }
+
+    /// Removes the command at `index` and returns it. As with `TinyVg::remove_command`, the
+    /// last remaining command cannot be removed.
+    pub fn remove_command(&mut self, index: usize) -> Result<DrawCommand, PatchError> {
+        let span = self.spans.get(index).cloned().ok_or(PatchError::IndexOutOfRange)?;
+        if self.spans.len() == 1 {
+            return Err(PatchError::LastCommand);
+        }
+        self.splice(index, span, Vec::new());
+        self.spans.remove(index);
+        Ok(self.document.draw_commands.remove(index))
+    }
+
+    fn encode(&self, index: usize, command: &DrawCommand) -> Result<Vec<u8>, PatchError> {
+        validate_command(&self.document, &self.document.header, index, command).map_err(PatchError::Invalid)?;
+        let mut writer = Cursor::new(Vec::new());
+        write_draw_commands(&mut writer, &self.document.header, std::slice::from_ref(command))
+            .expect("validated commands can be written");
+        Ok(writer.into_inner())
+    }
+
+    /// Replaces the bytes of `span` with `encoded`, which becomes the span of the command
+    /// at `index`, and moves the spans of the later commands.
+    fn splice(&mut self, index: usize, span: Range<usize>, encoded: Vec<u8>) {
+        let new_end = span.start + encoded.len();
+        let shift = new_end as isize - span.end as isize;
+        self.bytes.splice(span.clone(), encoded);
+        self.spans[index] = span.start..new_end;
+        for later in &mut self.spans[index + 1..] {
+            *later = (later.start as isize + shift) as usize..(later.end as isize + shift) as usize;
+        }
+        self.end_of_document = (self.end_of_document as isize + shift) as usize;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EncodedDocument, PatchError};
+    use crate::commands::{DrawCommand, FillRectanglesData, FlatColored, Rectangle, Style};
+    use crate::common::Unit;
+    use crate::tokens::ColorTokens;
+    use crate::TinyVg;
+
+    /// A 16 by 16 document with one color and one rectangle.
+    const DOCUMENT: [u8; 25] = [0x72, 0x56, 1, 0x00, 16, 0, 16, 0, 1, 255, 0, 0, 255, 0x02, 0, 0, 0, 0, 0, 0, 16, 0, 16, 0, 0x00];
+
+    fn rectangle(x: f64) -> DrawCommand {
+        DrawCommand::FillRectangles(FillRectanglesData {
+            style: Style::FlatColor(FlatColored { color_index: 0 }),
+            rectangles: vec![Rectangle { x: Unit(x), y: Unit(0.0), width: Unit(4.0), height: Unit(4.0) }],
+        })
+    }
+
+    #[test]
+    fn appended_commands_come_before_trailing_bytes() {
+        let mut bytes = DOCUMENT.to_vec();
+        let mut tokens = ColorTokens::new();
+        tokens.insert("accent", 0);
+        tokens.write_trailing(&mut bytes);
+
+        let mut document = EncodedDocument::from_bytes(bytes).unwrap();
+        let original = document.document().draw_commands[0].clone();
+        document.insert_command(1, rectangle(8.0)).unwrap();
+        document.insert_command(0, rectangle(4.0)).unwrap();
+        document.insert_command(3, rectangle(12.0)).unwrap();
+
+        let bytes = document.into_bytes();
+        let reparsed = TinyVg::from_bytes(&bytes).unwrap();
+        assert_eq!(reparsed.draw_commands, [rectangle(4.0), original, rectangle(8.0), rectangle(12.0)]);
+        assert_eq!(ColorTokens::from_trailing_data(&bytes), Some(tokens));
+    }
+
+    #[test]
+    fn the_last_command_is_kept() {
+        let mut document = EncodedDocument::from_bytes(DOCUMENT.to_vec()).unwrap();
+        assert_eq!(document.remove_command(1), Err(PatchError::IndexOutOfRange));
+        assert_eq!(document.remove_command(0), Err(PatchError::LastCommand));
+
+        document.insert_command(1, rectangle(8.0)).unwrap();
+        assert_eq!(document.remove_command(1), Ok(rectangle(8.0)));
+        assert_eq!(document.as_bytes(), DOCUMENT);
+    }
+}
@@ -0,0 +1,348 @@
+use crate::commands::{ArcCircle, ArcEllipse, CubicBezier, Path, PathCommand, PathCommands, Point, QuadraticBezier, Segment};
+use crate::common::Unit;
+use crate::precision::path_units;
+
+impl Path {
+    /// Parses SVG path data, as found in the `d` attribute of an SVG `<path>` or the
+    /// `pathData` attribute of an Android VectorDrawable, into a TinyVG path.
+    ///
+    /// Relative commands are resolved to absolute coordinates and the shorthand curves
+    /// (`S` and `T`) are expanded using the reflected control point. Arcs are kept as
+    /// arcs. Subpaths without any drawing commands are dropped. Returns `None` if the data
+    /// is malformed.
+    pub fn from_svg_d(data: &str) -> Option<Path> {
+        let mut tokens = Tokenizer { bytes: data.as_bytes(), position: 0 };
+        let mut builder = PathBuilder::default();
+        let mut command: Option<u8> = None;
+        let mut started = false;
+
+        loop {
+            tokens.skip_separators();
+            let Some(byte) = tokens.peek() else {
+                break;
+            };
+
+            if byte.is_ascii_alphabetic() {
+                command = Some(byte);
+                tokens.position += 1;
+            } else {
+                // Coordinates without a command letter repeat the previous command, except
+                // that a repeated move-to becomes a line-to.
+                command = match command? {
+                    b'M' => Some(b'L'),
+                    b'm' => Some(b'l'),
+                    b'Z' | b'z' => return None,
+                    other => Some(other),
+                };
+            }
+
+            let letter = command?;
+            // Path data must start with a move-to.
+            if !started && !matches!(letter, b'M' | b'm') {
+                return None;
+            }
+            started = true;
+            let relative = letter.is_ascii_lowercase();
+            let (origin_x, origin_y) = if relative { builder.current } else { (0.0, 0.0) };
+
+            match letter.to_ascii_uppercase() {
+                b'M' => {
+                    let (x, y) = tokens.pair()?;
+                    builder.move_to(origin_x + x, origin_y + y);
+                }
+                b'L' => {
+                    let (x, y) = tokens.pair()?;
+                    builder.line_to(origin_x + x, origin_y + y);
+                }
+                b'H' => {
+                    let x = origin_x + tokens.number()?;
+                    builder.push(PathCommand::HorizontalLine(Unit(x), None), (x, builder.current.1));
+                }
+                b'V' => {
+                    let y = origin_y + tokens.number()?;
+                    builder.push(PathCommand::VerticalLine(Unit(y), None), (builder.current.0, y));
+                }
+                b'C' => {
+                    let (x1, y1) = tokens.pair()?;
+                    let (x2, y2) = tokens.pair()?;
+                    let (x, y) = tokens.pair()?;
+                    builder.cubic_to((origin_x + x1, origin_y + y1), (origin_x + x2, origin_y + y2), (origin_x + x, origin_y + y));
+                }
+                b'S' => {
+                    let control_0 = builder.reflected(builder.last_cubic_control);
+                    let (x2, y2) = tokens.pair()?;
+                    let (x, y) = tokens.pair()?;
+                    builder.cubic_to(control_0, (origin_x + x2, origin_y + y2), (origin_x + x, origin_y + y));
+                }
+                b'Q' => {
+                    let (x1, y1) = tokens.pair()?;
+                    let (x, y) = tokens.pair()?;
+                    builder.quadratic_to((origin_x + x1, origin_y + y1), (origin_x + x, origin_y + y));
+                }
+                b'T' => {
+                    let control = builder.reflected(builder.last_quadratic_control);
+                    let (x, y) = tokens.pair()?;
+                    builder.quadratic_to(control, (origin_x + x, origin_y + y));
+                }
+                b'A' => {
+                    let radius_x = tokens.number()?.abs();
+                    let radius_y = tokens.number()?.abs();
+                    let rotation = tokens.number()?;
+                    let large_arc = tokens.flag()?;
+                    let sweep = tokens.flag()?;
+                    let (x, y) = tokens.pair()?;
+                    builder.arc_to(radius_x, radius_y, rotation, large_arc, sweep, (origin_x + x, origin_y + y));
+                }
+                b'Z' => builder.close(),
+                _ => return None,
+            }
+        }
+
+        // Numbers too large for an f64, or sums of them, end up infinite.
+        let path = builder.finish();
+        let mut finite = true;
+        path_units(&path, &mut |unit| finite &= unit.0.is_finite());
+        finite.then_some(path)
+    }
+}
+
+struct Tokenizer<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Tokenizer<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).copied()
+    }
+
+    fn skip_separators(&mut self) {
+        while let Some(byte) = self.peek() {
+            if byte.is_ascii_whitespace() || byte == b',' {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn skip_digits(&mut self) -> usize {
+        let start = self.position;
+        while self.peek().is_some_and(|byte| byte.is_ascii_digit()) {
+            self.position += 1;
+        }
+        self.position - start
+    }
+
+    fn number(&mut self) -> Option<f64> {
+        self.skip_separators();
+        let start = self.position;
+
+        if matches!(self.peek(), Some(b'+' | b'-')) {
+            self.position += 1;
+        }
+        let mut digits = self.skip_digits();
+        if self.peek() == Some(b'.') {
+            self.position += 1;
+            digits += self.skip_digits();
+        }
+        if digits == 0 {
+            return None;
+        }
+
+        // Only consume the exponent if it is complete, so that e.g. `2em` is not misread.
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            let mantissa_end = self.position;
+            self.position += 1;
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.position += 1;
+            }
+            if self.skip_digits() == 0 {
+                self.position = mantissa_end;
+            }
+        }
+
+        std::str::from_utf8(&self.bytes[start..self.position]).ok()?.parse().ok()
+    }
+
+    fn pair(&mut self) -> Option<(f64, f64)> {
+        Some((self.number()?, self.number()?))
+    }
+
+    /// Arc flags are a single digit and may be written without separators, as in `a1 1 0 00 2 2`.
+    fn flag(&mut self) -> Option<bool> {
+        self.skip_separators();
+        let flag = match self.peek()? {
+            b'0' => false,
+            b'1' => true,
+            _ => return None,
+        };
+        self.position += 1;
+        Some(flag)
+    }
+}
+
+#[derive(Default)]
+struct PathBuilder {
+    segments: Vec<Segment>,
+    current_segment: Option<Segment>,
+    current: (f64, f64),
+    subpath_start: (f64, f64),
+    last_cubic_control: Option<(f64, f64)>,
+    last_quadratic_control: Option<(f64, f64)>,
+}
+
+fn point(point: (f64, f64)) -> Point {
+    Point::new(Unit(point.0), Unit(point.1))
+}
+
+impl PathBuilder {
+    fn finish_segment(&mut self) {
+        if let Some(segment) = self.current_segment.take()
+            && !segment.path_commands.is_empty()
+        {
+            self.segments.push(segment);
+        }
+    }
+
+    fn move_to(&mut self, x: f64, y: f64) {
+        self.finish_segment();
+        self.current = (x, y);
+        self.subpath_start = (x, y);
+        self.current_segment = Some(Segment { start: point(self.current), path_commands: PathCommands::new() });
+        self.last_cubic_control = None;
+        self.last_quadratic_control = None;
+    }
+
+    /// Appends a command ending at `end`. Drawing after a close path without a new
+    /// move-to starts a new segment at the start of the closed one.
+    fn push(&mut self, command: PathCommand, end: (f64, f64)) {
+        let start = self.current;
+        self.current_segment
+            .get_or_insert_with(|| Segment { start: point(start), path_commands: PathCommands::new() })
+            .path_commands
+            .push(command);
+        self.current = end;
+        self.last_cubic_control = None;
+        self.last_quadratic_control = None;
+    }
+
+    fn line_to(&mut self, x: f64, y: f64) {
+        self.push(PathCommand::Line(point((x, y)), None), (x, y));
+    }
+
+    fn cubic_to(&mut self, control_0: (f64, f64), control_1: (f64, f64), end: (f64, f64)) {
+        let cubic = CubicBezier {
+            control_point_0: point(control_0),
+            control_point_1: point(control_1),
+            point_1: point(end),
+        };
+        self.push(PathCommand::CubicBezier(cubic, None), end);
+        self.last_cubic_control = Some(control_1);
+    }
+
+    fn quadratic_to(&mut self, control: (f64, f64), end: (f64, f64)) {
+        let quadratic = QuadraticBezier { control_point: point(control), point_1: point(end) };
+        self.push(PathCommand::QuadraticBezier(quadratic, None), end);
+        self.last_quadratic_control = Some(control);
+    }
+
+    fn arc_to(&mut self, radius_x: f64, radius_y: f64, rotation: f64, large_arc: bool, sweep: bool, end: (f64, f64)) {
+        let target = point(end);
+        let command = if radius_x == radius_y {
+            PathCommand::ArcCircle(ArcCircle { large_arc, sweep, radius: Unit(radius_x), target }, None)
+        } else {
+            PathCommand::ArcEllipse(ArcEllipse {
+                large_arc,
+                sweep,
+                radius_x: Unit(radius_x),
+                radius_y: Unit(radius_y),
+                rotation: Unit(rotation),
+                target,
+            }, None)
+        };
+        self.push(command, end);
+    }
+
+    /// The reflection of the previous curve's last control point about the current
+    /// point, or the current point itself if the previous command was another kind.
+    fn reflected(&self, control: Option<(f64, f64)>) -> (f64, f64) {
+        match control {
+            Some((x, y)) => (2.0 * self.current.0 - x, 2.0 * self.current.1 - y),
+            None => self.current,
+        }
+    }
+
+    fn close(&mut self) {
+        if let Some(segment) = &mut self.current_segment
+            && !segment.path_commands.is_empty()
+        {
+            segment.path_commands.push(PathCommand::ClosePath(None));
+        }
+        self.finish_segment();
+        self.current = self.subpath_start;
+        self.last_cubic_control = None;
+        self.last_quadratic_control = None;
+    }
+
+    fn finish(mut self) -> Path {
+        self.finish_segment();
+        Path { segments: self.segments }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{Path, PathCommand};
+
+    /// Parses path data and writes it back in the absolute form of `Path::to_svg_d`.
+    fn normalized(data: &str) -> Option<String> {
+        Path::from_svg_d(data).map(|path| path.to_svg_d())
+    }
+
+    #[test]
+    fn relative_commands_become_absolute() {
+        assert_eq!(normalized("m10 20 l5 5 5-5 h-10 v5 z").unwrap(), "M 10 20 L 15 25 L 20 20 H 10 V 25 Z");
+        assert_eq!(normalized("M10 20 15 25").unwrap(), "M 10 20 L 15 25");
+        assert_eq!(normalized("m1e1,2E0 l.5-.5").unwrap(), "M 10 2 L 10.5 1.5");
+    }
+
+    #[test]
+    fn shorthand_curves_reflect_the_previous_control_point() {
+        assert_eq!(normalized("M0 0 C0 10 10 10 10 0 S20 -10 20 0").unwrap(), "M 0 0 C 0 10 10 10 10 0 C 10 -10 20 -10 20 0");
+        assert_eq!(normalized("M0 0 Q5 10 10 0 t10 0").unwrap(), "M 0 0 Q 5 10 10 0 Q 15 -10 20 0");
+        // Without a previous curve the control point is the current point.
+        assert_eq!(normalized("M0 0 L5 5 T10 0").unwrap(), "M 0 0 L 5 5 Q 5 5 10 0");
+    }
+
+    #[test]
+    fn arcs_keep_their_parameters() {
+        let path = Path::from_svg_d("M0 0 a5 5 0 0010 0 A5 3 30 1 1 0 0").unwrap();
+        let commands = &path.segments[0].path_commands;
+        assert!(matches!(commands[0], PathCommand::ArcCircle(..)));
+        assert!(matches!(commands[1], PathCommand::ArcEllipse(..)));
+        assert_eq!(path.to_svg_d(), "M 0 0 A 5 5 0 0 0 10 0 A 5 3 30 1 1 0 0");
+    }
+
+    #[test]
+    fn subpaths_without_drawing_are_dropped() {
+        assert_eq!(normalized("M0 0 M5 5 L6 6 M7 7").unwrap(), "M 5 5 L 6 6");
+        // Drawing after a close path continues from the start of the closed subpath.
+        assert_eq!(normalized("M1 1 L2 1 Z L1 2").unwrap(), "M 1 1 L 2 1 Z M 1 1 L 1 2");
+        assert_eq!(normalized("").unwrap(), "");
+    }
+
+    #[test]
+    fn malformed_data_is_rejected() {
+        for data in ["10 10", "M10", "M0 0 X1 2", "M0 0 Z 1 2", "M0 0 A5 5 0 2 0 1 1", "M0 0 L1 e5", "Z", "L1 1 M0 0 Z"] {
+            assert_eq!(Path::from_svg_d(data), None, "{data}");
+        }
+    }
+
+    #[test]
+    fn non_finite_numbers_are_rejected() {
+        for data in ["M1e400 0 L1 1", "M0 0 L1 -1e309", "m1e308 0 l1e308 0"] {
+            assert_eq!(Path::from_svg_d(data), None, "{data}");
+        }
+    }
+}
@@ -0,0 +1,971 @@
+use crate::builder::Canvas;
+use crate::color_table::RgbaF32;
+use crate::commands::Point;
+use crate::common::{Affine, Unit};
+use crate::TinyVg;
+use flate2::read::ZlibDecoder;
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+
+/// How deeply form XObjects may draw each other, which also stops forms that draw themselves.
+const MAX_FORM_DEPTH: usize = 16;
+
+/// How many references are followed to resolve an object, which stops reference cycles.
+const MAX_REFERENCE_DEPTH: usize = 32;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PdfImportError {
+    /// The data is not a PDF file, or the objects the page needs could not be read.
+    InvalidPdf,
+    /// The file is encrypted, which is not supported.
+    Encrypted,
+    /// The file has no page with the requested index.
+    PageNotFound,
+    /// A stream is compressed with a filter other than `FlateDecode` without predictors.
+    UnsupportedFilter,
+}
+
+/// Returns the number of pages of a PDF file.
+pub fn pdf_page_count(pdf_bytes: &[u8]) -> Result<usize, PdfImportError> {
+    Ok(PdfFile::parse(pdf_bytes)?.pages()?.len())
+}
+
+/// Converts page `page_index`, counting from 0, of a PDF file into a document whose units
+/// are PDF points with the origin at the top left of the page's crop box.
+///
+/// Filled and stroked paths become fill, line and outline fill paths in their flat color,
+/// including the paths drawn by form XObjects. Gray, RGB and CMYK colors are taken as sRGB,
+/// and the constant alpha of graphics states is applied. Fills use the even-odd rule of
+/// TinyVG, so nonzero fills of self-intersecting paths may differ. Text, images, shadings,
+/// patterns and clipping paths are left out with a warning, and dashed strokes are drawn solid.
+pub fn pdf_page_to_tinyvg(pdf_bytes: &[u8], page_index: usize) -> Result<TinyVg, PdfImportError> {
+    let file = PdfFile::parse(pdf_bytes)?;
+    let pages = file.pages()?;
+    let page = *pages.get(page_index).ok_or(PdfImportError::PageNotFound)?;
+
+    let media_box = file.inherited(page, b"CropBox").or_else(|| file.inherited(page, b"MediaBox"));
+    let [left, bottom, right, top] = match media_box.and_then(|media_box| file.numbers(media_box)).as_deref() {
+        Some(&[x0, y0, x1, y1]) => [x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)],
+        _ => [0.0, 0.0, 612.0, 792.0],
+    };
+    let (width, height) = (right - left, top - bottom);
+    let rotation = file.inherited(page, b"Rotate").and_then(|rotation| file.number(rotation)).unwrap_or(0.0);
+
+    // Move the crop box to the origin with the y axis pointing down, then turn the page
+    // clockwise by its rotation.
+    let flip = Affine([1.0, 0.0, 0.0, -1.0, -left, top]);
+    let (page_transform, size) = match (rotation as i64).rem_euclid(360) {
+        90 => (flip.then(&Affine([0.0, 1.0, -1.0, 0.0, height, 0.0])), (height, width)),
+        180 => (flip.then(&Affine([-1.0, 0.0, 0.0, -1.0, width, height])), (width, height)),
+        270 => (flip.then(&Affine([0.0, -1.0, 1.0, 0.0, 0.0, width])), (height, width)),
+        _ => (flip, (width, height)),
+    };
+
+    let mut interpreter = Interpreter {
+        file: &file,
+        canvas: Canvas::new(size.0.ceil() as u32, size.1.ceil() as u32),
+        state: GraphicsState { ctm: page_transform, ..GraphicsState::default() },
+        saved_states: Vec::new(),
+        current: None,
+        subpath_start: Point::new(Unit(0.0), Unit(0.0)),
+        warned: HashSet::new(),
+    };
+    let resources = file.inherited(page, b"Resources").and_then(|resources| file.dictionary(resources));
+    let contents = match file.get(page, b"Contents") {
+        Some(Object::Array(streams)) => streams.iter().map(|stream| file.stream_data(stream)).collect::<Result<Vec<_>, _>>()?,
+        Some(stream) => vec![file.stream_data(stream)?],
+        None => Vec::new(),
+    };
+    // The content streams of a page are one stream split at token boundaries.
+    interpreter.run(&contents.join(&b'\n'), resources, 0)?;
+
+    Ok(interpreter.canvas.finish())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Object {
+    Null,
+    Boolean(bool),
+    Number(f64),
+    Name(Vec<u8>),
+    String(Vec<u8>),
+    Array(Vec<Object>),
+    Dictionary(Dictionary),
+    /// A stream with its dictionary and its still encoded data.
+    Stream(Dictionary, Vec<u8>),
+    /// A reference to the object with the number.
+    Reference(u32),
+}
+
+type Dictionary = HashMap<Vec<u8>, Object>;
+
+/// What a content stream is made of: operands followed by their operator.
+enum Token<'a> {
+    Object(Object),
+    Operator(&'a [u8]),
+}
+
+fn is_whitespace(byte: u8) -> bool {
+    matches!(byte, b'\0' | b'\t' | b'\n' | b'\x0C' | b'\r' | b' ')
+}
+
+fn is_delimiter(byte: u8) -> bool {
+    matches!(byte, b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%')
+}
+
+fn is_regular(byte: u8) -> bool {
+    !is_whitespace(byte) && !is_delimiter(byte)
+}
+
+/// Reads the objects and operators of PDF syntax from a byte slice.
+struct Lexer<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(data: &'a [u8], position: usize) -> Self {
+        Lexer { data, position }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(&byte) = self.data.get(self.position) {
+            if byte == b'%' {
+                while self.data.get(self.position).is_some_and(|&byte| byte != b'\n' && byte != b'\r') {
+                    self.position += 1;
+                }
+            } else if is_whitespace(byte) {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// The run of regular characters at the current position.
+    fn regular(&mut self) -> &'a [u8] {
+        let start = self.position;
+        while self.data.get(self.position).is_some_and(|&byte| is_regular(byte)) {
+            self.position += 1;
+        }
+        &self.data[start..self.position]
+    }
+
+    fn next_token(&mut self) -> Option<Token<'a>> {
+        self.skip_whitespace();
+        let byte = *self.data.get(self.position)?;
+        let object = match byte {
+            b'/' => {
+                self.position += 1;
+                Object::Name(decode_name(self.regular()))
+            }
+            b'(' => Object::String(self.literal_string()?),
+            b'<' if self.data.get(self.position + 1) == Some(&b'<') => {
+                self.position += 2;
+                let mut dictionary = Dictionary::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.data[self.position..].starts_with(b">>") {
+                        self.position += 2;
+                        break;
+                    }
+                    let Some(Object::Name(key)) = self.next_object() else {
+                        return None;
+                    };
+                    dictionary.insert(key, self.next_object()?);
+                }
+                Object::Dictionary(dictionary)
+            }
+            b'<' => {
+                self.position += 1;
+                let end = self.position + self.data[self.position..].iter().position(|&byte| byte == b'>')?;
+                let digits: Vec<u8> = self.data[self.position..end].iter().copied().filter(u8::is_ascii_hexdigit).collect();
+                self.position = end + 1;
+                let digit = |byte: u8| (byte as char).to_digit(16).unwrap_or(0) as u8;
+                Object::String(digits.chunks(2).map(|pair| digit(pair[0]) << 4 | pair.get(1).map_or(0, |&low| digit(low))).collect())
+            }
+            b'[' => {
+                self.position += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.data.get(self.position)? == &b']' {
+                        self.position += 1;
+                        break;
+                    }
+                    items.push(self.next_object()?);
+                }
+                Object::Array(items)
+            }
+            _ if is_regular(byte) => {
+                let word = self.regular();
+                match word {
+                    b"true" => Object::Boolean(true),
+                    b"false" => Object::Boolean(false),
+                    b"null" => Object::Null,
+                    _ => match std::str::from_utf8(word).ok().and_then(|word| word.parse::<f64>().ok()) {
+                        Some(number) => self.reference_or_number(number),
+                        None => return Some(Token::Operator(word)),
+                    },
+                }
+            }
+            _ => {
+                // A stray delimiter such as `)` or `}`.
+                self.position += 1;
+                return Some(Token::Operator(&self.data[self.position - 1..self.position]));
+            }
+        };
+        Some(Token::Object(object))
+    }
+
+    fn next_object(&mut self) -> Option<Object> {
+        match self.next_token()? {
+            Token::Object(object) => Some(object),
+            Token::Operator(_) => None,
+        }
+    }
+
+    /// Turns `number generation R` into a reference, and otherwise returns the number.
+    fn reference_or_number(&mut self, number: f64) -> Object {
+        let start = self.position;
+        self.skip_whitespace();
+        let generation = self.regular();
+        self.skip_whitespace();
+        if !generation.is_empty() && generation.iter().all(u8::is_ascii_digit) && self.regular() == b"R" && number.fract() == 0.0 {
+            return Object::Reference(number as u32);
+        }
+        self.position = start;
+        Object::Number(number)
+    }
+
+    fn literal_string(&mut self) -> Option<Vec<u8>> {
+        self.position += 1;
+        let mut string = Vec::new();
+        let mut depth = 0;
+        loop {
+            let byte = *self.data.get(self.position)?;
+            self.position += 1;
+            match byte {
+                b'(' => depth += 1,
+                b')' if depth == 0 => return Some(string),
+                b')' => depth -= 1,
+                b'\\' => {
+                    let escaped = *self.data.get(self.position)?;
+                    self.position += 1;
+                    match escaped {
+                        b'n' => string.push(b'\n'),
+                        b'r' => string.push(b'\r'),
+                        b't' => string.push(b'\t'),
+                        b'b' => string.push(0x08),
+                        b'f' => string.push(0x0C),
+                        b'0'..=b'7' => {
+                            let mut value = (escaped - b'0') as u32;
+                            for _ in 0..2 {
+                                match self.data.get(self.position) {
+                                    Some(&digit @ b'0'..=b'7') => {
+                                        value = value * 8 + (digit - b'0') as u32;
+                                        self.position += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            string.push(value as u8);
+                        }
+                        // A backslash at the end of a line continues the string on the next one.
+                        b'\r' => {
+                            if self.data.get(self.position) == Some(&b'\n') {
+                                self.position += 1;
+                            }
+                        }
+                        b'\n' => {}
+                        _ => string.push(escaped),
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            string.push(byte);
+        }
+    }
+
+    /// Skips an inline image after its `BI` operator: the dictionary up to `ID`, and the
+    /// image data up to `EI` on its own.
+    fn skip_inline_image(&mut self) {
+        while let Some(token) = self.next_token() {
+            if let Token::Operator(b"ID") = token {
+                break;
+            }
+        }
+        while self.position < self.data.len() {
+            let rest = &self.data[self.position..];
+            let at_end = rest.starts_with(b"EI") && rest.get(2).is_none_or(|&byte| !is_regular(byte));
+            let after_whitespace = self.position > 0 && is_whitespace(self.data[self.position - 1]);
+            self.position += 1;
+            if at_end && after_whitespace {
+                self.position += 1;
+                return;
+            }
+        }
+    }
+}
+
+fn decode_name(name: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(name.len());
+    let mut index = 0;
+    while index < name.len() {
+        if name[index] == b'#'
+            && let Some(value) = name.get(index + 1..index + 3).and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok())
+        {
+            decoded.push(value);
+            index += 3;
+        } else {
+            decoded.push(name[index]);
+            index += 1;
+        }
+    }
+    decoded
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// Reads the `number generation` in front of an `obj` keyword.
+fn object_number(before: &[u8]) -> Option<u32> {
+    let mut end = before.len();
+    let mut numbers = [0u32; 2];
+    for number in &mut numbers {
+        while end > 0 && is_whitespace(before[end - 1]) {
+            end -= 1;
+        }
+        let start = before[..end].iter().rposition(|byte| !byte.is_ascii_digit()).map_or(0, |index| index + 1);
+        *number = std::str::from_utf8(&before[start..end]).ok()?.parse().ok()?;
+        end = start;
+    }
+    if end > 0 && is_regular(before[end - 1]) {
+        return None;
+    }
+    Some(numbers[1])
+}
+
+/// Reads the object after an `obj` keyword, together with its data if it is a stream.
+fn parse_indirect_object(data: &[u8], position: usize) -> Option<Object> {
+    let mut lexer = Lexer::new(data, position);
+    let object = lexer.next_object()?;
+    let Object::Dictionary(dictionary) = object else {
+        return Some(object);
+    };
+
+    lexer.skip_whitespace();
+    if !data[lexer.position..].starts_with(b"stream") {
+        return Some(Object::Dictionary(dictionary));
+    }
+    let mut start = lexer.position + 6;
+    if data.get(start) == Some(&b'\r') {
+        start += 1;
+    }
+    if data.get(start) == Some(&b'\n') {
+        start += 1;
+    }
+
+    // The length may be a reference to an object that was not read yet, and is wrong in
+    // some files, so it is only trusted when `endstream` follows it.
+    let length = match dictionary.get(b"Length".as_slice()) {
+        Some(Object::Number(length)) if *length >= 0.0 => Some(*length as usize),
+        _ => None,
+    };
+    let end = length
+        .filter(|&length| {
+            let rest = data.get(start + length..).unwrap_or_default();
+            let whitespace = rest.iter().take_while(|&&byte| is_whitespace(byte)).count();
+            rest[whitespace..].starts_with(b"endstream")
+        })
+        .map(|length| start + length)
+        .or_else(|| {
+            let end = start + find(data.get(start..)?, b"endstream")?;
+            Some(if data[..end].ends_with(b"\r\n") { end - 2 } else if data[..end].ends_with(b"\n") || data[..end].ends_with(b"\r") { end - 1 } else { end })
+        })?;
+
+    Some(Object::Stream(dictionary, data.get(start..end)?.to_vec()))
+}
+
+static NULL: Object = Object::Null;
+
+/// The objects of a PDF file by their number.
+struct PdfFile {
+    objects: HashMap<u32, Object>,
+    /// The dictionaries of the trailers and cross-reference streams.
+    trailers: Vec<Dictionary>,
+}
+
+impl PdfFile {
+    /// Reads every `number generation obj` in the file, and the objects packed into object
+    /// streams. The cross-reference table is not needed for this, which also makes damaged
+    /// files readable. When an object is defined more than once, as incremental updates do,
+    /// the last definition wins.
+    fn parse(data: &[u8]) -> Result<Self, PdfImportError> {
+        if find(&data[..data.len().min(1024)], b"%PDF-").is_none() {
+            return Err(PdfImportError::InvalidPdf);
+        }
+
+        let mut file = PdfFile { objects: HashMap::new(), trailers: Vec::new() };
+        let mut position = 0;
+        while let Some(offset) = find(&data[position..], b"obj") {
+            let keyword = position + offset;
+            position = keyword + 3;
+            if data.get(position).is_some_and(|&byte| is_regular(byte)) {
+                continue;
+            }
+            if let Some(number) = object_number(&data[..keyword])
+                && let Some(object) = parse_indirect_object(data, position)
+            {
+                file.objects.insert(number, object);
+            }
+        }
+
+        let mut position = 0;
+        while let Some(offset) = find(&data[position..], b"trailer") {
+            position += offset + 7;
+            if let Some(Object::Dictionary(trailer)) = Lexer::new(data, position).next_object() {
+                file.trailers.push(trailer);
+            }
+        }
+
+        let mut object_streams = Vec::new();
+        for object in file.objects.values() {
+            if let Object::Stream(dictionary, data) = object {
+                match file.name(dictionary.get(b"Type".as_slice()).unwrap_or(&NULL)) {
+                    Some(b"XRef") => file.trailers.push(dictionary.clone()),
+                    Some(b"ObjStm") => {
+                        let number = |key: &[u8]| file.get(dictionary, key).and_then(|value| file.number(value)).unwrap_or(0.0) as usize;
+                        object_streams.push((file.decode_stream(dictionary, data)?, number(b"N"), number(b"First")));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        for (data, count, first) in object_streams {
+            file.unpack_object_stream(&data, count, first);
+        }
+
+        if file.trailers.iter().any(|trailer| trailer.contains_key(b"Encrypt".as_slice())) {
+            return Err(PdfImportError::Encrypted);
+        }
+        Ok(file)
+    }
+
+    /// Adds the `count` objects of a decoded object stream that are not defined outside of
+    /// one. The stream starts with pairs of object numbers and offsets, where the offsets
+    /// count from `first`.
+    fn unpack_object_stream(&mut self, data: &[u8], count: usize, first: usize) {
+        let mut lexer = Lexer::new(data, 0);
+        let mut entries = Vec::with_capacity(count.min(data.len()));
+        for _ in 0..count {
+            let (Some(Object::Number(number)), Some(Object::Number(offset))) = (lexer.next_object(), lexer.next_object()) else {
+                break;
+            };
+            entries.push((number as u32, offset as usize));
+        }
+        for (number, offset) in entries {
+            if let Some(object) = Lexer::new(data, first + offset).next_object() {
+                self.objects.entry(number).or_insert(object);
+            }
+        }
+    }
+
+    /// Follows references until an object that is not one. Missing objects are null.
+    fn resolve<'a>(&'a self, mut object: &'a Object) -> &'a Object {
+        for _ in 0..MAX_REFERENCE_DEPTH {
+            match object {
+                Object::Reference(number) => object = self.objects.get(number).unwrap_or(&NULL),
+                _ => return object,
+            }
+        }
+        &NULL
+    }
+
+    fn get<'a>(&'a self, dictionary: &'a Dictionary, key: &[u8]) -> Option<&'a Object> {
+        dictionary.get(key).map(|object| self.resolve(object)).filter(|object| **object != Object::Null)
+    }
+
+    /// Looks up a key of a page, or of the first page tree node above it that has the key.
+    fn inherited<'a>(&'a self, page: &'a Dictionary, key: &[u8]) -> Option<&'a Object> {
+        let mut node = page;
+        for _ in 0..MAX_REFERENCE_DEPTH {
+            if let Some(object) = self.get(node, key) {
+                return Some(object);
+            }
+            node = self.get(node, b"Parent").and_then(|parent| self.dictionary(parent))?;
+        }
+        None
+    }
+
+    fn number(&self, object: &Object) -> Option<f64> {
+        match self.resolve(object) {
+            Object::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    fn numbers(&self, object: &Object) -> Option<Vec<f64>> {
+        match self.resolve(object) {
+            Object::Array(items) => items.iter().map(|item| self.number(item)).collect(),
+            _ => None,
+        }
+    }
+
+    fn name<'a>(&'a self, object: &'a Object) -> Option<&'a [u8]> {
+        match self.resolve(object) {
+            Object::Name(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// The dictionary of a dictionary or a stream.
+    fn dictionary<'a>(&'a self, object: &'a Object) -> Option<&'a Dictionary> {
+        match self.resolve(object) {
+            Object::Dictionary(dictionary) | Object::Stream(dictionary, _) => Some(dictionary),
+            _ => None,
+        }
+    }
+
+    /// The decoded data of a stream.
+    fn stream_data(&self, object: &Object) -> Result<Vec<u8>, PdfImportError> {
+        match self.resolve(object) {
+            Object::Stream(dictionary, data) => self.decode_stream(dictionary, data),
+            _ => Err(PdfImportError::InvalidPdf),
+        }
+    }
+
+    fn decode_stream(&self, dictionary: &Dictionary, data: &[u8]) -> Result<Vec<u8>, PdfImportError> {
+        let filters: Vec<&[u8]> = match self.get(dictionary, b"Filter") {
+            None => Vec::new(),
+            Some(Object::Array(filters)) => filters.iter().map(|filter| self.name(filter).unwrap_or_default()).collect(),
+            Some(filter) => vec![self.name(filter).unwrap_or_default()],
+        };
+        let predicted = |parameters: &Object| {
+            self.dictionary(parameters).and_then(|parameters| self.get(parameters, b"Predictor")).and_then(|predictor| self.number(predictor)).unwrap_or(1.0) > 1.0
+        };
+        let uses_predictor = match self.get(dictionary, b"DecodeParms") {
+            Some(Object::Array(parameters)) => parameters.iter().any(predicted),
+            Some(parameters) => predicted(parameters),
+            None => false,
+        };
+        if uses_predictor || filters.iter().any(|filter| *filter != b"FlateDecode" && *filter != b"Fl") {
+            return Err(PdfImportError::UnsupportedFilter);
+        }
+
+        let mut data = data.to_vec();
+        for _ in filters {
+            let mut decoded = Vec::new();
+            ZlibDecoder::new(data.as_slice()).read_to_end(&mut decoded).map_err(|_| PdfImportError::InvalidPdf)?;
+            data = decoded;
+        }
+        Ok(data)
+    }
+
+    /// The pages in order, found by walking the page tree from the document catalog.
+    fn pages(&self) -> Result<Vec<&Dictionary>, PdfImportError> {
+        // Incremental updates append trailers, so the last one is the current one.
+        let catalog = self
+            .trailers
+            .iter()
+            .rev()
+            .find_map(|trailer| self.get(trailer, b"Root"))
+            .or_else(|| {
+                self.objects.values().find(|object| self.dictionary(object).is_some_and(|dictionary| {
+                    self.get(dictionary, b"Type").and_then(|kind| self.name(kind)) == Some(b"Catalog")
+                }))
+            })
+            .and_then(|catalog| self.dictionary(catalog))
+            .ok_or(PdfImportError::InvalidPdf)?;
+        let root = catalog.get(b"Pages".as_slice()).ok_or(PdfImportError::InvalidPdf)?;
+
+        let mut pages = Vec::new();
+        let mut visited = HashSet::new();
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            if let Object::Reference(number) = node
+                && !visited.insert(*number)
+            {
+                continue;
+            }
+            let Some(dictionary) = self.dictionary(node) else {
+                continue;
+            };
+            match self.get(dictionary, b"Kids") {
+                Some(Object::Array(kids)) => stack.extend(kids.iter().rev()),
+                _ => pages.push(dictionary),
+            }
+        }
+        Ok(pages)
+    }
+}
+
+/// Lines of width 0 are drawn as thin as the device allows; they are given this width in
+/// points.
+const HAIRLINE_WIDTH: f64 = 0.25;
+
+#[derive(Debug, Clone)]
+struct GraphicsState {
+    /// Maps user space into document units.
+    ctm: Affine,
+    fill: RgbaF32,
+    stroke: RgbaF32,
+    line_width: f64,
+}
+
+impl Default for GraphicsState {
+    fn default() -> Self {
+        GraphicsState { ctm: Affine::IDENTITY, fill: RgbaF32(0.0, 0.0, 0.0, 1.0), stroke: RgbaF32(0.0, 0.0, 0.0, 1.0), line_width: 1.0 }
+    }
+}
+
+/// Runs content streams, drawing their paths onto a canvas.
+struct Interpreter<'a> {
+    file: &'a PdfFile,
+    canvas: Canvas,
+    state: GraphicsState,
+    saved_states: Vec<GraphicsState>,
+    /// The current point in document units, or `None` outside of a path.
+    current: Option<Point>,
+    subpath_start: Point,
+    /// The kinds of skipped content that were warned about, so that each is reported once.
+    warned: HashSet<&'static str>,
+}
+
+impl<'a> Interpreter<'a> {
+    fn run(&mut self, content: &[u8], resources: Option<&'a Dictionary>, depth: usize) -> Result<(), PdfImportError> {
+        let mut lexer = Lexer::new(content, 0);
+        let mut operands = Vec::new();
+        while let Some(token) = lexer.next_token() {
+            match token {
+                Token::Object(object) => operands.push(object),
+                Token::Operator(b"BI") => {
+                    lexer.skip_inline_image();
+                    self.skip("inline images");
+                    operands.clear();
+                }
+                Token::Operator(operator) => {
+                    self.execute(operator, &operands, resources, depth)?;
+                    operands.clear();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, operator: &[u8], operands: &[Object], resources: Option<&'a Dictionary>, depth: usize) -> Result<(), PdfImportError> {
+        let file = self.file;
+        let numbers: Vec<f64> = operands.iter().filter_map(|operand| file.number(operand)).collect();
+        let point = |index: usize| self.state.ctm.apply(Point::new(Unit(numbers[index]), Unit(numbers[index + 1])));
+        let resource = |category: &[u8]| {
+            let Some(Object::Name(name)) = operands.first() else {
+                return None;
+            };
+            let resources = file.get(resources?, category).and_then(|category| file.dictionary(category))?;
+            file.get(resources, name)
+        };
+
+        match (operator, numbers.len()) {
+            (b"q", _) => self.saved_states.push(self.state.clone()),
+            (b"Q", _) => {
+                if let Some(state) = self.saved_states.pop() {
+                    self.state = state;
+                }
+            }
+            (b"cm", 6) => {
+                let matrix = Affine([numbers[0], numbers[1], numbers[2], numbers[3], numbers[4], numbers[5]]);
+                self.state.ctm = matrix.then(&self.state.ctm);
+            }
+            (b"w", 1) => self.state.line_width = numbers[0],
+
+            (b"m", 2) => self.move_to(point(0)),
+            (b"l", 2) => self.line_to(point(0)),
+            (b"c", 6) => self.curve_to(point(0), point(2), point(4)),
+            (b"v", 4) => {
+                let control_point_0 = self.current.unwrap_or(point(0));
+                self.curve_to(control_point_0, point(0), point(2));
+            }
+            (b"y", 4) => self.curve_to(point(0), point(2), point(2)),
+            (b"h", _) => self.close(),
+            (b"re", 4) => {
+                let [x, y, width, height] = [numbers[0], numbers[1], numbers[2], numbers[3]];
+                let corner = |x: f64, y: f64| self.state.ctm.apply(Point::new(Unit(x), Unit(y)));
+                let corners = [corner(x, y), corner(x + width, y), corner(x + width, y + height), corner(x, y + height)];
+                self.move_to(corners[0]);
+                for corner in &corners[1..] {
+                    self.line_to(*corner);
+                }
+                self.close();
+            }
+
+            (b"f" | b"F" | b"f*", _) => self.paint(true, false),
+            (b"S", _) => self.paint(false, true),
+            (b"s", _) => {
+                self.close();
+                self.paint(false, true);
+            }
+            (b"B" | b"B*", _) => self.paint(true, true),
+            (b"b" | b"b*", _) => {
+                self.close();
+                self.paint(true, true);
+            }
+            (b"n", _) => self.end_path(),
+            (b"W" | b"W*", _) => self.skip("clipping paths"),
+
+            (b"g", 1) => self.state.fill = with_alpha(device_color(&numbers), self.state.fill.3),
+            (b"G", 1) => self.state.stroke = with_alpha(device_color(&numbers), self.state.stroke.3),
+            (b"rg", 3) | (b"k", 4) => self.state.fill = with_alpha(device_color(&numbers), self.state.fill.3),
+            (b"RG", 3) | (b"K", 4) => self.state.stroke = with_alpha(device_color(&numbers), self.state.stroke.3),
+            // Only the number of components tells the color spaces that are set apart.
+            (b"sc" | b"scn", 1 | 3 | 4) if numbers.len() == operands.len() => {
+                self.state.fill = with_alpha(device_color(&numbers), self.state.fill.3);
+            }
+            (b"SC" | b"SCN", 1 | 3 | 4) if numbers.len() == operands.len() => {
+                self.state.stroke = with_alpha(device_color(&numbers), self.state.stroke.3);
+            }
+            (b"sc" | b"scn" | b"SC" | b"SCN", _) => self.skip("patterns"),
+            (b"cs", _) => self.state.fill = with_alpha(device_color(&[0.0]), self.state.fill.3),
+            (b"CS", _) => self.state.stroke = with_alpha(device_color(&[0.0]), self.state.stroke.3),
+            (b"gs", _) => {
+                if let Some(parameters) = resource(b"ExtGState").and_then(|parameters| file.dictionary(parameters)) {
+                    let parameter = |key: &[u8]| file.get(parameters, key).and_then(|value| file.number(value));
+                    if let Some(width) = parameter(b"LW") {
+                        self.state.line_width = width;
+                    }
+                    if let Some(alpha) = parameter(b"ca") {
+                        self.state.fill.3 = alpha as f32;
+                    }
+                    if let Some(alpha) = parameter(b"CA") {
+                        self.state.stroke.3 = alpha as f32;
+                    }
+                }
+            }
+            (b"d", _) => {
+                if matches!(operands.first().map(|dashes| file.resolve(dashes)), Some(Object::Array(dashes)) if !dashes.is_empty()) {
+                    self.skip("dashes of strokes");
+                }
+            }
+
+            (b"Do", _) => {
+                let Some(xobject @ Object::Stream(dictionary, _)) = resource(b"XObject") else {
+                    return Ok(());
+                };
+                match file.get(dictionary, b"Subtype").and_then(|subtype| file.name(subtype)) {
+                    Some(b"Form") if depth < MAX_FORM_DEPTH => {
+                        let content = file.stream_data(xobject)?;
+                        let matrix = match file.get(dictionary, b"Matrix").and_then(|matrix| file.numbers(matrix)).as_deref() {
+                            Some(&[a, b, c, d, e, f]) => Affine([a, b, c, d, e, f]),
+                            _ => Affine::IDENTITY,
+                        };
+                        let form_resources = file.get(dictionary, b"Resources").and_then(|resources| file.dictionary(resources)).or(resources);
+
+                        let saved_state = self.state.clone();
+                        let saved_depth = self.saved_states.len();
+                        self.state.ctm = matrix.then(&self.state.ctm);
+                        self.run(&content, form_resources, depth + 1)?;
+                        self.end_path();
+                        self.saved_states.truncate(saved_depth);
+                        self.state = saved_state;
+                    }
+                    Some(b"Image") => self.skip("images"),
+                    _ => {}
+                }
+            }
+            (b"BT", _) => self.skip("text"),
+            (b"sh", _) => self.skip("shadings"),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn skip(&mut self, content: &'static str) {
+        if self.warned.insert(content) {
+            log_warning!("skipped {} of the PDF page", content);
+        }
+    }
+
+    fn move_to(&mut self, point: Point) {
+        self.canvas.move_to(point.x.0, point.y.0);
+        self.current = Some(point);
+        self.subpath_start = point;
+    }
+
+    fn line_to(&mut self, point: Point) {
+        self.canvas.line_to(point.x.0, point.y.0);
+        self.current = Some(point);
+    }
+
+    fn curve_to(&mut self, control_point_0: Point, control_point_1: Point, point: Point) {
+        self.canvas.curve_to(control_point_0.x.0, control_point_0.y.0, control_point_1.x.0, control_point_1.y.0, point.x.0, point.y.0);
+        self.current = Some(point);
+    }
+
+    fn close(&mut self) {
+        self.canvas.close();
+        if self.current.is_some() {
+            self.current = Some(self.subpath_start);
+        }
+    }
+
+    /// Draws the current path with the fill and stroke of the graphics state, and ends it.
+    fn paint(&mut self, fill: bool, stroke: bool) {
+        if fill {
+            self.canvas.set_fill(self.state.fill);
+        } else {
+            self.canvas.set_no_fill();
+        }
+        if stroke {
+            // Strokes are as wide as the line width measured in user space, on average over
+            // the directions of a non-uniform transform.
+            let width = self.state.line_width * self.state.ctm.determinant().abs().sqrt();
+            self.canvas.set_stroke(self.state.stroke, if width > 0.0 { width } else { HAIRLINE_WIDTH });
+        } else {
+            self.canvas.set_no_stroke();
+        }
+        self.canvas.fill_and_stroke();
+        self.end_path();
+    }
+
+    fn end_path(&mut self) {
+        self.canvas.begin_path();
+        self.current = None;
+    }
+}
+
+/// Converts the components of a DeviceGray, DeviceRGB or DeviceCMYK color to an opaque color.
+fn device_color(components: &[f64]) -> RgbaF32 {
+    let channel = |value: f64| value.clamp(0.0, 1.0) as f32;
+    match *components {
+        [gray] => RgbaF32(channel(gray), channel(gray), channel(gray), 1.0),
+        [red, green, blue] => RgbaF32(channel(red), channel(green), channel(blue), 1.0),
+        [cyan, magenta, yellow, black] => {
+            let white = 1.0 - channel(black);
+            RgbaF32((1.0 - channel(cyan)) * white, (1.0 - channel(magenta)) * white, (1.0 - channel(yellow)) * white, 1.0)
+        }
+        _ => RgbaF32(0.0, 0.0, 0.0, 1.0),
+    }
+}
+
+fn with_alpha(color: RgbaF32, alpha: f32) -> RgbaF32 {
+    RgbaF32(color.0, color.1, color.2, alpha)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pdf_page_count, pdf_page_to_tinyvg, PdfImportError};
+    use crate::color_table::RgbaF32;
+    use crate::commands::DrawCommand;
+    use crate::common::Unit;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    /// A PDF file with the given objects, numbered from 1, and a trailer with the catalog
+    /// as object 1. There is no cross-reference table, which the importer does not need.
+    fn pdf(objects: &[&[u8]]) -> Vec<u8> {
+        let mut file = b"%PDF-1.7\n".to_vec();
+        for (index, object) in objects.iter().enumerate() {
+            file.extend(format!("{} 0 obj\n", index + 1).as_bytes());
+            file.extend(*object);
+            file.extend(b"\nendobj\n");
+        }
+        file.extend(b"trailer\n<< /Root 1 0 R >>\n%%EOF\n");
+        file
+    }
+
+    fn stream(dictionary: &str, data: &[u8]) -> Vec<u8> {
+        let mut object = format!("<< {dictionary} /Length {} >>\nstream\n", data.len()).into_bytes();
+        object.extend(data);
+        object.extend(b"\nendstream");
+        object
+    }
+
+    /// A one page file whose page draws `content` on a 100 by 50 point media box.
+    fn single_page(page: &str, content: &[u8]) -> Vec<u8> {
+        let page = format!("<< /Type /Page /Parent 2 0 R /MediaBox [0 0 100 50] /Contents 4 0 R {page} >>");
+        pdf(&[b"<< /Type /Catalog /Pages 2 0 R >>", b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>", page.as_bytes(), &stream("", content)])
+    }
+
+    fn bounds(document: &crate::TinyVg) -> (f64, f64, f64, f64) {
+        let bounds = document.content_bounds().unwrap();
+        (bounds.x.0, bounds.y.0, bounds.width.0, bounds.height.0)
+    }
+
+    #[test]
+    fn filled_rectangles_are_flipped_to_the_top_left_origin() {
+        let document = pdf_page_to_tinyvg(&single_page("", b"1 0 0 rg 10 5 30 20 re f"), 0).unwrap();
+        assert_eq!((document.header.width, document.header.height), (100, 50));
+        assert_eq!(document.color_table, vec![RgbaF32(1.0, 0.0, 0.0, 1.0)]);
+        assert!(matches!(document.draw_commands.as_slice(), [DrawCommand::FillPath(_)]));
+        assert_eq!(bounds(&document), (10.0, 25.0, 30.0, 20.0));
+    }
+
+    #[test]
+    fn strokes_keep_their_width_and_color() {
+        let document = pdf_page_to_tinyvg(&single_page("", b"0 0 1 RG 4 w 10 10 m 90 10 l S"), 0).unwrap();
+        match document.draw_commands.as_slice() {
+            [DrawCommand::DrawLinePath(data)] => assert_eq!(data.line_width, Unit(4.0)),
+            commands => panic!("unexpected commands {commands:?}"),
+        }
+        assert_eq!(document.color_table, vec![RgbaF32(0.0, 0.0, 1.0, 1.0)]);
+    }
+
+    #[test]
+    fn rotated_pages_swap_their_size() {
+        let document = pdf_page_to_tinyvg(&single_page("/Rotate 90", b"0 g 0 0 10 50 re f"), 0).unwrap();
+        assert_eq!((document.header.width, document.header.height), (50, 100));
+        assert_eq!(bounds(&document), (0.0, 0.0, 50.0, 10.0));
+    }
+
+    #[test]
+    fn compressed_content_is_decoded() {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"0 g 0 0 100 50 re f").unwrap();
+        let content = stream("/Filter /FlateDecode", &encoder.finish().unwrap());
+        let page = b"<< /Type /Page /Parent 2 0 R /MediaBox [0 0 100 50] /Contents 4 0 R >>";
+        let file = pdf(&[b"<< /Type /Catalog /Pages 2 0 R >>", b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>", page, &content]);
+        assert_eq!(bounds(&pdf_page_to_tinyvg(&file, 0).unwrap()), (0.0, 0.0, 100.0, 50.0));
+    }
+
+    #[test]
+    fn forms_are_drawn_and_cannot_recurse_forever() {
+        // The form draws a square and then itself again.
+        let form = stream("/Type /XObject /Subtype /Form /BBox [0 0 10 10] /Resources << /XObject << /F 5 0 R >> >>", b"0 0 10 10 re f /F Do");
+        let page = b"<< /Type /Page /Parent 2 0 R /MediaBox [0 0 100 50] /Contents 4 0 R /Resources << /XObject << /F 5 0 R >> >> >>";
+        let file = pdf(&[b"<< /Type /Catalog /Pages 2 0 R >>", b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>", page, &stream("", b"/F Do"), &form]);
+        let document = pdf_page_to_tinyvg(&file, 0).unwrap();
+        assert!(!document.draw_commands.is_empty() && document.draw_commands.len() <= super::MAX_FORM_DEPTH + 1);
+    }
+
+    #[test]
+    fn pages_are_counted_through_the_page_tree() {
+        let page = |number: u32| format!("<< /Type /Page /Parent {number} 0 R /MediaBox [0 0 10 10] >>");
+        let (page_4, page_5) = (page(3), page(2));
+        let file = pdf(&[
+            b"<< /Type /Catalog /Pages 2 0 R >>",
+            b"<< /Type /Pages /Kids [3 0 R 5 0 R 2 0 R] /Count 3 >>",
+            b"<< /Type /Pages /Parent 2 0 R /Kids [4 0 R] /Count 1 >>",
+            page_4.as_bytes(),
+            page_5.as_bytes(),
+        ]);
+        // The page tree refers back to its root, which is not walked twice.
+        assert_eq!(pdf_page_count(&file), Ok(2));
+        assert_eq!(pdf_page_to_tinyvg(&file, 2).err(), Some(PdfImportError::PageNotFound));
+    }
+
+    #[test]
+    fn unsupported_files_are_rejected() {
+        assert_eq!(pdf_page_count(b"not a pdf"), Err(PdfImportError::InvalidPdf));
+
+        let mut encrypted = single_page("", b"");
+        encrypted.extend(b"trailer\n<< /Root 1 0 R /Encrypt 9 0 R >>\n");
+        assert_eq!(pdf_page_count(&encrypted), Err(PdfImportError::Encrypted));
+
+        let hex = stream("/Filter /ASCIIHexDecode", b"30206720>");
+        let page = b"<< /Type /Page /Parent 2 0 R /Contents 4 0 R >>";
+        let file = pdf(&[b"<< /Type /Catalog /Pages 2 0 R >>", b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>", page, &hex]);
+        assert_eq!(pdf_page_to_tinyvg(&file, 0).err(), Some(PdfImportError::UnsupportedFilter));
+    }
+}
@@ -0,0 +1,214 @@
+use crate::render::RgbaImage;
+use flate2::read::ZlibDecoder;
+use std::io::Read;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// The largest number of pixels that is decoded, to keep corrupt headers from
+/// allocating unbounded memory.
+const MAX_PIXELS: u64 = 1 << 26;
+
+/// The column and row of the first pixel of each Adam7 pass, and the distance between
+/// the pixels of the pass along each axis.
+const ADAM7_PASSES: [(usize, usize, usize, usize); 7] = [(0, 0, 8, 8), (4, 0, 8, 8), (0, 4, 4, 8), (2, 0, 4, 4), (0, 2, 2, 4), (1, 0, 2, 2), (0, 1, 1, 2)];
+
+struct PngHeader {
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+impl PngHeader {
+    fn channels(&self) -> usize {
+        match self.color_type {
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1,
+        }
+    }
+
+    fn bits_per_pixel(&self) -> usize {
+        self.channels() * self.bit_depth as usize
+    }
+}
+
+/// Decodes a PNG image of any color type, bit depth and interlacing into 8-bit RGBA.
+/// Returns `None` if the data is not a valid PNG. Ancillary chunks other than the
+/// transparency chunk, such as gamma, are ignored.
+pub(crate) fn decode_png(data: &[u8]) -> Option<RgbaImage> {
+    let mut rest = data.strip_prefix(&SIGNATURE)?;
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut transparency: &[u8] = &[];
+    let mut compressed = Vec::new();
+
+    while rest.len() >= 12 {
+        let length = u32::from_be_bytes(rest[..4].try_into().ok()?) as usize;
+        let kind = &rest[4..8];
+        let chunk = rest.get(8..8 + length)?;
+        rest = rest.get(12 + length..)?;
+
+        match kind {
+            b"IHDR" if chunk.len() == 13 => {
+                let header = header.insert(PngHeader {
+                    width: u32::from_be_bytes(chunk[0..4].try_into().ok()?) as usize,
+                    height: u32::from_be_bytes(chunk[4..8].try_into().ok()?) as usize,
+                    bit_depth: chunk[8],
+                    color_type: chunk[9],
+                    interlaced: chunk[12] == 1,
+                });
+                let valid_depth = match header.color_type {
+                    0 => matches!(header.bit_depth, 1 | 2 | 4 | 8 | 16),
+                    3 => matches!(header.bit_depth, 1 | 2 | 4 | 8),
+                    2 | 4 | 6 => matches!(header.bit_depth, 8 | 16),
+                    _ => false,
+                };
+                if !valid_depth || header.width as u64 * header.height as u64 > MAX_PIXELS {
+                    return None;
+                }
+            }
+            b"PLTE" => palette = chunk,
+            b"tRNS" => transparency = chunk,
+            b"IDAT" => compressed.extend_from_slice(chunk),
+            b"IEND" => break,
+            _ => {}
+        }
+    }
+
+    let header = header?;
+    if header.color_type == 3 && palette.is_empty() {
+        return None;
+    }
+
+    let passes: &[(usize, usize, usize, usize)] = if header.interlaced { &ADAM7_PASSES } else { &[(0, 0, 1, 1)] };
+    // The columns, rows and bytes per scanline of each pass. Passes without pixels are
+    // left out of the data entirely.
+    let layouts: Vec<(usize, usize, usize)> = passes
+        .iter()
+        .map(|&(first_x, first_y, step_x, step_y)| {
+            let columns = header.width.saturating_sub(first_x).div_ceil(step_x);
+            let rows = header.height.saturating_sub(first_y).div_ceil(step_y);
+            (columns, rows, (columns * header.bits_per_pixel()).div_ceil(8))
+        })
+        .collect();
+    let expected: usize = layouts.iter().filter(|layout| layout.0 > 0).map(|&(_, rows, stride)| (stride + 1) * rows).sum();
+
+    let mut filtered = Vec::with_capacity(expected);
+    ZlibDecoder::new(compressed.as_slice()).take(expected as u64).read_to_end(&mut filtered).ok()?;
+
+    let mut image = RgbaImage::new(header.width as u32, header.height as u32);
+    let mut input = filtered.as_slice();
+    for (&(first_x, first_y, step_x, step_y), &(columns, rows, stride)) in passes.iter().zip(&layouts) {
+        if columns == 0 || rows == 0 {
+            continue;
+        }
+
+        let pass = input.get(..(stride + 1) * rows)?;
+        input = &input[pass.len()..];
+        let scanlines = unfilter(pass, stride, header.bits_per_pixel().div_ceil(8))?;
+
+        for (row, scanline) in scanlines.chunks_exact(stride).enumerate() {
+            for column in 0..columns {
+                let pixel = decode_pixel(&header, scanline, column, palette, transparency);
+                let (x, y) = (first_x + column * step_x, first_y + row * step_y);
+                let start = (y * header.width + x) * 4;
+                image.data[start..start + 4].copy_from_slice(&pixel);
+            }
+        }
+    }
+
+    Some(image)
+}
+
+/// Reverses the per scanline filters, where each scanline of `data` is prefixed with its
+/// filter type. `distance` is the number of bytes between corresponding bytes of
+/// neighbouring pixels.
+fn unfilter(data: &[u8], stride: usize, distance: usize) -> Option<Vec<u8>> {
+    let mut scanlines = vec![0u8; data.len() / (stride + 1) * stride];
+    let mut previous = vec![0u8; stride];
+
+    for (row, line) in data.chunks_exact(stride + 1).enumerate() {
+        let current = &mut scanlines[row * stride..(row + 1) * stride];
+        current.copy_from_slice(&line[1..]);
+        for i in 0..stride {
+            let left = if i >= distance { current[i - distance] } else { 0 };
+            let up = previous[i];
+            let up_left = if i >= distance { previous[i - distance] } else { 0 };
+            let predictor = match line[0] {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return None,
+            };
+            current[i] = current[i].wrapping_add(predictor);
+        }
+        previous.copy_from_slice(current);
+    }
+
+    Some(scanlines)
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let (distance_left, distance_up, distance_up_left) =
+        ((estimate - left as i16).abs(), (estimate - up as i16).abs(), (estimate - up_left as i16).abs());
+    if distance_left <= distance_up && distance_left <= distance_up_left {
+        left
+    } else if distance_up <= distance_up_left {
+        up
+    } else {
+        up_left
+    }
+}
+
+/// Reads sample `index` of a scanline at the image's bit depth.
+fn sample(scanline: &[u8], index: usize, bit_depth: u8) -> u16 {
+    match bit_depth {
+        16 => u16::from_be_bytes([scanline[index * 2], scanline[index * 2 + 1]]),
+        8 => scanline[index] as u16,
+        _ => {
+            let bit = index * bit_depth as usize;
+            let shift = 8 - bit_depth as usize - bit % 8;
+            ((scanline[bit / 8] >> shift) & ((1 << bit_depth) - 1)) as u16
+        }
+    }
+}
+
+fn decode_pixel(header: &PngHeader, scanline: &[u8], column: usize, palette: &[u8], transparency: &[u8]) -> [u8; 4] {
+    let depth = header.bit_depth;
+    let first = column * header.channels();
+    let raw = |channel: usize| sample(scanline, first + channel, depth);
+    let to_byte = |value: u16| match depth {
+        16 => (value >> 8) as u8,
+        _ => (value as u32 * 255 / ((1 << depth) - 1)) as u8,
+    };
+    // The transparency chunk stores one 16-bit sample per channel for the single color
+    // that is fully transparent.
+    let transparent = |channels: usize| {
+        transparency.len() >= channels * 2
+            && (0..channels).all(|channel| u16::from_be_bytes([transparency[channel * 2], transparency[channel * 2 + 1]]) == raw(channel))
+    };
+
+    match header.color_type {
+        0 => {
+            let gray = to_byte(raw(0));
+            [gray, gray, gray, if transparent(1) { 0 } else { 255 }]
+        }
+        2 => [to_byte(raw(0)), to_byte(raw(1)), to_byte(raw(2)), if transparent(3) { 0 } else { 255 }],
+        3 => {
+            let index = raw(0) as usize;
+            let color = palette.get(index * 3..index * 3 + 3).unwrap_or(&[0, 0, 0]);
+            [color[0], color[1], color[2], transparency.get(index).copied().unwrap_or(255)]
+        }
+        4 => {
+            let gray = to_byte(raw(0));
+            [gray, gray, gray, to_byte(raw(1))]
+        }
+        _ => [to_byte(raw(0)), to_byte(raw(1)), to_byte(raw(2)), to_byte(raw(3))],
+    }
+}
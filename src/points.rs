@@ -0,0 +1,165 @@
+use crate::commands::{DrawCommand, Line, Path, PathCommand, Point, Points, Rectangle, Style};
+use crate::common::Unit;
+use crate::TinyVg;
+
+impl TinyVg {
+    /// Every point stored in the draw commands: vertices, line ends, path points including
+    /// control points and arc targets, gradient points and text hint centers. Rectangles and
+    /// horizontal and vertical path lines store single coordinates instead of points and are
+    /// left out, see `for_each_point_mut` to visit those as well.
+    pub fn points_mut(&mut self) -> impl Iterator<Item = &mut Point> {
+        let mut points = Vec::new();
+        for command in &mut self.draw_commands {
+            let (styles, geometry) = command_parts(command);
+            for style in styles {
+                points.extend(style_points_mut(style));
+            }
+            match geometry {
+                Geometry::Points(vertices) => points.extend(vertices.iter_mut()),
+                Geometry::Lines(lines) => points.extend(lines.iter_mut().flat_map(|line| [&mut line.start, &mut line.end])),
+                Geometry::Rectangles(_) => {}
+                Geometry::Path(path) => {
+                    for segment in &mut path.segments {
+                        points.push(&mut segment.start);
+                        for path_command in &mut segment.path_commands {
+                            match path_command {
+                                PathCommand::Line(point, _) => points.push(point),
+                                PathCommand::CubicBezier(cubic, _) => {
+                                    points.extend([&mut cubic.control_point_0, &mut cubic.control_point_1, &mut cubic.point_1])
+                                }
+                                PathCommand::QuadraticBezier(quadratic, _) => points.extend([&mut quadratic.control_point, &mut quadratic.point_1]),
+                                PathCommand::ArcCircle(arc, _) => points.push(&mut arc.target),
+                                PathCommand::ArcEllipse(arc, _) => points.push(&mut arc.target),
+                                PathCommand::HorizontalLine(..) | PathCommand::VerticalLine(..) | PathCommand::ClosePath(_) => {}
+                            }
+                        }
+                    }
+                }
+                Geometry::Point(point) => points.push(point),
+            }
+        }
+        points.into_iter()
+    }
+
+    /// Calls `f` on every coordinate of the draw commands, each given as a point, for
+    /// transforms, rounding passes and unit conversions that should not match on every kind
+    /// of command. Besides the points of `points_mut`, this visits the top left and bottom
+    /// right corners of rectangles and the end points of horizontal and vertical path lines.
+    /// These keep only what they store of the changed points, so rectangles stay axis
+    /// aligned and horizontal lines horizontal.
+    ///
+    /// Line widths, arc radii and rotations, and text heights are not coordinates and are not
+    /// visited, so arcs keep their shape and sweep direction under scaling or mirroring.
+    pub fn for_each_point_mut(&mut self, mut f: impl FnMut(&mut Point)) {
+        for command in &mut self.draw_commands {
+            let (styles, geometry) = command_parts(command);
+            for style in styles {
+                style_points_mut(style).for_each(&mut f);
+            }
+            match geometry {
+                Geometry::Points(vertices) => vertices.iter_mut().for_each(&mut f),
+                Geometry::Lines(lines) => lines.iter_mut().for_each(|line| {
+                    f(&mut line.start);
+                    f(&mut line.end);
+                }),
+                Geometry::Rectangles(rectangles) => {
+                    for rectangle in rectangles {
+                        let mut top_left = Point::new(rectangle.x, rectangle.y);
+                        let mut bottom_right = Point::new(rectangle.x + rectangle.width, rectangle.y + rectangle.height);
+                        f(&mut top_left);
+                        f(&mut bottom_right);
+                        rectangle.x = Unit(top_left.x.0.min(bottom_right.x.0));
+                        rectangle.y = Unit(top_left.y.0.min(bottom_right.y.0));
+                        rectangle.width = Unit((bottom_right.x.0 - top_left.x.0).abs());
+                        rectangle.height = Unit((bottom_right.y.0 - top_left.y.0).abs());
+                    }
+                }
+                Geometry::Path(path) => for_each_path_point_mut(path, &mut f),
+                Geometry::Point(point) => f(point),
+            }
+        }
+    }
+}
+
+/// The geometry of a command, apart from its styles.
+enum Geometry<'a> {
+    Points(&'a mut Points),
+    Lines(&'a mut Vec<Line>),
+    Rectangles(&'a mut Vec<Rectangle>),
+    Path(&'a mut Path),
+    Point(&'a mut Point),
+}
+
+fn command_parts(command: &mut DrawCommand) -> (Vec<&mut Style>, Geometry<'_>) {
+    match command {
+        DrawCommand::FillPolygon(data) => (vec![&mut data.style], Geometry::Points(&mut data.points)),
+        DrawCommand::FillRectangles(data) => (vec![&mut data.style], Geometry::Rectangles(&mut data.rectangles)),
+        DrawCommand::FillPath(data) => (vec![&mut data.style], Geometry::Path(&mut data.path)),
+        DrawCommand::DrawLines(data) => (vec![&mut data.line_style], Geometry::Lines(&mut data.lines)),
+        DrawCommand::DrawLineLoop(data) => (vec![&mut data.line_style], Geometry::Points(&mut data.points)),
+        DrawCommand::DrawLineStrip(data) => (vec![&mut data.style], Geometry::Points(&mut data.points)),
+        DrawCommand::DrawLinePath(data) => (vec![&mut data.style], Geometry::Path(&mut data.path)),
+        DrawCommand::OutlineFillPolygon(data) => (vec![&mut data.fill_style, &mut data.line_style], Geometry::Points(&mut data.points)),
+        DrawCommand::OutlineFillRectangles(data) => {
+            (vec![&mut data.fill_style, &mut data.line_style], Geometry::Rectangles(&mut data.rectangles))
+        }
+        DrawCommand::OutlineFillPath(data) => (vec![&mut data.fill_style, &mut data.line_style], Geometry::Path(&mut data.path)),
+        DrawCommand::TextHint(data) => (Vec::new(), Geometry::Point(&mut data.center)),
+    }
+}
+
+fn style_points_mut(style: &mut Style) -> impl Iterator<Item = &mut Point> {
+    let points = match style {
+        Style::FlatColor(_) => None,
+        Style::LinearGradient(gradient) => Some([&mut gradient.point_0, &mut gradient.point_1]),
+        Style::RadialGradient(gradient) => Some([&mut gradient.point_0, &mut gradient.point_1]),
+    };
+    points.into_iter().flatten()
+}
+
+/// Visits the points of a path. Horizontal and vertical lines are visited as points at the
+/// height or position of the line's start, which is tracked through the unchanged path.
+fn for_each_path_point_mut(path: &mut Path, f: &mut impl FnMut(&mut Point)) {
+    for segment in &mut path.segments {
+        let start = segment.start;
+        let mut current = start;
+        f(&mut segment.start);
+
+        for path_command in &mut segment.path_commands {
+            current = match path_command {
+                PathCommand::Line(point, _) => visit(f, point),
+                PathCommand::HorizontalLine(x, _) => {
+                    let mut point = Point::new(*x, current.y);
+                    f(&mut point);
+                    *x = point.x;
+                    Point::new(*x, current.y)
+                }
+                PathCommand::VerticalLine(y, _) => {
+                    let mut point = Point::new(current.x, *y);
+                    f(&mut point);
+                    *y = point.y;
+                    Point::new(current.x, *y)
+                }
+                PathCommand::CubicBezier(cubic, _) => {
+                    f(&mut cubic.control_point_0);
+                    f(&mut cubic.control_point_1);
+                    visit(f, &mut cubic.point_1)
+                }
+                PathCommand::QuadraticBezier(quadratic, _) => {
+                    f(&mut quadratic.control_point);
+                    visit(f, &mut quadratic.point_1)
+                }
+                PathCommand::ArcCircle(arc, _) => visit(f, &mut arc.target),
+                PathCommand::ArcEllipse(arc, _) => visit(f, &mut arc.target),
+                PathCommand::ClosePath(_) => start,
+            };
+        }
+    }
+}
+
+/// Calls `f` on an end point and returns the point as it was before.
+fn visit(f: &mut impl FnMut(&mut Point), point: &mut Point) -> Point {
+    let end = *point;
+    f(point);
+    end
+}
@@ -0,0 +1,379 @@
+use crate::commands::{DrawCommand, Path, PathCommand, Point, Style};
+use crate::common::Unit;
+use crate::header::CoordinateRange;
+use crate::TinyVg;
+
+/// Calls `f` with every value of the command that is encoded as a unit: coordinates,
+/// sizes, line widths, radii and rotations.
+pub(crate) fn for_each_unit(command: &DrawCommand, f: &mut impl FnMut(Unit)) {
+    match command {
+        DrawCommand::FillPolygon(data) => {
+            style_units(&data.style, f);
+            data.points.iter().for_each(|point| point_units(point, f));
+        }
+        DrawCommand::FillRectangles(data) => {
+            style_units(&data.style, f);
+            for rectangle in &data.rectangles {
+                [rectangle.x, rectangle.y, rectangle.width, rectangle.height].into_iter().for_each(&mut *f);
+            }
+        }
+        DrawCommand::FillPath(data) => {
+            style_units(&data.style, f);
+            path_units(&data.path, f);
+        }
+        DrawCommand::DrawLines(data) => {
+            style_units(&data.line_style, f);
+            f(data.line_width);
+            for line in &data.lines {
+                point_units(&line.start, f);
+                point_units(&line.end, f);
+            }
+        }
+        DrawCommand::DrawLineLoop(data) => {
+            style_units(&data.line_style, f);
+            f(data.line_width);
+            data.points.iter().for_each(|point| point_units(point, f));
+        }
+        DrawCommand::DrawLineStrip(data) => {
+            style_units(&data.style, f);
+            f(data.line_width);
+            data.points.iter().for_each(|point| point_units(point, f));
+        }
+        DrawCommand::DrawLinePath(data) => {
+            style_units(&data.style, f);
+            f(data.line_width);
+            path_units(&data.path, f);
+        }
+        DrawCommand::OutlineFillPolygon(data) => {
+            style_units(&data.fill_style, f);
+            style_units(&data.line_style, f);
+            f(data.line_width);
+            data.points.iter().for_each(|point| point_units(point, f));
+        }
+        DrawCommand::OutlineFillRectangles(data) => {
+            style_units(&data.fill_style, f);
+            style_units(&data.line_style, f);
+            f(data.line_width);
+            for rectangle in &data.rectangles {
+                [rectangle.x, rectangle.y, rectangle.width, rectangle.height].into_iter().for_each(&mut *f);
+            }
+        }
+        DrawCommand::OutlineFillPath(data) => {
+            style_units(&data.fill_style, f);
+            style_units(&data.line_style, f);
+            f(data.line_width);
+            path_units(&data.path, f);
+        }
+        DrawCommand::TextHint(data) => {
+            point_units(&data.center, f);
+            f(data.rotation);
+            f(data.height);
+            for (start, end) in &data.glyph_offset {
+                f(*start);
+                f(*end);
+            }
+        }
+    }
+}
+
+fn point_units(point: &Point, f: &mut impl FnMut(Unit)) {
+    f(point.x);
+    f(point.y);
+}
+
+fn style_units(style: &Style, f: &mut impl FnMut(Unit)) {
+    let (point_0, point_1) = match style {
+        Style::FlatColor(_) => return,
+        Style::LinearGradient(gradient) => (gradient.point_0, gradient.point_1),
+        Style::RadialGradient(gradient) => (gradient.point_0, gradient.point_1),
+    };
+    [point_0.x, point_0.y, point_1.x, point_1.y].into_iter().for_each(f);
+}
+
+pub(crate) fn path_units(path: &Path, f: &mut impl FnMut(Unit)) {
+    for segment in &path.segments {
+        f(segment.start.x);
+        f(segment.start.y);
+        for path_command in &segment.path_commands {
+            let line_width = match path_command {
+                PathCommand::Line(point, line_width) => {
+                    point_units(point, f);
+                    line_width
+                }
+                PathCommand::HorizontalLine(unit, line_width) | PathCommand::VerticalLine(unit, line_width) => {
+                    f(*unit);
+                    line_width
+                }
+                PathCommand::CubicBezier(cubic, line_width) => {
+                    for point in [&cubic.control_point_0, &cubic.control_point_1, &cubic.point_1] {
+                        point_units(point, f);
+                    }
+                    line_width
+                }
+                PathCommand::QuadraticBezier(quadratic, line_width) => {
+                    for point in [&quadratic.control_point, &quadratic.point_1] {
+                        point_units(point, f);
+                    }
+                    line_width
+                }
+                PathCommand::ArcCircle(arc, line_width) => {
+                    [arc.radius, arc.target.x, arc.target.y].into_iter().for_each(&mut *f);
+                    line_width
+                }
+                PathCommand::ArcEllipse(arc, line_width) => {
+                    [arc.radius_x, arc.radius_y, arc.rotation, arc.target.x, arc.target.y].into_iter().for_each(&mut *f);
+                    line_width
+                }
+                PathCommand::ClosePath(line_width) => line_width,
+            };
+            if let Some(line_width) = line_width {
+                f(*line_width);
+            }
+        }
+    }
+}
+
+/// Like `for_each_unit`, but allows the values to be changed in place.
+pub(crate) fn for_each_unit_mut(command: &mut DrawCommand, f: &mut impl FnMut(&mut Unit)) {
+    match command {
+        DrawCommand::FillPolygon(data) => {
+            style_units_mut(&mut data.style, f);
+            data.points.iter_mut().for_each(|point| point_units_mut(point, f));
+        }
+        DrawCommand::FillRectangles(data) => {
+            style_units_mut(&mut data.style, f);
+            for rectangle in &mut data.rectangles {
+                [&mut rectangle.x, &mut rectangle.y, &mut rectangle.width, &mut rectangle.height].into_iter().for_each(&mut *f);
+            }
+        }
+        DrawCommand::FillPath(data) => {
+            style_units_mut(&mut data.style, f);
+            path_units_mut(&mut data.path, f);
+        }
+        DrawCommand::DrawLines(data) => {
+            style_units_mut(&mut data.line_style, f);
+            f(&mut data.line_width);
+            for line in &mut data.lines {
+                point_units_mut(&mut line.start, f);
+                point_units_mut(&mut line.end, f);
+            }
+        }
+        DrawCommand::DrawLineLoop(data) => {
+            style_units_mut(&mut data.line_style, f);
+            f(&mut data.line_width);
+            data.points.iter_mut().for_each(|point| point_units_mut(point, f));
+        }
+        DrawCommand::DrawLineStrip(data) => {
+            style_units_mut(&mut data.style, f);
+            f(&mut data.line_width);
+            data.points.iter_mut().for_each(|point| point_units_mut(point, f));
+        }
+        DrawCommand::DrawLinePath(data) => {
+            style_units_mut(&mut data.style, f);
+            f(&mut data.line_width);
+            path_units_mut(&mut data.path, f);
+        }
+        DrawCommand::OutlineFillPolygon(data) => {
+            style_units_mut(&mut data.fill_style, f);
+            style_units_mut(&mut data.line_style, f);
+            f(&mut data.line_width);
+            data.points.iter_mut().for_each(|point| point_units_mut(point, f));
+        }
+        DrawCommand::OutlineFillRectangles(data) => {
+            style_units_mut(&mut data.fill_style, f);
+            style_units_mut(&mut data.line_style, f);
+            f(&mut data.line_width);
+            for rectangle in &mut data.rectangles {
+                [&mut rectangle.x, &mut rectangle.y, &mut rectangle.width, &mut rectangle.height].into_iter().for_each(&mut *f);
+            }
+        }
+        DrawCommand::OutlineFillPath(data) => {
+            style_units_mut(&mut data.fill_style, f);
+            style_units_mut(&mut data.line_style, f);
+            f(&mut data.line_width);
+            path_units_mut(&mut data.path, f);
+        }
+        DrawCommand::TextHint(data) => {
+            point_units_mut(&mut data.center, f);
+            f(&mut data.rotation);
+            f(&mut data.height);
+            for (start, end) in &mut data.glyph_offset {
+                f(start);
+                f(end);
+            }
+        }
+    }
+}
+
+fn point_units_mut(point: &mut Point, f: &mut impl FnMut(&mut Unit)) {
+    f(&mut point.x);
+    f(&mut point.y);
+}
+
+fn style_units_mut(style: &mut Style, f: &mut impl FnMut(&mut Unit)) {
+    let (point_0, point_1) = match style {
+        Style::FlatColor(_) => return,
+        Style::LinearGradient(gradient) => (&mut gradient.point_0, &mut gradient.point_1),
+        Style::RadialGradient(gradient) => (&mut gradient.point_0, &mut gradient.point_1),
+    };
+    point_units_mut(point_0, f);
+    point_units_mut(point_1, f);
+}
+
+fn path_units_mut(path: &mut Path, f: &mut impl FnMut(&mut Unit)) {
+    for segment in &mut path.segments {
+        point_units_mut(&mut segment.start, f);
+        for path_command in &mut segment.path_commands {
+            let line_width = match path_command {
+                PathCommand::Line(point, line_width) => {
+                    point_units_mut(point, f);
+                    line_width
+                }
+                PathCommand::HorizontalLine(unit, line_width) | PathCommand::VerticalLine(unit, line_width) => {
+                    f(unit);
+                    line_width
+                }
+                PathCommand::CubicBezier(cubic, line_width) => {
+                    for point in [&mut cubic.control_point_0, &mut cubic.control_point_1, &mut cubic.point_1] {
+                        point_units_mut(point, f);
+                    }
+                    line_width
+                }
+                PathCommand::QuadraticBezier(quadratic, line_width) => {
+                    for point in [&mut quadratic.control_point, &mut quadratic.point_1] {
+                        point_units_mut(point, f);
+                    }
+                    line_width
+                }
+                PathCommand::ArcCircle(arc, line_width) => {
+                    [&mut arc.radius, &mut arc.target.x, &mut arc.target.y].into_iter().for_each(&mut *f);
+                    line_width
+                }
+                PathCommand::ArcEllipse(arc, line_width) => {
+                    [&mut arc.radius_x, &mut arc.radius_y, &mut arc.rotation, &mut arc.target.x, &mut arc.target.y].into_iter().for_each(&mut *f);
+                    line_width
+                }
+                PathCommand::ClosePath(_) => continue,
+            };
+            if let Some(line_width) = line_width {
+                f(line_width);
+            }
+        }
+    }
+}
+
+/// Every unit value in the document's commands.
+pub(crate) fn unit_values(document: &TinyVg) -> Vec<f64> {
+    let mut values = Vec::new();
+    for command in &document.draw_commands {
+        for_each_unit(command, &mut |unit| values.push(unit.0));
+    }
+    values
+}
+
+/// The largest raw integer a unit can hold in the range.
+pub(crate) fn raw_limit(range: CoordinateRange) -> f64 {
+    match range {
+        CoordinateRange::Reduced => i8::MAX as f64,
+        CoordinateRange::Default => i16::MAX as f64,
+        CoordinateRange::Enhanced => i32::MAX as f64,
+    }
+}
+
+/// The largest width or height the header can hold in the range.
+pub(crate) fn size_limit(range: CoordinateRange) -> u32 {
+    match range {
+        CoordinateRange::Reduced => u8::MAX as u32,
+        CoordinateRange::Default => u16::MAX as u32,
+        CoordinateRange::Enhanced => u32::MAX,
+    }
+}
+
+/// The difference between `value` and the nearest value representable with `scale`
+/// fraction bits.
+pub(crate) fn quantization_error(value: f64, scale: u8) -> f64 {
+    let factor = (1u32 << scale) as f64;
+    (value - (value * factor).round() / factor).abs()
+}
+
+/// The worst-case quantization error of `values` at `scale`, or `None` if a value does
+/// not fit the range at that scale.
+fn worst_error(values: &[f64], scale: u8, range: CoordinateRange) -> Option<f64> {
+    let factor = (1u32 << scale) as f64;
+    values.iter().try_fold(0.0f64, |worst, &value| {
+        ((value * factor).round().abs() <= raw_limit(range)).then(|| worst.max(quantization_error(value, scale)))
+    })
+}
+
+/// The scale with the smallest worst-case error in the range together with that error,
+/// preferring the larger scale on ties.
+fn best_scale(values: &[f64], range: CoordinateRange) -> Option<(u8, f64)> {
+    (0..=15u8)
+        .rev()
+        .filter_map(|scale| worst_error(values, scale, range).map(|error| (scale, error)))
+        .fold(None, |best: Option<(u8, f64)>, (scale, error)| match best {
+            Some((_, best_error)) if best_error <= error => best,
+            _ => Some((scale, error)),
+        })
+}
+
+/// Picks the scale and coordinate range that minimize the worst-case quantization error of
+/// the document's units. The `preferred` range is kept, or the next larger one that holds
+/// every unit and the header size, unless a smaller range is just as accurate. Falls
+/// back to the enhanced range without fraction bits if nothing fits.
+pub(crate) fn optimal_precision(document: &TinyVg, preferred: CoordinateRange) -> (CoordinateRange, u8) {
+    let values = unit_values(document);
+    let size = document.header.width.max(document.header.height);
+
+    let ranges = [CoordinateRange::Reduced, CoordinateRange::Default, CoordinateRange::Enhanced];
+    let start = ranges.iter().position(|&range| range as u8 == preferred as u8).unwrap_or(0);
+    let candidates: Vec<(CoordinateRange, Option<(u8, f64)>)> = ranges
+        .iter()
+        .map(|&range| (range, if size <= size_limit(range) { best_scale(&values, range) } else { None }))
+        .collect();
+
+    let Some((kept_index, (_, kept_error))) = candidates[start..]
+        .iter()
+        .enumerate()
+        .find_map(|(offset, (_, best))| best.map(|best| (start + offset, best)))
+    else {
+        return (CoordinateRange::Enhanced, 0);
+    };
+
+    candidates[..=kept_index]
+        .iter()
+        .find_map(|&(range, best)| best.filter(|&(_, error)| error <= kept_error).map(|(scale, _)| (range, scale)))
+        .unwrap_or((CoordinateRange::Enhanced, 0))
+}
+
+impl TinyVg {
+    /// The scale and coordinate range that encode the document's geometry with the smallest
+    /// worst-case quantization error. The current coordinate range is kept unless it cannot
+    /// hold the geometry and header size, in which case a larger one is chosen, or a smaller
+    /// range is just as accurate.
+    pub fn optimal_precision(&self) -> (CoordinateRange, u8) {
+        optimal_precision(self, self.header.coordinate_range)
+    }
+
+    /// The smallest coordinate range that holds every unit at the header's scale, along
+    /// with the header size: `Reduced` for tiny icons and `Enhanced` for large documents or
+    /// high scales. Returns `None` if not even the enhanced range can hold the geometry.
+    pub fn smallest_coordinate_range(&self) -> Option<CoordinateRange> {
+        let factor = (1u32 << self.header.scale.min(15)) as f64;
+        let largest = unit_values(self).iter().map(|value| (value * factor).round().abs()).fold(0.0, f64::max);
+        let size = self.header.width.max(self.header.height);
+
+        [CoordinateRange::Reduced, CoordinateRange::Default, CoordinateRange::Enhanced]
+            .into_iter()
+            .find(|&range| largest <= raw_limit(range) && size <= size_limit(range))
+    }
+
+    /// Applies `optimal_precision` to the header, which decides how the document's values
+    /// are rounded when it is encoded. Returns the chosen range and scale.
+    pub fn requantize(&mut self) -> (CoordinateRange, u8) {
+        let (coordinate_range, scale) = self.optimal_precision();
+        self.header.coordinate_range = coordinate_range;
+        self.header.scale = scale;
+        (coordinate_range, scale)
+    }
+}
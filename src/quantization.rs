@@ -0,0 +1,74 @@
+use crate::header::CoordinateRange;
+use crate::precision::{for_each_unit, quantization_error, raw_limit};
+use crate::TinyVg;
+
+/// The rounding error of a single draw command's units, in display units.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CommandQuantization {
+    /// The index of the command in `TinyVg::draw_commands`.
+    pub command_index: usize,
+    pub max_error: f64,
+    pub mean_error: f64,
+
+    /// The number of units too large to be encoded at all.
+    pub out_of_range: usize,
+}
+
+/// The rounding error that encoding a document with a given scale and coordinate range
+/// would introduce.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantizationReport {
+    /// One entry per draw command, in drawing order. Text hints are included.
+    pub commands: Vec<CommandQuantization>,
+    pub max_error: f64,
+    pub mean_error: f64,
+    pub out_of_range: usize,
+}
+
+impl QuantizationReport {
+    /// Whether every unit can be encoded and is rounded by at most `tolerance`.
+    pub fn within(&self, tolerance: f64) -> bool {
+        self.out_of_range == 0 && self.max_error <= tolerance
+    }
+}
+
+impl TinyVg {
+    /// Measures how far every coordinate, size, line width, radius and rotation would move
+    /// if the document were encoded with `scale` fraction bits in the given coordinate range.
+    /// Units that do not fit the range are counted as out of range and are left out of the
+    /// error statistics.
+    pub fn quantization_report(&self, scale: u8, range: CoordinateRange) -> QuantizationReport {
+        let factor = (1u32 << scale.min(15)) as f64;
+        let mut commands = Vec::with_capacity(self.draw_commands.len());
+        let (mut total_error, mut total_count) = (0.0, 0usize);
+
+        for (command_index, command) in self.draw_commands.iter().enumerate() {
+            let mut entry = CommandQuantization { command_index, max_error: 0.0, mean_error: 0.0, out_of_range: 0 };
+            let mut count = 0usize;
+            for_each_unit(command, &mut |unit| {
+                if (unit.0 * factor).round().abs() > raw_limit(range) {
+                    entry.out_of_range += 1;
+                    return;
+                }
+                let error = quantization_error(unit.0, scale.min(15));
+                entry.max_error = entry.max_error.max(error);
+                entry.mean_error += error;
+                count += 1;
+            });
+
+            total_error += entry.mean_error;
+            total_count += count;
+            if count > 0 {
+                entry.mean_error /= count as f64;
+            }
+            commands.push(entry);
+        }
+
+        QuantizationReport {
+            max_error: commands.iter().map(|entry| entry.max_error).fold(0.0, f64::max),
+            mean_error: if total_count > 0 { total_error / total_count as f64 } else { 0.0 },
+            out_of_range: commands.iter().map(|entry| entry.out_of_range).sum(),
+            commands,
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! The scanline rasterizer shared by the mask and color renderers. Commands are
+//! flattened into outlines and point sampled on a supersampled grid.
+
+use crate::commands::Point;
+use crate::fit::FitMode;
+use crate::geometry::{command_outline, stroke_polygons, FillRule};
+use crate::render::StrokeStyle;
+use crate::TinyVg;
+
+/// The number of coverage samples per pixel along each axis.
+pub(crate) const SAMPLES_PER_AXIS: usize = 4;
+
+/// The flattening tolerance in output pixels.
+const FLATTEN_TOLERANCE: f64 = 0.1;
+
+impl TinyVg {
+    /// Point samples the coverage of the document stretched to `size`, taking
+    /// `samples_per_axis` samples per pixel along each axis. The returned grid is
+    /// `size * samples_per_axis` samples large and stored row by row.
+    pub(crate) fn coverage_samples(&self, size: (u32, u32), samples_per_axis: usize) -> Vec<bool> {
+        let sample_width = size.0 as usize * samples_per_axis;
+        let sample_height = size.1 as usize * samples_per_axis;
+        let mut samples = vec![false; sample_width * sample_height];
+
+        let affine = self.fit_transform((size.0 as f64, size.1 as f64), FitMode::Stretch);
+        // The tolerance is given in output pixels, so convert it back into document units.
+        let [a, b, c, d, _, _] = affine.0;
+        let scale = (a * d - b * c).abs().sqrt().max(f64::EPSILON);
+        let sample_scale = samples_per_axis as f64;
+
+        for command in &self.draw_commands {
+            let Some(mut outline) = command_outline(command, FLATTEN_TOLERANCE / scale) else {
+                continue;
+            };
+            outline.transform(&affine);
+
+            let to_samples = |points: &[Point]| -> Vec<(f64, f64)> {
+                points.iter().map(|point| (point.x.0 * sample_scale, point.y.0 * sample_scale)).collect()
+            };
+
+            if !outline.fill.is_empty() {
+                let polygons: Vec<Vec<(f64, f64)>> = outline.fill.iter().map(|polyline| to_samples(&polyline.points)).collect();
+                fill_polygons(&polygons, outline.fill_rule, sample_width, sample_height, &mut samples);
+            }
+
+            let half_width = outline.line_width / 2.0;
+            for polyline in &outline.stroke {
+                let polygons: Vec<Vec<(f64, f64)>> = stroke_polygons(polyline, half_width, &StrokeStyle::default(), FLATTEN_TOLERANCE)
+                    .into_iter()
+                    .map(|polygon| polygon.into_iter().map(|(x, y)| (x * sample_scale, y * sample_scale)).collect())
+                    .collect();
+                fill_polygons(&polygons, FillRule::NonZero, sample_width, sample_height, &mut samples);
+            }
+        }
+
+        samples
+    }
+}
+
+/// Marks every sample whose center lies inside the polygons, using a scanline
+/// pass over the polygon edges.
+pub(crate) fn fill_polygons(polygons: &[Vec<(f64, f64)>], fill_rule: FillRule, width: usize, height: usize, samples: &mut [bool]) {
+    let mut edges: Vec<((f64, f64), (f64, f64))> = Vec::new();
+    let mut min_y = f64::INFINITY;
+    let mut max_y = f64::NEG_INFINITY;
+    for polygon in polygons {
+        for i in 0..polygon.len() {
+            let start = polygon[i];
+            let end = polygon[(i + 1) % polygon.len()];
+            if start.1 != end.1 {
+                edges.push((start, end));
+                min_y = min_y.min(start.1.min(end.1));
+                max_y = max_y.max(start.1.max(end.1));
+            }
+        }
+    }
+
+    if edges.is_empty() {
+        return;
+    }
+
+    let first_row = (min_y - 0.5).ceil().max(0.0) as usize;
+    let last_row = ((max_y - 0.5).floor().max(-1.0) as usize).min(height.saturating_sub(1));
+
+    let mut crossings: Vec<(f64, i32)> = Vec::new();
+    for row in first_row..=last_row {
+        let y = row as f64 + 0.5;
+        crossings.clear();
+        for &((x0, y0), (x1, y1)) in &edges {
+            let direction = if y0 <= y && y1 > y {
+                1
+            } else if y1 <= y && y0 > y {
+                -1
+            } else {
+                continue;
+            };
+            let x = x0 + (y - y0) * (x1 - x0) / (y1 - y0);
+            crossings.push((x, direction));
+        }
+        crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut winding = 0;
+        for i in 0..crossings.len().saturating_sub(1) {
+            winding += crossings[i].1;
+            let inside = match fill_rule {
+                FillRule::EvenOdd => (i + 1) % 2 == 1,
+                FillRule::NonZero => winding != 0,
+            };
+            if !inside {
+                continue;
+            }
+
+            // Cover the samples whose centers lie in [start, end).
+            let start = (crossings[i].0 - 0.5).ceil().max(0.0) as usize;
+            let end = ((crossings[i + 1].0 - 0.5).ceil().max(0.0) as usize).min(width);
+            if start < end {
+                samples[row * width + start..row * width + end].fill(true);
+            }
+        }
+    }
+}
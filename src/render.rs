@@ -0,0 +1,675 @@
+use crate::color::{linear_to_srgb, srgb_to_linear};
+use crate::color_table::{ColorSpace, ColorTable, RgbaF32};
+use crate::bounds::{bounds_rectangle, union};
+use crate::commands::{DrawCommand, Point, Style};
+use crate::common::{Affine, Unit};
+use crate::fit::FitMode;
+use crate::geometry::{command_outline, stroke_polygons, FillRule, Outline};
+use crate::header::TinyVgHeader;
+use crate::raster::{fill_polygons, SAMPLES_PER_AXIS};
+use crate::shapes::ResolvedStyle;
+use crate::TinyVg;
+
+/// The flattening tolerance in output pixels.
+const RENDER_TOLERANCE: f64 = 0.1;
+
+/// An image with 8-bit sRGB channels and straight alpha, stored row by row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    /// Four bytes per pixel in the order red, green, blue, alpha.
+    pub data: Vec<u8>,
+}
+
+impl RgbaImage {
+    /// A fully transparent image.
+    pub fn new(width: u32, height: u32) -> Self {
+        RgbaImage { width, height, data: vec![0; width as usize * height as usize * 4] }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let start = (y as usize * self.width as usize + x as usize) * 4;
+        self.data[start..start + 4].try_into().ok()
+    }
+
+    /// Copies `image` into this image with its top left corner at `(x, y)`, leaving out
+    /// the parts that fall outside.
+    pub fn copy_from(&mut self, image: &RgbaImage, x: u32, y: u32) {
+        let columns = image.width.min(self.width.saturating_sub(x)) as usize * 4;
+        for row in 0..image.height.min(self.height.saturating_sub(y)) as usize {
+            let source = row * image.width as usize * 4;
+            let target = ((y as usize + row) * self.width as usize + x as usize) * 4;
+            self.data[target..target + columns].copy_from_slice(&image.data[source..source + columns]);
+        }
+    }
+}
+
+/// An image with floating point channels in linear light with the sRGB primaries, the scRGB
+/// of `RgbaF32` color tables, and straight alpha, stored row by row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RgbaF32Image {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<RgbaF32>,
+}
+
+impl RgbaF32Image {
+    pub fn pixel(&self, x: u32, y: u32) -> Option<RgbaF32> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(self.data[y as usize * self.width as usize + x as usize])
+    }
+}
+
+/// A rectangle of whole pixels.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Viewport {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Which part of a document to render and how, see `TinyVg::render_rgba_with`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderParams {
+    /// The transform from document units to pixels.
+    pub transform: Affine,
+
+    /// The pixels to render, in the coordinates `transform` maps to. The image has the size
+    /// of the viewport, with the viewport's top left corner at its origin, so a large or
+    /// zoomed in document can be rendered one visible part at a time.
+    pub viewport: Viewport,
+
+    /// Whether gradients are dithered with blue noise of one 8-bit step, which hides the
+    /// banding of slow gradients over large areas in 8-bit output.
+    pub dither: bool,
+
+    /// What the document is drawn over.
+    pub background: Background,
+
+    /// How the ends and corners of strokes are drawn, which the format leaves to renderers.
+    pub stroke: StrokeStyle,
+}
+
+/// How the ends and corners of strokes are drawn. The default of round caps and joins
+/// matches the reference renderer.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct StrokeStyle {
+    pub cap: LineCap,
+    pub join: LineJoin,
+
+    /// The longest miter of a `LineJoin::Miter` join, relative to the line width. Sharper
+    /// corners are beveled instead.
+    pub miter_limit: f64,
+}
+
+impl Default for StrokeStyle {
+    fn default() -> Self {
+        StrokeStyle { cap: LineCap::Round, join: LineJoin::Round, miter_limit: 4.0 }
+    }
+}
+
+/// The shape of the ends of open strokes.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LineCap {
+    /// The stroke ends at the end point.
+    Butt,
+
+    /// The stroke ends in a half circle around the end point.
+    Round,
+
+    /// The stroke extends beyond the end point by half the line width.
+    Square,
+}
+
+/// The shape of the corners where the edges of a stroke meet.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LineJoin {
+    /// The outer edges are extended until they meet, up to the miter limit.
+    Miter,
+
+    /// The corner is rounded with a circle around the corner point.
+    Round,
+
+    /// The corner is cut off straight between the ends of the outer edges.
+    Bevel,
+}
+
+/// What a document is rendered over.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Background {
+    /// Fully transparent pixels, so the image can be composited later.
+    #[default]
+    Transparent,
+
+    /// An sRGB color, which may be translucent.
+    Color(RgbaF32),
+
+    /// White and light gray squares of `cell_size` pixels, as preview tools show behind
+    /// transparent content. The squares start at the top left corner of the image.
+    Checkerboard { cell_size: u32 },
+}
+
+impl RenderParams {
+    /// Renders the whole document into an image of `size` (width, height), placed as given
+    /// by `mode`.
+    pub fn fit(document: &TinyVg, size: (u32, u32), mode: FitMode) -> RenderParams {
+        RenderParams {
+            transform: document.fit_transform((size.0 as f64, size.1 as f64), mode),
+            viewport: Viewport { x: 0, y: 0, width: size.0, height: size.1 },
+            dither: false,
+            background: Background::Transparent,
+            stroke: StrokeStyle::default(),
+        }
+    }
+}
+
+impl TinyVg {
+    /// Renders the document in color into an image of `size` (width, height), placed as
+    /// given by `mode`. Fills and strokes are antialiased and composited over each other
+    /// in command order, and gradients are interpolated in linear light. Text hints are
+    /// not drawn.
+    pub fn render_rgba(&self, size: (u32, u32), mode: FitMode) -> RgbaImage {
+        self.render_rgba_with(&RenderParams::fit(self, size, mode))
+    }
+
+    /// Renders the document like `render_rgba` into an image with floating point channels,
+    /// for HDR pipelines. Channels are not clamped, so the scRGB colors of `RgbaF32`
+    /// documents that are brighter than white or outside the sRGB gamut keep their values.
+    pub fn render_rgba_f32(&self, size: (u32, u32), mode: FitMode) -> RgbaF32Image {
+        self.render_rgba_f32_with(&RenderParams::fit(self, size, mode))
+    }
+
+    /// Renders the document at its display size on a screen of `dpi`, see
+    /// `TinyVgHeader::pixel_size_at_dpi`, so icons come out at the same physical size on
+    /// HiDPI screens. Unbounded sides are 0 pixels wide.
+    pub fn render_at_dpi(&self, dpi: f64) -> RgbaImage {
+        let (width, height) = self.header.pixel_size_at_dpi(dpi);
+        self.render_rgba_with(&RenderParams {
+            transform: Affine::scale(TinyVgHeader::dpi_scale(dpi)),
+            viewport: Viewport { x: 0, y: 0, width, height },
+            dither: false,
+            background: Background::Transparent,
+            stroke: StrokeStyle::default(),
+        })
+    }
+
+    /// Renders the document into square images of each of `sizes` pixels, fitted as with
+    /// `FitMode::Contain`, such as the 16, 32, 64 and 128 pixel images of an icon set. The
+    /// geometry is flattened once, at the precision the largest size needs, and shared by
+    /// all sizes.
+    pub fn render_sizes(&self, sizes: &[u32]) -> Vec<RgbaImage> {
+        let Some(&largest) = sizes.iter().max() else {
+            return Vec::new();
+        };
+        let scale = self.fit_transform((largest as f64, largest as f64), FitMode::Contain).determinant().abs().sqrt().max(f64::EPSILON);
+        let outlines: Vec<(&DrawCommand, Outline)> = self
+            .draw_commands
+            .iter()
+            .filter_map(|command| Some((command, command_outline(command, RENDER_TOLERANCE / scale)?)))
+            .collect();
+        let color_table = self.color_table_in(ColorSpace::Srgb);
+
+        sizes
+            .iter()
+            .map(|&size| {
+                let mut canvas = Canvas::new((size, size), false, StrokeStyle::default());
+                let affine = self.fit_transform((size as f64, size as f64), FitMode::Contain);
+                if let Some(to_document) = affine.inverse() {
+                    for (command, outline) in &outlines {
+                        canvas.draw(command, outline.clone(), &affine, &to_document, &color_table);
+                    }
+                }
+                canvas.into_image()
+            })
+            .collect()
+    }
+
+    /// Renders the viewport of `params` like `render_rgba`. Commands whose bounds, see
+    /// `commands_in`, are outside the viewport are skipped before they are flattened at
+    /// the render tolerance, and only pixels inside the viewport are touched.
+    pub fn render_rgba_with(&self, params: &RenderParams) -> RgbaImage {
+        self.render_canvas(params).into_image()
+    }
+
+    /// Renders the viewport of `params` like `render_rgba_f32`.
+    pub fn render_rgba_f32_with(&self, params: &RenderParams) -> RgbaF32Image {
+        self.render_canvas(params).into_f32_image()
+    }
+
+    fn render_canvas(&self, params: &RenderParams) -> Canvas {
+        let viewport = params.viewport;
+        let mut canvas = Canvas::new((viewport.width, viewport.height), params.dither, params.stroke);
+        canvas.fill_background(params.background);
+        let affine = params.transform.then(&Affine::translate(-viewport.x as f64, -viewport.y as f64));
+        let Some(to_document) = affine.inverse() else {
+            return canvas;
+        };
+        // The tolerance is given in output pixels, so convert it back into document units.
+        let scale = affine.determinant().abs().sqrt().max(f64::EPSILON);
+        // The canvas blends sRGB colors, whatever the encoding stores.
+        let color_table = self.color_table_in(ColorSpace::Srgb);
+
+        let corners = [(0.0, 0.0), (viewport.width as f64, 0.0), (0.0, viewport.height as f64), (viewport.width as f64, viewport.height as f64)]
+            .map(|(x, y)| to_document.apply(Point::new(Unit(x), Unit(y))));
+        let visible = corners
+            .iter()
+            .map(|corner| (corner.x.0, corner.y.0, corner.x.0, corner.y.0))
+            .reduce(union)
+            .map(bounds_rectangle)
+            .expect("a viewport has corners");
+
+        for command in self.commands_in(visible) {
+            if let Some(outline) = command_outline(command, RENDER_TOLERANCE / scale) {
+                canvas.draw(command, outline, &affine, &to_document, &color_table);
+            }
+        }
+
+        canvas
+    }
+}
+
+/// The style of the fill and of the outline of a command.
+fn command_styles(command: &DrawCommand) -> (Option<&Style>, Option<&Style>) {
+    match command {
+        DrawCommand::FillPolygon(data) => (Some(&data.style), None),
+        DrawCommand::FillRectangles(data) => (Some(&data.style), None),
+        DrawCommand::FillPath(data) => (Some(&data.style), None),
+        DrawCommand::DrawLines(data) => (None, Some(&data.line_style)),
+        DrawCommand::DrawLineLoop(data) => (None, Some(&data.line_style)),
+        DrawCommand::DrawLineStrip(data) => (None, Some(&data.style)),
+        DrawCommand::DrawLinePath(data) => (None, Some(&data.style)),
+        DrawCommand::OutlineFillPolygon(data) => (Some(&data.fill_style), Some(&data.line_style)),
+        DrawCommand::OutlineFillRectangles(data) => (Some(&data.fill_style), Some(&data.line_style)),
+        DrawCommand::OutlineFillPath(data) => (Some(&data.fill_style), Some(&data.line_style)),
+        DrawCommand::TextHint(_) => (None, None),
+    }
+}
+
+/// Collects the coverage of one shape in samples and then paints it over the pixels.
+struct Canvas {
+    width: usize,
+    height: usize,
+    samples: Vec<bool>,
+    /// The pixels covered since the last paint, as (left, top, right, bottom).
+    covered: Option<(usize, usize, usize, usize)>,
+    /// Premultiplied sRGB colors.
+    pixels: Vec<[f32; 4]>,
+    dither: bool,
+    stroke_style: StrokeStyle,
+}
+
+impl Canvas {
+    fn new(size: (u32, u32), dither: bool, stroke_style: StrokeStyle) -> Self {
+        let (width, height) = (size.0 as usize, size.1 as usize);
+        Canvas {
+            width,
+            height,
+            samples: vec![false; width * height * SAMPLES_PER_AXIS * SAMPLES_PER_AXIS],
+            covered: None,
+            pixels: vec![[0.0; 4]; width * height],
+            dither,
+            stroke_style,
+        }
+    }
+
+    /// Covers and paints the fill and then the stroke of a command, whose outline is given
+    /// in document units and mapped to the pixels by `affine`.
+    fn draw(&mut self, command: &DrawCommand, mut outline: Outline, affine: &Affine, to_document: &Affine, color_table: &ColorTable) {
+        outline.transform(affine);
+        let (fill_style, line_style) = command_styles(command);
+
+        if let Some(style) = fill_style
+            && !outline.fill.is_empty()
+        {
+            let polygons: Vec<Vec<(f64, f64)>> =
+                outline.fill.iter().map(|polyline| polyline.points.iter().map(|point| (point.x.0, point.y.0)).collect()).collect();
+            self.cover(&polygons, outline.fill_rule);
+            self.paint(&ResolvedStyle::resolve(style, color_table), to_document);
+        }
+
+        if let Some(style) = line_style
+            && !outline.stroke.is_empty()
+        {
+            // All strokes of a command are covered before painting, so that where they
+            // overlap the color is not blended twice.
+            for polyline in &outline.stroke {
+                self.cover(&stroke_polygons(polyline, outline.line_width / 2.0, &self.stroke_style, RENDER_TOLERANCE), FillRule::NonZero);
+            }
+            self.paint(&ResolvedStyle::resolve(style, color_table), to_document);
+        }
+    }
+
+    fn fill_background(&mut self, background: Background) {
+        let premultiplied = |color: RgbaF32| {
+            let alpha = color.3.clamp(0.0, 1.0);
+            [color.0 * alpha, color.1 * alpha, color.2 * alpha, alpha]
+        };
+        match background {
+            Background::Transparent => self.pixels.fill([0.0; 4]),
+            Background::Color(color) => self.pixels.fill(premultiplied(color)),
+            Background::Checkerboard { cell_size } => {
+                let cell_size = cell_size.max(1) as usize;
+                let (light, dark) = ([1.0; 4], [0.8, 0.8, 0.8, 1.0]);
+                for (index, pixel) in self.pixels.iter_mut().enumerate() {
+                    let (x, y) = (index % self.width, index / self.width);
+                    *pixel = if (x / cell_size + y / cell_size).is_multiple_of(2) { light } else { dark };
+                }
+            }
+        }
+    }
+
+    /// Adds the inside of polygons given in pixels to the covered samples.
+    fn cover(&mut self, polygons: &[Vec<(f64, f64)>], fill_rule: FillRule) {
+        let points = || polygons.iter().flatten();
+        let (Some(left), Some(top)) = (points().map(|point| point.0).reduce(f64::min), points().map(|point| point.1).reduce(f64::min))
+        else {
+            return;
+        };
+        let right = points().map(|point| point.0).fold(left, f64::max);
+        let bottom = points().map(|point| point.1).fold(top, f64::max);
+        let clamp = |value: f64, limit: usize| (value.max(0.0) as usize).min(limit);
+        let bounds = (clamp(left.floor(), self.width), clamp(top.floor(), self.height), clamp(right.ceil(), self.width), clamp(bottom.ceil(), self.height));
+        self.covered = Some(match self.covered {
+            Some(covered) => (covered.0.min(bounds.0), covered.1.min(bounds.1), covered.2.max(bounds.2), covered.3.max(bounds.3)),
+            None => bounds,
+        });
+
+        let scale = SAMPLES_PER_AXIS as f64;
+        let polygons: Vec<Vec<(f64, f64)>> =
+            polygons.iter().map(|polygon| polygon.iter().map(|&(x, y)| (x * scale, y * scale)).collect()).collect();
+        fill_polygons(&polygons, fill_rule, self.width * SAMPLES_PER_AXIS, self.height * SAMPLES_PER_AXIS, &mut self.samples);
+    }
+
+    /// Paints the covered samples with `style`, whose points are mapped to the pixels by
+    /// the inverse of `to_document`, and clears them for the next shape.
+    fn paint(&mut self, style: &ResolvedStyle, to_document: &Affine) {
+        let Some((left, top, right, bottom)) = self.covered.take() else {
+            return;
+        };
+        let sample_width = self.width * SAMPLES_PER_AXIS;
+        let samples_per_pixel = (SAMPLES_PER_AXIS * SAMPLES_PER_AXIS) as f32;
+        let dither = self.dither && !matches!(style, ResolvedStyle::FlatColor(_));
+
+        for y in top..bottom {
+            for x in left..right {
+                let mut covered = 0;
+                for sample_y in 0..SAMPLES_PER_AXIS {
+                    let row = (y * SAMPLES_PER_AXIS + sample_y) * sample_width + x * SAMPLES_PER_AXIS;
+                    for sample in &mut self.samples[row..row + SAMPLES_PER_AXIS] {
+                        covered += *sample as u32;
+                        *sample = false;
+                    }
+                }
+                if covered == 0 {
+                    continue;
+                }
+
+                let center = to_document.apply(Point::new(Unit(x as f64 + 0.5), Unit(y as f64 + 0.5)));
+                let mut color = style_color(style, center);
+                if dither {
+                    let offset = (blue_noise(x, y) - 0.5) / 255.0;
+                    color = RgbaF32(color.0 + offset, color.1 + offset, color.2 + offset, color.3);
+                }
+                let alpha = color.3.clamp(0.0, 1.0) * covered as f32 / samples_per_pixel;
+                let pixel = &mut self.pixels[y * self.width + x];
+                let source = [color.0 * alpha, color.1 * alpha, color.2 * alpha, alpha];
+                for (channel, source) in pixel.iter_mut().zip(source) {
+                    *channel = source + *channel * (1.0 - alpha);
+                }
+            }
+        }
+    }
+
+    fn into_image(self) -> RgbaImage {
+        let mut data = Vec::with_capacity(self.pixels.len() * 4);
+        for [red, green, blue, alpha] in self.pixels {
+            let straight = |channel: f32| if alpha > 0.0 { channel / alpha } else { 0.0 };
+            let byte = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+            data.extend([byte(straight(red)), byte(straight(green)), byte(straight(blue)), byte(alpha)]);
+        }
+        RgbaImage { width: self.width as u32, height: self.height as u32, data }
+    }
+
+    fn into_f32_image(self) -> RgbaF32Image {
+        let data = self
+            .pixels
+            .into_iter()
+            .map(|[red, green, blue, alpha]| {
+                let straight = |channel: f32| if alpha > 0.0 { channel / alpha } else { 0.0 };
+                RgbaF32(straight(red), straight(green), straight(blue), alpha).to_linear()
+            })
+            .collect();
+        RgbaF32Image { width: self.width as u32, height: self.height as u32, data }
+    }
+}
+
+/// A noise value from 0 to 1 for a pixel. This is interleaved gradient noise, which like
+/// blue noise has little low frequency content, so the dither reads as fine grain.
+fn blue_noise(x: usize, y: usize) -> f32 {
+    let value = 0.06711056 * x as f32 + 0.00583715 * y as f32;
+    (52.982_918 * value.fract()).fract()
+}
+
+/// The color of a style at a point in document units. Gradients are clamped to their end
+/// colors beyond their points.
+fn style_color(style: &ResolvedStyle, point: Point) -> RgbaF32 {
+    match *style {
+        ResolvedStyle::FlatColor(color) => color,
+        ResolvedStyle::LinearGradient { point_0, point_1, color_0, color_1 } => {
+            let (dx, dy) = (point_1.x.0 - point_0.x.0, point_1.y.0 - point_0.y.0);
+            let length_squared = dx * dx + dy * dy;
+            let t = if length_squared > 0.0 { ((point.x.0 - point_0.x.0) * dx + (point.y.0 - point_0.y.0) * dy) / length_squared } else { 0.0 };
+            mix(color_0, color_1, t)
+        }
+        ResolvedStyle::RadialGradient { point_0, point_1, color_0, color_1 } => {
+            let radius = (point_1.x.0 - point_0.x.0).hypot(point_1.y.0 - point_0.y.0);
+            let t = if radius > 0.0 { (point.x.0 - point_0.x.0).hypot(point.y.0 - point_0.y.0) / radius } else { 0.0 };
+            mix(color_0, color_1, t)
+        }
+    }
+}
+
+/// Interpolates two sRGB colors in linear light.
+fn mix(a: RgbaF32, b: RgbaF32, t: f64) -> RgbaF32 {
+    let t = t.clamp(0.0, 1.0);
+    let channel = |a: f32, b: f32| {
+        let (a, b) = (srgb_to_linear(a as f64), srgb_to_linear(b as f64));
+        linear_to_srgb(a + (b - a) * t) as f32
+    };
+    RgbaF32(channel(a.0, b.0), channel(a.1, b.1), channel(a.2, b.2), a.3 + (b.3 - a.3) * t as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Background, LineCap, LineJoin, RenderParams, RgbaImage, StrokeStyle, Viewport};
+    use crate::color_table::RgbaF32;
+    use crate::commands::{DrawCommand, DrawLineStripData, FillRectanglesData, FlatColored, LinearGradient, Point, Rectangle, Style};
+    use crate::common::{Affine, Unit};
+    use crate::fit::FitMode;
+    use crate::header::{ColorEncoding, CoordinateRange, TinyVgHeader, TINYVG_VERSION};
+    use crate::TinyVg;
+
+    /// A 4 by 4 `RgbaF32` document filled with one linear gray.
+    fn gray_square(value: f32) -> TinyVg {
+        let header = TinyVgHeader {
+            magic: [0x72, 0x56],
+            version: TINYVG_VERSION,
+            scale: 0,
+            color_encoding: ColorEncoding::RgbaF32,
+            coordinate_range: CoordinateRange::Default,
+            width: 4,
+            height: 4,
+            color_count: 1,
+        };
+        let color_table = vec![RgbaF32(value, value, value, 1.0)];
+        let rectangle = Rectangle { x: Unit(0.0), y: Unit(0.0), width: Unit(4.0), height: Unit(4.0) };
+        let draw_commands = vec![DrawCommand::FillRectangles(FillRectanglesData {
+            style: Style::FlatColor(FlatColored { color_index: 0 }),
+            rectangles: vec![rectangle],
+        })];
+        TinyVg { header, color_table, draw_commands }
+    }
+
+    #[test]
+    fn linear_colors_are_rendered_in_srgb() {
+        // Linear 0.2158605 is sRGB 128 / 255.
+        let document = gray_square(0.2158605);
+        assert_eq!(document.render_rgba((4, 4), FitMode::Stretch).pixel(1, 1), Some([128, 128, 128, 255]));
+    }
+
+    #[test]
+    fn float_images_keep_colors_brighter_than_white() {
+        let mut document = gray_square(4.0);
+        assert_eq!(document.render_rgba((4, 4), FitMode::Stretch).pixel(1, 1), Some([255, 255, 255, 255]));
+
+        let pixel = document.render_rgba_f32((4, 4), FitMode::Stretch).pixel(1, 1).unwrap();
+        assert!((pixel.0 - 4.0).abs() < 1e-4 && pixel.3 == 1.0, "{pixel:?}");
+
+        document.color_table[0] = RgbaF32(0.5, 0.5, 0.5, 0.5);
+        let pixel = document.render_rgba_f32((4, 4), FitMode::Stretch).pixel(1, 1).unwrap();
+        assert!((pixel.0 - 0.5).abs() < 1e-4 && (pixel.3 - 0.5).abs() < 1e-6, "{pixel:?}");
+    }
+
+    #[test]
+    fn viewports_render_part_of_the_document() {
+        let mut document = gray_square(1.0);
+        document.header.width = 8;
+        document.header.height = 8;
+        // Zoom in four times and render the pixels around the square's bottom right corner.
+        let params = RenderParams {
+            transform: Affine::scale(4.0),
+            viewport: Viewport { x: 12, y: 12, width: 8, height: 8 },
+            ..RenderParams::fit(&document, (8, 8), FitMode::None)
+        };
+        let image = document.render_rgba_with(&params);
+        assert_eq!((image.width, image.height), (8, 8));
+        assert_eq!(image.pixel(3, 3), Some([255, 255, 255, 255]));
+        assert_eq!(image.pixel(4, 4), Some([0, 0, 0, 0]));
+
+        let outside = RenderParams { viewport: Viewport { x: 40, y: 0, width: 4, height: 4 }, ..params };
+        assert!(document.render_rgba_with(&outside).data.iter().all(|&channel| channel == 0));
+    }
+
+    #[test]
+    fn backgrounds_are_drawn_under_the_document() {
+        let mut document = gray_square(1.0);
+        document.header.width = 8;
+        document.header.height = 8;
+        let mut params = RenderParams::fit(&document, (8, 8), FitMode::None);
+
+        params.background = Background::Color(RgbaF32(1.0, 0.0, 0.0, 1.0));
+        let image = document.render_rgba_with(&params);
+        assert_eq!(image.pixel(0, 0), Some([255, 255, 255, 255]));
+        assert_eq!(image.pixel(7, 7), Some([255, 0, 0, 255]));
+
+        params.background = Background::Checkerboard { cell_size: 2 };
+        let image = document.render_rgba_with(&params);
+        assert_eq!(image.pixel(4, 4), Some([255, 255, 255, 255]));
+        assert_eq!(image.pixel(6, 4), Some([204, 204, 204, 255]));
+    }
+
+    #[test]
+    fn sizes_match_single_renders() {
+        let document = gray_square(0.5);
+        let images = document.render_sizes(&[16, 1, 5]);
+        assert_eq!(images.len(), 3);
+        for (image, size) in images.iter().zip([16, 1, 5]) {
+            assert_eq!(image, &document.render_rgba((size, size), FitMode::Contain));
+        }
+        assert!(document.render_sizes(&[]).is_empty());
+    }
+
+    #[test]
+    fn dpi_scales_the_rendered_size() {
+        let image = gray_square(1.0).render_at_dpi(192.0);
+        assert_eq!((image.width, image.height), (8, 8));
+        assert_eq!(image.pixel(7, 7), Some([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn dithering_breaks_up_gradient_bands() {
+        let mut document = gray_square(0.0);
+        document.header.width = 64;
+        document.header.height = 64;
+        // A gradient from black to sRGB 4 / 255 across the whole document.
+        document.color_table.push(RgbaF32(0.0012141, 0.0012141, 0.0012141, 1.0));
+        document.draw_commands = vec![DrawCommand::FillRectangles(FillRectanglesData {
+            style: Style::LinearGradient(LinearGradient {
+                point_0: Point::new(Unit(0.0), Unit(0.0)),
+                point_1: Point::new(Unit(64.0), Unit(0.0)),
+                color_index_0: 0,
+                color_index_1: 1,
+            }),
+            rectangles: vec![Rectangle { x: Unit(0.0), y: Unit(0.0), width: Unit(64.0), height: Unit(64.0) }],
+        })];
+
+        let mut params = RenderParams::fit(&document, (64, 64), FitMode::Stretch);
+        let banded = document.render_rgba_with(&params);
+        params.dither = true;
+        let dithered = document.render_rgba_with(&params);
+
+        let column = |image: &RgbaImage, x: u32| (0..64).map(|y| image.pixel(x, y).unwrap()[0]).collect::<Vec<u8>>();
+        let mean = |image: &RgbaImage| image.data.chunks_exact(4).map(|pixel| pixel[0] as f64).sum::<f64>() / 4096.0;
+        assert!((0..64).all(|x| column(&banded, x).windows(2).all(|pair| pair[0] == pair[1])));
+        assert!((0..64).any(|x| column(&dithered, x).windows(2).any(|pair| pair[0] != pair[1])));
+        assert!((mean(&banded) - mean(&dithered)).abs() < 0.25);
+    }
+
+    /// The alpha of a pixel of a 16 by 16 document with a white line strip of width 4
+    /// through `points`.
+    fn stroke_alpha(points: &[(f64, f64)], stroke: StrokeStyle, pixel: (u32, u32)) -> u8 {
+        let mut document = gray_square(1.0);
+        document.header.width = 16;
+        document.header.height = 16;
+        document.draw_commands = vec![DrawCommand::DrawLineStrip(DrawLineStripData {
+            style: Style::FlatColor(FlatColored { color_index: 0 }),
+            line_width: Unit(4.0),
+            points: points.iter().map(|&(x, y)| Point::new(Unit(x), Unit(y))).collect(),
+        })];
+        let params = RenderParams { stroke, ..RenderParams::fit(&document, (16, 16), FitMode::None) };
+        document.render_rgba_with(&params).pixel(pixel.0, pixel.1).unwrap()[3]
+    }
+
+    #[test]
+    fn stroke_caps_end_lines() {
+        let line = [(4.0, 8.0), (12.0, 8.0)];
+        let cap = |cap| StrokeStyle { cap, ..StrokeStyle::default() };
+
+        // Butt caps end at the end points, square caps half the line width beyond them.
+        assert_eq!(stroke_alpha(&line, cap(LineCap::Butt), (4, 7)), 255);
+        assert_eq!(stroke_alpha(&line, cap(LineCap::Butt), (3, 7)), 0);
+        assert_eq!(stroke_alpha(&line, cap(LineCap::Square), (2, 6)), 255);
+        assert_eq!(stroke_alpha(&line, cap(LineCap::Square), (13, 9)), 255);
+        assert_eq!(stroke_alpha(&line, cap(LineCap::Square), (1, 7)), 0);
+
+        // Round caps cover the half circle, but not the corners of the square.
+        let round = stroke_alpha(&line, cap(LineCap::Round), (2, 6));
+        assert!(0 < round && round < 255, "{round}");
+        assert_eq!(stroke_alpha(&line, cap(LineCap::Round), (3, 7)), 255);
+    }
+
+    #[test]
+    fn stroke_joins_shape_corners() {
+        let corner = [(4.0, 12.0), (4.0, 4.0), (12.0, 4.0)];
+        let join = |join, miter_limit| StrokeStyle { cap: LineCap::Butt, join, miter_limit };
+
+        // The pixel at the outer tip of the corner is covered by a miter only.
+        assert_eq!(stroke_alpha(&corner, join(LineJoin::Miter, 4.0), (2, 2)), 255);
+        assert_eq!(stroke_alpha(&corner, join(LineJoin::Bevel, 4.0), (2, 2)), 0);
+        let round = stroke_alpha(&corner, join(LineJoin::Round, 4.0), (2, 2));
+        assert!(0 < round && round < 255, "{round}");
+
+        // A right angle has a miter of √2 line widths, so a lower limit bevels it.
+        assert_eq!(stroke_alpha(&corner, join(LineJoin::Miter, 1.2), (2, 2)), 0);
+
+        // Bevels still fill the corner up to the line between the outer edges.
+        assert_eq!(stroke_alpha(&corner, join(LineJoin::Bevel, 4.0), (3, 3)), 255);
+    }
+}
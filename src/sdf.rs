@@ -0,0 +1,393 @@
+use crate::fit::FitMode;
+use crate::geometry::{command_outline, Outline};
+use crate::TinyVg;
+
+/// The number of point samples per output pixel along each axis used to locate
+/// the silhouette before measuring distances.
+const SUPERSAMPLING: usize = 4;
+
+/// Stands in for an infinite squared distance in the distance transform.
+const FAR: f64 = 1e20;
+
+/// Renders the silhouette of the document (the union of every fill and stroke,
+/// stretched to `size`) into an 8-bit signed distance field stored row by row.
+///
+/// Each pixel holds the distance to the nearest silhouette edge in pixels, mapped so that
+/// 128 lies on the edge and every `spread` pixels move the value by 127. Values above 128
+/// are inside the shape.
+pub fn render_sdf(document: &TinyVg, size: (u32, u32), spread: f64) -> Vec<u8> {
+    signed_distances(document, size)
+        .into_iter()
+        .map(|distance| encode_distance(distance, spread))
+        .collect()
+}
+
+/// Maps a signed distance in pixels to a byte, with the edge at 128.
+pub(crate) fn encode_distance(distance: f64, spread: f64) -> u8 {
+    let normalized = if spread > 0.0 { distance / spread } else { distance.signum() };
+    (128.0 + normalized * 127.0).round().clamp(0.0, 255.0) as u8
+}
+
+/// The signed distance of every pixel center to the silhouette in pixels, positive
+/// inside the silhouette.
+pub(crate) fn signed_distances(document: &TinyVg, size: (u32, u32)) -> Vec<f64> {
+    let (width, height) = (size.0 as usize, size.1 as usize);
+    let grid_width = width * SUPERSAMPLING;
+    let grid_height = height * SUPERSAMPLING;
+    let inside = document.coverage_samples(size, SUPERSAMPLING);
+
+    let to_inside = distance_transform(&inside, true, grid_width, grid_height);
+    let to_outside = distance_transform(&inside, false, grid_width, grid_height);
+
+    let mut distances = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let index = (y * SUPERSAMPLING + SUPERSAMPLING / 2) * grid_width + x * SUPERSAMPLING + SUPERSAMPLING / 2;
+            // Distances are measured between sample centers, while the edge lies halfway between two samples.
+            let distance = if inside[index] {
+                to_outside[index].sqrt() - 0.5
+            } else {
+                0.5 - to_inside[index].sqrt()
+            };
+            distances.push(distance / SUPERSAMPLING as f64);
+        }
+    }
+
+    distances
+}
+
+/// Computes the squared euclidean distance from every cell to the nearest cell whose
+/// value equals `target`, using the separable algorithm by Felzenszwalb and Huttenlocher.
+fn distance_transform(cells: &[bool], target: bool, width: usize, height: usize) -> Vec<f64> {
+    let mut grid: Vec<f64> = cells.iter().map(|&cell| if cell == target { 0.0 } else { FAR }).collect();
+
+    let length = width.max(height);
+    let mut input = vec![0.0; length];
+    let mut output = vec![0.0; length];
+    let mut parabolas = vec![0usize; length];
+    let mut boundaries = vec![0.0; length + 1];
+
+    for x in 0..width {
+        for y in 0..height {
+            input[y] = grid[y * width + x];
+        }
+        distance_transform_1d(&input[..height], &mut output[..height], &mut parabolas, &mut boundaries);
+        for y in 0..height {
+            grid[y * width + x] = output[y];
+        }
+    }
+
+    for y in 0..height {
+        let row = &mut grid[y * width..(y + 1) * width];
+        input[..width].copy_from_slice(row);
+        distance_transform_1d(&input[..width], &mut output[..width], &mut parabolas, &mut boundaries);
+        row.copy_from_slice(&output[..width]);
+    }
+
+    grid
+}
+
+/// The one dimensional squared distance transform: the lower envelope of the
+/// parabolas rooted at every sample.
+fn distance_transform_1d(input: &[f64], output: &mut [f64], parabolas: &mut [usize], boundaries: &mut [f64]) {
+    if input.is_empty() {
+        return;
+    }
+
+    let mut k = 0;
+    parabolas[0] = 0;
+    boundaries[0] = f64::NEG_INFINITY;
+    boundaries[1] = f64::INFINITY;
+
+    for q in 1..input.len() {
+        let intersection = |vertex: usize| {
+            let (q_f, v_f) = (q as f64, vertex as f64);
+            ((input[q] + q_f * q_f) - (input[vertex] + v_f * v_f)) / (2.0 * q_f - 2.0 * v_f)
+        };
+
+        let mut s = intersection(parabolas[k]);
+        while s <= boundaries[k] {
+            k -= 1;
+            s = intersection(parabolas[k]);
+        }
+        k += 1;
+        parabolas[k] = q;
+        boundaries[k] = s;
+        boundaries[k + 1] = f64::INFINITY;
+    }
+
+    k = 0;
+    for (q, value) in output.iter_mut().enumerate() {
+        while boundaries[k + 1] < q as f64 {
+            k += 1;
+        }
+        let offset = q as f64 - parabolas[k] as f64;
+        *value = offset * offset + input[parabolas[k]];
+    }
+}
+
+/// The flattening tolerance for MSDF contours in output pixels.
+const MSDF_TOLERANCE: f64 = 0.05;
+
+/// The largest difference in pixels between the median of the channels and the silhouette
+/// distance before a pixel falls back to the silhouette distance.
+const MAX_MEDIAN_ERROR: f64 = 1.0;
+
+/// How far from an edge, in pixels, the silhouette is probed to see whether the edge lies on its boundary.
+const BOUNDARY_PROBE: f64 = 0.01;
+
+/// Joints where the direction turns by more than this angle, in radians, are treated as corners.
+const CORNER_ANGLE: f64 = 8.0 * std::f64::consts::PI / 180.0;
+
+const RED: u8 = 0b001;
+const GREEN: u8 = 0b010;
+const BLUE: u8 = 0b100;
+const WHITE: u8 = RED | GREEN | BLUE;
+
+/// The edge colors cycled through between corners. Neighbouring colors share exactly one channel.
+const EDGE_COLORS: [u8; 3] = [RED | GREEN, GREEN | BLUE, RED | BLUE];
+
+/// A straight piece of a flattened contour.
+struct ColoredEdge {
+    start: (f64, f64),
+    end: (f64, f64),
+    /// The channels this edge contributes to.
+    color: u8,
+    /// Whether the point on the inside of the shape lies to the left of the edge direction.
+    inside_left: bool,
+    /// Whether the start and end are sharp corners, where the distance is measured to the
+    /// extended edge instead of the end point.
+    start_corner: bool,
+    end_corner: bool,
+}
+
+impl ColoredEdge {
+    /// Returns the unsigned true distance to the edge, how perpendicular the edge is to the
+    /// direction of the point, and the signed pseudo-distance, positive inside.
+    fn distances(&self, point: (f64, f64)) -> (f64, f64, f64) {
+        let (ax, ay) = self.start;
+        let (dx, dy) = (self.end.0 - ax, self.end.1 - ay);
+        let (px, py) = (point.0 - ax, point.1 - ay);
+        let length_squared = dx * dx + dy * dy;
+        let t = if length_squared > 0.0 { (px * dx + py * dy) / length_squared } else { 0.0 };
+        let clamped = t.clamp(0.0, 1.0);
+        let (cx, cy) = (px - dx * clamped, py - dy * clamped);
+        let distance = cx.hypot(cy);
+
+        let length = length_squared.sqrt();
+        let cross = if length > 0.0 { (dx * py - dy * px) / length } else { 0.0 };
+        let orthogonality = if distance > 0.0 { (cross / distance).abs() } else { 1.0 };
+
+        let sign = if (cross >= 0.0) == self.inside_left { 1.0 } else { -1.0 };
+        let beyond_corner = (t < 0.0 && self.start_corner) || (t > 1.0 && self.end_corner);
+        let pseudo_distance = if beyond_corner && length > 0.0 { cross.abs() } else { distance };
+
+        (distance, orthogonality, sign * pseudo_distance)
+    }
+}
+
+/// Renders a multi-channel signed distance field of the document's filled contours,
+/// stretched to `size`. Pixels are stored row by row as RGB triplets, using the same
+/// encoding as [`render_sdf`]; the shape is reconstructed from the median of the three
+/// channels, which keeps corners sharp when the field is magnified.
+///
+/// Edges are colored per contour, switching colors at sharp corners. Stroked geometry has
+/// no contour of its own, so wherever the channels disagree with the silhouette the pixel
+/// falls back to the single-channel distance of [`render_sdf`].
+pub fn render_msdf(document: &TinyVg, size: (u32, u32), spread: f64) -> Vec<u8> {
+    let (width, height) = (size.0 as usize, size.1 as usize);
+    let edges = colored_edges(document, size);
+    let true_distances = signed_distances(document, size);
+
+    let mut pixels = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            let point = (x as f64 + 0.5, y as f64 + 0.5);
+            let true_distance = true_distances[y * width + x];
+            let mut channels = [0.0; 3];
+
+            for (channel, value) in channels.iter_mut().enumerate() {
+                let mask = 1 << channel;
+                let mut best: Option<(f64, f64, f64)> = None;
+                for edge in edges.iter().filter(|edge| edge.color & mask != 0) {
+                    let candidate = edge.distances(point);
+                    let closer = match best {
+                        None => true,
+                        Some((distance, orthogonality, _)) => {
+                            candidate.0 < distance - 1e-9 || (candidate.0 <= distance + 1e-9 && candidate.1 > orthogonality)
+                        }
+                    };
+                    if closer {
+                        best = Some(candidate);
+                    }
+                }
+                *value = best.map_or(true_distance, |(_, _, pseudo_distance)| pseudo_distance);
+            }
+
+            // Overlapping contours and strokes make the channels disagree with the real silhouette,
+            // which shows up as a wrong sign or a median far away from the true distance.
+            let median = channels[0].max(channels[1]).min(channels[0].min(channels[1]).max(channels[2]));
+            if (median > 0.0) != (true_distance > 0.0) || (median - true_distance).abs() > MAX_MEDIAN_ERROR {
+                channels = [true_distance; 3];
+            }
+
+            pixels.extend(channels.iter().map(|&distance| encode_distance(distance, spread)));
+        }
+    }
+
+    pixels
+}
+
+/// Flattens the filled contours of every command into output pixel space, assigns edge
+/// colors and keeps the edges that lie on the boundary of the silhouette.
+fn colored_edges(document: &TinyVg, size: (u32, u32)) -> Vec<ColoredEdge> {
+    let affine = document.fit_transform((size.0 as f64, size.1 as f64), FitMode::Stretch);
+    let [a, b, c, d, _, _] = affine.0;
+    let scale = (a * d - b * c).abs().sqrt().max(f64::EPSILON);
+
+    let outlines: Vec<Outline> = document
+        .draw_commands
+        .iter()
+        .filter_map(|command| command_outline(command, MSDF_TOLERANCE / scale))
+        .map(|mut outline| {
+            outline.transform(&affine);
+            outline
+        })
+        .collect();
+    let silhouette_contains = |point: (f64, f64)| outlines.iter().any(|outline| outline.contains(point));
+
+    let mut edges = Vec::new();
+    for outline in &outlines {
+        for polyline in &outline.fill {
+            let contour: Vec<(f64, f64)> = polyline.points.iter().map(|point| (point.x.0, point.y.0)).collect();
+            if contour.len() < 3 {
+                continue;
+            }
+
+            // Edges covered by other shapes, or by the same shape under the even-odd rule, are
+            // not part of the silhouette. The side that is inside decides the sign of the distance.
+            for mut edge in color_contour(&contour, &polyline.joints) {
+                let (dx, dy) = (edge.end.0 - edge.start.0, edge.end.1 - edge.start.1);
+                let length = dx.hypot(dy);
+                if length == 0.0 {
+                    continue;
+                }
+                let (nx, ny) = (-dy / length * BOUNDARY_PROBE, dx / length * BOUNDARY_PROBE);
+                let (mx, my) = ((edge.start.0 + edge.end.0) / 2.0, (edge.start.1 + edge.end.1) / 2.0);
+                let left = silhouette_contains((mx + nx, my + ny));
+                let right = silhouette_contains((mx - nx, my - ny));
+                if left != right {
+                    edge.inside_left = left;
+                    edges.push(edge);
+                }
+            }
+        }
+    }
+
+    edges
+}
+
+/// Splits a closed contour into edges and colors them so that the edges meeting at a
+/// sharp corner never share all of their channels.
+fn color_contour(contour: &[(f64, f64)], joints: &[usize]) -> Vec<ColoredEdge> {
+    let count = contour.len();
+    let direction = |from: usize, to: usize| {
+        let (dx, dy) = (contour[to].0 - contour[from].0, contour[to].1 - contour[from].1);
+        let length = dx.hypot(dy);
+        if length > 0.0 { (dx / length, dy / length) } else { (0.0, 0.0) }
+    };
+
+    let corners: Vec<usize> = joints
+        .iter()
+        .copied()
+        .filter(|&joint| joint < count)
+        .filter(|&joint| {
+            let incoming = direction((joint + count - 1) % count, joint);
+            let outgoing = direction(joint, (joint + 1) % count);
+            let dot = incoming.0 * outgoing.0 + incoming.1 * outgoing.1;
+            let cross = incoming.0 * outgoing.1 - incoming.1 * outgoing.0;
+            // For unit directions the cross product is the sine of the turn, which only
+            // identifies the angle for turns below 90 degrees.
+            dot <= 0.0 || cross.abs() > CORNER_ANGLE.sin()
+        })
+        .collect();
+
+    let mut colors = vec![WHITE; count];
+    match corners.len() {
+        0 => {}
+        1 => {
+            // A teardrop: split the contour into three spans so the corner stays sharp.
+            let corner = corners[0];
+            for offset in 0..count {
+                colors[(corner + offset) % count] = EDGE_COLORS[(offset * 3 / count).min(2)];
+            }
+        }
+        corner_count => {
+            for (span, &corner) in corners.iter().enumerate() {
+                let next_corner = corners[(span + 1) % corner_count];
+                let mut color = EDGE_COLORS[span % 3];
+                // Avoid giving the last span the same color as the first one.
+                if span == corner_count - 1 && span % 3 == 0 {
+                    color = EDGE_COLORS[1];
+                }
+                let mut edge = corner;
+                loop {
+                    colors[edge] = color;
+                    edge = (edge + 1) % count;
+                    if edge == next_corner {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    (0..count)
+        .map(|edge| ColoredEdge {
+            start: contour[edge],
+            end: contour[(edge + 1) % count],
+            color: colors[edge],
+            inside_left: true,
+            start_corner: corners.contains(&edge),
+            end_corner: corners.contains(&((edge + 1) % count)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{color_contour, render_sdf};
+    use crate::TinyVg;
+
+    /// A 16 by 16 document with one color and one rectangle.
+    const DOCUMENT: [u8; 25] = [0x72, 0x56, 1, 0x00, 16, 0, 16, 0, 1, 255, 0, 0, 255, 0x02, 0, 0, 0, 0, 0, 0, 16, 0, 16, 0, 0x00];
+
+    #[test]
+    fn distances_are_signed_around_the_edge() {
+        // Narrow the rectangle to the left half of the document.
+        let mut data = DOCUMENT;
+        data[20] = 8;
+        let document = TinyVg::from_bytes(&data).unwrap();
+
+        let sdf = render_sdf(&document, (16, 16), 4.0);
+        let pixel = |x: usize| sdf[8 * 16 + x];
+        assert_eq!(pixel(2), 255);
+        assert_eq!(pixel(13), 0);
+        // The pixel centers next to the edge lie half a pixel away from it.
+        assert!((140..=148).contains(&pixel(7)), "{}", pixel(7));
+        assert!((108..=116).contains(&pixel(8)), "{}", pixel(8));
+    }
+
+    #[test]
+    fn corners_turn_by_more_than_the_corner_angle() {
+        let corners = |contour: &[(f64, f64)]| -> Vec<usize> {
+            let joints: Vec<usize> = (0..contour.len()).collect();
+            let edges = color_contour(contour, &joints);
+            (0..edges.len()).filter(|&edge| edges[edge].start_corner).collect()
+        };
+
+        // A bend of about 3 degrees at (10, 0) is smooth, while one of 15 degrees is a corner.
+        assert_eq!(corners(&[(0.0, 0.0), (10.0, 0.0), (20.0, 0.5), (20.0, 10.0), (0.0, 10.0)]), [0, 2, 3, 4]);
+        assert_eq!(corners(&[(0.0, 0.0), (10.0, 0.0), (20.0, 2.68), (20.0, 10.0), (0.0, 10.0)]), [0, 1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,112 @@
+use crate::commands::{DrawCommand, Rectangle, TextHintData};
+use crate::common::Unit;
+use crate::TinyVg;
+
+/// An occurrence of the searched text in a text hint.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextMatch {
+    /// The index of the text hint in `TinyVg::draw_commands`.
+    pub command_index: usize,
+
+    /// The byte range of the match within the hint's text.
+    pub range: std::ops::Range<usize>,
+
+    /// The area covered by the matched glyphs, from the descender to the ascender line.
+    /// Rotated text is covered by the bounding box of the rotated area.
+    pub region: Rectangle,
+}
+
+impl TinyVg {
+    /// Finds every occurrence of `query` in the document's text hints, in drawing order,
+    /// for "find in document" features of viewers. Matching is exact and occurrences do
+    /// not overlap. An empty query matches nothing.
+    ///
+    /// The region of a match covers only the matched glyphs if the hint has one glyph per
+    /// character, and the whole text otherwise.
+    pub fn find_text(&self, query: &str) -> Vec<TextMatch> {
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let mut matches = Vec::new();
+        for (command_index, command) in self.draw_commands.iter().enumerate() {
+            let DrawCommand::TextHint(data) = command else {
+                continue;
+            };
+            for (start, _) in data.text.match_indices(query) {
+                let range = start..start + query.len();
+                matches.push(TextMatch { command_index, region: match_region(data, &range), range });
+            }
+        }
+        matches
+    }
+}
+
+impl TinyVg {
+    /// The text of the document's text hints in reading order, as a plain-text description
+    /// for alt attributes and screen readers. Hints are read top to bottom by their centers,
+    /// and hints whose centers are less than half a text height apart vertically count as
+    /// one line, read left to right. Runs of whitespace become single spaces. Rotation is
+    /// not taken into account. Documents without text give an empty string.
+    pub fn describe(&self) -> String {
+        let mut hints: Vec<&TextHintData> = self
+            .draw_commands
+            .iter()
+            .filter_map(|command| match command {
+                DrawCommand::TextHint(data) if !data.text.trim().is_empty() => Some(data),
+                _ => None,
+            })
+            .collect();
+        hints.sort_by(|a, b| a.center.y.0.total_cmp(&b.center.y.0));
+
+        let mut lines: Vec<Vec<&TextHintData>> = Vec::new();
+        for hint in hints {
+            match lines.last_mut() {
+                Some(line) if hint.center.y.0 - line[0].center.y.0 < line[0].height.0.abs() / 2.0 => line.push(hint),
+                _ => lines.push(vec![hint]),
+            }
+        }
+
+        let mut words = Vec::new();
+        for mut line in lines {
+            line.sort_by(|a, b| a.center.x.0.total_cmp(&b.center.x.0));
+            words.extend(line.iter().flat_map(|hint| hint.text.split_whitespace()));
+        }
+        words.join(" ")
+    }
+}
+
+fn match_region(data: &TextHintData, range: &std::ops::Range<usize>) -> Rectangle {
+    let all_glyphs = || {
+        let start = data.glyph_offset.iter().map(|(start, _)| start.0).fold(f64::INFINITY, f64::min);
+        let end = data.glyph_offset.iter().map(|(_, end)| end.0).fold(f64::NEG_INFINITY, f64::max);
+        if start <= end { (start, end) } else { (0.0, 0.0) }
+    };
+
+    let (start, end) = if data.glyph_offset.len() == data.text.chars().count() {
+        let first_glyph = data.text[..range.start].chars().count();
+        let glyph_count = data.text[range.clone()].chars().count();
+        let glyphs = &data.glyph_offset[first_glyph..first_glyph + glyph_count];
+        let start = glyphs.iter().map(|(start, _)| start.0).fold(f64::INFINITY, f64::min);
+        let end = glyphs.iter().map(|(_, end)| end.0).fold(f64::NEG_INFINITY, f64::max);
+        (start, end)
+    } else {
+        all_glyphs()
+    };
+
+    // Offsets run along the rotated descender line, and the ascender line lies `height`
+    // above it.
+    let (sin, cos) = data.rotation.0.to_radians().sin_cos();
+    let (center_x, center_y) = (data.center.x.0, data.center.y.0);
+    let (up_x, up_y) = (sin * data.height.0, -cos * data.height.0);
+    let corners = [start, end].into_iter().flat_map(|offset| {
+        let (x, y) = (center_x + cos * offset, center_y + sin * offset);
+        [(x, y), (x + up_x, y + up_y)]
+    });
+
+    let (min_x, min_y, max_x, max_y) = corners.fold(
+        (f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        |(min_x, min_y, max_x, max_y), (x, y)| (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)),
+    );
+    Rectangle { x: Unit(min_x), y: Unit(min_y), width: Unit(max_x - min_x), height: Unit(max_y - min_y) }
+}
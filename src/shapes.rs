@@ -0,0 +1,122 @@
+use crate::color_table::{ColorTable, RgbaF32};
+use crate::commands::{DrawCommand, Path, PathCommand, PathCommands, Point, Segment, Style};
+use crate::common::Unit;
+use crate::transform::rectangles_path;
+use crate::TinyVg;
+
+/// A style with its color table indices replaced by the colors they refer to. Missing
+/// colors resolve to transparent black.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolvedStyle {
+    FlatColor(RgbaF32),
+    LinearGradient { point_0: Point, point_1: Point, color_0: RgbaF32, color_1: RgbaF32 },
+    RadialGradient { point_0: Point, point_1: Point, color_0: RgbaF32, color_1: RgbaF32 },
+}
+
+impl ResolvedStyle {
+    pub fn resolve(style: &Style, color_table: &ColorTable) -> Self {
+        let color = |index: u64| color_table.get(index as usize).copied().unwrap_or(RgbaF32(0.0, 0.0, 0.0, 0.0));
+        match style {
+            Style::FlatColor(flat) => ResolvedStyle::FlatColor(color(flat.color_index)),
+            Style::LinearGradient(gradient) => ResolvedStyle::LinearGradient {
+                point_0: gradient.point_0,
+                point_1: gradient.point_1,
+                color_0: color(gradient.color_index_0),
+                color_1: color(gradient.color_index_1),
+            },
+            Style::RadialGradient(gradient) => ResolvedStyle::RadialGradient {
+                point_0: gradient.point_0,
+                point_1: gradient.point_1,
+                color_0: color(gradient.color_index_0),
+                color_1: color(gradient.color_index_1),
+            },
+        }
+    }
+}
+
+/// A draw command in a normalized form: its geometry as a path, and how that path is
+/// filled and stroked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shape {
+    /// Polygons, line loops and rectangles become closed segments, line strips an open
+    /// segment and line lists one open segment per line.
+    pub geometry: Path,
+    pub fill: Option<ResolvedStyle>,
+
+    /// The stroke style and line width. Line widths given on individual path commands
+    /// take precedence over this one.
+    pub stroke: Option<(ResolvedStyle, Unit)>,
+}
+
+impl TinyVg {
+    /// Iterates over the document's draw commands as shapes, in drawing order, so that
+    /// consumers handle one kind of geometry instead of every command type. Text hints
+    /// draw nothing and are skipped.
+    pub fn shapes(&self) -> impl Iterator<Item = Shape> + '_ {
+        self.draw_commands.iter().filter_map(|command| command_shape(command, &self.color_table))
+    }
+}
+
+fn command_shape(command: &DrawCommand, color_table: &ColorTable) -> Option<Shape> {
+    let resolve = |style: &Style| ResolvedStyle::resolve(style, color_table);
+    let shape = match command {
+        DrawCommand::FillPolygon(data) => Shape { geometry: polyline_path(&data.points, true), fill: Some(resolve(&data.style)), stroke: None },
+        DrawCommand::FillRectangles(data) => Shape { geometry: rectangles_path(&data.rectangles), fill: Some(resolve(&data.style)), stroke: None },
+        DrawCommand::FillPath(data) => Shape { geometry: data.path.clone(), fill: Some(resolve(&data.style)), stroke: None },
+        DrawCommand::DrawLines(data) => Shape {
+            geometry: Path {
+                segments: data
+                    .lines
+                    .iter()
+                    .map(|line| Segment { start: line.start, path_commands: [PathCommand::Line(line.end, None)].into_iter().collect() })
+                    .collect(),
+            },
+            fill: None,
+            stroke: Some((resolve(&data.line_style), data.line_width)),
+        },
+        DrawCommand::DrawLineLoop(data) => Shape {
+            geometry: polyline_path(&data.points, true),
+            fill: None,
+            stroke: Some((resolve(&data.line_style), data.line_width)),
+        },
+        DrawCommand::DrawLineStrip(data) => Shape {
+            geometry: polyline_path(&data.points, false),
+            fill: None,
+            stroke: Some((resolve(&data.style), data.line_width)),
+        },
+        DrawCommand::DrawLinePath(data) => Shape {
+            geometry: data.path.clone(),
+            fill: None,
+            stroke: Some((resolve(&data.style), data.line_width)),
+        },
+        DrawCommand::OutlineFillPolygon(data) => Shape {
+            geometry: polyline_path(&data.points, true),
+            fill: Some(resolve(&data.fill_style)),
+            stroke: Some((resolve(&data.line_style), data.line_width)),
+        },
+        DrawCommand::OutlineFillRectangles(data) => Shape {
+            geometry: rectangles_path(&data.rectangles),
+            fill: Some(resolve(&data.fill_style)),
+            stroke: Some((resolve(&data.line_style), data.line_width)),
+        },
+        DrawCommand::OutlineFillPath(data) => Shape {
+            geometry: data.path.clone(),
+            fill: Some(resolve(&data.fill_style)),
+            stroke: Some((resolve(&data.line_style), data.line_width)),
+        },
+        DrawCommand::TextHint(_) => return None,
+    };
+    Some(shape)
+}
+
+/// A single segment through the points, or an empty path if there are none.
+fn polyline_path(points: &[Point], closed: bool) -> Path {
+    let Some((start, rest)) = points.split_first() else {
+        return Path { segments: Vec::new() };
+    };
+    let mut path_commands: PathCommands = rest.iter().map(|point| PathCommand::Line(*point, None)).collect();
+    if closed {
+        path_commands.push(PathCommand::ClosePath(None));
+    }
+    Path { segments: vec![Segment { start: *start, path_commands }] }
+}
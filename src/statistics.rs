@@ -0,0 +1,89 @@
+use crate::commands::{CommandType, DrawCommand, PathCommandType, StyleType};
+use crate::encoded_size::{path_command_size, style_size, unit_size, varuint_size};
+use crate::header::ColorEncoding;
+use crate::palette::for_each_style;
+use crate::TinyVg;
+
+/// How often a type occurs and how many bytes its occurrences take up when encoded.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct TypeStatistics {
+    pub count: usize,
+    pub bytes: usize,
+}
+
+/// Where the bytes of an encoded document go. The groups only list types that occur, in
+/// the order of their encoded value. Command bytes include their styles and path commands,
+/// which are also counted on their own.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statistics {
+    pub header_bytes: usize,
+    pub color_table_bytes: usize,
+    pub commands: Vec<(CommandType, TypeStatistics)>,
+    pub path_commands: Vec<(PathCommandType, TypeStatistics)>,
+    pub styles: Vec<(StyleType, TypeStatistics)>,
+
+    /// The size of the whole file, including the end of document byte.
+    pub total_bytes: usize,
+}
+
+impl TinyVg {
+    /// Counts the commands, path commands and styles of the document by type along with
+    /// the bytes they take up when encoded with the document's header, to show why a file
+    /// is as large as it is.
+    pub fn statistics(&self) -> Statistics {
+        let header = &self.header;
+        let unit = unit_size(header.coordinate_range);
+        let header_bytes = 4 + 2 * unit + varuint_size(self.color_table.len() as u64);
+        let color_bytes = match header.color_encoding {
+            ColorEncoding::Rgba8888 => 4,
+            ColorEncoding::Rgb565 => 2,
+            ColorEncoding::RgbaF32 => 16,
+            ColorEncoding::Custom => 0,
+        };
+        let color_table_bytes = self.color_table.len() * color_bytes;
+
+        let mut commands = [TypeStatistics::default(); 12];
+        let mut path_commands = [TypeStatistics::default(); 8];
+        let mut styles = [TypeStatistics::default(); 3];
+        let add = |entry: &mut TypeStatistics, bytes: usize| {
+            entry.count += 1;
+            entry.bytes += bytes;
+        };
+
+        for command in &self.draw_commands {
+            add(&mut commands[command.command_type() as usize], command.encoded_size(header));
+            for_each_style(command, &mut |style| {
+                add(&mut styles[StyleType::from_style(style) as usize], style_size(style, unit));
+            });
+
+            let path = match command {
+                DrawCommand::FillPath(data) => &data.path,
+                DrawCommand::DrawLinePath(data) => &data.path,
+                DrawCommand::OutlineFillPath(data) => &data.path,
+                _ => continue,
+            };
+            for path_command in path.segments.iter().flat_map(|segment| &segment.path_commands) {
+                add(&mut path_commands[path_command.command_type() as usize], path_command_size(path_command, unit));
+            }
+        }
+
+        let command_bytes: usize = commands.iter().map(|entry| entry.bytes).sum();
+        Statistics {
+            header_bytes,
+            color_table_bytes,
+            commands: occurring(&commands, |value| CommandType::from_u8(value).expect("counted commands have a known type")),
+            path_commands: occurring(&path_commands, PathCommandType::from_u8),
+            styles: occurring(&styles, |value| StyleType::from_u8(value).expect("counted styles have a known type")),
+            total_bytes: header_bytes + color_table_bytes + command_bytes + 1,
+        }
+    }
+}
+
+fn occurring<T>(entries: &[TypeStatistics], from_u8: fn(u8) -> T) -> Vec<(T, TypeStatistics)> {
+    entries
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| entry.count > 0)
+        .map(|(value, entry)| (from_u8(value as u8), *entry))
+        .collect()
+}
@@ -0,0 +1,91 @@
+use crate::color_table::ColorTable;
+use crate::commands::{DrawCommand, FillPathData, FillPolygonData, FlatColored, Path, PathCommand, PathCommands, Point, Segment, Style};
+use crate::common::{Affine, Unit};
+use crate::png::decode_png;
+use crate::render::RgbaImage;
+use crate::svg_to_tvg::svg_to_tvg::ImageHandling;
+use crate::svg_to_tvg::usvg_conversion_utils::set_color;
+use crate::trace::trace_regions;
+use usvg::{ImageKind, Opacity, Transform};
+
+/// Decodes the pixels of a raster image, or returns `None` for formats that cannot be
+/// decoded, which is every format but PNG.
+pub(crate) fn decode_raster(kind: &ImageKind) -> Option<RgbaImage> {
+    match kind {
+        ImageKind::PNG(data) => decode_png(data),
+        _ => None,
+    }
+}
+
+/// The commands that replace a raster image of `size` in the image's coordinates, drawn
+/// with `transform` and `opacity`. Returns nothing for `ImageHandling::Report`.
+pub(crate) fn raster_commands(
+    raster: &RgbaImage,
+    size: usvg::Size,
+    transform: &Transform,
+    opacity: &Opacity,
+    handling: ImageHandling,
+    color_table: &mut ColorTable,
+) -> Vec<DrawCommand> {
+    if raster.width == 0 || raster.height == 0 {
+        return Vec::new();
+    }
+    // Maps pixel corners onto the image's rectangle and then into the document.
+    let pixel_to_document = Affine::scale_non_uniform(size.width() as f64 / raster.width as f64, size.height() as f64 / raster.height as f64)
+        .then(&Affine::from(*transform));
+    let point = |x: f64, y: f64| pixel_to_document.apply(Point::new(Unit(x), Unit(y)));
+    let mut style = |color: [u8; 4]| {
+        let alpha = Opacity::new_clamped(color[3] as f32 / 255.0 * opacity.get());
+        Style::FlatColor(FlatColored { color_index: set_color(color_table, &usvg::Color::new_rgb(color[0], color[1], color[2]), &alpha) })
+    };
+
+    match handling {
+        ImageHandling::Report => Vec::new(),
+        ImageHandling::AverageColor => {
+            let Some(color) = average_color(raster) else {
+                return Vec::new();
+            };
+            let (width, height) = (raster.width as f64, raster.height as f64);
+            vec![DrawCommand::FillPolygon(FillPolygonData {
+                style: style(color),
+                points: [point(0.0, 0.0), point(width, 0.0), point(width, height), point(0.0, height)].into_iter().collect(),
+            })]
+        }
+        ImageHandling::Vectorize { colors } => trace_regions(raster, colors)
+            .into_iter()
+            .map(|region| {
+                let segments = region
+                    .contours
+                    .iter()
+                    .map(|contour| {
+                        let mut path_commands: PathCommands =
+                            contour[1..].iter().map(|&(x, y)| PathCommand::Line(point(x as f64, y as f64), None)).collect();
+                        path_commands.push(PathCommand::ClosePath(None));
+                        Segment { start: point(contour[0].0 as f64, contour[0].1 as f64), path_commands }
+                    })
+                    .collect();
+                DrawCommand::FillPath(FillPathData { style: style(region.color), path: Path { segments } })
+            })
+            .collect(),
+    }
+}
+
+/// The average of the pixels weighted by their alpha, together with the average alpha.
+/// Returns `None` for fully transparent images.
+fn average_color(raster: &RgbaImage) -> Option<[u8; 4]> {
+    let mut sums = [0u64; 4];
+    for pixel in raster.data.chunks_exact(4) {
+        let alpha = pixel[3] as u64;
+        for channel in 0..3 {
+            sums[channel] += pixel[channel] as u64 * alpha;
+        }
+        sums[3] += alpha;
+    }
+    if sums[3] == 0 {
+        return None;
+    }
+
+    let pixels = raster.data.len() as u64 / 4;
+    let channel = |sum: u64| ((sum + sums[3] / 2) / sums[3]) as u8;
+    Some([channel(sums[0]), channel(sums[1]), channel(sums[2]), ((sums[3] + pixels / 2) / pixels) as u8])
+}
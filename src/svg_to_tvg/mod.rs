@@ -1,2 +1,4 @@
 pub mod svg_to_tvg;
-mod usvg_conversion_utils;
\ No newline at end of file
+#[cfg(feature = "trace")]
+mod image_conversion;
+mod usvg_conversion_utils;
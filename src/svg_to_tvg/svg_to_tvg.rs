@@ -1,42 +1,122 @@
-use crate::color_table::{ColorTable, RgbaF32};
-use crate::commands::{CommandType, CubicBezier, DrawCommand, DrawLinePathData, FillPathData, OutlineFillPathData, Path, PathCommand, PathCommandType, Point, QuadraticBezier, Segment, Style, StyleType};
-use crate::common::{write_point, write_size, write_unit, write_variable_sized_unsigned_number, Unit};
-use crate::header::{ColorEncoding, CoordinateRange, TinyVgHeader};
+use crate::color_table::ColorTable;
+use crate::commands::{CubicBezier, DrawCommand, DrawLinePathData, FillPathData, OutlineFillPathData, Path, PathCommand, PathCommands, Point, QuadraticBezier, Rectangle, Segment};
+use crate::common::Unit;
+use crate::encoder::write_tvg;
+use crate::header::{ColorEncoding, CoordinateRange, TinyVgHeader, TINYVG_VERSION};
+#[cfg(feature = "trace")]
+use crate::svg_to_tvg::image_conversion::{decode_raster, raster_commands};
 use crate::svg_to_tvg::usvg_conversion_utils::{usvg_paint_to_tinyvg_style, usvg_point_to_tinyvg_point};
-use crate::TinyVgParseError;
-use byteorder::{LittleEndian, WriteBytesExt};
-use std::io::{Cursor, Write};
+use crate::validation::{ValidationError, OUTLINE_ITEM_LIMIT};
+use crate::TinyVg;
 use usvg::tiny_skia_path::PathSegment;
-use usvg::{Node, Opacity, Transform};
+use usvg::{ImageKind, Node, Opacity, Paint, Transform};
 
-pub fn svg_to_tvg(svg_bytes: &[u8]) -> Vec<u8> {
+/// Converts an SVG to a TinyVG file. The color table uses the smallest encoding that
+/// stores every color with 8-bit precision, which is all SVG colors carry.
+pub fn svg_to_tvg(svg_bytes: &[u8]) -> Result<Vec<u8>, SvgConversionError> {
+    svg_to_tvg_with_options(svg_bytes, &SvgConversionOptions::default()).map(|(tvg, _)| tvg)
+}
+
+/// Converts an SVG like `svg_to_tvg`, writing the color table with `color_encoding`.
+pub fn svg_to_tvg_with_color_encoding(svg_bytes: &[u8], color_encoding: ColorEncoding) -> Result<Vec<u8>, SvgConversionError> {
+    let options = SvgConversionOptions { color_encoding: Some(color_encoding), ..Default::default() };
+    svg_to_tvg_with_options(svg_bytes, &options).map(|(tvg, _)| tvg)
+}
+
+/// Why an SVG could not be converted.
+#[derive(Debug)]
+pub enum SvgConversionError {
+    /// The SVG could not be parsed.
+    Parse(usvg::Error),
+
+    /// The converted document cannot be encoded, for example because the SVG is larger
+    /// than the coordinate range allows.
+    Invalid(ValidationError),
+}
+
+/// How embedded raster images are converted. Only PNG images can be decoded; images in
+/// other formats are reported as unsupported content whatever the handling. Decoding
+/// images requires the `trace` feature.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum ImageHandling {
+    /// Leave the image out and report it as unsupported content.
+    #[default]
+    Report,
+
+    /// Replace the image with a rectangle filled with its average color.
+    #[cfg(feature = "trace")]
+    AverageColor,
+
+    /// Reduce the image to at most `colors` colors and trace the pixels of each color
+    /// into a fill path along the pixel edges.
+    #[cfg(feature = "trace")]
+    Vectorize { colors: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SvgConversionOptions {
+    /// The encoding of the color table, or `None` for the smallest encoding that stores
+    /// every color with 8-bit precision.
+    pub color_encoding: Option<ColorEncoding>,
+    pub images: ImageHandling,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum UnsupportedContentKind {
+    Image,
+    Text,
+    /// A fill or stroke painted with a pattern, which is left out of the path's command.
+    Pattern,
+}
+
+/// An element of the SVG that was left out of the converted document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnsupportedContent {
+    pub kind: UnsupportedContentKind,
+    pub id: String,
+    /// The bounding box of the element in document units, or `None` if it is empty.
+    pub bounds: Option<Rectangle>,
+}
+
+/// Converts an SVG to a TinyVG file with the given options, and returns the elements that
+/// were left out of it.
+pub fn svg_to_tvg_with_options(
+    svg_bytes: &[u8],
+    options: &SvgConversionOptions,
+) -> Result<(Vec<u8>, Vec<UnsupportedContent>), SvgConversionError> {
     let opt = usvg::Options::default();
-    let tree = usvg::Tree::from_data(svg_bytes, &opt).expect("Failed to parse the SVG");
+    let tree = usvg::Tree::from_data(svg_bytes, &opt).map_err(SvgConversionError::Parse)?;
 
     let size = tree.size();
 
     let width = size.width() as u32;
     let height = size.height() as u32;
 
-    let coordinate_limit = width.max(height);
-    let mut scale_bits = 0;
-    while scale_bits < 15 && (coordinate_limit << (scale_bits + 1)) <= i16::MAX as u32
-    {
-        scale_bits += 1;
-    }
-
     let mut color_table: ColorTable = Vec::new();
     let mut draw_commands: Vec<DrawCommand> = Vec::new();
+    let mut unsupported: Vec<UnsupportedContent> = Vec::new();
 
-    let mut stack: Vec<(&Node, Transform, Opacity)> = tree.root().children().iter().rev().map(|c| (c, tree.root().transform(), tree.root().opacity())).collect();
-    while let Some((node, transform, opacity)) = stack.pop() {
+    // Nodes are visited together with the id of their group, as usvg moves the id of an
+    // `<image>` onto the group that places it.
+    let mut stack: Vec<(&Node, Transform, Opacity, &str)> =
+        tree.root().children().iter().rev().map(|c| (c, tree.root().transform(), tree.root().opacity(), tree.root().id())).collect();
+    while let Some((node, transform, opacity, group_id)) = stack.pop() {
 
         match node {
             Node::Group(group) => {
-                let new_transform = transform.post_concat(group.transform());
+                if group.clip_path().is_some() {
+                    log_warning!("ignored the clip path of group `{}`", group.id());
+                }
+                if group.mask().is_some() {
+                    log_warning!("ignored the mask of group `{}`", group.id());
+                }
+                if !group.filters().is_empty() {
+                    log_warning!("ignored the filters of group `{}`", group.id());
+                }
+                let new_transform = transform.pre_concat(group.transform());
                 let new_opacity = Opacity::new(opacity.get() * group.opacity().get()).unwrap();
                 for child in group.children().iter().rev() {
-                    stack.push((child, new_transform, new_opacity));
+                    stack.push((child, new_transform, new_opacity, group.id()));
                 }
             }
             Node::Path(path) => {
@@ -44,7 +124,7 @@ pub fn svg_to_tvg(svg_bytes: &[u8]) -> Vec<u8> {
                 let mut segments: Vec<Segment> = Vec::new();
                 let mut current = Segment {
                     start: Point::new(Unit(0.0), Unit(0.0)),
-                    path_commands: Vec::new(),
+                    path_commands: PathCommands::new(),
                 };
 
                 for seg in new_path.segments() {
@@ -57,7 +137,7 @@ pub fn svg_to_tvg(svg_bytes: &[u8]) -> Vec<u8> {
 
                             current = Segment {
                                 start: usvg_point_to_tinyvg_point(p),
-                                path_commands: Vec::new(),
+                                path_commands: PathCommands::new(),
                             };
                         }
 
@@ -83,7 +163,7 @@ pub fn svg_to_tvg(svg_bytes: &[u8]) -> Vec<u8> {
                         }
 
                         PathSegment::Close => {
-                            current.path_commands.push(PathCommand::ClosePath);
+                            current.path_commands.push(PathCommand::ClosePath(None));
                         }
                     }
                 }
@@ -91,48 +171,62 @@ pub fn svg_to_tvg(svg_bytes: &[u8]) -> Vec<u8> {
                 if !current.path_commands.is_empty() {
                     segments.push(current.clone());
                 }
+                // Paths without any drawn segment would be empty commands.
+                if segments.is_empty() {
+                    continue;
+                }
 
                 let fill = path.fill();
                 let stroke = path.stroke();
+                if stroke.is_some_and(|stroke| stroke.dasharray().is_some()) {
+                    log_warning!("drew the dashed stroke of path `{}` solid", path.id());
+                }
+                let is_pattern = |paint: &Paint| matches!(paint, Paint::Pattern(_));
+                if fill.is_some_and(|fill| is_pattern(fill.paint())) || stroke.is_some_and(|stroke| is_pattern(stroke.paint())) {
+                    log_warning!("skipped the pattern paint of path `{}`", path.id());
+                    unsupported.push(UnsupportedContent {
+                        kind: UnsupportedContentKind::Pattern,
+                        id: path.id().to_string(),
+                        bounds: bounds_rectangle(path.bounding_box(), transform),
+                    });
+                }
+
+                let fill_style = fill.and_then(|fill| {
+                    let fill_opacity = Opacity::new(fill.opacity().get() * opacity.get()).unwrap();
+                    usvg_paint_to_tinyvg_style(fill.paint(), &fill_opacity, &transform, &mut color_table)
+                });
+                let line = stroke.and_then(|stroke| {
+                    let stroke_opacity = Opacity::new(stroke.opacity().get() * opacity.get()).unwrap();
+                    let stroke_style = usvg_paint_to_tinyvg_style(stroke.paint(), &stroke_opacity, &transform, &mut color_table)?;
+                    Some((stroke_style, Unit(stroke.width().get() as f64)))
+                });
 
                 let path = Path {
                     segments,
                 };
 
-                let cmd = match (fill, stroke) {
-                    (Some(fill), Some(stroke)) => {
-
-                        let fill_opacity = Opacity::new(&fill.opacity().get() * opacity.get()).unwrap();
-                        let fill_style = usvg_paint_to_tinyvg_style(&fill.paint(), &fill_opacity, &mut color_table);
-
-                        let stroke_opacity = Opacity::new(&stroke.opacity().get() * opacity.get()).unwrap();
-                        let stroke_style = usvg_paint_to_tinyvg_style(&stroke.paint(), &stroke_opacity, &mut color_table);
-
-                        let data = OutlineFillPathData {
-                            path,
-                            fill_style,
-                            line_style: stroke_style,
-                            line_width: Unit(stroke.width().get() as f64),
-                        };
-                        DrawCommand::OutlineFillPath(data)
+                let cmd = match (fill_style, line) {
+                    (Some(fill_style), Some((stroke_style, line_width))) => {
+                        // Outline fill commands store at most 64 segments, so longer paths
+                        // are filled and stroked by two commands.
+                        if path.segments.len() > OUTLINE_ITEM_LIMIT {
+                            draw_commands.push(DrawCommand::FillPath(FillPathData { style: fill_style, path: path.clone() }));
+                            DrawCommand::DrawLinePath(DrawLinePathData { style: stroke_style, path, line_width })
+                        } else {
+                            DrawCommand::OutlineFillPath(OutlineFillPathData { path, fill_style, line_style: stroke_style, line_width })
+                        }
                     }
-                    (Some(fill), None) => {
-                        let fill_opacity = Opacity::new(&fill.opacity().get() * opacity.get()).unwrap();
-                        let fill_style = usvg_paint_to_tinyvg_style(&fill.paint(), &fill_opacity, &mut color_table);
-
+                    (Some(fill_style), None) => {
                         DrawCommand::FillPath(FillPathData {
                             style: fill_style,
                             path
                         })
                     }
-                    (None, Some(stroke)) => {
-                        let stroke_opacity = Opacity::new(&stroke.opacity().get() * opacity.get()).unwrap();
-                        let stroke_style = usvg_paint_to_tinyvg_style(&stroke.paint(), &stroke_opacity, &mut color_table);
-
+                    (None, Some((stroke_style, line_width))) => {
                         DrawCommand::DrawLinePath(DrawLinePathData {
                             style: stroke_style,
                             path,
-                            line_width: Unit(stroke.width().get() as f64),
+                            line_width,
                         })
                     }
                     (None, None) => continue,
@@ -141,253 +235,121 @@ pub fn svg_to_tvg(svg_bytes: &[u8]) -> Vec<u8> {
                 draw_commands.push(cmd);
             }
 
-            Node::Image(_img) => {}
-            Node::Text(_text) => {
+            Node::Image(image) => {
+                if !image.is_visible() {
+                    continue;
+                }
+                if let ImageKind::SVG(subtree) = image.kind() {
+                    let root = subtree.root();
+                    let new_transform = transform.pre_concat(root.transform());
+                    let new_opacity = Opacity::new(opacity.get() * root.opacity().get()).unwrap();
+                    for child in root.children().iter().rev() {
+                        stack.push((child, new_transform, new_opacity, group_id));
+                    }
+                    continue;
+                }
+
+                let converted: Option<Vec<DrawCommand>> = match options.images {
+                    ImageHandling::Report => None,
+                    #[cfg(feature = "trace")]
+                    handling => decode_raster(image.kind())
+                        .map(|raster| raster_commands(&raster, image.size(), &transform, &opacity, handling, &mut color_table)),
+                };
+                match converted {
+                    Some(commands) => draw_commands.extend(commands),
+                    None => {
+                        let id = if image.id().is_empty() { group_id } else { image.id() };
+                        log_warning!("skipped image `{}`", id);
+                        unsupported.push(UnsupportedContent {
+                            kind: UnsupportedContentKind::Image,
+                            id: id.to_string(),
+                            bounds: bounds_rectangle(image.bounding_box(), transform),
+                        });
+                    }
+                }
+            }
+            Node::Text(text) => {
                 // TODO: We should probably support this.
+                log_warning!("skipped text `{}`", text.id());
+                unsupported.push(UnsupportedContent {
+                    kind: UnsupportedContentKind::Text,
+                    id: text.id().to_string(),
+                    bounds: bounds_rectangle(text.bounding_box(), transform),
+                });
             }
         }
     }
 
 
-    let mut writer = Cursor::new(Vec::new());
     let header = TinyVgHeader {
         magic: [0x72, 0x56],
-        version: 1,
-        scale: scale_bits,
-        color_encoding: ColorEncoding::RgbaF32,
+        version: TINYVG_VERSION,
+        scale: 0,
+        color_encoding: options.color_encoding.unwrap_or_else(|| ColorEncoding::smallest_for(&color_table, 0.5 / 255.0)),
         coordinate_range: CoordinateRange::Default,
         width,
         height,
         color_count: color_table.len() as u64,
     };
+    let mut document = TinyVg { header, color_table, draw_commands };
+    document.requantize();
 
-    write_header(&header, &mut writer).unwrap();
-    write_color_table(&mut writer, &header, &color_table).unwrap();
-    write_draw_commands(&mut writer, &header, &draw_commands).unwrap();
-    write_end(&mut writer).unwrap();
-
-    writer.into_inner()
+    let tvg = write_tvg(&document).map_err(SvgConversionError::Invalid)?;
+    Ok((tvg, unsupported))
 }
 
-pub fn write_header(header: &TinyVgHeader, cursor: &mut Cursor<Vec<u8>>) -> Result<(), TinyVgParseError> {
-    cursor.write_all(&header.magic).map_err(|_| TinyVgParseError::InvalidHeader)?;
-    cursor.write_u8(header.version).map_err(|_| TinyVgParseError::InvalidHeader)?;
-
-    // Pack scale (bits 0–3), color encoding (bits 4–5), coordinate range (bits 6–7)
-    let scc = (header.scale & 0x0F) | ((header.color_encoding as u8) << 4) | ((header.coordinate_range as u8) << 6);
-    cursor.write_u8(scc).map_err(|_| TinyVgParseError::InvalidHeader)?;
-
-    write_size(&header.coordinate_range, cursor, header.width)?;
-    write_size(&header.coordinate_range, cursor, header.height)?;
-    write_variable_sized_unsigned_number(cursor, header.color_count)?;
-
-    Ok(())
+/// The bounding box of a rectangle in an element's coordinates after `transform`.
+fn bounds_rectangle(rect: usvg::Rect, transform: Transform) -> Option<Rectangle> {
+    let rect = rect.transform(transform)?;
+    Some(Rectangle {
+        x: Unit(rect.x() as f64),
+        y: Unit(rect.y() as f64),
+        width: Unit(rect.width() as f64),
+        height: Unit(rect.height() as f64),
+    })
 }
 
-pub fn write_color_table(
-    cursor: &mut Cursor<Vec<u8>>,
-    header: &TinyVgHeader,
-    colors: &[RgbaF32],
-) -> Result<(), TinyVgParseError> {
-    if header.color_encoding != ColorEncoding::RgbaF32 {
-        return Err(TinyVgParseError::InvalidColorTable);
-    }
+#[cfg(test)]
+mod tests {
+    use super::{svg_to_tvg, svg_to_tvg_with_options, SvgConversionOptions, UnsupportedContentKind};
+    use crate::commands::DrawCommand;
+    use crate::TinyVg;
 
-    for &RgbaF32(r, g, b, a) in colors {
-        cursor.write_f32::<LittleEndian>(r).map_err(|_| TinyVgParseError::InvalidColorTable)?;
-        cursor.write_f32::<LittleEndian>(g).map_err(|_| TinyVgParseError::InvalidColorTable)?;
-        cursor.write_f32::<LittleEndian>(b).map_err(|_| TinyVgParseError::InvalidColorTable)?;
-        cursor.write_f32::<LittleEndian>(a).map_err(|_| TinyVgParseError::InvalidColorTable)?;
+    fn convert(body: &str) -> TinyVg {
+        let svg = format!(r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">{body}</svg>"#);
+        TinyVg::from_bytes(&svg_to_tvg(svg.as_bytes()).unwrap()).unwrap()
     }
 
-    Ok(())
-}
-
-pub fn write_draw_commands(cursor: &mut Cursor<Vec<u8>>, header: &TinyVgHeader, commands: &Vec<DrawCommand>) -> Result<(), TinyVgParseError> {
-    for command in commands {
-        match command {
-            DrawCommand::FillPolygon(_) => {}
-            DrawCommand::FillRectangles(_) => {}
-            DrawCommand::FillPath(data) => {
-                write_command_and_primary_style(cursor, CommandType::FillPath, StyleType::from_style(&data.style))?;
-                write_variable_sized_unsigned_number(cursor, data.path.segments.len() as u64 - 1)?;
-                write_style(cursor, header, &data.style)?;
-                write_path(&data.path, cursor, header)?;
-            }
-            DrawCommand::DrawLines(_) => {}
-            DrawCommand::DrawLineLoop(_) => {}
-            DrawCommand::DrawLineStrip(_) => {}
-            DrawCommand::DrawLinePath(data) => {
-                write_command_and_primary_style(cursor, CommandType::DrawLinePath, StyleType::from_style(&data.style))?;
-                write_variable_sized_unsigned_number(cursor, data.path.segments.len() as u64 - 1)?;
-                write_style(cursor, header, &data.style)?;
-                write_unit(header.scale, cursor, &header.coordinate_range, data.line_width)?;
-                write_path(&data.path, cursor, header)?;
+    #[test]
+    fn long_outlined_paths_are_split() {
+        let d: String = (0..65).map(|i| format!("M{i} 0 h1 v1 z ")).collect();
+        let document = convert(&format!(r#"<path d="{d}" fill="red" stroke="blue"/>"#));
+        match document.draw_commands.as_slice() {
+            [DrawCommand::FillPath(fill), DrawCommand::DrawLinePath(line)] => {
+                assert_eq!(fill.path.segments.len(), 65);
+                assert_eq!(line.path.segments.len(), 65);
             }
-            DrawCommand::OutlineFillPolygon(_) => {}
-            DrawCommand::OutlineFillRectangles(_) => {}
-            DrawCommand::OutlineFillPath(data) => {
-                write_command_and_primary_style(cursor, CommandType::OutlineFillPath, StyleType::from_style(&data.fill_style))?;
-                let segment_count = data.path.segments.len() - 1;
-
-                let line_style_type = StyleType::from_style(&data.line_style);
-                let segment_and_style = ((line_style_type as u8) << 6) | (segment_count as u8);
-                cursor
-                    .write_all(&[segment_and_style])
-                    .map_err(|_| TinyVgParseError::InvalidCommand)?;
-
-                write_style(cursor, header, &data.fill_style)?;
-                write_style(cursor, header, &data.line_style)?;
-
-                write_unit(header.scale, cursor, &header.coordinate_range, data.line_width)?;
-
-                write_path(&data.path, cursor, header)?;
-            }
-            DrawCommand::TextHint(_) => {}
+            commands => panic!("unexpected commands {commands:?}"),
         }
     }
 
-    Ok(())
-}
-
-pub fn write_end(
-    cursor: &mut Cursor<Vec<u8>>,
-) -> Result<(), TinyVgParseError> {
-    cursor
-        .write_u8(0)
-        .map_err(|_| TinyVgParseError::InvalidCommand)?;
-
-    Ok(())
-}
-
-pub fn write_style(cursor: &mut Cursor<Vec<u8>>, header: &TinyVgHeader, style: &Style) -> Result<(), TinyVgParseError> {
-    match style {
-        Style::FlatColor(flat_colored) => {
-            write_variable_sized_unsigned_number(cursor, flat_colored.color_index)?;
-        }
-        Style::LinearGradient(linear_gradient) => {
-            write_unit(header.scale, cursor, &header.coordinate_range, linear_gradient.point_0.x)?;
-            write_unit(header.scale, cursor, &header.coordinate_range, linear_gradient.point_0.y)?;
-            write_unit(header.scale, cursor, &header.coordinate_range, linear_gradient.point_1.x)?;
-            write_unit(header.scale, cursor, &header.coordinate_range, linear_gradient.point_1.y)?;
-
-            write_variable_sized_unsigned_number(cursor, linear_gradient.color_index_0)?;
-            write_variable_sized_unsigned_number(cursor, linear_gradient.color_index_1)?;
-        }
-        Style::RadialGradient(radial_gradient) => {
-            write_unit(header.scale, cursor, &header.coordinate_range, radial_gradient.point_0.x)?;
-            write_unit(header.scale, cursor, &header.coordinate_range, radial_gradient.point_0.y)?;
-            write_unit(header.scale, cursor, &header.coordinate_range, radial_gradient.point_1.x)?;
-            write_unit(header.scale, cursor, &header.coordinate_range, radial_gradient.point_1.y)?;
-
-            write_variable_sized_unsigned_number(cursor, radial_gradient.color_index_0)?;
-            write_variable_sized_unsigned_number(cursor, radial_gradient.color_index_1)?;
-        }
+    #[test]
+    fn empty_paths_are_dropped() {
+        let document = convert(r#"<path d="M0 0" fill="red"/><rect width="10" height="10" fill="blue"/>"#);
+        assert_eq!(document.draw_commands.len(), 1);
     }
 
-    Ok(())
-}
-
-pub fn write_path(path: &Path, cursor: &mut Cursor<Vec<u8>>, header: &TinyVgHeader) -> Result<(), TinyVgParseError> {
-    for segment in &path.segments {
-        let cmd_count = segment.path_commands.len();
-        if cmd_count == 0 {
-            return Err(TinyVgParseError::InvalidCommand);
-        }
-        write_variable_sized_unsigned_number(cursor, (cmd_count - 1) as u64)?;
+    #[test]
+    fn pattern_paints_are_reported() {
+        let svg = br#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+            <pattern id="dots" width="4" height="4" patternUnits="userSpaceOnUse"><circle cx="2" cy="2" r="1"/></pattern>
+            <rect id="patterned" width="10" height="10" fill="url(#dots)" stroke="red"/>
+        </svg>"#;
+        let (tvg, unsupported) = svg_to_tvg_with_options(svg, &SvgConversionOptions::default()).unwrap();
+        assert_eq!(unsupported.len(), 1);
+        assert_eq!(unsupported[0].kind, UnsupportedContentKind::Pattern);
+        assert_eq!(unsupported[0].id, "patterned");
+        let document = TinyVg::from_bytes(&tvg).unwrap();
+        assert!(matches!(document.draw_commands.as_slice(), [DrawCommand::DrawLinePath(_)]));
     }
-
-    for segment in &path.segments {
-        let Point { x, y } = segment.start;
-        write_unit(header.scale, cursor, &header.coordinate_range, x)?;
-        write_unit(header.scale, cursor, &header.coordinate_range, y)?;
-
-        for cmd in &segment.path_commands {
-            let (base_tag, has_lw, lw) = match cmd {
-                PathCommand::Line(_, lw)              => (PathCommandType::Line as u8, lw.is_some(), lw),
-                PathCommand::HorizontalLine(_, lw)    => (PathCommandType::HorizontalLine as u8, lw.is_some(), lw),
-                PathCommand::VerticalLine(_, lw)      => (PathCommandType::VerticalLine as u8, lw.is_some(), lw),
-                PathCommand::CubicBezier(_, lw)       => (PathCommandType::CubicBezier as u8, lw.is_some(), lw),
-                PathCommand::ArcCircle(_, lw)         => (PathCommandType::ArcCircle as u8, lw.is_some(), lw),
-                PathCommand::ArcEllipse(_, lw)        => (PathCommandType::ArcEllipse as u8, lw.is_some(), lw),
-                PathCommand::QuadraticBezier(_, lw)   => (PathCommandType::QuadraticBezier as u8, lw.is_some(), lw),
-                PathCommand::ClosePath                             => (PathCommandType::ClosePath as u8, false, &None),
-            };
-
-            let mut tag = base_tag & 0b0000_0111;
-            if has_lw {
-                tag |= 0b0001_0000;
-            }
-            cursor.write_all(&[tag]).map_err(|_| TinyVgParseError::InvalidCommand)?;
-
-            if let Some(unit) = *lw {
-                write_unit(header.scale, cursor, &header.coordinate_range, unit)?;
-            }
-
-            match cmd {
-                PathCommand::Line(p, _) => {
-                    write_point(p, header, cursor)?
-                }
-                PathCommand::HorizontalLine(u, _) => {
-                    write_unit(header.scale, cursor, &header.coordinate_range, *u)?;
-                }
-                PathCommand::VerticalLine(u, _) => {
-                    write_unit(header.scale, cursor, &header.coordinate_range, *u)?;
-                }
-                PathCommand::CubicBezier(c, _) => {
-                    write_point(&c.control_point_0, header, cursor)?;
-                    write_point(&c.control_point_1, header, cursor)?;
-                    write_point(&c.point_1, header, cursor)?
-                }
-                PathCommand::ArcCircle(a, _) => {
-                    let mut flags = 0;
-                    if a.large_arc { flags |= 0b01; }
-                    if a.sweep     { flags |= 0b10; }
-                    cursor.write_all(&[flags]).map_err(|_| TinyVgParseError::InvalidCommand)?;
-                    write_unit(header.scale, cursor, &header.coordinate_range, a.radius)?;
-                    write_point(&a.target, header, cursor)?
-                }
-                PathCommand::ArcEllipse(a, _) => {
-                    let mut flags = 0;
-                    if a.large_arc { flags |= 0b01; }
-                    if a.sweep     { flags |= 0b10; }
-                    cursor.write_all(&[flags]).map_err(|_| TinyVgParseError::InvalidCommand)?;
-                    write_unit(header.scale, cursor, &header.coordinate_range, a.radius_x)?;
-                    write_unit(header.scale, cursor, &header.coordinate_range, a.radius_y)?;
-                    write_unit(header.scale, cursor, &header.coordinate_range, a.rotation)?;
-                    write_point(&a.target, header, cursor)?
-                }
-                PathCommand::QuadraticBezier(q, _) => {
-                    write_point(&q.control_point, header, cursor)?;
-                    write_point(&q.point_1, header, cursor)?
-                }
-                PathCommand::ClosePath => {}
-            }
-        }
-    }
-
-    Ok(())
 }
-
-
-pub fn write_command_and_primary_style(
-    cursor: &mut Cursor<Vec<u8>>,
-    command: CommandType,
-    style_type: StyleType,
-) -> Result<(), TinyVgParseError> {
-    let command_u8 = command as u8;
-    let style_u8 = style_type as u8;
-
-    if command_u8 > 0b0011_1111 {
-        return Err(TinyVgParseError::InvalidCommand);
-    }
-
-
-    let combined = (style_u8 << 6) | (command_u8 & 0b0011_1111);
-
-    cursor
-        .write_all(&[combined])
-        .map_err(|_| TinyVgParseError::InvalidCommand)?;
-
-    Ok(())
-}
\ No newline at end of file
@@ -1,7 +1,20 @@
-use usvg::{Opacity, Paint};
+use usvg::{Opacity, Paint, Transform};
 use crate::color_table::{ColorTable, RgbaF32};
 use crate::commands::{FlatColored, LinearGradient, Point, RadialGradient, Style};
-use crate::common::Unit;
+use crate::common::{Affine, Unit};
+
+impl From<usvg::Transform> for Affine {
+    fn from(transform: usvg::Transform) -> Self {
+        Affine([
+            transform.sx as f64,
+            transform.ky as f64,
+            transform.kx as f64,
+            transform.sy as f64,
+            transform.tx as f64,
+            transform.ty as f64,
+        ])
+    }
+}
 
 pub(crate) fn set_color(color_table: &mut ColorTable, usvg_color: &usvg::Color, opacity: &Opacity) -> u64 {
     let new_color = RgbaF32(usvg_color.red as f32 / 255.0, usvg_color.green as f32 / 255.0, usvg_color.blue as f32 / 255.0, opacity.get());
@@ -15,17 +28,24 @@ pub(crate) fn set_color(color_table: &mut ColorTable, usvg_color: &usvg::Color,
     (color_table.len() - 1) as u64
 }
 
-pub(crate) fn usvg_paint_to_tinyvg_style(paint: &Paint, opacity: &Opacity, color_table: &mut ColorTable) -> Style {
+/// The style for `paint` on a path whose geometry was transformed by `transform`. Gradient
+/// points are moved by the gradient's own transform followed by `transform`, so that they
+/// line up with the path. Returns `None` for patterns, which TinyVG cannot paint.
+pub(crate) fn usvg_paint_to_tinyvg_style(paint: &Paint, opacity: &Opacity, transform: &Transform, color_table: &mut ColorTable) -> Option<Style> {
 
-    match paint {
+    let style = match paint {
         Paint::Color(color) => {
             Style::FlatColor(FlatColored {
                 color_index: set_color(color_table, color, opacity),
             })
         }
         Paint::LinearGradient(gradient) => {
-            let point_0 = Point::new(Unit(gradient.x1() as f64), Unit(gradient.y1() as f64));
-            let point_1 = Point::new(Unit(gradient.x2() as f64), Unit(gradient.y2() as f64));
+            warn_gradient_approximations(gradient);
+            let (point_0, point_1) = transform_linear_gradient(
+                Point::from((gradient.x1() as f64, gradient.y1() as f64)),
+                Point::from((gradient.x2() as f64, gradient.y2() as f64)),
+                &gradient_affine(gradient, transform),
+            );
 
             let stop_0 = gradient.stops().first().unwrap();
             let stop_1 = gradient.stops().last().unwrap();
@@ -37,11 +57,16 @@ pub(crate) fn usvg_paint_to_tinyvg_style(paint: &Paint, opacity: &Opacity, color
             })
         }
         Paint::RadialGradient(gradient) => {
-            let (x1, y1) = (gradient.fx(), gradient.fy());
-            let (x2, y2) = (gradient.cx(), gradient.cy() + gradient.r().get());
+            warn_gradient_approximations(gradient);
+            let (center, radius) = radial_gradient_circle(gradient);
 
-            let point_0 = Point::new(Unit(x1 as f64), Unit(y1 as f64));
-            let point_1 = Point::new(Unit(x2 as f64), Unit(y2 as f64));
+            let affine = gradient_affine(gradient, transform);
+            if !is_similarity(&affine) {
+                log_warning!("kept radial gradient `{}` circular under a transform that distorts it", gradient.id());
+            }
+            // Circles that the transform turns into ellipses keep the same area.
+            let point_0 = affine.apply(center);
+            let point_1 = point_0 + Point::from((radius * affine.determinant().abs().sqrt(), 0.0));
 
             let stop_0 = gradient.stops().first().unwrap();
             let stop_1 = gradient.stops().last().unwrap();
@@ -53,12 +78,151 @@ pub(crate) fn usvg_paint_to_tinyvg_style(paint: &Paint, opacity: &Opacity, color
                 color_index_1: set_color(color_table, &stop_1.color(), &Opacity::new(stop_1.opacity().get() * opacity.get()).unwrap()),
             })
         }
-        Paint::Pattern(_) => {
-            panic!("Pattern is not supported");
-        }
+        Paint::Pattern(_) => return None,
+    };
+    Some(style)
+}
+
+/// TinyVG gradients have exactly two colors.
+fn warn_gradient_approximations(gradient: &usvg::BaseGradient) {
+    if gradient.stops().len() > 2 {
+        log_warning!("reduced gradient `{}` with {} stops to its first and last stop", gradient.id(), gradient.stops().len());
+    }
+}
+
+/// How far the center of a converted radial gradient moves from the SVG circle's center
+/// toward its focal point, as a fraction of the distance between them.
+const FOCAL_POINT_WEIGHT: f64 = 0.3;
+
+/// The center and radius of a TinyVG radial gradient that approximates an SVG one, in the
+/// gradient's own coordinates.
+///
+/// TinyVG radial gradients run from the first color at their center to the second color on
+/// a circle around it. SVG ones run from the first stop at the focal point (fx, fy) to the
+/// last stop on the circle (cx, cy, r), along rays that start at the focal point. Without
+/// a focal offset the two agree. With one, no TinyVG gradient matches, so the circle is
+/// kept, which leaves the end color where SVG puts it, and its center is moved toward the
+/// focal point by `FOCAL_POINT_WEIGHT`. Of the centers between the two points, that one
+/// has about the smallest average difference in gradient position inside the circle for
+/// any focal offset. The radius is then scaled to the offset of the last stop.
+fn radial_gradient_circle(gradient: &usvg::RadialGradient) -> (Point, f64) {
+    let center = Point::from((gradient.cx() as f64, gradient.cy() as f64));
+    let focal = Point::from((gradient.fx() as f64, gradient.fy() as f64));
+    let last_offset = gradient.stops().last().map_or(1.0, |stop| stop.offset().get() as f64);
+    (center.lerp(&focal, FOCAL_POINT_WEIGHT), gradient.r().get() as f64 * last_offset)
+}
+
+/// The transform from the gradient's own coordinates to those of the converted path.
+fn gradient_affine(gradient: &usvg::BaseGradient, transform: &Transform) -> Affine {
+    Affine::from(gradient.transform()).then(&Affine::from(*transform))
+}
+
+/// Whether the transform keeps circles circular, as rotations, mirrors, uniform scales and
+/// translations do.
+fn is_similarity(affine: &Affine) -> bool {
+    let [a, b, c, d, _, _] = affine.0;
+    let tolerance = 1e-6 * (a.abs() + b.abs() + c.abs() + d.abs()).max(f64::MIN_POSITIVE);
+    (a * c + b * d).abs() <= tolerance && (a.hypot(b) - c.hypot(d)).abs() <= tolerance
+}
+
+/// The end points of a linear gradient from `point_0` to `point_1` after `affine`. A
+/// transform that rotates or skews the gradient turns its lines of equal color, which
+/// must stay perpendicular to the gradient in TinyVG, so the second point is placed where
+/// the transformed gradient reaches its end color in the direction across those lines.
+fn transform_linear_gradient(point_0: Point, point_1: Point, affine: &Affine) -> (Point, Point) {
+    let start = affine.apply(point_0);
+    let direction = point_1 - point_0;
+    let length_squared = direction.x.0 * direction.x.0 + direction.y.0 * direction.y.0;
+    let [a, b, c, d, _, _] = affine.0;
+    let determinant = affine.determinant();
+    if length_squared == 0.0 || determinant == 0.0 {
+        return (start, affine.apply(point_1));
     }
+
+    // The position along the gradient changes by the direction divided by its squared
+    // length per unit moved in gradient space, so in path space by the inverse transpose
+    // of the transform's linear part applied to it.
+    let (gx, gy) = (direction.x.0 / length_squared, direction.y.0 / length_squared);
+    let gradient_x = (d * gx - b * gy) / determinant;
+    let gradient_y = (-c * gx + a * gy) / determinant;
+    let gradient_squared = gradient_x * gradient_x + gradient_y * gradient_y;
+    (start, start + Point::from((gradient_x / gradient_squared, gradient_y / gradient_squared)))
 }
 
 pub(crate) fn usvg_point_to_tinyvg_point(usvg_point: usvg::tiny_skia_path::Point) -> Point {
     Point::new(Unit(usvg_point.x as f64), Unit(usvg_point.y as f64))
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::FOCAL_POINT_WEIGHT;
+    use crate::fit::FitMode;
+    use crate::render::RgbaImage;
+    use crate::svg_to_tvg::svg_to_tvg::svg_to_tvg;
+    use crate::TinyVg;
+
+    /// Renders a 100 by 100 square filled with a radial gradient around (50, 50) with a
+    /// radius of 40 and its focal point at `focal`, which fades from opaque to transparent
+    /// white, so that the alpha of a pixel shows the gradient position there.
+    fn render_radial_gradient(focal: (f64, f64)) -> RgbaImage {
+        let svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+                <radialGradient id="g" gradientUnits="userSpaceOnUse" cx="50" cy="50" r="40" fx="{}" fy="{}">
+                    <stop offset="0" stop-color="white"/><stop offset="1" stop-color="white" stop-opacity="0"/>
+                </radialGradient>
+                <rect width="100" height="100" fill="url(#g)"/>
+            </svg>"#,
+            focal.0, focal.1
+        );
+        let document = TinyVg::from_bytes(&svg_to_tvg(svg.as_bytes()).unwrap()).unwrap();
+        document.render_rgba((100, 100), FitMode::Stretch)
+    }
+
+    /// The alpha an SVG renderer paints at the center of a pixel: one minus the gradient
+    /// position, which is where the pixel lies on the circles that grow from the focal
+    /// point to the gradient's circle.
+    fn reference_alpha(x: u32, y: u32, focal: (f64, f64)) -> f64 {
+        let (qx, qy) = (x as f64 + 0.5 - focal.0, y as f64 + 0.5 - focal.1);
+        let (dx, dy) = (50.0 - focal.0, 50.0 - focal.1);
+        let a = dx * dx + dy * dy - 40.0 * 40.0;
+        let qd = qx * dx + qy * dy;
+        let t = (qd - (qd * qd - a * (qx * qx + qy * qy)).sqrt()) / a;
+        (1.0 - t.clamp(0.0, 1.0)) * 255.0
+    }
+
+    /// The alpha of a TinyVG radial gradient around the SVG circle's center moved toward
+    /// the focal point by `weight`, at the center of a pixel.
+    fn circle_alpha(x: u32, y: u32, focal: (f64, f64), weight: f64) -> f64 {
+        let center = (50.0 + (focal.0 - 50.0) * weight, 50.0 + (focal.1 - 50.0) * weight);
+        let distance = (x as f64 + 0.5 - center.0).hypot(y as f64 + 0.5 - center.1);
+        (1.0 - (distance / 40.0).min(1.0)) * 255.0
+    }
+
+    fn pixels() -> impl Iterator<Item = (u32, u32)> {
+        (0..100).flat_map(|y| (0..100).map(move |x| (x, y)))
+    }
+
+    #[test]
+    fn radial_gradients_without_focal_offset_match_svg() {
+        let focal = (50.0, 50.0);
+        let image = render_radial_gradient(focal);
+        assert!(pixels().all(|(x, y)| (image.pixel(x, y).unwrap()[3] as f64 - reference_alpha(x, y, focal)).abs() <= 1.0));
+        assert_eq!(image.pixel(50, 50), Some([255, 255, 255, 250]));
+        assert_eq!(image.pixel(95, 50), Some([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn radial_gradients_with_focal_offset_move_the_center_toward_it() {
+        let focal = (30.0, 50.0);
+        let image = render_radial_gradient(focal);
+        assert!(pixels().all(|(x, y)| (image.pixel(x, y).unwrap()[3] as f64 - circle_alpha(x, y, focal, FOCAL_POINT_WEIGHT)).abs() <= 1.0));
+
+        // Against the SVG reference, the moved center is closer on average than keeping the
+        // center or moving it all the way to the focal point.
+        let mean_error = |weight: f64| {
+            pixels().map(|(x, y)| (circle_alpha(x, y, focal, weight) - reference_alpha(x, y, focal)).abs()).sum::<f64>() / 10_000.0
+        };
+        let error = mean_error(FOCAL_POINT_WEIGHT);
+        assert!(error < 10.0, "{error}");
+        assert!(error < mean_error(0.0) && error < mean_error(1.0));
+    }
+}
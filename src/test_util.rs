@@ -0,0 +1,200 @@
+//! Proptest strategies for documents and a round-trip check, for property testing code
+//! that reads, writes or rewrites TinyVG files.
+
+use crate::color_table::RgbaF32;
+use crate::commands::{
+    ArcCircle, ArcEllipse, CubicBezier, DrawCommand, DrawLineLoopData, DrawLinePathData, DrawLineStripData, DrawLinesData,
+    FillPathData, FillPolygonData, FillRectanglesData, FlatColored, Line, LinearGradient, OutlineFillPathData,
+    OutlineFillPolygonData, OutlineFillRectanglesData, Path, PathCommand, Point, Points, QuadraticBezier, RadialGradient, Rectangle,
+    Segment, Style, TextHintData,
+};
+use crate::common::Unit;
+use crate::header::{ColorEncoding, CoordinateRange, TinyVgHeader, TINYVG_VERSION};
+use crate::precision::{raw_limit, size_limit};
+use crate::TinyVg;
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::strategy::{BoxedStrategy, Just, Strategy};
+
+/// The most colors, commands, items per command, segments per path and path commands per
+/// segment that are generated.
+const MAX_ITEMS: usize = 8;
+
+/// Headers with any scale, coordinate range and writable color encoding, and a size that
+/// fits the range. The color count is 0, `document` sets it to match the color table.
+pub fn header() -> impl Strategy<Value = TinyVgHeader> {
+    let color_encoding = prop_oneof![Just(ColorEncoding::Rgba8888), Just(ColorEncoding::Rgb565), Just(ColorEncoding::RgbaF32)];
+    let coordinate_range = prop_oneof![Just(CoordinateRange::Default), Just(CoordinateRange::Reduced), Just(CoordinateRange::Enhanced)];
+
+    (0..=15u8, color_encoding, coordinate_range).prop_flat_map(|(scale, color_encoding, coordinate_range)| {
+        let limit = size_limit(coordinate_range);
+        (0..=limit, 0..=limit).prop_map(move |(width, height)| TinyVgHeader {
+            magic: [0x72, 0x56],
+            version: TINYVG_VERSION,
+            scale,
+            color_encoding,
+            coordinate_range,
+            width,
+            height,
+            color_count: 0,
+        })
+    })
+}
+
+/// Valid documents whose colors and units are exactly representable with their header, so
+/// encoding and parsing them gives back an equal document.
+pub fn document() -> impl Strategy<Value = TinyVg> {
+    header().prop_flat_map(|header| {
+        vec(color(header.color_encoding), 1..=MAX_ITEMS).prop_flat_map(move |color_table| {
+            let header = TinyVgHeader { color_count: color_table.len() as u64, ..header.clone() };
+            vec(draw_command(&header, color_table.len()), 0..=MAX_ITEMS).prop_map(move |draw_commands| TinyVg {
+                header: header.clone(),
+                color_table: color_table.clone(),
+                draw_commands,
+            })
+        })
+    })
+}
+
+/// Colors that `encoding` stores exactly. Custom encodings get the colors of `RgbaF32`.
+pub fn color(encoding: ColorEncoding) -> BoxedStrategy<RgbaF32> {
+    match encoding {
+        ColorEncoding::Rgba8888 => any::<[u8; 4]>()
+            .prop_map(|[r, g, b, a]| RgbaF32(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, a as f32 / 255.0))
+            .boxed(),
+        ColorEncoding::Rgb565 => (0..=31u8, 0..=63u8, 0..=31u8)
+            .prop_map(|(r, g, b)| RgbaF32(r as f32 / 31.0, g as f32 / 63.0, b as f32 / 31.0, 1.0))
+            .boxed(),
+        ColorEncoding::RgbaF32 | ColorEncoding::Custom => {
+            (0.0f32..=1.0, 0.0f32..=1.0, 0.0f32..=1.0, 0.0f32..=1.0).prop_map(|(r, g, b, a)| RgbaF32(r, g, b, a)).boxed()
+        }
+    }
+}
+
+/// Units that fit the header's coordinate range at its scale.
+pub fn unit(header: &TinyVgHeader) -> impl Strategy<Value = Unit> + Clone + use<> {
+    let limit = raw_limit(header.coordinate_range) as i32;
+    let scale = header.scale.min(15);
+    (-limit..=limit).prop_map(move |raw| Unit::from_raw(raw, scale))
+}
+
+pub fn point(header: &TinyVgHeader) -> impl Strategy<Value = Point> + Clone + use<> {
+    (unit(header), unit(header)).prop_map(|(x, y)| Point { x, y })
+}
+
+pub fn rectangle(header: &TinyVgHeader) -> impl Strategy<Value = Rectangle> + Clone + use<> {
+    (unit(header), unit(header), unit(header), unit(header)).prop_map(|(x, y, width, height)| Rectangle { x, y, width, height })
+}
+
+/// Flat colors and gradients referencing colors of a table with `color_count` entries,
+/// which must not be 0.
+pub fn style(header: &TinyVgHeader, color_count: usize) -> BoxedStrategy<Style> {
+    let index = 0..color_count as u64;
+    prop_oneof![
+        index.clone().prop_map(|color_index| Style::FlatColor(FlatColored { color_index })),
+        (point(header), point(header), index.clone(), index.clone()).prop_map(|(point_0, point_1, color_index_0, color_index_1)| {
+            Style::LinearGradient(LinearGradient { point_0, point_1, color_index_0, color_index_1 })
+        }),
+        (point(header), point(header), index.clone(), index).prop_map(|(point_0, point_1, color_index_0, color_index_1)| {
+            Style::RadialGradient(RadialGradient { point_0, point_1, color_index_0, color_index_1 })
+        }),
+    ]
+    .boxed()
+}
+
+/// Path commands of every type, each with or without a line width.
+pub fn path_command(header: &TinyVgHeader) -> BoxedStrategy<PathCommand> {
+    let line_width = proptest::option::of(unit(header));
+    prop_oneof![
+        (point(header), line_width.clone()).prop_map(|(point, line_width)| PathCommand::Line(point, line_width)),
+        (unit(header), line_width.clone()).prop_map(|(x, line_width)| PathCommand::HorizontalLine(x, line_width)),
+        (unit(header), line_width.clone()).prop_map(|(y, line_width)| PathCommand::VerticalLine(y, line_width)),
+        (point(header), point(header), point(header), line_width.clone()).prop_map(
+            |(control_point_0, control_point_1, point_1, line_width)| {
+                PathCommand::CubicBezier(CubicBezier { control_point_0, control_point_1, point_1 }, line_width)
+            }
+        ),
+        (any::<bool>(), any::<bool>(), unit(header), point(header), line_width.clone()).prop_map(
+            |(large_arc, sweep, radius, target, line_width)| {
+                PathCommand::ArcCircle(ArcCircle { large_arc, sweep, radius, target }, line_width)
+            }
+        ),
+        (any::<bool>(), any::<bool>(), unit(header), unit(header), unit(header), point(header), line_width.clone()).prop_map(
+            |(large_arc, sweep, radius_x, radius_y, rotation, target, line_width)| {
+                PathCommand::ArcEllipse(ArcEllipse { large_arc, sweep, radius_x, radius_y, rotation, target }, line_width)
+            }
+        ),
+        line_width.clone().prop_map(PathCommand::ClosePath),
+        (point(header), point(header), line_width).prop_map(|(control_point, point_1, line_width)| {
+            PathCommand::QuadraticBezier(QuadraticBezier { control_point, point_1 }, line_width)
+        }),
+    ]
+    .boxed()
+}
+
+/// Paths with at least one segment, none of them empty.
+pub fn path(header: &TinyVgHeader) -> BoxedStrategy<Path> {
+    let segment = (point(header), vec(path_command(header), 1..=MAX_ITEMS))
+        .prop_map(|(start, path_commands)| Segment { start, path_commands: path_commands.into_iter().collect() });
+    vec(segment, 1..=MAX_ITEMS).prop_map(|segments| Path { segments }).boxed()
+}
+
+/// Valid draw commands of every type referencing colors of a table with `color_count`
+/// entries, which must not be 0.
+pub fn draw_command(header: &TinyVgHeader, color_count: usize) -> BoxedStrategy<DrawCommand> {
+    let style = || style(header, color_count);
+    let points = || vec(point(header), 1..=MAX_ITEMS).prop_map(|points| points.into_iter().collect::<Points>());
+    let rectangles = || vec(rectangle(header), 1..=MAX_ITEMS);
+    let line = (point(header), point(header)).prop_map(|(start, end)| Line { start, end });
+    let glyph_offsets = vec((unit(header), unit(header)), 0..=MAX_ITEMS);
+
+    prop_oneof![
+        (style(), points()).prop_map(|(style, points)| DrawCommand::FillPolygon(FillPolygonData { style, points })),
+        (style(), rectangles())
+            .prop_map(|(style, rectangles)| DrawCommand::FillRectangles(FillRectanglesData { style, rectangles })),
+        (style(), path(header)).prop_map(|(style, path)| DrawCommand::FillPath(FillPathData { style, path })),
+        (style(), unit(header), vec(line, 1..=MAX_ITEMS)).prop_map(|(line_style, line_width, lines)| {
+            DrawCommand::DrawLines(DrawLinesData { lines, line_width, line_style })
+        }),
+        (style(), unit(header), points()).prop_map(|(line_style, line_width, points)| {
+            DrawCommand::DrawLineLoop(DrawLineLoopData { line_style, line_width, points })
+        }),
+        (style(), unit(header), points()).prop_map(|(style, line_width, points)| {
+            DrawCommand::DrawLineStrip(DrawLineStripData { style, line_width, points })
+        }),
+        (style(), unit(header), path(header)).prop_map(|(style, line_width, path)| {
+            DrawCommand::DrawLinePath(DrawLinePathData { style, line_width, path })
+        }),
+        (style(), style(), unit(header), points()).prop_map(|(fill_style, line_style, line_width, points)| {
+            DrawCommand::OutlineFillPolygon(OutlineFillPolygonData { fill_style, line_style, line_width, points })
+        }),
+        (style(), style(), unit(header), rectangles()).prop_map(|(fill_style, line_style, line_width, rectangles)| {
+            DrawCommand::OutlineFillRectangles(OutlineFillRectanglesData { fill_style, line_style, line_width, rectangles })
+        }),
+        (style(), style(), unit(header), path(header)).prop_map(|(fill_style, line_style, line_width, path)| {
+            DrawCommand::OutlineFillPath(OutlineFillPathData { path, fill_style, line_style, line_width })
+        }),
+        (point(header), unit(header), unit(header), any::<String>(), glyph_offsets).prop_map(
+            |(center, rotation, height, text, glyph_offset)| {
+                DrawCommand::TextHint(TextHintData {
+                    center,
+                    rotation,
+                    height,
+                    text,
+                    glyph_length: glyph_offset.len() as u64,
+                    glyph_offset,
+                })
+            }
+        ),
+    ]
+    .boxed()
+}
+
+/// Encodes `document`, parses the result and asserts that it equals the document. The
+/// document must be valid and its colors and units exactly representable with its
+/// header, as for the documents of `document()`.
+pub fn assert_roundtrip(document: &TinyVg) {
+    let bytes = document.to_bytes().unwrap_or_else(|error| panic!("the document cannot be encoded: {error:?}"));
+    let parsed = TinyVg::from_bytes(&bytes).unwrap_or_else(|error| panic!("the encoded document cannot be parsed: {error:?}"));
+    assert_eq!(&parsed, document, "the document changed in the round trip");
+}
@@ -0,0 +1,219 @@
+use crate::builder::TinyVgBuilder;
+use crate::color_table::RgbaF32;
+use crate::commands::{CubicBezier, DrawCommand, Path, PathCommand, PathCommands, Point, QuadraticBezier, Segment, TextHintData};
+use crate::common::Unit;
+use crate::shapes::ResolvedStyle;
+use crate::TinyVg;
+use rustybuzz::{Face, UnicodeBuffer};
+use ttf_parser::{GlyphId, OutlineBuilder};
+
+/// A parsed TrueType or OpenType font, used to shape and outline text.
+pub struct Font<'a> {
+    face: Face<'a>,
+}
+
+impl<'a> Font<'a> {
+    /// Parses the font at `index` in a font file or collection. Returns `None` if the
+    /// data is not a valid font.
+    pub fn from_bytes(data: &'a [u8], index: u32) -> Option<Self> {
+        Face::from_slice(data, index).map(|face| Font { face })
+    }
+
+    /// The display units per font unit at the given font size.
+    fn scale(&self, size: f64) -> f64 {
+        size / self.face.units_per_em() as f64
+    }
+}
+
+/// A glyph placed by the shaper, in display units relative to the start of the baseline.
+pub(crate) struct ShapedGlyph {
+    pub(crate) glyph_id: GlyphId,
+    pub(crate) x: f64,
+    pub(crate) y: f64,
+    pub(crate) advance: f64,
+}
+
+/// Shapes a single line of text, applying the font's kerning, ligatures and other
+/// substitutions.
+pub(crate) fn shape(font: &Font, text: &str, size: f64) -> Vec<ShapedGlyph> {
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(text);
+    buffer.guess_segment_properties();
+    let output = rustybuzz::shape(&font.face, &[], buffer);
+
+    let scale = font.scale(size);
+    let mut pen = 0.0;
+    output
+        .glyph_infos()
+        .iter()
+        .zip(output.glyph_positions())
+        .map(|(info, position)| {
+            let glyph = ShapedGlyph {
+                glyph_id: GlyphId(info.glyph_id as u16),
+                x: pen + position.x_offset as f64 * scale,
+                y: -position.y_offset as f64 * scale,
+                advance: position.x_advance as f64 * scale,
+            };
+            pen += glyph.advance;
+            glyph
+        })
+        .collect()
+}
+
+/// The outline of a glyph as path segments, with the glyph origin at `origin`. Font units
+/// point up, so they are flipped for the y-down canvas.
+pub(crate) fn glyph_segments(font: &Font, glyph_id: GlyphId, size: f64, origin: (f64, f64)) -> Vec<Segment> {
+    let mut builder = SegmentBuilder { scale: font.scale(size), origin, segments: Vec::new(), current: None };
+    font.face.outline_glyph(glyph_id, &mut builder);
+    builder.finish_segment();
+    builder.segments
+}
+
+/// A text hint describing shaped text whose baseline starts at `position`.
+pub(crate) fn text_hint(font: &Font, glyphs: &[ShapedGlyph], text: &str, size: f64, position: Point) -> TextHintData {
+    let scale = font.scale(size);
+    let width: f64 = glyphs.iter().map(|glyph| glyph.advance).sum();
+    let center_x = position.x.0 + width / 2.0;
+    let descender = -font.face.descender() as f64 * scale;
+    let glyph_offset = centered_offsets(glyphs);
+
+    TextHintData {
+        center: Point::new(Unit(center_x), Unit(position.y.0 + descender)),
+        rotation: Unit(0.0),
+        height: Unit((font.face.ascender() as f64 - font.face.descender() as f64) * scale),
+        text: text.to_string(),
+        glyph_length: glyph_offset.len() as u64,
+        glyph_offset,
+    }
+}
+
+/// The start and end of every glyph's advance relative to the center of the text.
+fn centered_offsets(glyphs: &[ShapedGlyph]) -> Vec<(Unit, Unit)> {
+    let width: f64 = glyphs.iter().map(|glyph| glyph.advance).sum();
+    glyphs.iter().map(|glyph| (Unit(glyph.x - width / 2.0), Unit(glyph.x + glyph.advance - width / 2.0))).collect()
+}
+
+/// Draws a line of text whose baseline starts at `position`, with the glyphs of the font
+/// at `size` display units per em. The shaped glyph outlines become a single fill path,
+/// followed by a text hint carrying the string for accessibility and text selection.
+///
+/// Glyph contours are filled with the even-odd rule of TinyVG paths, so fonts with
+/// overlapping contours may show holes where they overlap.
+///
+/// Returns the advance width of the text, where following text would start.
+pub fn draw_text(builder: &mut TinyVgBuilder, text: &str, font: &Font, size: f64, position: Point, fill: impl Into<ResolvedStyle>) -> f64 {
+    let glyphs = shape(font, text, size);
+    let segments: Vec<Segment> = glyphs
+        .iter()
+        .flat_map(|glyph| glyph_segments(font, glyph.glyph_id, size, (position.x.0 + glyph.x, position.y.0 + glyph.y)))
+        .collect();
+
+    if !segments.is_empty() {
+        builder.fill_path(Path { segments }, fill);
+    }
+    builder.push(DrawCommand::TextHint(text_hint(font, &glyphs, text, size, position)));
+    glyphs.iter().map(|glyph| glyph.advance).sum()
+}
+
+impl TextHintData {
+    /// Replaces the text and recomputes the glyph offsets by shaping it with the font at
+    /// the hint's height. The glyphs stay centered on the hint's center.
+    pub fn set_text_with_font(&mut self, text: impl Into<String>, font: &Font) {
+        let text = text.into();
+        let (ascender, descender) = (font.face.ascender() as f64, font.face.descender() as f64);
+        let units_per_em = font.face.units_per_em() as f64;
+        let size = if ascender > descender { self.height.0 * units_per_em / (ascender - descender) } else { self.height.0 };
+
+        self.glyph_offset = centered_offsets(&shape(font, &text, size));
+        self.glyph_length = self.glyph_offset.len() as u64;
+        self.text = text;
+    }
+}
+
+/// Selects a glyph of a font, see `glyph_to_tvg`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum GlyphSelector {
+    /// The glyph the font maps the character to.
+    Codepoint(char),
+    GlyphId(u16),
+}
+
+/// Converts a single glyph into a standalone square document of `size` by `size` display
+/// units, as when migrating icon fonts to TinyVG assets. The font's em square is scaled to
+/// the document size with the glyph's advance centered horizontally, and the baseline
+/// placed so that the ascender and descender fit.
+///
+/// Returns `None` if the font has no such glyph.
+pub fn glyph_to_tvg(font: &Font, glyph: GlyphSelector, size: u32, color: RgbaF32) -> Option<TinyVg> {
+    let glyph_id = match glyph {
+        GlyphSelector::Codepoint(codepoint) => font.face.glyph_index(codepoint)?,
+        GlyphSelector::GlyphId(id) if id < font.face.number_of_glyphs() => GlyphId(id),
+        GlyphSelector::GlyphId(_) => return None,
+    };
+
+    let size_f64 = size as f64;
+    let scale = font.scale(size_f64);
+    let advance = font.face.glyph_hor_advance(glyph_id).unwrap_or(0) as f64 * scale;
+    let (ascender, descender) = (font.face.ascender() as f64, font.face.descender() as f64);
+    let baseline = if ascender > descender { size_f64 * ascender / (ascender - descender) } else { size_f64 };
+
+    let segments = glyph_segments(font, glyph_id, size_f64, ((size_f64 - advance) / 2.0, baseline));
+    let mut builder = TinyVgBuilder::new(size, size);
+    if !segments.is_empty() {
+        builder.fill_path(Path { segments }, color);
+    }
+    Some(builder.build())
+}
+
+struct SegmentBuilder {
+    scale: f64,
+    origin: (f64, f64),
+    segments: Vec<Segment>,
+    current: Option<Segment>,
+}
+
+impl SegmentBuilder {
+    fn point(&self, x: f32, y: f32) -> Point {
+        Point::new(Unit(self.origin.0 + x as f64 * self.scale), Unit(self.origin.1 - y as f64 * self.scale))
+    }
+
+    fn push(&mut self, command: PathCommand) {
+        if let Some(segment) = &mut self.current {
+            segment.path_commands.push(command);
+        }
+    }
+
+    fn finish_segment(&mut self) {
+        if let Some(segment) = self.current.take()
+            && !segment.path_commands.is_empty()
+        {
+            self.segments.push(segment);
+        }
+    }
+}
+
+impl OutlineBuilder for SegmentBuilder {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.finish_segment();
+        self.current = Some(Segment { start: self.point(x, y), path_commands: PathCommands::new() });
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.push(PathCommand::Line(self.point(x, y), None));
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let quadratic = QuadraticBezier { control_point: self.point(x1, y1), point_1: self.point(x, y) };
+        self.push(PathCommand::QuadraticBezier(quadratic, None));
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let cubic = CubicBezier { control_point_0: self.point(x1, y1), control_point_1: self.point(x2, y2), point_1: self.point(x, y) };
+        self.push(PathCommand::CubicBezier(cubic, None));
+    }
+
+    fn close(&mut self) {
+        self.push(PathCommand::ClosePath(None));
+        self.finish_segment();
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
+
+use crate::color_table::RgbaF32;
+use crate::common::{write_variable_sized_unsigned_number, Reader};
+use crate::{ParseOptions, TinyVg, TinyVgParseError};
+
+/// Marks the start of a token block after the end of a document.
+const TRAILING_MAGIC: [u8; 4] = *b"TVGT";
+
+/// Names for color table entries, such as "accent" or "bg", so that one icon can be
+/// instantiated with different palettes by name with `TinyVg::apply_token_values`.
+///
+/// Tokens are kept outside the format: either in a sidecar text file with one
+/// `name = index` line per token, or in a block after the document's end of document byte.
+/// Parsers skip that block with a warning, but strict parsing rejects it.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ColorTokens {
+    entries: Vec<(String, u64)>,
+}
+
+impl ColorTokens {
+    pub fn new() -> ColorTokens {
+        ColorTokens::default()
+    }
+
+    /// Names the color at `color_index`, replacing an earlier token of the same name.
+    /// Several names may refer to the same color.
+    pub fn insert(&mut self, name: &str, color_index: u64) {
+        match self.entries.iter_mut().find(|(existing, _)| existing == name) {
+            Some(entry) => entry.1 = color_index,
+            None => self.entries.push((name.to_string(), color_index)),
+        }
+    }
+
+    pub fn color_index(&self, name: &str) -> Option<u64> {
+        self.entries.iter().find(|(existing, _)| existing == name).map(|&(_, index)| index)
+    }
+
+    /// The tokens in the order they were first inserted.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u64)> {
+        self.entries.iter().map(|(name, index)| (name.as_str(), *index))
+    }
+
+    /// Writes the tokens in the sidecar format, one `name = index` line each.
+    pub fn to_sidecar(&self) -> String {
+        self.iter().map(|(name, index)| format!("{name} = {index}\n")).collect()
+    }
+
+    /// Reads tokens in the sidecar format. Empty lines and lines starting with `#` are
+    /// skipped. Returns `None` if a line is not a name and an index separated by `=`.
+    pub fn from_sidecar(text: &str) -> Option<ColorTokens> {
+        let mut tokens = ColorTokens::new();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let (name, index) = line.split_once('=')?;
+            let name = name.trim();
+            if name.is_empty() {
+                return None;
+            }
+            tokens.insert(name, index.trim().parse().ok()?);
+        }
+        Some(tokens)
+    }
+
+    /// Appends the tokens to an encoded document as a block after its end of document byte:
+    /// the bytes `TVGT`, the number of tokens and for each token the length of its UTF-8
+    /// name, the name and the color index, with all numbers stored as VarUInt.
+    pub fn write_trailing(&self, file: &mut Vec<u8>) {
+        let mut cursor = Cursor::new(TRAILING_MAGIC.to_vec());
+        cursor.set_position(TRAILING_MAGIC.len() as u64);
+        self.write_entries(&mut cursor).expect("writing to memory cannot fail");
+        file.extend_from_slice(&cursor.into_inner());
+    }
+
+    /// Reads the token block that `write_trailing` appended to an encoded document. Returns
+    /// `None` if the document does not parse or no valid block follows it.
+    pub fn from_trailing_data(file: &[u8]) -> Option<ColorTokens> {
+        let (_, consumed) = TinyVg::from_bytes_prefix(file, &ParseOptions::default()).ok()?;
+        let block = file[consumed..].strip_prefix(&TRAILING_MAGIC)?;
+
+        let mut reader = Reader::new(block, TinyVgParseError::TrailingData);
+        let count = reader.read_variable_sized_unsigned_number().ok()?;
+        let mut tokens = ColorTokens::new();
+        for _ in 0..count {
+            let length = usize::try_from(reader.read_variable_sized_unsigned_number().ok()?).ok()?;
+            let name = std::str::from_utf8(reader.take(length).ok()?).ok()?;
+            tokens.insert(name, reader.read_variable_sized_unsigned_number().ok()?);
+        }
+        Some(tokens)
+    }
+
+    fn write_entries(&self, cursor: &mut Cursor<Vec<u8>>) -> Result<(), TinyVgParseError> {
+        write_variable_sized_unsigned_number(cursor, self.entries.len() as u64)?;
+        for (name, index) in &self.entries {
+            write_variable_sized_unsigned_number(cursor, name.len() as u64)?;
+            cursor.write_all(name.as_bytes()).map_err(|_| TinyVgParseError::InvalidHeader)?;
+            write_variable_sized_unsigned_number(cursor, *index)?;
+        }
+        Ok(())
+    }
+}
+
+impl TinyVg {
+    /// Sets the color of every token named in `values` that `tokens` maps to an entry of
+    /// the color table. Names without a token and tokens past the end of the color table are
+    /// skipped. Returns the number of colors set.
+    pub fn apply_token_values(&mut self, tokens: &ColorTokens, values: &HashMap<String, RgbaF32>) -> usize {
+        let mut applied = 0;
+        for (name, color) in values {
+            if let Some(entry) = tokens.color_index(name).and_then(|index| self.color_table.get_mut(index as usize)) {
+                *entry = *color;
+                applied += 1;
+            }
+        }
+        applied
+    }
+}
@@ -0,0 +1,318 @@
+use crate::color_table::RgbaF32;
+use crate::builder::TinyVgBuilder;
+use crate::commands::{CubicBezier, Path, PathCommand, PathCommands, Point, Segment};
+use crate::geometry::signed_area;
+use crate::optimize::simplify_closed;
+use crate::png::decode_png;
+use crate::render::RgbaImage;
+use crate::TinyVg;
+use std::collections::HashMap;
+
+/// Pixels with less alpha than this are treated as transparent and left untraced.
+const ALPHA_THRESHOLD: u8 = 128;
+
+/// How raster images are traced into documents.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TraceOptions {
+    /// The largest number of colors the image is reduced to.
+    pub colors: usize,
+
+    /// Outlines that enclose fewer pixels than this are dropped, which removes noise.
+    pub speckle_area: f64,
+
+    /// How far in pixels the straightened outlines may stray from the pixel edges.
+    pub tolerance: f64,
+
+    /// Decides which vertices of the straightened outlines are kept as corners, like the
+    /// `alphamax` parameter of potrace. 0 keeps every vertex as a corner, which gives
+    /// polygons, and 4/3 or more rounds every vertex.
+    pub corner_threshold: f64,
+}
+
+impl Default for TraceOptions {
+    fn default() -> Self {
+        TraceOptions { colors: 16, speckle_area: 2.0, tolerance: 1.0, corner_threshold: 1.0 }
+    }
+}
+
+/// Traces a PNG image into a document of the same size in pixels, see `trace_image`.
+/// Returns `None` if the data is not a valid PNG.
+pub fn trace_png(data: &[u8], options: &TraceOptions) -> Option<TinyVg> {
+    decode_png(data).map(|image| trace_image(&image, options))
+}
+
+/// Traces a raster image into a document of the same size in pixels. The image is reduced
+/// to `options.colors` colors, the pixels of each color are outlined, and the outlines are
+/// straightened and rounded in the manner of potrace.
+///
+/// Colors are drawn from the most to the least common, each one covering the pixels of
+/// the colors drawn after it as well. Later colors are painted on top, so neighbouring
+/// colors overlap instead of leaving gaps where their rounded outlines differ.
+pub fn trace_image(image: &RgbaImage, options: &TraceOptions) -> TinyVg {
+    let (palette, indices) = posterize(image, options.colors);
+    let (width, height) = (image.width as i32, image.height as i32);
+
+    let mut counts = vec![0usize; palette.len()];
+    for index in indices.iter().flatten() {
+        counts[*index] += 1;
+    }
+    let mut order: Vec<usize> = (0..palette.len()).collect();
+    order.sort_by_key(|&index| std::cmp::Reverse(counts[index]));
+    // The position of each color in the drawing order.
+    let mut layers = vec![0; palette.len()];
+    for (layer, &index) in order.iter().enumerate() {
+        layers[index] = layer;
+    }
+
+    let mut builder = TinyVgBuilder::new(image.width, image.height);
+    for (layer, &index) in order.iter().enumerate() {
+        let inside = |x: i32, y: i32| {
+            x >= 0 && y >= 0 && x < width && y < height && indices[(y * width + x) as usize].is_some_and(|index| layers[index] >= layer)
+        };
+        let segments: Vec<Segment> = trace_contours(width, height, inside)
+            .into_iter()
+            .map(|contour| contour.into_iter().map(|(x, y)| (x as f64, y as f64)).collect::<Vec<_>>())
+            .filter(|contour| signed_area(contour).abs() >= options.speckle_area)
+            .map(|contour| smooth_contour(&straighten(&contour, options.tolerance), options.corner_threshold))
+            .collect();
+        if segments.is_empty() {
+            continue;
+        }
+
+        let [red, green, blue, alpha] = palette[index].map(|channel| channel as f32 / 255.0);
+        builder.fill_path(Path { segments }, RgbaF32(red, green, blue, alpha));
+    }
+
+    builder.build()
+}
+
+/// Replaces runs of pixel edges by straight lines that stay within `tolerance` of them.
+fn straighten(contour: &[(f64, f64)], tolerance: f64) -> Vec<(f64, f64)> {
+    let points: Vec<Point> = contour.iter().map(|&point| Point::from(point)).collect();
+    let straightened = simplify_closed(&points, tolerance);
+    if straightened.len() < 3 {
+        return contour.to_vec();
+    }
+    straightened.iter().map(|point| (point.x.0, point.y.0)).collect()
+}
+
+/// Turns a polygon into a closed segment that runs through the midpoints of its edges.
+/// Each vertex either stays a corner or is rounded by a cubic curve, following potrace:
+/// the further a vertex lies from the line between its neighbours, the sharper it is, and
+/// vertices sharper than `corner_threshold` are kept as corners.
+fn smooth_contour(polygon: &[(f64, f64)], corner_threshold: f64) -> Segment {
+    let point = |(x, y): (f64, f64)| Point::from((x, y));
+    let between = |from: (f64, f64), to: (f64, f64), t: f64| (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t);
+    let count = polygon.len();
+
+    let mut path_commands = PathCommands::new();
+    for j in 0..count {
+        let (previous, vertex, next) = (polygon[(j + count - 1) % count], polygon[j], polygon[(j + 1) % count]);
+        let end = between(vertex, next, 0.5);
+
+        let denominator = (next.0 - previous.0).abs() + (next.1 - previous.1).abs();
+        let alpha = if denominator > 0.0 {
+            let cross = (vertex.0 - previous.0) * (next.1 - previous.1) - (vertex.1 - previous.1) * (next.0 - previous.0);
+            let distance = cross.abs() / denominator;
+            let alpha = if distance > 1.0 { 1.0 - 1.0 / distance } else { 0.0 };
+            alpha / 0.75
+        } else {
+            4.0 / 3.0
+        };
+
+        if alpha >= corner_threshold {
+            path_commands.push(PathCommand::Line(point(vertex), None));
+            path_commands.push(PathCommand::Line(point(end), None));
+        } else {
+            let alpha = alpha.clamp(0.55, 1.0);
+            path_commands.push(PathCommand::CubicBezier(
+                CubicBezier {
+                    control_point_0: point(between(previous, vertex, 0.5 + 0.5 * alpha)),
+                    control_point_1: point(between(next, vertex, 0.5 + 0.5 * alpha)),
+                    point_1: point(end),
+                },
+                None,
+            ));
+        }
+    }
+    path_commands.push(PathCommand::ClosePath(None));
+
+    Segment { start: point(between(polygon[count - 1], polygon[0], 0.5)), path_commands }
+}
+
+/// The pixels of one color of a posterized image, outlined by closed contours along the
+/// pixel edges. The contours are given as pixel corners, run clockwise around the region
+/// and counterclockwise around its holes, and never cross.
+#[cfg(feature = "svg-to-tvg")]
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TracedRegion {
+    pub color: [u8; 4],
+    pub contours: Vec<Vec<(i32, i32)>>,
+}
+
+/// Reduces the opaque pixels of `image` to at most `colors` colors with median cut and
+/// traces the pixels of each color into a region, without straightening or rounding the
+/// outlines.
+#[cfg(feature = "svg-to-tvg")]
+pub(crate) fn trace_regions(image: &RgbaImage, colors: usize) -> Vec<TracedRegion> {
+    let (palette, indices) = posterize(image, colors);
+    let (width, height) = (image.width as i32, image.height as i32);
+
+    palette
+        .into_iter()
+        .enumerate()
+        .map(|(index, color)| {
+            let inside = |x: i32, y: i32| x >= 0 && y >= 0 && x < width && y < height && indices[(y * width + x) as usize] == Some(index);
+            TracedRegion { color, contours: trace_contours(width, height, inside) }
+        })
+        .filter(|region| !region.contours.is_empty())
+        .collect()
+}
+
+/// Returns the palette and, for every pixel, the index of its palette color, or `None`
+/// for transparent pixels.
+fn posterize(image: &RgbaImage, colors: usize) -> (Vec<[u8; 4]>, Vec<Option<usize>>) {
+    let pixels: Vec<[u8; 4]> = image.data.chunks_exact(4).map(|pixel| [pixel[0], pixel[1], pixel[2], pixel[3]]).collect();
+    let mut counts: HashMap<[u8; 4], u64> = HashMap::new();
+    for pixel in pixels.iter().filter(|pixel| pixel[3] >= ALPHA_THRESHOLD) {
+        *counts.entry(*pixel).or_default() += 1;
+    }
+    if counts.is_empty() {
+        return (Vec::new(), vec![None; pixels.len()]);
+    }
+
+    // Repeatedly split the box with the widest channel range at the median of that channel.
+    let mut boxes: Vec<Vec<([u8; 4], u64)>> = vec![counts.into_iter().collect()];
+    while boxes.len() < colors.max(1) {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, colors)| colors.len() > 1)
+            .map(|(index, colors)| {
+                let range = |channel: usize| {
+                    let values = colors.iter().map(|(color, _)| color[channel]);
+                    values.clone().max().unwrap_or(0) - values.min().unwrap_or(0)
+                };
+                let (channel, range) = (0..3).map(|channel| (channel, range(channel))).max_by_key(|&(_, range)| range).unwrap_or((0, 0));
+                (index, channel, range)
+            })
+            .max_by_key(|&(_, _, range)| range);
+        let Some((index, channel, _)) = widest else {
+            break;
+        };
+
+        let mut colors = boxes.swap_remove(index);
+        colors.sort_by_key(|(color, _)| color[channel]);
+        let total: u64 = colors.iter().map(|(_, count)| count).sum();
+        let mut seen = 0;
+        let split = colors
+            .iter()
+            .position(|(_, count)| {
+                seen += count;
+                seen * 2 >= total
+            })
+            .map_or(1, |position| position + 1)
+            .clamp(1, colors.len() - 1);
+        let upper = colors.split_off(split);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+
+    let palette: Vec<[u8; 4]> = boxes
+        .iter()
+        .map(|colors| {
+            let total: u64 = colors.iter().map(|(_, count)| count).sum();
+            let mean = |channel: usize| ((colors.iter().map(|(color, count)| color[channel] as u64 * count).sum::<u64>() + total / 2) / total) as u8;
+            [mean(0), mean(1), mean(2), mean(3)]
+        })
+        .collect();
+
+    let mut nearest: HashMap<[u8; 4], usize> = HashMap::new();
+    let indices = pixels
+        .iter()
+        .map(|pixel| {
+            if pixel[3] < ALPHA_THRESHOLD {
+                return None;
+            }
+            Some(*nearest.entry(*pixel).or_insert_with(|| {
+                let distance = |color: &[u8; 4]| (0..4).map(|channel| (color[channel] as i32 - pixel[channel] as i32).pow(2)).sum::<i32>();
+                (0..palette.len()).min_by_key(|&index| distance(&palette[index])).unwrap_or(0)
+            }))
+        })
+        .collect();
+
+    (palette, indices)
+}
+
+/// Outlines the pixels for which `inside` holds. Every pixel side between an inside and an
+/// outside pixel becomes an edge, directed so that the inside pixel lies on its right, and
+/// the edges are then joined into loops. Where two regions touch only at a corner the loop
+/// turns right, so that diagonal neighbours are outlined separately.
+fn trace_contours(width: i32, height: i32, inside: impl Fn(i32, i32) -> bool) -> Vec<Vec<(i32, i32)>> {
+    let mut edges: Vec<((i32, i32), (i32, i32))> = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            if !inside(x, y) {
+                continue;
+            }
+            if !inside(x, y - 1) {
+                edges.push(((x, y), (x + 1, y)));
+            }
+            if !inside(x + 1, y) {
+                edges.push(((x + 1, y), (x + 1, y + 1)));
+            }
+            if !inside(x, y + 1) {
+                edges.push(((x + 1, y + 1), (x, y + 1)));
+            }
+            if !inside(x - 1, y) {
+                edges.push(((x, y + 1), (x, y)));
+            }
+        }
+    }
+
+    let mut outgoing: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+    for (index, edge) in edges.iter().enumerate() {
+        outgoing.entry(edge.0).or_default().push(index);
+    }
+
+    let direction = |index: usize| {
+        let (start, end) = edges[index];
+        (end.0 - start.0, end.1 - start.1)
+    };
+    let mut used = vec![false; edges.len()];
+    let mut contours = Vec::new();
+    for first in 0..edges.len() {
+        if used[first] {
+            continue;
+        }
+
+        let mut contour = vec![edges[first].0];
+        let mut current = first;
+        loop {
+            used[current] = true;
+            let (dx, dy) = direction(current);
+            let candidates = &outgoing[&edges[current].1];
+            // Turn right, go straight or turn left, in that order of preference.
+            let next = [(-dy, dx), (dx, dy), (dy, -dx)]
+                .into_iter()
+                .find_map(|turn| candidates.iter().copied().find(|&candidate| direction(candidate) == turn))
+                .expect("every edge of a closed outline has a successor");
+            if used[next] {
+                break;
+            }
+            if direction(next) != (dx, dy) {
+                contour.push(edges[next].0);
+            }
+            current = next;
+        }
+
+        // The contour started at the start of the first edge, which is no corner when the
+        // last edge runs in the same direction.
+        if contour.len() > 2 && direction(first) == direction(current) {
+            contour.remove(0);
+        }
+        contours.push(contour);
+    }
+
+    contours
+}
@@ -0,0 +1,488 @@
+use crate::commands::{
+    CenterArc, DrawCommand, FillPathData, OutlineFillPathData, Path, PathCommand, PathCommands, Point, Rectangle, Segment, Style,
+};
+use crate::common::{Affine, Unit};
+use crate::precision::raw_limit;
+use crate::TinyVg;
+
+impl TinyVg {
+    /// Scales the document to a nominal size of `new_width` by `new_height` display units.
+    /// All geometry is scaled along with it, and line widths by the geometric mean of the
+    /// two scale factors. The header's scale and coordinate range are re-selected so that the
+    /// rescaled coordinates keep as much precision as the range allows.
+    ///
+    /// A document whose current width or height is 0 is not scaled along that axis.
+    pub fn resize(&mut self, new_width: u32, new_height: u32) {
+        let scale = |new: u32, old: u32| if old == 0 { 1.0 } else { new as f64 / old as f64 };
+        let transform = Affine::scale_non_uniform(scale(new_width, self.header.width), scale(new_height, self.header.height));
+        self.transform_commands(&transform);
+
+        self.header.width = new_width;
+        self.header.height = new_height;
+        self.requantize();
+    }
+
+    /// Mirrors the document left to right, as for right-to-left variants of icons.
+    /// Coordinates are mirrored around the document width, which is exact.
+    pub fn flip_horizontal(&mut self) {
+        let width = self.header.width as f64;
+        self.transform_commands(&Affine([-1.0, 0.0, 0.0, 1.0, width, 0.0]));
+        self.fit_coordinate_range();
+    }
+
+    /// Mirrors the document top to bottom. Coordinates are mirrored around the document
+    /// height, which is exact.
+    pub fn flip_vertical(&mut self) {
+        let height = self.header.height as f64;
+        self.transform_commands(&Affine([1.0, 0.0, 0.0, -1.0, 0.0, height]));
+        self.fit_coordinate_range();
+    }
+
+    /// Rotates the document clockwise by `turns` quarter turns, swapping its width and
+    /// height for odd turns. Negative values rotate counterclockwise. Coordinates are only
+    /// swapped and mirrored, so no precision is lost. Like the flips, it widens the
+    /// coordinate range if geometry outside the document no longer fits it.
+    pub fn rotate_quarter_turns(&mut self, turns: i32) {
+        let width = self.header.width as f64;
+        let height = self.header.height as f64;
+        let transform = match turns.rem_euclid(4) {
+            0 => return,
+            1 => Affine([0.0, 1.0, -1.0, 0.0, height, 0.0]),
+            2 => Affine([-1.0, 0.0, 0.0, -1.0, width, height]),
+            _ => Affine([0.0, -1.0, 1.0, 0.0, 0.0, width]),
+        };
+        self.transform_commands(&transform);
+
+        if turns % 2 != 0 {
+            std::mem::swap(&mut self.header.width, &mut self.header.height);
+        }
+        self.fit_coordinate_range();
+    }
+
+    /// Widens the coordinate range if the units no longer fit it at the current scale, as
+    /// geometry outside the document can move further out when mirrored. The scale is only
+    /// re-selected if not even the enhanced range holds them.
+    fn fit_coordinate_range(&mut self) {
+        match self.smallest_coordinate_range() {
+            Some(range) if raw_limit(range) > raw_limit(self.header.coordinate_range) => self.header.coordinate_range = range,
+            Some(_) => {}
+            None => {
+                self.requantize();
+            }
+        }
+    }
+
+    fn transform_commands(&mut self, transform: &Affine) {
+        for command in &mut self.draw_commands {
+            transform_command(command, transform);
+        }
+    }
+}
+
+/// How much a transform preserves, which decides how each primitive can be mapped.
+struct TransformKind {
+    /// Maps horizontal lines to horizontal lines and vertical lines to vertical lines.
+    axis_aligned: bool,
+    /// Maps horizontal lines to vertical lines and vice versa, as quarter turns do.
+    axis_swapped: bool,
+    /// Maps circles to circles: rotations and uniform scales, possibly mirrored.
+    similarity: bool,
+    /// Reverses the orientation of curves.
+    flips: bool,
+    /// The average scale factor.
+    scale: f64,
+    /// The rotation of the x-axis in degrees.
+    angle: f64,
+}
+
+impl TransformKind {
+    fn new(transform: &Affine) -> Self {
+        let [a, b, c, d, _, _] = transform.0;
+        let determinant = transform.determinant();
+        TransformKind {
+            axis_aligned: b == 0.0 && c == 0.0,
+            axis_swapped: a == 0.0 && d == 0.0,
+            similarity: ((a - d).abs() < 1e-9 && (b + c).abs() < 1e-9) || ((a + d).abs() < 1e-9 && (b - c).abs() < 1e-9),
+            flips: determinant < 0.0,
+            scale: determinant.abs().sqrt(),
+            angle: axis_angle(a, b),
+        }
+    }
+}
+
+/// The angle of the transformed x-axis in degrees, exact for the axis directions so
+/// that flips and quarter turns do not introduce drift into arc rotations.
+fn axis_angle(a: f64, b: f64) -> f64 {
+    match (a, b) {
+        (a, 0.0) if a >= 0.0 => 0.0,
+        (_, 0.0) => 180.0,
+        (0.0, b) if b > 0.0 => 90.0,
+        (0.0, _) => -90.0,
+        _ => b.atan2(a).to_degrees(),
+    }
+}
+
+/// Applies the transform to all geometry of the command, including gradient points and
+/// line widths, which are scaled by the average scale factor. Rectangles stay rectangles
+/// under scales, flips and quarter turns; under any other transform rectangle commands
+/// become the equivalent path commands.
+pub(crate) fn transform_command(command: &mut DrawCommand, transform: &Affine) {
+    let kind = TransformKind::new(transform);
+    let scale_width = |width: &mut Unit| width.0 *= kind.scale;
+    let transform_points = |points: &mut [Point]| {
+        for point in points {
+            *point = transform.apply(*point);
+        }
+    };
+
+    match command {
+        DrawCommand::FillPolygon(data) => {
+            transform_points(&mut data.points);
+            transform_style(&mut data.style, transform);
+        }
+        DrawCommand::FillRectangles(data) => {
+            transform_style(&mut data.style, transform);
+            if !(kind.axis_aligned || kind.axis_swapped) {
+                *command = DrawCommand::FillPath(FillPathData {
+                    style: data.style.clone(),
+                    path: transform_path(&rectangles_path(&data.rectangles), transform),
+                });
+                return;
+            }
+            transform_rectangles(&mut data.rectangles, transform);
+        }
+        DrawCommand::FillPath(data) => {
+            data.path = transform_path(&data.path, transform);
+            transform_style(&mut data.style, transform);
+        }
+        DrawCommand::DrawLines(data) => {
+            for line in &mut data.lines {
+                line.start = transform.apply(line.start);
+                line.end = transform.apply(line.end);
+            }
+            scale_width(&mut data.line_width);
+            transform_style(&mut data.line_style, transform);
+        }
+        DrawCommand::DrawLineLoop(data) => {
+            transform_points(&mut data.points);
+            scale_width(&mut data.line_width);
+            transform_style(&mut data.line_style, transform);
+        }
+        DrawCommand::DrawLineStrip(data) => {
+            transform_points(&mut data.points);
+            scale_width(&mut data.line_width);
+            transform_style(&mut data.style, transform);
+        }
+        DrawCommand::DrawLinePath(data) => {
+            data.path = transform_path(&data.path, transform);
+            scale_width(&mut data.line_width);
+            transform_style(&mut data.style, transform);
+        }
+        DrawCommand::OutlineFillPolygon(data) => {
+            transform_points(&mut data.points);
+            scale_width(&mut data.line_width);
+            transform_style(&mut data.fill_style, transform);
+            transform_style(&mut data.line_style, transform);
+        }
+        DrawCommand::OutlineFillRectangles(data) => {
+            scale_width(&mut data.line_width);
+            transform_style(&mut data.fill_style, transform);
+            transform_style(&mut data.line_style, transform);
+            if !(kind.axis_aligned || kind.axis_swapped) {
+                *command = DrawCommand::OutlineFillPath(OutlineFillPathData {
+                    path: transform_path(&rectangles_path(&data.rectangles), transform),
+                    fill_style: data.fill_style.clone(),
+                    line_style: data.line_style.clone(),
+                    line_width: data.line_width,
+                });
+                return;
+            }
+            transform_rectangles(&mut data.rectangles, transform);
+        }
+        DrawCommand::OutlineFillPath(data) => {
+            data.path = transform_path(&data.path, transform);
+            scale_width(&mut data.line_width);
+            transform_style(&mut data.fill_style, transform);
+            transform_style(&mut data.line_style, transform);
+        }
+        DrawCommand::TextHint(data) => {
+            data.center = transform.apply(data.center);
+            data.rotation.0 = if kind.flips { kind.angle - data.rotation.0 } else { kind.angle + data.rotation.0 };
+            data.height.0 *= kind.scale;
+            for (start, end) in &mut data.glyph_offset {
+                start.0 *= kind.scale;
+                end.0 *= kind.scale;
+            }
+        }
+    }
+}
+
+fn transform_style(style: &mut Style, transform: &Affine) {
+    match style {
+        Style::FlatColor(_) => {}
+        Style::LinearGradient(gradient) => {
+            gradient.point_0 = transform.apply(gradient.point_0);
+            gradient.point_1 = transform.apply(gradient.point_1);
+        }
+        Style::RadialGradient(gradient) => {
+            gradient.point_0 = transform.apply(gradient.point_0);
+            gradient.point_1 = transform.apply(gradient.point_1);
+        }
+    }
+}
+
+/// Only valid for transforms that keep rectangles axis-aligned.
+fn transform_rectangles(rectangles: &mut [Rectangle], transform: &Affine) {
+    for rectangle in rectangles {
+        let corner_0 = transform.apply(Point::new(rectangle.x, rectangle.y));
+        let corner_1 = transform.apply(Point::new(Unit(rectangle.x.0 + rectangle.width.0), Unit(rectangle.y.0 + rectangle.height.0)));
+        rectangle.x = Unit(corner_0.x.0.min(corner_1.x.0));
+        rectangle.y = Unit(corner_0.y.0.min(corner_1.y.0));
+        rectangle.width = Unit((corner_1.x.0 - corner_0.x.0).abs());
+        rectangle.height = Unit((corner_1.y.0 - corner_0.y.0).abs());
+    }
+}
+
+/// One closed segment per rectangle.
+pub(crate) fn rectangles_path(rectangles: &[Rectangle]) -> Path {
+    let segments = rectangles
+        .iter()
+        .map(|rectangle| {
+            let right = Unit(rectangle.x.0 + rectangle.width.0);
+            let bottom = Unit(rectangle.y.0 + rectangle.height.0);
+            Segment {
+                start: Point::new(rectangle.x, rectangle.y),
+                path_commands: [
+                    PathCommand::HorizontalLine(right, None),
+                    PathCommand::VerticalLine(bottom, None),
+                    PathCommand::HorizontalLine(rectangle.x, None),
+                    PathCommand::ClosePath(None),
+                ]
+                .into_iter()
+                .collect(),
+            }
+        })
+        .collect();
+
+    Path { segments }
+}
+
+/// Applies the transform to every point of the path. Horizontal and vertical lines and
+/// arcs are kept where the transform preserves them, and are converted to lines and
+/// cubic curves otherwise.
+pub(crate) fn transform_path(path: &Path, transform: &Affine) -> Path {
+    let [a, b, c, d, e, f] = transform.0;
+    let kind = TransformKind::new(transform);
+    let scale_width = |line_width: &Option<Unit>| line_width.map(|width| Unit(width.0 * kind.scale));
+
+    let segments = path
+        .segments
+        .iter()
+        .map(|segment| {
+            let mut current = segment.start;
+            let mut path_commands = PathCommands::with_capacity(segment.path_commands.len());
+
+            for path_command in &segment.path_commands {
+                let start = current;
+                match path_command {
+                    PathCommand::Line(point, line_width) => {
+                        current = *point;
+                        path_commands.push(PathCommand::Line(transform.apply(*point), scale_width(line_width)));
+                    }
+                    PathCommand::HorizontalLine(x, line_width) => {
+                        current = Point::new(*x, current.y);
+                        path_commands.push(if kind.axis_aligned {
+                            PathCommand::HorizontalLine(Unit(a * x.0 + e), scale_width(line_width))
+                        } else if kind.axis_swapped {
+                            PathCommand::VerticalLine(Unit(b * x.0 + f), scale_width(line_width))
+                        } else {
+                            PathCommand::Line(transform.apply(current), scale_width(line_width))
+                        });
+                    }
+                    PathCommand::VerticalLine(y, line_width) => {
+                        current = Point::new(current.x, *y);
+                        path_commands.push(if kind.axis_aligned {
+                            PathCommand::VerticalLine(Unit(d * y.0 + f), scale_width(line_width))
+                        } else if kind.axis_swapped {
+                            PathCommand::HorizontalLine(Unit(c * y.0 + e), scale_width(line_width))
+                        } else {
+                            PathCommand::Line(transform.apply(current), scale_width(line_width))
+                        });
+                    }
+                    PathCommand::CubicBezier(cubic, line_width) => {
+                        current = cubic.point_1;
+                        let mut cubic = cubic.clone();
+                        cubic.control_point_0 = transform.apply(cubic.control_point_0);
+                        cubic.control_point_1 = transform.apply(cubic.control_point_1);
+                        cubic.point_1 = transform.apply(cubic.point_1);
+                        path_commands.push(PathCommand::CubicBezier(cubic, scale_width(line_width)));
+                    }
+                    PathCommand::QuadraticBezier(quadratic, line_width) => {
+                        current = quadratic.point_1;
+                        let mut quadratic = quadratic.clone();
+                        quadratic.control_point = transform.apply(quadratic.control_point);
+                        quadratic.point_1 = transform.apply(quadratic.point_1);
+                        path_commands.push(PathCommand::QuadraticBezier(quadratic, scale_width(line_width)));
+                    }
+                    PathCommand::ArcCircle(arc, line_width) if kind.similarity => {
+                        current = arc.target;
+                        let mut arc = arc.clone();
+                        arc.radius = Unit(arc.radius.0 * kind.scale);
+                        arc.sweep ^= kind.flips;
+                        arc.target = transform.apply(arc.target);
+                        path_commands.push(PathCommand::ArcCircle(arc, scale_width(line_width)));
+                    }
+                    PathCommand::ArcEllipse(arc, line_width) if kind.similarity || (kind.axis_aligned && arc.rotation.0 == 0.0) => {
+                        current = arc.target;
+                        let mut arc = arc.clone();
+                        if kind.similarity {
+                            arc.radius_x = Unit(arc.radius_x.0 * kind.scale);
+                            arc.radius_y = Unit(arc.radius_y.0 * kind.scale);
+                            arc.rotation = Unit(if kind.flips { kind.angle - arc.rotation.0 } else { kind.angle + arc.rotation.0 });
+                        } else {
+                            arc.radius_x = Unit(arc.radius_x.0 * a.abs());
+                            arc.radius_y = Unit(arc.radius_y.0 * d.abs());
+                        }
+                        arc.sweep ^= kind.flips;
+                        arc.target = transform.apply(arc.target);
+                        path_commands.push(PathCommand::ArcEllipse(arc, scale_width(line_width)));
+                    }
+                    PathCommand::ArcCircle(arc, line_width) => {
+                        current = arc.target;
+                        let arc = CenterArc::from_endpoints(start, arc.target, arc.radius.0, arc.radius.0, 0.0, arc.large_arc, arc.sweep);
+                        push_arc(&mut path_commands, arc, current, scale_width(line_width), transform);
+                    }
+                    PathCommand::ArcEllipse(arc, line_width) => {
+                        current = arc.target;
+                        let arc = CenterArc::from_endpoints(start, arc.target, arc.radius_x.0, arc.radius_y.0, arc.rotation.0, arc.large_arc, arc.sweep);
+                        push_arc(&mut path_commands, arc, current, scale_width(line_width), transform);
+                    }
+                    PathCommand::ClosePath(line_width) => {
+                        current = segment.start;
+                        path_commands.push(PathCommand::ClosePath(scale_width(line_width)));
+                    }
+                }
+            }
+
+            Segment { start: transform.apply(segment.start), path_commands }
+        })
+        .collect();
+
+    Path { segments }
+}
+
+/// Pushes an arc as transformed cubic curves, or as a line if it is degenerate.
+fn push_arc(path_commands: &mut PathCommands, arc: Option<CenterArc>, target: Point, line_width: Option<Unit>, transform: &Affine) {
+    let Some(arc) = arc else {
+        path_commands.push(PathCommand::Line(transform.apply(target), line_width));
+        return;
+    };
+
+    for mut cubic in arc.to_cubics() {
+        cubic.control_point_0 = transform.apply(cubic.control_point_0);
+        cubic.control_point_1 = transform.apply(cubic.control_point_1);
+        cubic.point_1 = transform.apply(cubic.point_1);
+        path_commands.push(PathCommand::CubicBezier(cubic, line_width));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{DrawCommand, DrawLineStripData, FillRectanglesData, FlatColored, Point, Rectangle, Style};
+    use crate::common::Unit;
+    use crate::header::CoordinateRange;
+    use crate::TinyVg;
+
+    /// A 16 by 16 document with one color and one rectangle.
+    const DOCUMENT: [u8; 25] = [0x72, 0x56, 1, 0x00, 16, 0, 16, 0, 1, 255, 0, 0, 255, 0x02, 0, 0, 0, 0, 0, 0, 16, 0, 16, 0, 0x00];
+
+    fn line_strip(points: &[(f64, f64)], line_width: f64) -> DrawCommand {
+        DrawCommand::DrawLineStrip(DrawLineStripData {
+            style: Style::FlatColor(FlatColored { color_index: 0 }),
+            line_width: Unit(line_width),
+            points: points.iter().map(|&(x, y)| Point::new(Unit(x), Unit(y))).collect(),
+        })
+    }
+
+    #[test]
+    fn resize_scales_geometry_and_line_widths() {
+        let mut document = TinyVg::from_bytes(&DOCUMENT).unwrap();
+        document.draw_commands.push(line_strip(&[(0.0, 0.0), (8.0, 4.0)], 2.0));
+        document.resize(32, 4);
+
+        assert_eq!((document.header.width, document.header.height), (32, 4));
+        let DrawCommand::FillRectangles(FillRectanglesData { rectangles, .. }) = &document.draw_commands[0] else {
+            panic!("{:?}", document.draw_commands[0]);
+        };
+        assert_eq!(rectangles[0], Rectangle { x: Unit(0.0), y: Unit(0.0), width: Unit(32.0), height: Unit(4.0) });
+        // The line width scales by the geometric mean of 2 and 0.25.
+        assert_eq!(document.draw_commands[1], line_strip(&[(0.0, 0.0), (16.0, 1.0)], 2.0 * 0.5f64.sqrt()));
+        assert_eq!(document.validate(), Ok(()));
+    }
+
+    #[test]
+    fn flips_and_rotations_round_trip() {
+        let mut original = TinyVg::from_bytes(&DOCUMENT).unwrap();
+        original.header.width = 24;
+        original.draw_commands.push(line_strip(&[(1.0, 2.0), (20.0, 3.0), (4.0, 15.0)], 1.5));
+
+        let mut document = original.clone();
+        document.flip_horizontal();
+        assert_eq!(document.draw_commands[1], line_strip(&[(23.0, 2.0), (4.0, 3.0), (20.0, 15.0)], 1.5));
+        document.flip_horizontal();
+        assert_eq!(document, original);
+
+        document.flip_vertical();
+        assert_eq!(document.draw_commands[1], line_strip(&[(1.0, 14.0), (20.0, 13.0), (4.0, 1.0)], 1.5));
+        document.flip_vertical();
+        assert_eq!(document, original);
+
+        document.rotate_quarter_turns(1);
+        assert_eq!((document.header.width, document.header.height), (16, 24));
+        assert_eq!(document.draw_commands[1], line_strip(&[(14.0, 1.0), (13.0, 20.0), (1.0, 4.0)], 1.5));
+        document.rotate_quarter_turns(-1);
+        assert_eq!(document, original);
+
+        for _ in 0..4 {
+            document.rotate_quarter_turns(1);
+        }
+        assert_eq!(document, original);
+
+        let mut flipped = original.clone();
+        flipped.flip_horizontal();
+        flipped.flip_vertical();
+        document.rotate_quarter_turns(2);
+        assert_eq!(document, flipped);
+    }
+
+    #[test]
+    fn flips_and_rotations_widen_the_coordinate_range() {
+        let mut original = TinyVg::from_bytes(&DOCUMENT).unwrap();
+        original.header.coordinate_range = CoordinateRange::Reduced;
+        original.draw_commands.push(line_strip(&[(-120.0, 0.0), (0.0, -120.0)], 1.0));
+        assert_eq!(original.validate(), Ok(()));
+
+        let mut document = original.clone();
+        document.flip_horizontal();
+        assert_eq!(document.header.coordinate_range, CoordinateRange::Default);
+        assert_eq!(document.validate(), Ok(()));
+
+        let mut document = original.clone();
+        document.rotate_quarter_turns(1);
+        assert_eq!(document.header.coordinate_range, CoordinateRange::Default);
+        assert_eq!(document.validate(), Ok(()));
+    }
+
+    #[test]
+    fn resize_reselects_the_coordinate_range() {
+        let mut document = TinyVg::from_bytes(&DOCUMENT).unwrap();
+        document.resize(100_000, 100_000);
+        assert_eq!(document.header.coordinate_range, CoordinateRange::Enhanced);
+        assert_eq!(document.validate(), Ok(()));
+
+        document.resize(16, 16);
+        assert_ne!(document.header.coordinate_range, CoordinateRange::Enhanced);
+        assert_eq!(document.validate(), Ok(()));
+        assert_eq!(document.draw_commands, TinyVg::from_bytes(&DOCUMENT).unwrap().draw_commands);
+    }
+}
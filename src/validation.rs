@@ -0,0 +1,172 @@
+use crate::commands::{DrawCommand, Path};
+use crate::header::{ColorEncoding, TinyVgHeader, MAX_SCALE};
+use crate::palette::for_each_color_index;
+use crate::precision::{for_each_unit, raw_limit, size_limit};
+use crate::TinyVg;
+
+/// The largest value a variable sized unsigned number may hold.
+const VARUINT_LIMIT: u64 = u32::MAX as u64;
+
+/// The most items an outline fill command can hold, as their count minus one is stored in
+/// 6 bits.
+pub(crate) const OUTLINE_ITEM_LIMIT: usize = 64;
+
+/// A reason why a document cannot be encoded as a valid file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    /// The header's width or height does not fit the coordinate range.
+    SizeOutOfRange,
+
+    /// The header's scale is larger than 15, the most fraction bits a unit can have.
+    ScaleOutOfRange,
+
+    /// No scale reaches the requested precision while the coordinates still span the
+    /// header's size in the coordinate range.
+    PrecisionOutOfRange,
+
+    /// Custom color encodings cannot be written.
+    UnsupportedColorEncoding,
+
+    /// A style references a color past the end of the color table.
+    MissingColor { command_index: usize, color_index: u64 },
+
+    /// A command has no points, rectangles, lines or path segments.
+    EmptyCommand { command_index: usize },
+
+    /// An outline fill command has more than 64 points, rectangles or segments.
+    TooManyItems { command_index: usize, count: usize },
+
+    /// A path segment has no path commands.
+    EmptySegment { command_index: usize, segment_index: usize },
+
+    /// A coordinate, size or width does not fit the coordinate range at the header's scale.
+    UnitOutOfRange { command_index: usize },
+
+    /// A coordinate, size or width is NaN or infinite.
+    NonFiniteUnit { command_index: usize },
+
+    /// A text hint's text or glyph list is too long to encode, or its glyph count does not
+    /// match its glyph offsets.
+    InvalidTextHint { command_index: usize },
+}
+
+impl TinyVg {
+    /// Checks that the document can be encoded as a valid file with its header's scale,
+    /// color encoding and coordinate range. Returns the first problem found.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate(self, &self.header)
+    }
+}
+
+/// Checks the document as if it was encoded with `header` instead of its own header.
+pub(crate) fn validate(document: &TinyVg, header: &TinyVgHeader) -> Result<(), ValidationError> {
+    if header.width.max(header.height) > size_limit(header.coordinate_range) {
+        return Err(ValidationError::SizeOutOfRange);
+    }
+    if header.scale > MAX_SCALE {
+        return Err(ValidationError::ScaleOutOfRange);
+    }
+    if header.color_encoding == ColorEncoding::Custom {
+        return Err(ValidationError::UnsupportedColorEncoding);
+    }
+
+    for (command_index, command) in document.draw_commands.iter().enumerate() {
+        validate_command(document, header, command_index, command)?;
+    }
+    Ok(())
+}
+
+/// Checks a single command as if it was the command at `command_index` of the document
+/// encoded with `header`.
+pub(crate) fn validate_command(
+    document: &TinyVg,
+    header: &TinyVgHeader,
+    command_index: usize,
+    command: &DrawCommand,
+) -> Result<(), ValidationError> {
+    let mut missing_color = None;
+    for_each_color_index(command, &mut |index| {
+        if index as usize >= document.color_table.len() {
+            missing_color.get_or_insert(index);
+        }
+    });
+    if let Some(color_index) = missing_color {
+        return Err(ValidationError::MissingColor { command_index, color_index });
+    }
+
+    let mut non_finite = false;
+    for_each_unit(command, &mut |unit| non_finite |= !unit.0.is_finite());
+    if non_finite {
+        return Err(ValidationError::NonFiniteUnit { command_index });
+    }
+
+    let factor = (1u32 << header.scale.min(15)) as f64;
+    let limit = raw_limit(header.coordinate_range);
+    let mut out_of_range = false;
+    for_each_unit(command, &mut |unit| out_of_range |= (unit.0 * factor).round().abs() > limit);
+    if out_of_range {
+        return Err(ValidationError::UnitOutOfRange { command_index });
+    }
+
+    validate_items(command_index, command)
+}
+
+fn validate_items(command_index: usize, command: &DrawCommand) -> Result<(), ValidationError> {
+    let (count, outline) = match command {
+        DrawCommand::FillPolygon(data) => (data.points.len(), false),
+        DrawCommand::FillRectangles(data) => (data.rectangles.len(), false),
+        DrawCommand::FillPath(data) => (validate_path(command_index, &data.path)?, false),
+        DrawCommand::DrawLines(data) => (data.lines.len(), false),
+        DrawCommand::DrawLineLoop(data) => (data.points.len(), false),
+        DrawCommand::DrawLineStrip(data) => (data.points.len(), false),
+        DrawCommand::DrawLinePath(data) => (validate_path(command_index, &data.path)?, false),
+        DrawCommand::OutlineFillPolygon(data) => (data.points.len(), true),
+        DrawCommand::OutlineFillRectangles(data) => (data.rectangles.len(), true),
+        DrawCommand::OutlineFillPath(data) => (validate_path(command_index, &data.path)?, true),
+        DrawCommand::TextHint(data) => {
+            let valid = data.text.len() as u64 <= VARUINT_LIMIT
+                && data.glyph_length <= VARUINT_LIMIT
+                && data.glyph_length == data.glyph_offset.len() as u64;
+            return if valid { Ok(()) } else { Err(ValidationError::InvalidTextHint { command_index }) };
+        }
+    };
+
+    if count == 0 {
+        return Err(ValidationError::EmptyCommand { command_index });
+    }
+    if outline && count > OUTLINE_ITEM_LIMIT {
+        return Err(ValidationError::TooManyItems { command_index, count });
+    }
+    Ok(())
+}
+
+/// Checks that every segment has path commands and returns the number of segments.
+fn validate_path(command_index: usize, path: &Path) -> Result<usize, ValidationError> {
+    match path.segments.iter().position(|segment| segment.path_commands.is_empty()) {
+        Some(segment_index) => Err(ValidationError::EmptySegment { command_index, segment_index }),
+        None => Ok(path.segments.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ValidationError;
+    use crate::commands::{DrawCommand, FillRectanglesData};
+    use crate::common::Unit;
+    use crate::TinyVg;
+
+    /// A 16 by 16 document with one color and one rectangle.
+    const DOCUMENT: [u8; 25] = [0x72, 0x56, 1, 0x00, 16, 0, 16, 0, 1, 255, 0, 0, 255, 0x02, 0, 0, 0, 0, 0, 0, 16, 0, 16, 0, 0x00];
+
+    #[test]
+    fn non_finite_units_are_invalid() {
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let mut document = TinyVg::from_bytes(&DOCUMENT).unwrap();
+            let DrawCommand::FillRectangles(FillRectanglesData { rectangles, .. }) = &mut document.draw_commands[0] else {
+                unreachable!();
+            };
+            rectangles[0].width = Unit(value);
+            assert_eq!(document.validate(), Err(ValidationError::NonFiniteUnit { command_index: 0 }));
+        }
+    }
+}
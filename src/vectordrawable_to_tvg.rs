@@ -0,0 +1,248 @@
+use crate::color_table::{ColorTable, RgbaF32};
+use crate::commands::{DrawCommand, DrawLinePathData, FillPathData, FlatColored, LinearGradient, OutlineFillPathData, Path, Point, RadialGradient, Style};
+use crate::common::{Affine, Unit};
+use crate::header::{ColorEncoding, CoordinateRange, TinyVgHeader, TINYVG_VERSION};
+use crate::encoder::write_tvg;
+use crate::transform::transform_path;
+use crate::{TinyVg, TinyVgParseError};
+use roxmltree::{Document, Node};
+
+const ANDROID_NAMESPACE: &str = "http://schemas.android.com/apk/res/android";
+
+/// Converts an Android VectorDrawable XML document into a TinyVG file.
+///
+/// The document size is taken from `android:width` and `android:height` in dp, and the
+/// viewport is scaled to fill it. Groups, paths with flat or gradient fills and strokes,
+/// and the vector, fill and stroke alpha are converted. Clip paths, sweep gradients (which
+/// are drawn with their first color) and color resource references are not supported.
+///
+/// Returns `InvalidHeader` if the XML is malformed or the root `<vector>` element lacks a
+/// valid size, and `InvalidCommand` if a `pathData` attribute cannot be parsed.
+pub fn vectordrawable_to_tvg(xml_bytes: &[u8]) -> Result<Vec<u8>, TinyVgParseError> {
+    let xml = std::str::from_utf8(xml_bytes).map_err(|_| TinyVgParseError::InvalidHeader)?;
+    let document = Document::parse(xml).map_err(|_| TinyVgParseError::InvalidHeader)?;
+    let vector = document.root_element();
+    if vector.tag_name().name() != "vector" {
+        return Err(TinyVgParseError::InvalidHeader);
+    }
+
+    let width = dimension_attribute(vector, "width").ok_or(TinyVgParseError::InvalidHeader)?;
+    let height = dimension_attribute(vector, "height").ok_or(TinyVgParseError::InvalidHeader)?;
+    let viewport_width = number_attribute(vector, "viewportWidth").unwrap_or(width);
+    let viewport_height = number_attribute(vector, "viewportHeight").unwrap_or(height);
+    if width <= 0.0 || height <= 0.0 || viewport_width <= 0.0 || viewport_height <= 0.0 {
+        return Err(TinyVgParseError::InvalidHeader);
+    }
+
+    let mut converter = Converter {
+        alpha: number_attribute(vector, "alpha").unwrap_or(1.0).clamp(0.0, 1.0) as f32,
+        color_table: Vec::new(),
+        draw_commands: Vec::new(),
+    };
+    let viewport_transform = Affine::scale_non_uniform(width / viewport_width, height / viewport_height);
+    converter.convert_children(vector, &viewport_transform)?;
+
+    let width = width.ceil() as u32;
+    let height = height.ceil() as u32;
+    let header = TinyVgHeader {
+        magic: [0x72, 0x56],
+        version: TINYVG_VERSION,
+        scale: 0,
+        color_encoding: ColorEncoding::smallest_for(&converter.color_table, 0.5 / 255.0),
+        coordinate_range: CoordinateRange::Default,
+        width,
+        height,
+        color_count: converter.color_table.len() as u64,
+    };
+    let mut document = TinyVg { header, color_table: converter.color_table, draw_commands: converter.draw_commands };
+    document.requantize();
+
+    write_tvg(&document).map_err(|_| TinyVgParseError::InvalidCommand)
+}
+
+struct Converter {
+    alpha: f32,
+    color_table: ColorTable,
+    draw_commands: Vec<DrawCommand>,
+}
+
+impl Converter {
+    fn convert_children(&mut self, parent: Node, transform: &Affine) -> Result<(), TinyVgParseError> {
+        for child in parent.children().filter(Node::is_element) {
+            match child.tag_name().name() {
+                "group" => {
+                    let group_transform = group_transform(child).then(transform);
+                    self.convert_children(child, &group_transform)?;
+                }
+                "path" => self.convert_path(child, transform)?,
+                name => log_warning!("skipped unsupported element `{name}`"),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn convert_path(&mut self, node: Node, transform: &Affine) -> Result<(), TinyVgParseError> {
+        let Some(path_data) = node.attribute((ANDROID_NAMESPACE, "pathData")) else {
+            return Ok(());
+        };
+        let path = Path::from_svg_d(path_data).ok_or(TinyVgParseError::InvalidCommand)?;
+        if path.segments.is_empty() {
+            return Ok(());
+        }
+        let path = transform_path(&path, transform);
+
+        let fill_alpha = self.alpha * number_attribute(node, "fillAlpha").unwrap_or(1.0) as f32;
+        let fill_style = self.paint_style(node, "fillColor", fill_alpha, transform);
+
+        let stroke_width = number_attribute(node, "strokeWidth").unwrap_or(0.0);
+        let stroke_alpha = self.alpha * number_attribute(node, "strokeAlpha").unwrap_or(1.0) as f32;
+        let stroke_style = if stroke_width > 0.0 {
+            self.paint_style(node, "strokeColor", stroke_alpha, transform)
+        } else {
+            None
+        };
+        let line_width = Unit(stroke_width * transform.determinant().abs().sqrt());
+
+        let command = match (fill_style, stroke_style) {
+            (Some(fill_style), Some(line_style)) => DrawCommand::OutlineFillPath(OutlineFillPathData {
+                path,
+                fill_style,
+                line_style,
+                line_width,
+            }),
+            (Some(style), None) => DrawCommand::FillPath(FillPathData { style, path }),
+            (None, Some(style)) => DrawCommand::DrawLinePath(DrawLinePathData { style, path, line_width }),
+            (None, None) => return Ok(()),
+        };
+        self.draw_commands.push(command);
+
+        Ok(())
+    }
+
+    /// Resolves a paint given either as a color attribute or as an inline
+    /// `<aapt:attr>` gradient. Returns `None` for missing or fully transparent paints.
+    fn paint_style(&mut self, node: Node, name: &str, alpha: f32, transform: &Affine) -> Option<Style> {
+        if let Some(color) = node.attribute((ANDROID_NAMESPACE, name)).and_then(parse_color) {
+            return self.flat_style(color, alpha);
+        }
+
+        let attribute_name = format!("android:{name}");
+        let gradient = node
+            .children()
+            .filter(|child| child.tag_name().name() == "attr" && child.attribute("name") == Some(attribute_name.as_str()))
+            .flat_map(|attr| attr.children())
+            .find(|child| child.tag_name().name() == "gradient")?;
+
+        let (color_0, color_1) = gradient_colors(gradient)?;
+        let point = |x: &str, y: &str| {
+            let x = number_attribute(gradient, x).unwrap_or(0.0);
+            let y = number_attribute(gradient, y).unwrap_or(0.0);
+            transform.apply(Point::new(Unit(x), Unit(y)))
+        };
+
+        match gradient.attribute((ANDROID_NAMESPACE, "type")).unwrap_or("linear") {
+            "linear" => Some(Style::LinearGradient(LinearGradient {
+                point_0: point("startX", "startY"),
+                point_1: point("endX", "endY"),
+                color_index_0: self.color_index(color_0, alpha),
+                color_index_1: self.color_index(color_1, alpha),
+            })),
+            "radial" => {
+                let center_x = number_attribute(gradient, "centerX").unwrap_or(0.0);
+                let center_y = number_attribute(gradient, "centerY").unwrap_or(0.0);
+                let radius = number_attribute(gradient, "gradientRadius").unwrap_or(0.0);
+                Some(Style::RadialGradient(RadialGradient {
+                    point_0: transform.apply(Point::new(Unit(center_x), Unit(center_y))),
+                    point_1: transform.apply(Point::new(Unit(center_x + radius), Unit(center_y))),
+                    color_index_0: self.color_index(color_0, alpha),
+                    color_index_1: self.color_index(color_1, alpha),
+                }))
+            }
+            kind => {
+                log_warning!("filled {kind} gradient with its start color");
+                self.flat_style(color_0, alpha)
+            }
+        }
+    }
+
+    fn flat_style(&mut self, color: RgbaF32, alpha: f32) -> Option<Style> {
+        if color.3 * alpha <= 0.0 {
+            return None;
+        }
+        Some(Style::FlatColor(FlatColored { color_index: self.color_index(color, alpha) }))
+    }
+
+    fn color_index(&mut self, color: RgbaF32, alpha: f32) -> u64 {
+        let color = RgbaF32(color.0, color.1, color.2, color.3 * alpha);
+        match self.color_table.iter().position(|existing| *existing == color) {
+            Some(index) => index as u64,
+            None => {
+                self.color_table.push(color);
+                (self.color_table.len() - 1) as u64
+            }
+        }
+    }
+}
+
+fn number_attribute(node: Node, name: &str) -> Option<f64> {
+    node.attribute((ANDROID_NAMESPACE, name))?.trim().parse().ok()
+}
+
+/// Parses a dimension such as `24dp`. The unit is ignored, since TinyVG display units
+/// correspond to density-independent pixels.
+fn dimension_attribute(node: Node, name: &str) -> Option<f64> {
+    let value = node.attribute((ANDROID_NAMESPACE, name))?.trim();
+    let number = value.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    number.parse().ok()
+}
+
+/// Parses an Android color literal: `#RGB`, `#ARGB`, `#RRGGBB` or `#AARRGGBB`.
+fn parse_color(value: &str) -> Option<RgbaF32> {
+    let hex = value.trim().strip_prefix('#')?;
+    let digits: Vec<u8> = hex.chars().map(|c| c.to_digit(16).map(|d| d as u8)).collect::<Option<_>>()?;
+    let (a, r, g, b) = match digits.as_slice() {
+        [r, g, b] => (0xFF, r * 17, g * 17, b * 17),
+        [a, r, g, b] => (a * 17, r * 17, g * 17, b * 17),
+        [r1, r0, g1, g0, b1, b0] => (0xFF, r1 << 4 | r0, g1 << 4 | g0, b1 << 4 | b0),
+        [a1, a0, r1, r0, g1, g0, b1, b0] => (a1 << 4 | a0, r1 << 4 | r0, g1 << 4 | g0, b1 << 4 | b0),
+        _ => return None,
+    };
+    Some(RgbaF32(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, a as f32 / 255.0))
+}
+
+/// The first and last color of a gradient, from its `<item>` stops if it has any and
+/// from `startColor` and `endColor` otherwise. TinyVG gradients only have two colors.
+fn gradient_colors(gradient: Node) -> Option<(RgbaF32, RgbaF32)> {
+    let mut stops: Vec<(f64, RgbaF32)> = gradient
+        .children()
+        .filter(|child| child.tag_name().name() == "item")
+        .filter_map(|item| {
+            let color = parse_color(item.attribute((ANDROID_NAMESPACE, "color"))?)?;
+            Some((number_attribute(item, "offset").unwrap_or(0.0), color))
+        })
+        .collect();
+
+    if stops.is_empty() {
+        let start = parse_color(gradient.attribute((ANDROID_NAMESPACE, "startColor"))?)?;
+        let end = parse_color(gradient.attribute((ANDROID_NAMESPACE, "endColor"))?)?;
+        return Some((start, end));
+    }
+
+    if stops.len() > 2 {
+        log_warning!("reduced gradient with {} stops to its first and last stop", stops.len());
+    }
+    stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+    Some((stops[0].1, stops[stops.len() - 1].1))
+}
+
+/// A group scales, then rotates around its pivot, then translates.
+fn group_transform(group: Node) -> Affine {
+    let attribute = |name: &str, default: f64| number_attribute(group, name).unwrap_or(default);
+    let (pivot_x, pivot_y) = (attribute("pivotX", 0.0), attribute("pivotY", 0.0));
+
+    Affine::translate(-pivot_x, -pivot_y)
+        .then(&Affine::scale_non_uniform(attribute("scaleX", 1.0), attribute("scaleY", 1.0)))
+        .then(&Affine::rotate(attribute("rotation", 0.0).to_radians()))
+        .then(&Affine::translate(pivot_x + attribute("translateX", 0.0), pivot_y + attribute("translateY", 0.0)))
+}
@@ -0,0 +1,49 @@
+use crate::commands::{
+    DrawCommand, DrawLineLoopData, DrawLinePathData, DrawLineStripData, DrawLinesData, FillPathData, FillPolygonData,
+    FillRectanglesData, OutlineFillPathData, OutlineFillPolygonData, OutlineFillRectanglesData, TextHintData,
+};
+use crate::TinyVg;
+
+/// Receives the draw commands of a document one at a time, see `TinyVg::accept`.
+///
+/// Every method does nothing by default, so a visitor only implements the commands it is
+/// interested in. `visit_command` is called first for every command and dispatches to the
+/// method for its type; override it to handle all commands in one place.
+pub trait CommandVisitor {
+    fn visit_command(&mut self, command: &DrawCommand) {
+        match command {
+            DrawCommand::FillPolygon(data) => self.visit_fill_polygon(data),
+            DrawCommand::FillRectangles(data) => self.visit_fill_rectangles(data),
+            DrawCommand::FillPath(data) => self.visit_fill_path(data),
+            DrawCommand::DrawLines(data) => self.visit_draw_lines(data),
+            DrawCommand::DrawLineLoop(data) => self.visit_draw_line_loop(data),
+            DrawCommand::DrawLineStrip(data) => self.visit_draw_line_strip(data),
+            DrawCommand::DrawLinePath(data) => self.visit_draw_line_path(data),
+            DrawCommand::OutlineFillPolygon(data) => self.visit_outline_fill_polygon(data),
+            DrawCommand::OutlineFillRectangles(data) => self.visit_outline_fill_rectangles(data),
+            DrawCommand::OutlineFillPath(data) => self.visit_outline_fill_path(data),
+            DrawCommand::TextHint(data) => self.visit_text_hint(data),
+        }
+    }
+
+    fn visit_fill_polygon(&mut self, _data: &FillPolygonData) {}
+    fn visit_fill_rectangles(&mut self, _data: &FillRectanglesData) {}
+    fn visit_fill_path(&mut self, _data: &FillPathData) {}
+    fn visit_draw_lines(&mut self, _data: &DrawLinesData) {}
+    fn visit_draw_line_loop(&mut self, _data: &DrawLineLoopData) {}
+    fn visit_draw_line_strip(&mut self, _data: &DrawLineStripData) {}
+    fn visit_draw_line_path(&mut self, _data: &DrawLinePathData) {}
+    fn visit_outline_fill_polygon(&mut self, _data: &OutlineFillPolygonData) {}
+    fn visit_outline_fill_rectangles(&mut self, _data: &OutlineFillRectanglesData) {}
+    fn visit_outline_fill_path(&mut self, _data: &OutlineFillPathData) {}
+    fn visit_text_hint(&mut self, _data: &TextHintData) {}
+}
+
+impl TinyVg {
+    /// Passes every draw command to the visitor, in drawing order.
+    pub fn accept(&self, visitor: &mut impl CommandVisitor) {
+        for command in &self.draw_commands {
+            visitor.visit_command(command);
+        }
+    }
+}
@@ -0,0 +1,11 @@
+use proptest::prelude::*;
+use tinyvg_rs::test_util::{assert_roundtrip, document};
+
+proptest! {
+    #![proptest_config(ProptestConfig { cases: 1000, ..ProptestConfig::default() })]
+
+    #[test]
+    fn roundtrip(document in document()) {
+        assert_roundtrip(&document);
+    }
+}